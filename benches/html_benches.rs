@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use html_css_parser::html::{HtmlTokenizer, HtmlParser};
+use html_css_parser::html::{extract, Element, HtmlTokenizer, HtmlParser, Node};
 
 fn tokenize_html(c: &mut Criterion) {
     let html = r#"
@@ -61,5 +61,55 @@ fn parse_html(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, tokenize_html, parse_html);
+// Roughly 1MB of article markup with links, images and inline scripts.
+fn large_document() -> String {
+    let mut html = String::from("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Large</title></head><body>");
+    for i in 0..2000 {
+        html.push_str(&format!(
+            r#"<article class="post" id="post-{i}">
+  <h2><a href="/posts/{i}">Post {i}</a></h2>
+  <p>Some <em>emphasized</em> text with a <a href="/tags/{i}" rel="tag">tag link</a> and more words.</p>
+  <img src="/images/{i}.png" alt="Image {i}">
+  <script>track('<a href="/phantom/{i}">', {i});</script>
+  <ul><li>One</li><li>Two</li><li>Three</li></ul>
+</article>
+"#
+        ));
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+fn extract_links(c: &mut Criterion) {
+    fn collect(nodes: &[Node], found: &mut Vec<Element>) {
+        for node in nodes {
+            if let Node::Element(element) = node {
+                if matches!(element.tag_name.as_str(), "a" | "img" | "meta") {
+                    found.push(element.clone());
+                }
+                collect(&element.children, found);
+            }
+        }
+    }
+
+    let html = large_document();
+    let mut group = c.benchmark_group("extract_links");
+
+    group.bench_function("only_tags", |b| {
+        b.iter(|| extract::only_tags(black_box(&html), &["a", "img", "meta"]))
+    });
+
+    group.bench_function("full_parse", |b| {
+        b.iter(|| {
+            let nodes = HtmlParser::new(black_box(&html)).parse();
+            let mut found = Vec::new();
+            collect(&nodes, &mut found);
+            found
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, tokenize_html, parse_html, extract_links);
 criterion_main!(benches);
\ No newline at end of file
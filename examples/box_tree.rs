@@ -0,0 +1,53 @@
+use html_css_parser::css::{box_tree, ComputedStyles, MediaEnv};
+use html_css_parser::html::Node;
+use html_css_parser::parse_page;
+
+fn main() {
+    // The document from the combined example, with a hidden banner.
+    let html = r##"        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Combined Parser Example</title>
+            <style>
+                body { font-family: Arial; margin: 0; }
+                .header { background: #333; color: white; padding: 20px; }
+                .content { max-width: 800px; margin: 0 auto; padding: 20px; }
+                #footer { background: #f0f0f0; text-align: center; padding: 10px; }
+                .highlight { background-color: yellow; font-weight: bold; }
+                .banner { display: none; }
+            </style>
+        </head>
+        <body>
+            <div class="banner">Cookies!</div>
+            <div class="header">
+                <h1>Website Title</h1>
+            </div>
+            <div class="content">
+                <p>This is the main content area.</p>
+                <p class="highlight">This paragraph is highlighted.</p>
+                <ul>
+                    <li>Item 1</li>
+                    <li>Item 2</li>
+                    <li>Item 3</li>
+                </ul>
+            </div>
+            <div id="footer">
+                <p>&copy; 2024 Example Website</p>
+            </div>
+        </body>
+        </html>
+    "##;
+
+    let (document, stylesheet) = parse_page(html);
+    let Some(root) = document
+        .children
+        .iter()
+        .find(|node| matches!(node, Node::Element(element) if element.tag_name == "html"))
+    else {
+        println!("No <html> element found");
+        return;
+    };
+
+    let styles = ComputedStyles::compute(root, &stylesheet, &MediaEnv::default());
+    print!("{}", box_tree(root, &styles));
+}
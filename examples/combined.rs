@@ -1,44 +1,51 @@
 use html_css_parser::html::{HtmlParser, Node, Element};
 use html_css_parser::css::{CssParser, Selector};
-use std::collections::HashMap;
 
-fn main() {
-    let html = r##"        <!DOCTYPE html>
-        <html>
-        <head>
-            <title>Combined Parser Example</title>
-            <style>
-                body { font-family: Arial; margin: 0; }
-                .header { background: #333; color: white; padding: 20px; }
-                .content { max-width: 800px; margin: 0 auto; padding: 20px; }
-                #footer { background: #f0f0f0; text-align: center; padding: 10px; }
-                .highlight { background-color: yellow; font-weight: bold; }
-            </style>
-        </head>
-        <body>
-            <div class="header">
-                <h1>Website Title</h1>
-            </div>
-            <div class="content">
-                <p>This is the main content area.</p>
-                <p class="highlight">This paragraph is highlighted.</p>
-                <ul>
-                    <li>Item 1</li>
-                    <li>Item 2</li>
-                    <li>Item 3</li>
-                </ul>
-            </div>
-            <div id="footer">
-                <p>&copy; 2024 Example Website</p>
-            </div>
-        </body>
-        </html>
-    "##;
+const SAMPLE_HTML: &str = r##"
+    <!DOCTYPE html>
+    <html>
+    <head>
+        <title>Combined Parser Example</title>
+        <style>
+            body { font-family: Arial; margin: 0; }
+            .header { background: #333; color: white; padding: 20px; }
+            .content { max-width: 800px; margin: 0 auto; padding: 20px; }
+            #footer { background: #f0f0f0; text-align: center; padding: 10px; }
+            .highlight { background-color: yellow; font-weight: bold; }
+            .content > p { line-height: 1.5; }
+            .feature h3 { font-size: 1.2em; }
+        </style>
+    </head>
+    <body>
+        <div class="header">
+            <h1>Website Title</h1>
+        </div>
+        <div class="content">
+            <p>This is the main content area.</p>
+            <p class="highlight">This paragraph is highlighted.</p>
+            <ul>
+                <li>Item 1</li>
+                <li>Item 2</li>
+                <li>Item 3</li>
+            </ul>
+        </div>
+        <div class="features">
+            <div class="feature"><h3>Fast</h3><p>Parses in microseconds.</p></div>
+            <div class="feature"><h3>Small</h3><p>No dependencies.</p></div>
+            <div class="feature"><h3>Safe</h3><p>Written in Rust.</p></div>
+        </div>
+        <div id="footer">
+            <p>&copy; 2024 Example Website</p>
+        </div>
+    </body>
+    </html>
+"##;
 
+fn main() {
     println!("=== Combined HTML & CSS Parser Example ===\n");
 
     // Parse the HTML
-    let mut html_parser = HtmlParser::new(html);
+    let mut html_parser = HtmlParser::new(SAMPLE_HTML);
     let document = html_parser.parse_document();
 
     if let Some(Node::Element(html_element)) = document {
@@ -76,26 +83,8 @@ fn main() {
 }
 
 fn extract_css_from_html(element: &Element) -> String {
-    let mut css_content = String::new();
-    
-    // Check if this is a style element
-    if element.tag_name.to_lowercase() == "style" {
-        for child in &element.children {
-            if let Node::Text(text) = child {
-                css_content.push_str(text);
-                css_content.push('\n');
-            }
-        }
-    }
-    
-    // Recursively check children
-    for child in &element.children {
-        if let Node::Element(child_element) = child {
-            css_content.push_str(&extract_css_from_html(child_element));
-        }
-    }
-    
-    css_content
+    let styles = element.query_selector_all("style").expect("a valid selector");
+    styles.iter().map(|style| style.text_content() + "\n").collect()
 }
 
 fn analyze_html_css_relationship(html_element: &Element, css_rules: &[html_css_parser::css::Rule]) {
@@ -156,6 +145,14 @@ fn analyze_html_css_relationship(html_element: &Element, css_rules: &[html_css_p
     if !unused_css_ids.is_empty() {
         println!("  Unused CSS IDs: {:?}", unused_css_ids);
     }
+
+    // Count the elements each rule applies to
+    for rule in css_rules {
+        for selector in &rule.selectors {
+            let count = html_element.all_matching(selector).len();
+            println!("  `{}` matches {} element(s)", selector, count);
+        }
+    }
     
     println!();
 }
@@ -166,21 +163,17 @@ fn collect_html_identifiers(
     ids: &mut std::collections::HashSet<String>,
     tags: &mut std::collections::HashSet<String>,
 ) {
-    tags.insert(element.tag_name.clone());
-    
-    if let Some(class_attr) = element.attributes.get("class") {
-        for class in class_attr.split_whitespace() {
-            classes.insert(class.to_string());
+    for element in std::iter::once(element).chain(element.descendants()) {
+        tags.insert(element.tag_name.clone());
+
+        if let Some(class_attr) = element.attribute("class") {
+            for class in class_attr.split_whitespace() {
+                classes.insert(class.to_string());
+            }
         }
-    }
-    
-    if let Some(id_attr) = element.attributes.get("id") {
-        ids.insert(id_attr.clone());
-    }
-    
-    for child in &element.children {
-        if let Node::Element(child_element) = child {
-            collect_html_identifiers(child_element, classes, ids, tags);
+
+        if let Some(id_attr) = element.attribute("id") {
+            ids.insert(id_attr.to_string());
         }
     }
 }
@@ -191,10 +184,10 @@ fn print_html_structure(element: &Element, depth: usize) {
     print!("{}<{}", indent, element.tag_name);
     
     // Print important attributes
-    if let Some(class) = element.attributes.get("class") {
+    if let Some(class) = element.attribute("class") {
         print!(" class=\"{}\"", class);
     }
-    if let Some(id) = element.attributes.get("id") {
+    if let Some(id) = element.attribute("id") {
         print!(" id=\"{}\"", id);
     }
     
@@ -232,4 +225,14 @@ mod tests {
             assert!(css.contains("color: red"));
         }
     }
+
+    #[test]
+    fn test_sample_feature_headings() {
+        let Some(Node::Element(html_element)) = HtmlParser::new(SAMPLE_HTML).parse_document() else {
+            panic!("sample should parse");
+        };
+        let headings = html_element.query_selector_all(".feature h3").unwrap();
+        assert_eq!(headings.len(), 3);
+        assert_eq!(headings[0].text_content(), "Fast");
+    }
 }
\ No newline at end of file
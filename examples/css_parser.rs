@@ -1,99 +1,102 @@
-use html_css_parser::css::{CssParser, CssTokenizer, Selector, Declaration};
+use html_css_parser::css::{AtRuleBlock, CssParser, CssRule, CssTokenizer};
 
-fn main() {
-    let css = r##"
-        /* Global styles */
-        * {
-            box-sizing: border-box;
-            margin: 0;
-            padding: 0;
-        }
+const SAMPLE_CSS: &str = r##"
+    /* Global styles */
+    * {
+        box-sizing: border-box;
+        margin: 0;
+        padding: 0;
+    }
 
-        body {
-            font-family: 'Helvetica Neue', Arial, sans-serif;
-            line-height: 1.6;
-            color: #333;
-            background-color: #f8f9fa;
-        }
+    body {
+        font-family: 'Helvetica Neue', Arial, sans-serif;
+        line-height: 1.6;
+        color: #333;
+        background-color: #f8f9fa;
+    }
 
-        .container {
-            max-width: 1200px;
-            margin: 0 auto;
-            padding: 0 20px;
-        }
+    .container {
+        max-width: 1200px;
+        margin: 0 auto;
+        padding: 0 20px;
+    }
 
-        #header {
-            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
-            color: white;
-            padding: 2rem 0;
-            text-align: center;
-        }
+    #header {
+        background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+        color: white;
+        padding: 2rem 0;
+        text-align: center;
+    }
 
-        .nav-menu {
-            display: flex;
-            justify-content: center;
-            list-style: none;
-            margin-top: 1rem;
-        }
+    .nav-menu {
+        display: flex;
+        justify-content: center;
+        list-style: none;
+        margin-top: 1rem;
+    }
 
-        .nav-menu li {
-            margin: 0 1rem;
-        }
+    .nav-menu li {
+        margin: 0 1rem;
+    }
 
-        .nav-menu a {
-            color: white;
-            text-decoration: none;
-            font-weight: 500;
-            transition: opacity 0.3s ease;
-        }
+    .nav-menu a {
+        color: white;
+        text-decoration: none;
+        font-weight: 500;
+        transition: opacity 0.3s ease;
+    }
 
-        .nav-menu a:hover {
-            opacity: 0.8;
-        }
+    .nav-menu a:hover {
+        opacity: 0.8;
+    }
 
-        .feature-grid {
-            display: grid;
-            grid-template-columns: repeat(auto-fit, minmax(300px, 1fr));
-            gap: 2rem;
-            margin: 2rem 0;
-        }
+    .feature-grid {
+        display: grid;
+        grid-template-columns: repeat(auto-fit, minmax(300px, 1fr));
+        gap: 2rem;
+        margin: 2rem 0;
+    }
 
-        .feature {
-            background: white;
-            padding: 1.5rem;
-            border-radius: 8px;
-            box-shadow: 0 2px 10px rgba(0, 0, 0, 0.1);
-            border-left: 4px solid #667eea;
-        }
+    .feature {
+        background: white;
+        padding: 1.5rem;
+        border-radius: 8px;
+        box-shadow: 0 2px 10px rgba(0, 0, 0, 0.1);
+        border-left: 4px solid #667eea;
+    }
 
-        .feature h3 {
-            color: #667eea;
-            margin-bottom: 0.5rem;
-            font-size: 1.25rem;
-        }
+    .feature h3 {
+        color: #667eea;
+        margin-bottom: 0.5rem;
+        font-size: 1.25rem;
+    }
 
-        .feature p {
-            color: #666;
-            line-height: 1.5;
-        }
+    .feature p {
+        color: #666;
+        line-height: 1.5;
+    }
 
-        @media (max-width: 768px) {
-            .container {
-                padding: 0 15px;
-            }
-            
-            .feature-grid {
-                grid-template-columns: 1fr;
-                gap: 1rem;
-            }
+    @media (max-width: 768px) {
+        .container {
+            padding: 0 15px;
         }
+        
+        .feature-grid {
+            grid-template-columns: 1fr;
+            gap: 1rem;
+        }
+    }
+
+    /* Utility classes */
+    .text-center { text-align: center; }
+    .text-bold { font-weight: bold !important; }
+    .mb-1 { margin-bottom: 1rem; }
+    .mb-2 { margin-bottom: 2rem; }
+"##;
+
 
-        /* Utility classes */
-        .text-center { text-align: center; }
-        .text-bold { font-weight: bold !important; }
-        .mb-1 { margin-bottom: 1rem; }
-        .mb-2 { margin-bottom: 2rem; }
-    "##;
+fn main() {
+    let css = SAMPLE_CSS;
 
     println!("=== CSS Tokenization Example ===");
     let tokenizer = CssTokenizer::new(css);
@@ -118,15 +121,7 @@ fn main() {
         
         // Print selectors
         for selector in &rule.selectors {
-            print!("  ");
-            match selector {
-                Selector::Type(name) => print!("{}", name),
-                Selector::Class(name) => print!(".{}", name),
-                Selector::Id(name) => print!("#{}", name),
-                Selector::Universal => print!("*"),
-                _ => print!("(complex selector)"),
-            }
-            println!();
+            println!("  {}", selector);
         }
         
         // Print declarations
@@ -141,6 +136,24 @@ fn main() {
         println!();
     }
 
+    // `parse` keeps only style rules; `parse_stylesheet` keeps at-rules too
+    println!("=== At-rules ===");
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    for rule in &stylesheet.rules {
+        if let CssRule::At(at_rule) = rule {
+            match &at_rule.block {
+                Some(AtRuleBlock::Rules(rules)) => {
+                    println!("@{} {}: {} nested rule(s)", at_rule.name, at_rule.prelude, rules.len());
+                }
+                Some(AtRuleBlock::Declarations(declarations)) => {
+                    println!("@{} {}: {} declaration(s)", at_rule.name, at_rule.prelude, declarations.len());
+                }
+                _ => println!("@{} {};", at_rule.name, at_rule.prelude),
+            }
+        }
+    }
+    println!();
+
     // Demonstrate specific parsing features
     demonstrate_parsing_features();
 }
@@ -201,6 +214,7 @@ fn demonstrate_parsing_features() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use html_css_parser::css::Selector;
 
     #[test]
     fn test_example_css_parsing() {
@@ -225,4 +239,34 @@ mod tests {
         assert_eq!(rule.declarations[1].property, "font-size");
         assert_eq!(rule.declarations[1].value, "14px");
     }
+
+    #[test]
+    fn test_sample_media_rule() {
+        let stylesheet = CssParser::new(SAMPLE_CSS).parse_stylesheet();
+        let media = stylesheet
+            .rules
+            .iter()
+            .find_map(|rule| match rule {
+                CssRule::At(at_rule) if at_rule.name == "media" => Some(at_rule),
+                _ => None,
+            })
+            .expect("sample has an @media rule");
+        assert_eq!(media.prelude, "(max-width: 768px)");
+
+        let Some(AtRuleBlock::Rules(rules)) = &media.block else {
+            panic!("expected nested rules, got {:?}", media.block);
+        };
+        let CssRule::Style(container) = &rules[0] else {
+            panic!("expected a style rule, got {:?}", rules[0]);
+        };
+        assert_eq!(container.selectors, [Selector::Class("container".to_string())]);
+        assert_eq!(container.declarations.len(), 1);
+        assert_eq!(container.declarations[0].property, "padding");
+        assert_eq!(container.declarations[0].value, "0 15px");
+        assert_eq!(rules.len(), 2);
+
+        // Rules after the block are unaffected.
+        let rules = CssParser::new(SAMPLE_CSS).parse();
+        assert_eq!(rules.last().map(|rule| rule.selectors[0].to_string()).as_deref(), Some(".mb-2"));
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use html_css_parser::html::{HtmlParser, HtmlTokenizer, Node, Element};
+use html_css_parser::html::{HtmlParser, HtmlTokenizer, Node};
 
 fn main() {
     let html = r##"
@@ -92,7 +92,11 @@ fn print_node(node: &Node, depth: usize) {
             
             // Print attributes
             for (name, value) in &element.attributes {
-                print!(" {}=\"{}\"", name, value);
+                if value.is_bool() {
+                    print!(" {}", name);
+                } else {
+                    print!(" {}=\"{}\"", name, value);
+                }
             }
             println!(">");
             
@@ -0,0 +1,325 @@
+use super::parser::{Declaration, Rule};
+use super::stylesheet::{AtRuleBlock, CssRule, Stylesheet};
+use super::tokenizer::CssToken;
+use super::value::ComponentValue;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+// Custom properties and the custom properties their values reference.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropertyGraph {
+    // Defined properties in order of first definition. A property defined
+    // more than once depends on everything any of its definitions references.
+    properties: Vec<String>,
+    dependencies: HashMap<String, Vec<String>>,
+}
+
+// Properties that reference each other in a loop; the first name is repeated
+// at the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    pub cycle: Vec<String>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "custom properties reference each other in a cycle: {}", self.cycle.join(" -> "))
+    }
+}
+
+impl Error for CycleError {}
+
+pub fn custom_property_graph(stylesheet: &Stylesheet) -> PropertyGraph {
+    let mut graph = PropertyGraph::default();
+
+    for_each_declaration(&stylesheet.rules, &mut |declaration| {
+        if !declaration.property.starts_with("--") {
+            return;
+        }
+        if !graph.dependencies.contains_key(&declaration.property) {
+            graph.properties.push(declaration.property.clone());
+        }
+
+        let dependencies = graph.dependencies.entry(declaration.property.clone()).or_default();
+        for reference in references(declaration) {
+            if !dependencies.contains(&reference) {
+                dependencies.push(reference);
+            }
+        }
+    });
+
+    graph
+}
+
+impl PropertyGraph {
+    pub fn properties(&self) -> impl Iterator<Item = &str> {
+        self.properties.iter().map(String::as_str)
+    }
+
+    // The custom properties `property`'s values reference, whether or not
+    // they're defined.
+    pub fn dependencies(&self, property: &str) -> &[String] {
+        self.dependencies.get(property).map_or(&[], Vec::as_slice)
+    }
+
+    // Defined properties ordered so that each comes after the defined
+    // properties it references.
+    pub fn resolution_order(&self) -> Result<Vec<String>, CycleError> {
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        let mut path = Vec::new();
+
+        for property in &self.properties {
+            self.visit(property, &mut path, &mut done, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    fn visit<'a>(
+        &'a self,
+        property: &'a str,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        order: &mut Vec<String>,
+    ) -> Result<(), CycleError> {
+        if done.contains(property) || !self.dependencies.contains_key(property) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|&visiting| visiting == property) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|name| name.to_string()).collect();
+            cycle.push(property.to_string());
+            return Err(CycleError { cycle });
+        }
+
+        path.push(property);
+        for dependency in self.dependencies(property) {
+            self.visit(dependency, path, done, order)?;
+        }
+        path.pop();
+
+        done.insert(property);
+        order.push(property.to_string());
+        Ok(())
+    }
+
+    // Defined properties that no `var()` in the stylesheet references, from
+    // either a custom property or a regular declaration.
+    pub fn unused_properties(&self, stylesheet: &Stylesheet) -> Vec<String> {
+        let mut referenced = HashSet::new();
+        for_each_declaration(&stylesheet.rules, &mut |declaration| {
+            referenced.extend(references(declaration));
+        });
+
+        self.properties
+            .iter()
+            .filter(|property| !referenced.contains(*property))
+            .cloned()
+            .collect()
+    }
+}
+
+fn for_each_declaration(rules: &[CssRule], visit: &mut impl FnMut(&Declaration)) {
+    for rule in rules {
+        match rule {
+            CssRule::Style(rule) => rule.declarations.iter().for_each(&mut *visit),
+            CssRule::At(at_rule) => match &at_rule.block {
+                Some(AtRuleBlock::Rules(rules)) => for_each_declaration(rules, visit),
+                Some(AtRuleBlock::Declarations(declarations)) => declarations.iter().for_each(&mut *visit),
+                Some(AtRuleBlock::Keyframes(keyframes)) => {
+                    keyframes.iter().flat_map(|keyframe| &keyframe.declarations).for_each(&mut *visit)
+                }
+                None => {}
+            },
+        }
+    }
+}
+
+// `rule` with each `var()` replaced by the value the rule gives the custom
+// property it names, or else by its fallback. A reference that can't be
+// resolved either way, including one in a cycle, is left as written.
+pub fn resolve_var_references(rule: &Rule) -> Rule {
+    let mut resolved = rule.clone();
+    let mut resolver = Resolver {
+        rule,
+        active: Vec::new(),
+        resolved: HashMap::new(),
+        hit_active: false,
+    };
+    for declaration in &mut resolved.declarations {
+        let values = declaration.component_values();
+        if !contains_var(&values) {
+            continue;
+        }
+        resolver.active = vec![declaration.property.clone()];
+        let mut value = String::new();
+        if resolver.write_resolved(&values, true, &mut value) {
+            declaration.value = value;
+            declaration.raw_value = None;
+        }
+    }
+    resolved
+}
+
+// Substituted values longer than this are invalid, as in browsers, so
+// references that double at each step can't grow without bound.
+const MAX_SUBSTITUTION_LEN: usize = 1 << 16;
+
+fn contains_var(values: &[ComponentValue]) -> bool {
+    values.iter().any(|value| match value {
+        ComponentValue::Function { name, arguments } => name.eq_ignore_ascii_case("var") || contains_var(arguments),
+        ComponentValue::Block { contents, .. } => contains_var(contents),
+        ComponentValue::Token(_) => false,
+    })
+}
+
+struct Resolver<'a> {
+    rule: &'a Rule,
+    // The properties being resolved, so cycles are caught.
+    active: Vec<String>,
+    // Each property's resolved value, `None` if it can't be resolved. Only
+    // values resolved without running into an active property are kept,
+    // since only those are the same wherever they're referenced from.
+    resolved: HashMap<String, Option<String>>,
+    hit_active: bool,
+}
+
+impl Resolver<'_> {
+    // Writes `values` with their `var()` references resolved. An unresolved
+    // reference is written as it is if `keep_unresolved` is set, and
+    // otherwise fails the whole write, as does a value growing too long.
+    fn write_resolved(&mut self, values: &[ComponentValue], keep_unresolved: bool, dest: &mut String) -> bool {
+        for value in values {
+            match value {
+                ComponentValue::Function { name, arguments } if name.eq_ignore_ascii_case("var") => {
+                    match self.resolve_reference(arguments) {
+                        Some(resolved) => dest.push_str(&resolved),
+                        None if keep_unresolved => write_values(std::slice::from_ref(value), dest),
+                        None => return false,
+                    }
+                }
+                ComponentValue::Function { name, arguments } => {
+                    dest.push_str(name);
+                    dest.push('(');
+                    if !self.write_resolved(arguments, keep_unresolved, dest) {
+                        return false;
+                    }
+                    dest.push(')');
+                }
+                ComponentValue::Block { open, contents } => {
+                    dest.push(*open);
+                    if !self.write_resolved(contents, keep_unresolved, dest) {
+                        return false;
+                    }
+                    dest.push(closing_bracket(*open));
+                }
+                ComponentValue::Token(token) => dest.push_str(&token.to_string()),
+            }
+            if dest.len() > MAX_SUBSTITUTION_LEN {
+                return false;
+            }
+        }
+        true
+    }
+
+    // The value of `var(arguments)`: the named property's, or the fallback's.
+    fn resolve_reference(&mut self, arguments: &[ComponentValue]) -> Option<String> {
+        let mut parts = arguments.splitn(2, |argument| *argument == ComponentValue::Token(CssToken::Comma));
+        let name = parts.next().map(trim_whitespace);
+        let fallback = parts.next().map(trim_whitespace);
+
+        if let Some([ComponentValue::Token(CssToken::Ident(property))]) = name {
+            if let Some(value) = self.resolve_property(property) {
+                return Some(value);
+            }
+        }
+
+        let mut value = String::new();
+        self.write_resolved(fallback?, false, &mut value).then_some(value)
+    }
+
+    fn resolve_property(&mut self, property: &str) -> Option<String> {
+        if self.active.iter().any(|active| active == property) {
+            self.hit_active = true;
+            return None;
+        }
+        if let Some(resolved) = self.resolved.get(property) {
+            return resolved.clone();
+        }
+        let declaration = self.rule.get(property).filter(|_| property.starts_with("--"))?;
+
+        let outer_hit_active = std::mem::replace(&mut self.hit_active, false);
+        let mut value = String::new();
+        self.active.push(property.to_string());
+        let resolved = self
+            .write_resolved(&declaration.component_values(), false, &mut value)
+            .then_some(value);
+        self.active.pop();
+        if !self.hit_active {
+            self.resolved.insert(property.to_string(), resolved.clone());
+        }
+        self.hit_active |= outer_hit_active;
+        resolved
+    }
+}
+
+fn write_values(values: &[ComponentValue], dest: &mut String) {
+    for value in values {
+        match value {
+            ComponentValue::Function { name, arguments } => {
+                dest.push_str(name);
+                dest.push('(');
+                write_values(arguments, dest);
+                dest.push(')');
+            }
+            ComponentValue::Block { open, contents } => {
+                dest.push(*open);
+                write_values(contents, dest);
+                dest.push(closing_bracket(*open));
+            }
+            ComponentValue::Token(token) => dest.push_str(&token.to_string()),
+        }
+    }
+}
+
+fn closing_bracket(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+fn trim_whitespace(values: &[ComponentValue]) -> &[ComponentValue] {
+    let is_value = |value: &ComponentValue| *value != ComponentValue::Token(CssToken::Whitespace);
+    let start = values.iter().position(is_value).unwrap_or(values.len());
+    let end = values.iter().rposition(is_value).map_or(start, |last| last + 1);
+    &values[start..end]
+}
+
+// Every custom property named by a `var()`, including ones in fallbacks.
+fn references(declaration: &Declaration) -> Vec<String> {
+    let mut found = Vec::new();
+    collect_references(&declaration.component_values(), &mut found);
+    found
+}
+
+fn collect_references(values: &[ComponentValue], found: &mut Vec<String>) {
+    for value in values {
+        match value {
+            ComponentValue::Function { name, arguments } => {
+                if name.eq_ignore_ascii_case("var") {
+                    let first = arguments.iter().find(|argument| **argument != ComponentValue::Token(CssToken::Whitespace));
+                    if let Some(ComponentValue::Token(CssToken::Ident(property))) = first {
+                        if property.starts_with("--") {
+                            found.push(property.clone());
+                        }
+                    }
+                }
+                collect_references(arguments, found);
+            }
+            ComponentValue::Block { contents, .. } => collect_references(contents, found),
+            ComponentValue::Token(_) => {}
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use super::media::px_per_unit;
+use super::parser::Declaration;
+use super::tokenizer::CssToken;
+use super::value::{parse_component_values, ComponentValue};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalcOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl CalcOp {
+    fn symbol(self) -> char {
+        match self {
+            CalcOp::Add => '+',
+            CalcOp::Subtract => '-',
+            CalcOp::Multiply => '*',
+            CalcOp::Divide => '/',
+        }
+    }
+}
+
+// The expression inside a `calc()`. Parentheses and nested `calc()` calls
+// only group, so they leave no node of their own.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum CalcExpr {
+    Number(f64),
+    Dimension { value: f64, unit: String },
+    Percentage(f64),
+    Binary { op: CalcOp, left: Box<CalcExpr>, right: Box<CalcExpr> },
+}
+
+impl CalcExpr {
+    // The value in px, with percentages taken of `percentage_basis` and `em`
+    // and `rem` as 16px. A unitless expression gives its number. `None` for
+    // units other than lengths, or division by zero.
+    pub fn evaluate(&self, percentage_basis: f64) -> Option<f64> {
+        match self {
+            CalcExpr::Number(number) => Some(*number),
+            CalcExpr::Dimension { value, unit } => Some(value * px_per_unit(unit)?),
+            CalcExpr::Percentage(percentage) => Some(percentage / 100.0 * percentage_basis),
+            CalcExpr::Binary { op, left, right } => {
+                let (left, right) = (left.evaluate(percentage_basis)?, right.evaluate(percentage_basis)?);
+                match op {
+                    CalcOp::Add => Some(left + right),
+                    CalcOp::Subtract => Some(left - right),
+                    CalcOp::Multiply => Some(left * right),
+                    CalcOp::Divide if right == 0.0 => None,
+                    CalcOp::Divide => Some(left / right),
+                }
+            }
+        }
+    }
+
+    // Whether the expression has no unit.
+    fn is_number(&self) -> bool {
+        match self {
+            CalcExpr::Number(_) => true,
+            CalcExpr::Dimension { .. } | CalcExpr::Percentage(_) => false,
+            CalcExpr::Binary { op: CalcOp::Multiply, left, right } => left.is_number() && right.is_number(),
+            CalcExpr::Binary { left, .. } => left.is_number(),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            CalcExpr::Binary { op: CalcOp::Add | CalcOp::Subtract, .. } => 1,
+            CalcExpr::Binary { .. } => 2,
+            _ => 3,
+        }
+    }
+
+    fn write_operand(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "(")?;
+            self.write_expr(f)?;
+            write!(f, ")")
+        } else {
+            self.write_expr(f)
+        }
+    }
+
+    fn write_expr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcExpr::Number(number) => write!(f, "{}", CssToken::Number(*number)),
+            CalcExpr::Dimension { value, unit } => write!(f, "{}", CssToken::Dimension { value: *value, unit: unit.clone() }),
+            CalcExpr::Percentage(percentage) => write!(f, "{}", CssToken::Percentage(*percentage)),
+            CalcExpr::Binary { op, left, right } => {
+                // Operators associate to the left, so a right operand of the
+                // same precedence needs parentheses.
+                let precedence = self.precedence();
+                left.write_operand(f, precedence)?;
+                write!(f, " {} ", op.symbol())?;
+                right.write_operand(f, precedence + 1)
+            }
+        }
+    }
+}
+
+// Written as a `calc()` call.
+impl fmt::Display for CalcExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "calc(")?;
+        self.write_expr(f)?;
+        write!(f, ")")
+    }
+}
+
+// Parses `input` if it is a single `calc()` call. The expression must be
+// well-typed: lengths can't be added to numbers, a product needs a unitless
+// side and a divisor must be unitless.
+pub fn parse_calc(input: &str) -> Option<CalcExpr> {
+    let values = parse_component_values(input);
+    let mut significant = values.iter().filter(|value| **value != ComponentValue::Token(CssToken::Whitespace));
+    match (significant.next()?, significant.next()) {
+        (ComponentValue::Function { name, arguments }, None) if name.eq_ignore_ascii_case("calc") => parse_sum(arguments),
+        _ => None,
+    }
+}
+
+impl Declaration {
+    // The value as an expression, if it is a single `calc()` call.
+    pub fn calc_value(&self) -> Option<CalcExpr> {
+        parse_calc(&self.value)
+    }
+}
+
+fn parse_sum(values: &[ComponentValue]) -> Option<CalcExpr> {
+    let values: Vec<&ComponentValue> = values
+        .iter()
+        .filter(|value| **value != ComponentValue::Token(CssToken::Whitespace))
+        .collect();
+    let mut position = 0;
+    let expr = parse_terms(&values, &mut position)?;
+    (position == values.len()).then_some(expr)
+}
+
+fn parse_terms(values: &[&ComponentValue], position: &mut usize) -> Option<CalcExpr> {
+    let mut expr = parse_product(values, position)?;
+    while let Some(ComponentValue::Token(CssToken::Delim(symbol @ ('+' | '-')))) = values.get(*position) {
+        *position += 1;
+        let right = parse_product(values, position)?;
+        if expr.is_number() != right.is_number() {
+            return None;
+        }
+        let op = if *symbol == '+' { CalcOp::Add } else { CalcOp::Subtract };
+        expr = CalcExpr::Binary { op, left: Box::new(expr), right: Box::new(right) };
+    }
+    Some(expr)
+}
+
+fn parse_product(values: &[&ComponentValue], position: &mut usize) -> Option<CalcExpr> {
+    let mut expr = parse_operand(values, position)?;
+    while let Some(ComponentValue::Token(CssToken::Delim(symbol @ ('*' | '/')))) = values.get(*position) {
+        *position += 1;
+        let right = parse_operand(values, position)?;
+        let op = if *symbol == '*' { CalcOp::Multiply } else { CalcOp::Divide };
+        let valid = match op {
+            CalcOp::Multiply => expr.is_number() || right.is_number(),
+            _ => right.is_number(),
+        };
+        if !valid {
+            return None;
+        }
+        expr = CalcExpr::Binary { op, left: Box::new(expr), right: Box::new(right) };
+    }
+    Some(expr)
+}
+
+fn parse_operand(values: &[&ComponentValue], position: &mut usize) -> Option<CalcExpr> {
+    let value = values.get(*position)?;
+    *position += 1;
+    match value {
+        ComponentValue::Token(CssToken::Number(number)) => Some(CalcExpr::Number(*number)),
+        ComponentValue::Token(CssToken::Dimension { value, unit }) => Some(CalcExpr::Dimension { value: *value, unit: unit.clone() }),
+        ComponentValue::Token(CssToken::Percentage(percentage)) => Some(CalcExpr::Percentage(*percentage)),
+        ComponentValue::Block { open: '(', contents } => parse_sum(contents),
+        ComponentValue::Function { name, arguments } if name.eq_ignore_ascii_case("calc") => parse_sum(arguments),
+        _ => None,
+    }
+}
@@ -0,0 +1,316 @@
+use super::parser::Declaration;
+use super::tokenizer::CssToken;
+use super::value::{parse_component_values, ComponentValue};
+use std::fmt;
+
+// An sRGB color. `a` is the opacity, from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0.0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    // The opacity as a channel from 0 to 255.
+    pub fn alpha(&self) -> u8 {
+        (self.a * 255.0).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+// The shortest hex form: `#rgb` or `#rrggbb`, with an alpha digit or pair
+// only when the color isn't opaque.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut channels = vec![self.r, self.g, self.b];
+        if self.alpha() != 255 {
+            channels.push(self.alpha());
+        }
+
+        // `#aabbcc` contracts to `#abc` when every channel repeats its digit.
+        let short = channels.iter().all(|channel| channel >> 4 == channel & 0xf);
+        write!(f, "#")?;
+        for channel in channels {
+            if short {
+                write!(f, "{:x}", channel & 0xf)?;
+            } else {
+                write!(f, "{:02x}", channel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Parses a hex color, an `rgb()` or `rgba()` call or a named color, with
+// whitespace allowed around it. `currentcolor` and other keywords that
+// depend on context give `None`.
+pub fn parse_color(input: &str) -> Option<Color> {
+    let values = parse_component_values(input);
+    let mut significant = values.iter().filter(|value| **value != ComponentValue::Token(CssToken::Whitespace));
+    let color = match significant.next()? {
+        ComponentValue::Token(CssToken::Hash(hash)) => hex_color(hash),
+        ComponentValue::Token(CssToken::Ident(name)) => named_color(name),
+        ComponentValue::Function { name, arguments } => function_color(name, arguments),
+        ComponentValue::Token(_) | ComponentValue::Block { .. } => None,
+    };
+    significant.next().is_none().then_some(color?)
+}
+
+impl Declaration {
+    // The value as a color, for properties such as `color` or
+    // `background-color` whose value is a single color.
+    pub fn color_value(&self) -> Option<Color> {
+        parse_color(&self.value)
+    }
+}
+
+pub(crate) fn hex_color(hash: &str) -> Option<Color> {
+    if !hash.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let digits: Vec<u8> = hash
+        .chars()
+        .map(|ch| ch.to_digit(16).unwrap_or(0) as u8)
+        .collect();
+
+    // `#abc` is short for `#aabbcc`.
+    let mut rgba = [255; 4];
+    match digits.len() {
+        3 | 4 => {
+            for (channel, digit) in rgba.iter_mut().zip(&digits) {
+                *channel = digit * 17;
+            }
+        }
+        6 | 8 => {
+            for (channel, pair) in rgba.iter_mut().zip(digits.chunks(2)) {
+                *channel = pair[0] * 16 + pair[1];
+            }
+        }
+        _ => return None,
+    }
+    let [r, g, b, a] = rgba;
+    Some(Color::rgba(r, g, b, f32::from(a) / 255.0))
+}
+
+// Understands the legacy comma-separated `rgb()`/`rgba()` syntax as well as
+// the space-separated one with an optional `/ alpha`.
+pub(crate) fn function_color(name: &str, arguments: &[ComponentValue]) -> Option<Color> {
+    if !name.eq_ignore_ascii_case("rgb") && !name.eq_ignore_ascii_case("rgba") {
+        return None;
+    }
+
+    let components: Vec<&CssToken> = arguments
+        .iter()
+        .filter_map(|argument| match argument {
+            ComponentValue::Token(CssToken::Whitespace | CssToken::Comma | CssToken::Delim('/')) => None,
+            ComponentValue::Token(token) => Some(token),
+            _ => None,
+        })
+        .collect();
+
+    let nested = arguments
+        .iter()
+        .any(|argument| !matches!(argument, ComponentValue::Token(_)));
+    if nested || !(3..=4).contains(&components.len()) {
+        return None;
+    }
+
+    let mut rgb = [0; 3];
+    for (channel, component) in rgb.iter_mut().zip(&components) {
+        let value = match component {
+            CssToken::Number(number) => *number,
+            CssToken::Percentage(percentage) => percentage / 100.0 * 255.0,
+            _ => return None,
+        };
+        *channel = value.round().clamp(0.0, 255.0) as u8;
+    }
+    let alpha = match components.get(3) {
+        None => 1.0,
+        Some(CssToken::Number(number)) => *number,
+        Some(CssToken::Percentage(percentage)) => percentage / 100.0,
+        Some(_) => return None,
+    };
+
+    let [r, g, b] = rgb;
+    Some(Color::rgba(r, g, b, alpha.clamp(0.0, 1.0) as f32))
+}
+
+pub(crate) fn named_color(name: &str) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    if name == "transparent" {
+        return Some(Color::TRANSPARENT);
+    }
+
+    NAMED_COLORS
+        .binary_search_by(|(candidate, _)| candidate.cmp(&name.as_str()))
+        .ok()
+        .map(|index| {
+            let rgb = NAMED_COLORS[index].1;
+            Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        })
+}
+
+
+// Sorted by name for binary search.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
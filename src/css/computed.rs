@@ -0,0 +1,133 @@
+use super::matching::MatchOptions;
+use super::media::MediaEnv;
+use super::parser::{parse_declaration_list, Declaration, Rule};
+use super::stylesheet::Stylesheet;
+use crate::html::{Element, Node};
+use std::collections::HashMap;
+
+// Properties an element takes from its parent unless it sets them itself.
+const INHERITED_PROPERTIES: &[&str] = &[
+    "color", "cursor", "direction", "font", "font-family", "font-size", "font-style", "font-variant",
+    "font-weight", "letter-spacing", "line-height", "list-style", "list-style-type", "quotes", "text-align",
+    "text-indent", "text-transform", "visibility", "white-space", "word-spacing",
+];
+
+// Property values for the elements of a tree, each addressed by its path of
+// child indexes from the root node the styles were computed for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComputedStyles {
+    elements: HashMap<Vec<usize>, HashMap<String, String>>,
+}
+
+impl ComputedStyles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Applies the stylesheet's rules for `env` and each element's `style`
+    // attribute, in the order `resolve_styles` describes.
+    pub fn compute(root: &Node, stylesheet: &Stylesheet, env: &MediaEnv) -> Self {
+        let rules = stylesheet.rules_for(env);
+        let mut styles = Self::new();
+        let mut path = Vec::new();
+        styles.compute_node(root, &rules, &HashMap::new(), &mut path, &mut Vec::new());
+        styles
+    }
+
+    // `ancestors` are the elements enclosing `node`, outermost first, so
+    // selectors with combinators can match.
+    fn compute_node<'a>(
+        &mut self,
+        node: &'a Node,
+        rules: &[&Rule],
+        inherited: &HashMap<String, String>,
+        path: &mut Vec<usize>,
+        ancestors: &mut Vec<&'a Element>,
+    ) {
+        let Node::Element(element) = node else {
+            return;
+        };
+
+        ancestors.push(element);
+        let mut values: HashMap<String, String> = inherited.clone();
+        for declaration in cascade(ancestors, rules) {
+            values.insert(declaration.property.clone(), declaration.value.clone());
+        }
+
+        let inherited: HashMap<String, String> = values
+            .iter()
+            .filter(|(property, _)| INHERITED_PROPERTIES.contains(&property.as_str()) || property.starts_with("--"))
+            .map(|(property, value)| (property.clone(), value.clone()))
+            .collect();
+        for (index, child) in element.children.iter().enumerate() {
+            path.push(index);
+            self.compute_node(child, rules, &inherited, path, ancestors);
+            path.pop();
+        }
+        ancestors.pop();
+
+        self.elements.insert(path.clone(), values);
+    }
+
+    pub fn get(&self, path: &[usize], property: &str) -> Option<&str> {
+        self.elements.get(path)?.get(property).map(String::as_str)
+    }
+
+    pub fn set(&mut self, path: &[usize], property: &str, value: &str) {
+        self.elements
+            .entry(path.to_vec())
+            .or_default()
+            .insert(property.to_string(), value.to_string());
+    }
+}
+
+// The winning declaration for each property set on `element` by `rules` or
+// its `style` attribute. `!important` declarations win over normal ones and
+// an inline style over stylesheet declarations of the same importance.
+// Among rules, the more specific selector wins, then the later rule. A rule
+// counts with the most specific of its selectors that match. A lone element
+// has no ancestors or siblings, so selectors with combinators don't match;
+// `ComputedStyles::compute` matches them against the whole tree.
+pub fn resolve_styles(element: &Element, rules: &[Rule]) -> HashMap<String, Declaration> {
+    let rules: Vec<&Rule> = rules.iter().collect();
+    cascade(&[element], &rules)
+        .into_iter()
+        .map(|declaration| (declaration.property.clone(), declaration))
+        .collect()
+}
+
+// The declarations that apply to the last element of `path`, from lowest to
+// highest priority. `path` runs from the outermost ancestor to the element.
+fn cascade(path: &[&Element], rules: &[&Rule]) -> Vec<Declaration> {
+    let Some(element) = path.last() else {
+        return Vec::new();
+    };
+    let options = MatchOptions::default();
+    let matched = rules.iter().filter_map(|rule| {
+        let specificity = rule
+            .selectors
+            .iter()
+            .filter(|selector| selector.matches_path(path, &options))
+            .map(|selector| selector.specificity())
+            .max()?;
+        Some(rule.declarations.iter().map(move |declaration| (specificity, declaration.clone())))
+    });
+    let inline = element
+        .attribute("style")
+        .map(parse_declaration_list)
+        .unwrap_or_default();
+
+    // Stylesheet, inline, important stylesheet, important inline. The sort
+    // is stable, so source order decides ties.
+    let mut declarations: Vec<(u8, (u32, u32, u32), Declaration)> = matched
+        .flatten()
+        .map(|(specificity, declaration)| (if declaration.important { 2 } else { 0 }, specificity, declaration))
+        .chain(
+            inline
+                .into_iter()
+                .map(|declaration| (if declaration.important { 3 } else { 1 }, (0, 0, 0), declaration)),
+        )
+        .collect();
+    declarations.sort_by_key(|(priority, specificity, _)| (*priority, *specificity));
+    declarations.into_iter().map(|(_, _, declaration)| declaration).collect()
+}
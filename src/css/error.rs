@@ -0,0 +1,69 @@
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum CssError {
+    InvalidSelector { found: String, span: Span },
+    InvalidDeclaration { found: String, span: Span },
+    InvalidToken { found: String, span: Span },
+    // A property name not followed by a colon; the span is where the colon
+    // was expected.
+    MissingColon { property: String, span: Span },
+    // A block still open at the end of input; the span is its opening brace.
+    UnclosedBlock { span: Span },
+    // A block nested too deeply to parse; the span is its opening brace.
+    NestingTooDeep { span: Span },
+}
+
+impl CssError {
+    pub fn span(&self) -> Span {
+        match self {
+            CssError::InvalidSelector { span, .. }
+            | CssError::InvalidDeclaration { span, .. }
+            | CssError::InvalidToken { span, .. }
+            | CssError::MissingColon { span, .. }
+            | CssError::UnclosedBlock { span }
+            | CssError::NestingTooDeep { span } => *span,
+        }
+    }
+}
+
+fn write_found(f: &mut fmt::Formatter<'_>, found: &str, span: &Span) -> fmt::Result {
+    if found.is_empty() {
+        write!(f, "unexpected end of input at line {}, column {}", span.line, span.column)
+    } else {
+        write!(f, "unexpected \"{}\" at line {}, column {}", found, span.line, span.column)
+    }
+}
+
+impl fmt::Display for CssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CssError::InvalidSelector { found, span } => {
+                write!(f, "invalid selector: ")?;
+                write_found(f, found, span)
+            }
+            CssError::InvalidDeclaration { found, span } => {
+                write!(f, "invalid declaration: ")?;
+                write_found(f, found, span)
+            }
+            CssError::InvalidToken { found, span } => {
+                write!(f, "invalid token: ")?;
+                write_found(f, found, span)
+            }
+            CssError::MissingColon { property, span } => {
+                write!(f, "missing colon after property \"{}\" at line {}, column {}", property, span.line, span.column)
+            }
+            CssError::UnclosedBlock { span } => {
+                write!(f, "unclosed block opened at line {}, column {}", span.line, span.column)
+            }
+            CssError::NestingTooDeep { span } => {
+                write!(f, "block nested too deeply at line {}, column {}", span.line, span.column)
+            }
+        }
+    }
+}
+
+impl Error for CssError {}
@@ -0,0 +1,54 @@
+use super::parser::{Rule, Selector};
+use super::stylesheet::{AtRuleBlock, CssRule, Stylesheet};
+use crate::dot::DotWriter;
+
+pub use crate::dot::DotOptions;
+
+pub fn to_dot(stylesheet: &Stylesheet) -> String {
+    to_dot_with(stylesheet, &DotOptions::default())
+}
+
+pub fn to_dot_with(stylesheet: &Stylesheet, options: &DotOptions) -> String {
+    let mut writer = DotWriter::new("css", options);
+    let root = writer.node("stylesheet", Some("ellipse"));
+    write_rules(&mut writer, &stylesheet.rules, root, 1);
+    writer.finish()
+}
+
+fn write_rules(writer: &mut DotWriter, rules: &[CssRule], parent: usize, depth: usize) {
+    if !writer.within_depth(depth) {
+        return;
+    }
+
+    for rule in rules {
+        match rule {
+            CssRule::Style(rule) => {
+                let id = writer.node(&rule_label(rule), None);
+                writer.edge(parent, id);
+            }
+            CssRule::At(at_rule) => {
+                let label = if at_rule.prelude.is_empty() {
+                    format!("@{}", at_rule.name)
+                } else {
+                    format!("@{} {}", at_rule.name, at_rule.prelude)
+                };
+                let id = writer.node(&label, Some("folder"));
+                writer.edge(parent, id);
+                if let Some(AtRuleBlock::Rules(rules)) = &at_rule.block {
+                    write_rules(writer, rules, id, depth + 1);
+                }
+            }
+        }
+    }
+}
+
+fn rule_label(rule: &Rule) -> String {
+    let selectors: Vec<String> = rule.selectors.iter().map(Selector::to_string).collect();
+    let count = rule.declarations.len();
+    format!(
+        "{} ({} declaration{})",
+        selectors.join(", "),
+        count,
+        if count == 1 { "" } else { "s" }
+    )
+}
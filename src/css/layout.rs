@@ -0,0 +1,145 @@
+use super::computed::ComputedStyles;
+use crate::html::render::{default_display, parse_display, Display};
+use crate::html::{Element, Node};
+use std::fmt;
+
+// The structure a layout engine starts from: which elements generate boxes
+// and how those boxes nest. There is no geometry.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum BoxNode {
+    // A block-level element, including table cells. `path` addresses the
+    // element the same way `ComputedStyles` does.
+    Block { tag_name: String, path: Vec<usize>, children: Vec<BoxNode> },
+    Inline { tag_name: String, path: Vec<usize>, children: Vec<BoxNode> },
+    // Wraps a run of inline-level boxes that has block siblings.
+    AnonymousBlock { children: Vec<BoxNode> },
+    Text(String),
+}
+
+impl BoxNode {
+    pub fn children(&self) -> &[BoxNode] {
+        match self {
+            BoxNode::Block { children, .. } | BoxNode::Inline { children, .. } | BoxNode::AnonymousBlock { children } => {
+                children
+            }
+            BoxNode::Text(_) => &[],
+        }
+    }
+
+    pub fn is_block_level(&self) -> bool {
+        matches!(self, BoxNode::Block { .. } | BoxNode::AnonymousBlock { .. })
+    }
+
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        match self {
+            BoxNode::Block { tag_name, .. } => writeln!(f, "{}block <{}>", indent, tag_name)?,
+            BoxNode::Inline { tag_name, .. } => writeln!(f, "{}inline <{}>", indent, tag_name)?,
+            BoxNode::AnonymousBlock { .. } => writeln!(f, "{}anonymous block", indent)?,
+            BoxNode::Text(text) => writeln!(f, "{}text {:?}", indent, text)?,
+        }
+        for child in self.children() {
+            child.write_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+// One line per box, children indented under their parent.
+impl fmt::Display for BoxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+// Builds the box tree for `document`, with `display` looked up in `styles`
+// (computed for `document`) and falling back to each element's default.
+// Elements with `display: none` generate no boxes, nor do their descendants.
+// The root always generates a block box, empty if it is hidden.
+pub fn box_tree(document: &Node, styles: &ComputedStyles) -> BoxNode {
+    let mut path = Vec::new();
+    match document {
+        Node::Element(element) => {
+            let children = match display(element, styles, &path) {
+                Display::None => Vec::new(),
+                _ => child_boxes(element, styles, &mut path),
+            };
+            BoxNode::Block {
+                tag_name: element.tag_name.clone(),
+                path,
+                children,
+            }
+        }
+        Node::Text(text) => BoxNode::AnonymousBlock {
+            children: vec![BoxNode::Text(text.clone())],
+        },
+        Node::Comment(_) => BoxNode::AnonymousBlock { children: Vec::new() },
+    }
+}
+
+fn display(element: &Element, styles: &ComputedStyles, path: &[usize]) -> Display {
+    styles
+        .get(path, "display")
+        .map_or_else(|| default_display(&element.tag_name), parse_display)
+}
+
+fn build(node: &Node, styles: &ComputedStyles, path: &mut Vec<usize>) -> Option<BoxNode> {
+    match node {
+        Node::Text(text) => Some(BoxNode::Text(text.clone())),
+        Node::Comment(_) => None,
+        Node::Element(element) => {
+            let display = display(element, styles, path);
+            if display == Display::None {
+                return None;
+            }
+            let tag_name = element.tag_name.clone();
+            let children = child_boxes(element, styles, path);
+            let path = path.clone();
+            Some(match display {
+                Display::Inline => BoxNode::Inline { tag_name, path, children },
+                _ => BoxNode::Block { tag_name, path, children },
+            })
+        }
+    }
+}
+
+// A block container holds either only block-level or only inline-level
+// boxes, so when both occur each run of inline-level boxes is wrapped in an
+// anonymous block. Runs that are only whitespace text are dropped there.
+// Blocks inside inline boxes are left nested rather than split out.
+fn child_boxes(element: &Element, styles: &ComputedStyles, path: &mut Vec<usize>) -> Vec<BoxNode> {
+    let mut boxes = Vec::new();
+    for (index, child) in element.children.iter().enumerate() {
+        path.push(index);
+        boxes.extend(build(child, styles, path));
+        path.pop();
+    }
+
+    if !boxes.iter().any(BoxNode::is_block_level) || boxes.iter().all(BoxNode::is_block_level) {
+        return boxes;
+    }
+
+    let mut wrapped = Vec::new();
+    let mut run: Vec<BoxNode> = Vec::new();
+    for child in boxes {
+        if child.is_block_level() {
+            flush_inline_run(&mut run, &mut wrapped);
+            wrapped.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+    flush_inline_run(&mut run, &mut wrapped);
+    wrapped
+}
+
+fn flush_inline_run(run: &mut Vec<BoxNode>, boxes: &mut Vec<BoxNode>) {
+    let children = std::mem::take(run);
+    let only_whitespace = children
+        .iter()
+        .all(|child| matches!(child, BoxNode::Text(text) if text.trim().is_empty()));
+    if !only_whitespace {
+        boxes.push(BoxNode::AnonymousBlock { children });
+    }
+}
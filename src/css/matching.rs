@@ -0,0 +1,310 @@
+use super::error::CssError;
+use super::parser::{try_parse_selector_list, AttrCase, AttrMatcher, Selector};
+use crate::html::{Element, Node, NodeId, QuirksMode, Tree};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchOptions {
+    // Class and id selectors match ASCII case-insensitively in quirks mode.
+    pub(crate) quirks_mode: QuirksMode,
+}
+
+impl MatchOptions {
+    pub fn quirks_mode(mut self, quirks_mode: QuirksMode) -> Self {
+        self.quirks_mode = quirks_mode;
+        self
+    }
+}
+
+impl From<QuirksMode> for MatchOptions {
+    fn from(quirks_mode: QuirksMode) -> Self {
+        Self { quirks_mode }
+    }
+}
+
+// Attributes whose values HTML compares ASCII case-insensitively unless the
+// selector carries an `s` flag.
+const CASE_INSENSITIVE_ATTRIBUTES: &[&str] = &[
+    "accept", "accept-charset", "align", "alink", "axis", "bgcolor", "charset", "checked", "clear",
+    "codetype", "color", "compact", "declare", "defer", "dir", "direction", "disabled", "enctype",
+    "face", "frame", "hreflang", "http-equiv", "lang", "language", "link", "media", "method",
+    "multiple", "nohref", "noresize", "noshade", "nowrap", "readonly", "rel", "rev", "rules", "scope",
+    "scrolling", "selected", "shape", "target", "text", "type", "valign", "valuetype", "vlink",
+];
+
+impl Selector {
+    pub fn matches(&self, element: &Element) -> bool {
+        self.matches_with(element, &MatchOptions::default())
+    }
+
+    // Combinators need the element's place in a tree, which a lone `Element`
+    // doesn't have, so they never match here; `Element::query_selector`
+    // handles them. Nor do pseudo-classes, which depend on state or
+    // position, or pseudo-elements.
+    pub fn matches_with(&self, element: &Element, options: &MatchOptions) -> bool {
+        let quirks = options.quirks_mode == QuirksMode::Quirks;
+
+        match self {
+            Selector::Type(name) => element.tag_name.eq_ignore_ascii_case(name),
+            Selector::Universal => true,
+            Selector::Class(class) => element.attribute("class").is_some_and(|classes| {
+                classes
+                    .split_ascii_whitespace()
+                    .any(|candidate| equals(candidate, class, quirks))
+            }),
+            Selector::Id(id) => element.attribute("id").is_some_and(|candidate| equals(candidate, id, quirks)),
+            Selector::Attribute { name, matcher, value, case } => {
+                let Some(actual) = element.attribute(name) else {
+                    return false;
+                };
+                let (Some(matcher), Some(expected)) = (matcher, value) else {
+                    return true;
+                };
+
+                let ignore_case = match case {
+                    Some(AttrCase::Insensitive) => true,
+                    Some(AttrCase::Sensitive) => false,
+                    None => CASE_INSENSITIVE_ATTRIBUTES.contains(&name.to_ascii_lowercase().as_str()),
+                };
+                attribute_matches(*matcher, actual, expected, ignore_case)
+            }
+            Selector::Compound(parts) => parts.iter().all(|part| part.matches_with(element, options)),
+            Selector::Descendant(..)
+            | Selector::Child(..)
+            | Selector::Adjacent(..)
+            | Selector::GeneralSibling(..)
+            | Selector::PseudoClass(..)
+            | Selector::PseudoElement(_) => false,
+        }
+    }
+
+    // Like `matches_with`, but for an element with its ancestors: `path`
+    // runs from the outermost ancestor to the element itself, so
+    // combinators can look up the tree and at earlier siblings.
+    pub(crate) fn matches_path(&self, path: &[&Element], options: &MatchOptions) -> bool {
+        self.matches_path_memo(path, options, &mut HashMap::new())
+    }
+
+    // `seen` keeps each combinator's result by selector and element, since
+    // an element's ancestors are fixed by the tree. Without it, nested
+    // descendant and sibling combinators retry the same pairs exponentially
+    // often.
+    fn matches_path_memo(&self, path: &[&Element], options: &MatchOptions, seen: &mut Seen) -> bool {
+        let Some((&element, ancestors)) = path.split_last() else {
+            return false;
+        };
+        let key = (self as *const Selector, element as *const Element);
+        if let Some(&matched) = seen.get(&key) {
+            return matched;
+        }
+
+        let matched = match self {
+            Selector::Compound(parts) => parts.iter().all(|part| part.matches_path_memo(path, options, seen)),
+            Selector::Descendant(ancestor, selector) => {
+                selector.matches_path_memo(path, options, seen)
+                    && (1..path.len()).any(|end| ancestor.matches_path_memo(&path[..end], options, seen))
+            }
+            Selector::Child(parent, selector) => {
+                selector.matches_path_memo(path, options, seen)
+                    && !ancestors.is_empty()
+                    && parent.matches_path_memo(ancestors, options, seen)
+            }
+            Selector::Adjacent(sibling, selector) => {
+                selector.matches_path_memo(path, options, seen)
+                    && preceding_siblings(ancestors, element).last().is_some_and(|&previous| {
+                        sibling.matches_path_memo(&sibling_path(ancestors, previous), options, seen)
+                    })
+            }
+            Selector::GeneralSibling(sibling, selector) => {
+                selector.matches_path_memo(path, options, seen)
+                    && preceding_siblings(ancestors, element)
+                        .into_iter()
+                        .any(|previous| sibling.matches_path_memo(&sibling_path(ancestors, previous), options, seen))
+            }
+            _ => return self.matches_with(element, options),
+        };
+        seen.insert(key, matched);
+        matched
+    }
+}
+
+type Seen = HashMap<(*const Selector, *const Element), bool>;
+
+// The element siblings before `element`, in document order. The root of a
+// path has none.
+fn preceding_siblings<'a>(ancestors: &[&'a Element], element: &Element) -> Vec<&'a Element> {
+    let Some(parent) = ancestors.last() else {
+        return Vec::new();
+    };
+
+    parent
+        .children
+        .iter()
+        .take_while(|child| !matches!(child, Node::Element(child) if std::ptr::eq(child, element)))
+        .filter_map(|child| match child {
+            Node::Element(sibling) => Some(sibling),
+            _ => None,
+        })
+        .collect()
+}
+
+fn sibling_path<'a>(ancestors: &[&'a Element], sibling: &'a Element) -> Vec<&'a Element> {
+    let mut path = ancestors.to_vec();
+    path.push(sibling);
+    path
+}
+
+impl Element {
+    // The first element matching any selector in the comma-separated
+    // `selectors`, searching this element and its descendants depth-first in
+    // document order.
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<&Element>, CssError> {
+        let selectors = try_parse_selector_list(selectors)?;
+        Ok(self.first_match(&selectors))
+    }
+
+    // Every element matching any of `selectors`, once each, in document
+    // order.
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<&Element>, CssError> {
+        let selectors = try_parse_selector_list(selectors)?;
+        Ok(self.all_matches(&selectors))
+    }
+
+    // Whether this element matches `selector` on its own, as
+    // `Selector::matches` decides.
+    pub fn matches(&self, selector: &Selector) -> bool {
+        selector.matches(self)
+    }
+
+    // Whether this element matches `selector` given its `ancestors`,
+    // outermost first, so that combinators can be checked. For `+` and `~`
+    // the last ancestor must be this element's parent in the same tree.
+    pub fn matches_with_ancestors(&self, ancestors: &[&Element], selector: &Selector) -> bool {
+        let mut path = ancestors.to_vec();
+        path.push(self);
+        selector.matches_path(&path, &MatchOptions::default())
+    }
+
+    // Whether this element matches any selector in the comma-separated
+    // `selectors` on its own.
+    pub fn matches_str(&self, selectors: &str) -> Result<bool, CssError> {
+        let selectors = try_parse_selector_list(selectors)?;
+        Ok(selectors.iter().any(|selector| self.matches(selector)))
+    }
+
+    // As `query_selector`, with `selector` already parsed.
+    pub fn first_matching(&self, selector: &Selector) -> Option<&Element> {
+        self.first_match(std::slice::from_ref(selector))
+    }
+
+    pub fn all_matching(&self, selector: &Selector) -> Vec<&Element> {
+        self.all_matches(std::slice::from_ref(selector))
+    }
+
+    fn first_match(&self, selectors: &[Selector]) -> Option<&Element> {
+        let mut found = None;
+        self.visit_matches(selectors, &mut Vec::new(), &mut |element| {
+            found = Some(element);
+            false
+        });
+        found
+    }
+
+    fn all_matches(&self, selectors: &[Selector]) -> Vec<&Element> {
+        let mut found = Vec::new();
+        self.visit_matches(selectors, &mut Vec::new(), &mut |element| {
+            found.push(element);
+            true
+        });
+        found
+    }
+
+    // Calls `visit` with each element matching one of `selectors` until it
+    // returns false, which the return value passes on.
+    fn visit_matches<'a>(
+        &'a self,
+        selectors: &[Selector],
+        path: &mut Vec<&'a Element>,
+        visit: &mut impl FnMut(&'a Element) -> bool,
+    ) -> bool {
+        path.push(self);
+        let options = MatchOptions::default();
+        let matched = selectors.iter().any(|selector| selector.matches_path(path, &options));
+        let mut keep_going = !matched || visit(self);
+        for child in &self.children {
+            if !keep_going {
+                break;
+            }
+            if let Node::Element(child) = child {
+                keep_going = child.visit_matches(selectors, path, visit);
+            }
+        }
+        path.pop();
+        keep_going
+    }
+}
+
+impl Tree {
+    // The nearest of `id` and its ancestors that matches `selector`, as the
+    // DOM's `closest()`. As with `Element::query_selector`, combinators see
+    // only the top-level element `id` is in and what's below it.
+    pub fn closest(&self, id: NodeId, selector: &Selector) -> Option<NodeId> {
+        let mut ids: Vec<NodeId> = std::iter::once(id)
+            .chain(self.ancestors(id))
+            .take_while(|&id| id != self.root())
+            .collect();
+        ids.reverse();
+
+        // Matching needs `Element`s linked as in the tree, so the subtree
+        // is built and followed down to `id`.
+        let Some(Node::Element(top)) = self.node(*ids.first()?) else {
+            return None;
+        };
+        let mut path = vec![&top];
+        for pair in ids.windows(2) {
+            let index = self.children(pair[0]).iter().position(|&child| child == pair[1])?;
+            match &path[path.len() - 1].children[index] {
+                Node::Element(element) => path.push(element),
+                _ => break,
+            }
+        }
+
+        let options = MatchOptions::default();
+        (1..=path.len())
+            .rev()
+            .find(|&end| selector.matches_path(&path[..end], &options))
+            .map(|end| ids[end - 1])
+    }
+}
+
+fn equals(a: &str, b: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+fn attribute_matches(matcher: AttrMatcher, actual: &str, expected: &str, ignore_case: bool) -> bool {
+    let (actual, expected) = if ignore_case {
+        (actual.to_ascii_lowercase(), expected.to_ascii_lowercase())
+    } else {
+        (actual.to_string(), expected.to_string())
+    };
+
+    match matcher {
+        AttrMatcher::Equals => actual == expected,
+        AttrMatcher::Includes => {
+            !expected.is_empty()
+                && !expected.contains(|c: char| c.is_ascii_whitespace())
+                && actual.split_ascii_whitespace().any(|word| word == expected)
+        }
+        AttrMatcher::DashMatch => {
+            actual == expected || actual.strip_prefix(&expected).is_some_and(|rest| rest.starts_with('-'))
+        }
+        // An empty value never matches for the substring matchers.
+        AttrMatcher::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+        AttrMatcher::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+        AttrMatcher::Substring => !expected.is_empty() && actual.contains(&expected),
+    }
+}
@@ -0,0 +1,316 @@
+use super::tokenizer::{CssToken, CssTokenizer, SignificantTokens};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaQualifier {
+    Only,
+    Not,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFeature {
+    pub name: String,
+    // `None` for boolean features such as `(color)`.
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    pub qualifier: Option<MediaQualifier>,
+    // `None` when the query is only a list of features, which means `all`.
+    pub media_type: Option<String>,
+    pub features: Vec<MediaFeature>,
+}
+
+// Comma-separated queries; the list matches if any of them does. An empty
+// list matches everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaQueryList {
+    pub queries: Vec<MediaQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaEnv {
+    pub media_type: String,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl MediaEnv {
+    pub fn screen(width: f64, height: f64) -> Self {
+        Self {
+            media_type: "screen".to_string(),
+            width,
+            height,
+        }
+    }
+
+    // A US Letter page at 96dpi.
+    pub fn print() -> Self {
+        Self {
+            media_type: "print".to_string(),
+            width: 816.0,
+            height: 1056.0,
+        }
+    }
+}
+
+impl Default for MediaEnv {
+    fn default() -> Self {
+        Self::screen(1024.0, 768.0)
+    }
+}
+
+impl MediaQuery {
+    pub fn evaluate(&self, env: &MediaEnv) -> bool {
+        let type_matches = match &self.media_type {
+            None => true,
+            Some(media_type) => media_type == "all" || *media_type == env.media_type.to_ascii_lowercase(),
+        };
+        let matches = type_matches && self.features.iter().all(|feature| feature.evaluate(env));
+
+        match self.qualifier {
+            Some(MediaQualifier::Not) => !matches,
+            _ => matches,
+        }
+    }
+}
+
+impl MediaQueryList {
+    pub fn evaluate(&self, env: &MediaEnv) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|query| query.evaluate(env))
+    }
+}
+
+impl MediaFeature {
+    // Features this crate doesn't model never match.
+    pub fn evaluate(&self, env: &MediaEnv) -> bool {
+        let (prefix, name) = match self.name.split_once('-') {
+            Some(("min", name)) => (Some("min"), name),
+            Some(("max", name)) => (Some("max"), name),
+            _ => (None, self.name.as_str()),
+        };
+
+        let actual = match name {
+            "width" => env.width,
+            "height" => env.height,
+            "orientation" => {
+                let orientation = if env.height >= env.width { "portrait" } else { "landscape" };
+                return prefix.is_none() && self.value.as_deref() == Some(orientation);
+            }
+            _ => return false,
+        };
+
+        let Some(value) = &self.value else {
+            return prefix.is_none() && actual != 0.0;
+        };
+        let Some(expected) = length_in_px(value) else {
+            return false;
+        };
+
+        match prefix {
+            Some("min") => actual >= expected,
+            Some("max") => actual <= expected,
+            _ => actual == expected,
+        }
+    }
+}
+
+fn length_in_px(value: &str) -> Option<f64> {
+    let mut tokenizer = CssTokenizer::new(value);
+    let token = tokenizer.next_token()?;
+    if tokenizer.next_token().is_some() {
+        return None;
+    }
+
+    match token {
+        CssToken::Number(0.0) => Some(0.0),
+        CssToken::Dimension { value, unit } => Some(value * px_per_unit(&unit)?),
+        _ => None,
+    }
+}
+
+// The size of one `unit` in px, taking `em` and `rem` as 16px.
+pub(crate) fn px_per_unit(unit: &str) -> Option<f64> {
+    let scale = match unit.to_ascii_lowercase().as_str() {
+        "px" => 1.0,
+        "em" | "rem" => 16.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "q" => 96.0 / 101.6,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => return None,
+    };
+    Some(scale)
+}
+
+pub fn parse_media_query(input: &str) -> Option<MediaQuery> {
+    let tokens: Vec<CssToken> = SignificantTokens::new(input).collect();
+
+    let mut position = 0;
+    let mut query = MediaQuery {
+        qualifier: None,
+        media_type: None,
+        features: Vec::new(),
+    };
+
+    if let Some(CssToken::Ident(ident)) = tokens.first() {
+        let ident = ident.to_ascii_lowercase();
+        let qualifier = match ident.as_str() {
+            "only" => Some(MediaQualifier::Only),
+            "not" => Some(MediaQualifier::Not),
+            _ => None,
+        };
+        if qualifier.is_some() {
+            query.qualifier = qualifier;
+            position += 1;
+        }
+
+        match tokens.get(position) {
+            Some(CssToken::Ident(media_type)) => {
+                query.media_type = Some(media_type.to_ascii_lowercase());
+                position += 1;
+            }
+            _ => return None,
+        }
+    }
+
+    while position < tokens.len() {
+        // Every feature after the media type is introduced by `and`.
+        if query.media_type.is_some() || !query.features.is_empty() {
+            match &tokens[position] {
+                CssToken::Ident(ident) if ident.eq_ignore_ascii_case("and") => position += 1,
+                _ => return None,
+            }
+        }
+        query.features.push(parse_feature(&tokens, &mut position)?);
+    }
+
+    if query.media_type.is_none() && query.features.is_empty() {
+        return None;
+    }
+
+    Some(query)
+}
+
+// A malformed query becomes `not all` rather than invalidating the whole list.
+pub fn parse_media_query_list(input: &str) -> MediaQueryList {
+    let mut tokenizer = CssTokenizer::new(input);
+    let mut queries = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+
+    loop {
+        let offset = tokenizer.byte_position();
+        let token = tokenizer.next_token();
+        let at_separator = match &token {
+            None => true,
+            Some(CssToken::Comma) => depth == 0,
+            Some(CssToken::LeftParen | CssToken::LeftBracket | CssToken::LeftBrace) => {
+                depth += 1;
+                false
+            }
+            Some(CssToken::RightParen | CssToken::RightBracket | CssToken::RightBrace) => {
+                depth = depth.saturating_sub(1);
+                false
+            }
+            Some(_) => false,
+        };
+        if !at_separator {
+            continue;
+        }
+
+        // A blank input is an empty list, not a single malformed query.
+        let query = &input[start..offset];
+        if token.is_some() || !queries.is_empty() || !query.trim().is_empty() {
+            queries.push(parse_media_query(query).unwrap_or_else(not_all));
+        }
+        if token.is_none() {
+            break;
+        }
+        start = tokenizer.byte_position();
+    }
+
+    MediaQueryList { queries }
+}
+
+fn not_all() -> MediaQuery {
+    MediaQuery {
+        qualifier: Some(MediaQualifier::Not),
+        media_type: Some("all".to_string()),
+        features: Vec::new(),
+    }
+}
+
+fn parse_feature(tokens: &[CssToken], position: &mut usize) -> Option<MediaFeature> {
+    if tokens.get(*position) != Some(&CssToken::LeftParen) {
+        return None;
+    }
+
+    let name = match tokens.get(*position + 1) {
+        Some(CssToken::Ident(name)) => name.to_ascii_lowercase(),
+        _ => return None,
+    };
+    *position += 2;
+
+    match tokens.get(*position) {
+        Some(CssToken::RightParen) => {
+            *position += 1;
+            Some(MediaFeature { name, value: None })
+        }
+        Some(CssToken::Colon) => {
+            *position += 1;
+            let start = *position;
+            while !matches!(tokens.get(*position), Some(CssToken::RightParen) | None) {
+                *position += 1;
+            }
+            if *position == start || *position == tokens.len() {
+                return None;
+            }
+
+            let value: String = tokens[start..*position].iter().map(|token| token.to_string()).collect();
+            *position += 1;
+            Some(MediaFeature { name, value: Some(value) })
+        }
+        _ => None,
+    }
+}
+
+impl fmt::Display for MediaFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "({}: {})", self.name, value),
+            None => write!(f, "({})", self.name),
+        }
+    }
+}
+
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        match self.qualifier {
+            Some(MediaQualifier::Only) => parts.push("only".to_string()),
+            Some(MediaQualifier::Not) => parts.push("not".to_string()),
+            None => {}
+        }
+        if let Some(media_type) = &self.media_type {
+            parts.push(media_type.clone());
+        }
+        for feature in &self.features {
+            if !parts.is_empty() && !matches!(parts.last().map(String::as_str), Some("only" | "not")) {
+                parts.push("and".to_string());
+            }
+            parts.push(feature.to_string());
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+impl fmt::Display for MediaQueryList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let queries: Vec<String> = self.queries.iter().map(MediaQuery::to_string).collect();
+        f.write_str(&queries.join(", "))
+    }
+}
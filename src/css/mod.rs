@@ -1,5 +1,31 @@
 pub mod tokenizer;
 pub mod parser;
+pub mod serializer;
+pub mod error;
+pub mod stylesheet;
+pub mod value;
+pub mod color;
+pub mod calc;
+pub mod media;
+pub mod matching;
+pub mod specificity;
+pub mod export;
+pub mod sanitize;
+pub mod validate;
+pub mod analyze;
+pub mod computed;
+pub mod layout;
 
-pub use tokenizer::{CssTokenizer, CssToken};
-pub use parser::{CssParser, Rule, Selector, Declaration};
\ No newline at end of file
+pub use tokenizer::{CssCheckpoint, CssTokenizer, CssToken, CssTokenRef, SignificantTokens, SpannedCssTokens};
+pub use parser::{CssParser, CssParseOptions, CssStats, Rule, Selector, AttrMatcher, AttrCase, Declaration, parse_declaration_list, try_parse_declaration, try_parse_selector_list};
+pub use serializer::{CssWriteOptions, serialize_declarations, serialize_rules};
+pub use stylesheet::{Stylesheet, CssRule, AtRule, AtRuleBlock, Keyframe, KeyframeSelector};
+pub use value::{ComponentValue, EquivalenceOptions};
+pub use color::{parse_color, Color};
+pub use calc::{parse_calc, CalcExpr, CalcOp};
+pub use media::{MediaQuery, MediaQueryList, MediaFeature, MediaQualifier, MediaEnv, parse_media_query, parse_media_query_list};
+pub use matching::MatchOptions;
+pub use computed::{resolve_styles, ComputedStyles};
+pub use layout::{box_tree, BoxNode};
+pub use sanitize::{sanitize_inline_style, StylePolicy};
+pub use error::CssError;
\ No newline at end of file
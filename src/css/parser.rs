@@ -1,46 +1,246 @@
+use super::error::CssError;
+use super::stylesheet::{AtRule, AtRuleBlock, CssRule, Keyframe, KeyframeSelector, Stylesheet};
 use super::tokenizer::{CssTokenizer, CssToken};
+use super::value::MAX_NESTING;
+use crate::progress::{Progress, ProgressCallback};
+use crate::span::{LineCounter, Span};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq)]
+// Each combinator nests the selector before it one level deeper, and
+// selectors are walked recursively, so longer ones are rejected as invalid.
+const MAX_COMPOUND_SELECTORS: usize = 256;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Declaration {
+    // ASCII-lowercased, except for custom properties, which are case-sensitive.
     pub property: String,
+    // The property name as written.
+    pub raw_property: String,
     pub value: String,
+    // The value exactly as authored, without `!important`, when the parser
+    // was asked to preserve it.
+    pub raw_value: Option<String>,
     pub important: bool,
+    // From the property name to the end of the value or `!important`.
+    pub span: Span,
+}
+
+// Declarations compare by content; where they were parsed doesn't matter.
+impl PartialEq for Declaration {
+    fn eq(&self, other: &Self) -> bool {
+        self.property == other.property
+            && self.raw_property == other.raw_property
+            && self.value == other.value
+            && self.raw_value == other.raw_value
+            && self.important == other.important
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Selector {
     Type(String),
     Class(String),
     Id(String),
     Universal,
+    Attribute {
+        name: String,
+        matcher: Option<AttrMatcher>,
+        value: Option<String>,
+        case: Option<AttrCase>,
+    },
+    // The argument of a functional pseudo-class such as `:nth-child(2n+1)`
+    // is kept as written, with whitespace collapsed.
+    PseudoClass(String, Option<String>),
+    PseudoElement(String),
+    Compound(Vec<Selector>),
     Descendant(Box<Selector>, Box<Selector>),
     Child(Box<Selector>, Box<Selector>),
     Adjacent(Box<Selector>, Box<Selector>),
     GeneralSibling(Box<Selector>, Box<Selector>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttrMatcher {
+    Equals,
+    Includes,
+    DashMatch,
+    Prefix,
+    Suffix,
+    Substring,
+}
+
+// The `i` and `s` flags of `[attr=value i]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttrCase {
+    Insensitive,
+    Sensitive,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    // From the first selector to the closing brace; `None` for rules that
+    // weren't parsed.
+    pub span: Option<Span>,
+}
+
+// Like declarations, rules compare by content.
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        self.selectors == other.selectors && self.declarations == other.declarations
+    }
+}
+
+pub(crate) fn normalize_property(property: &str) -> String {
+    if property.starts_with("--") {
+        property.to_string()
+    } else {
+        property.to_ascii_lowercase()
+    }
+}
+
+fn is_name_or_number(token: &CssToken) -> bool {
+    matches!(token, CssToken::Ident(_)) || numeric_value(token).is_some()
+}
+
+fn numeric_value(token: &CssToken) -> Option<f64> {
+    match token {
+        CssToken::Number(value) | CssToken::Dimension { value, .. } | CssToken::Percentage(value) => Some(*value),
+        _ => None,
+    }
+}
+
+impl Rule {
+    // The declaration that wins for `property` within this rule: the last one,
+    // unless an earlier one is `!important` and the later ones aren't.
+    pub fn get(&self, property: &str) -> Option<&Declaration> {
+        let property = normalize_property(property);
+        let mut matching = self.declarations.iter().filter(|declaration| declaration.property == property);
+
+        let mut winner = matching.next()?;
+        for declaration in matching {
+            if declaration.important || !winner.important {
+                winner = declaration;
+            }
+        }
+        Some(winner)
+    }
+
+    // Drops every declaration that `get` wouldn't return, keeping the winners
+    // in their original order.
+    pub fn dedup_declarations(&mut self) {
+        let mut winners: HashMap<&str, usize> = HashMap::new();
+        for (index, declaration) in self.declarations.iter().enumerate() {
+            match winners.get(declaration.property.as_str()) {
+                Some(&winner) if !declaration.important && self.declarations[winner].important => {}
+                _ => {
+                    winners.insert(&declaration.property, index);
+                }
+            }
+        }
+
+        let mut keep: Vec<bool> = vec![false; self.declarations.len()];
+        for index in winners.into_values() {
+            keep[index] = true;
+        }
+        let mut keep = keep.into_iter();
+        self.declarations.retain(|_| keep.next().unwrap_or(false));
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CssStats {
+    pub tokens: usize,
+    pub rules: usize,
+    pub bytes: usize,
+    pub max_depth: usize,
+    // Rules and declarations dropped because they failed to parse.
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CssParseOptions {
+    pub(crate) preserve_raw_values: bool,
+}
+
+impl CssParseOptions {
+    pub fn preserve_raw_values(mut self, enabled: bool) -> Self {
+        self.preserve_raw_values = enabled;
+        self
+    }
 }
 
 pub struct CssParser {
     tokens: Vec<CssToken>,
+    // Source span of each token, also used to tell where a comment separated
+    // two tokens once comments have been filtered out.
+    spans: Vec<Span>,
+    end_span: Span,
     position: usize,
+    depth: usize,
+    stats: CssStats,
+    progress: Progress,
+    // The input, kept only when raw values are preserved.
+    source: Option<String>,
+    // Why each skipped piece of input was skipped.
+    errors: Vec<CssError>,
 }
 
 impl CssParser {
+    pub fn with_options(input: &str, options: CssParseOptions) -> Self {
+        let mut parser = Self::new(input);
+        if options.preserve_raw_values {
+            parser.source = Some(input.to_string());
+        }
+        parser
+    }
+
     pub fn new(input: &str) -> Self {
-        let tokenizer = CssTokenizer::new(input);
-        let tokens: Vec<CssToken> = tokenizer.filter(|token| !matches!(token, CssToken::Whitespace)).collect();
-        
+        let (tokens, spans): (Vec<CssToken>, Vec<Span>) = CssTokenizer::new(input)
+            .spanned()
+            .filter(|(token, _)| !matches!(token, CssToken::Comment(_)))
+            .unzip();
+
         Self {
             tokens,
+            spans,
+            end_span: LineCounter::new(input).span(input.len(), input.len()),
             position: 0,
+            depth: 0,
+            stats: CssStats::default(),
+            progress: Progress::new(input.len()),
+            source: None,
+            errors: Vec::new(),
         }
     }
 
+    // Calls `callback` every `interval` tokens while parsing, and once more
+    // when parsing completes.
+    pub fn on_progress(mut self, interval: usize, callback: ProgressCallback) -> Self {
+        self.progress.set_callback(interval, callback);
+        self
+    }
+
+    pub fn stats(&self) -> CssStats {
+        CssStats {
+            tokens: self.position,
+            bytes: self.position.checked_sub(1).map_or(0, |last| self.spans[last].end),
+            ..self.stats.clone()
+        }
+    }
+
+    fn enter_block(&mut self) {
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+    }
+
     fn current_token(&self) -> Option<&CssToken> {
         self.tokens.get(self.position)
     }
@@ -48,10 +248,66 @@ impl CssParser {
     fn advance(&mut self) {
         if self.position < self.tokens.len() {
             self.position += 1;
+            self.progress.update(self.position, self.spans[self.position - 1].end);
         }
     }
 
-    fn parse_selector(&mut self) -> Option<Selector> {
+    fn current_span(&self) -> Span {
+        self.spans.get(self.position).copied().unwrap_or(self.end_span)
+    }
+
+    fn current_text(&self) -> String {
+        self.current_token().map(|token| token.to_string()).unwrap_or_default()
+    }
+
+    fn text_at(&self, index: usize) -> String {
+        self.tokens.get(index).map(|token| token.to_string()).unwrap_or_default()
+    }
+
+    fn span_at(&self, index: usize) -> Span {
+        self.spans.get(index).copied().unwrap_or(self.end_span)
+    }
+
+    // Explains why the declaration starting at `start` didn't parse.
+    fn declaration_error(&self, start: usize) -> CssError {
+        let skip_whitespace = |mut index: usize| {
+            while self.tokens.get(index) == Some(&CssToken::Whitespace) {
+                index += 1;
+            }
+            index
+        };
+
+        let property_index = skip_whitespace(start);
+        let Some(CssToken::Ident(property)) = self.tokens.get(property_index) else {
+            return CssError::InvalidDeclaration {
+                found: self.text_at(property_index),
+                span: self.span_at(property_index),
+            };
+        };
+
+        let colon_index = skip_whitespace(property_index + 1);
+        if self.tokens.get(colon_index) != Some(&CssToken::Colon) {
+            return CssError::MissingColon {
+                property: property.clone(),
+                span: self.span_at(colon_index),
+            };
+        }
+
+        CssError::InvalidDeclaration {
+            found: self.text_at(property_index),
+            span: self.span_at(property_index),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while matches!(self.current_token(), Some(CssToken::Whitespace)) {
+            self.advance();
+        }
+        self.position > start
+    }
+
+    fn parse_simple_selector(&mut self) -> Option<Selector> {
         match self.current_token()? {
             CssToken::Ident(name) => {
                 let selector = Selector::Type(name.clone());
@@ -77,15 +333,194 @@ impl CssParser {
                 self.advance();
                 Some(Selector::Universal)
             }
+            CssToken::LeftBracket => self.parse_attribute_selector(),
+            CssToken::Colon => self.parse_pseudo_selector(),
             _ => None,
         }
     }
 
+    fn parse_pseudo_selector(&mut self) -> Option<Selector> {
+        self.advance(); // Skip ':'
+        let element = matches!(self.current_token(), Some(CssToken::Colon));
+        if element {
+            self.advance(); // Skip second ':'
+        }
+
+        let name = match self.current_token() {
+            Some(CssToken::Ident(name)) => name.to_ascii_lowercase(),
+            _ => return None,
+        };
+        self.advance();
+
+        if element {
+            return Some(Selector::PseudoElement(name));
+        }
+
+        if !matches!(self.current_token(), Some(CssToken::LeftParen)) {
+            // CSS2 pseudo-elements may still be written with one colon.
+            if matches!(name.as_str(), "before" | "after" | "first-line" | "first-letter") {
+                return Some(Selector::PseudoElement(name));
+            }
+            return Some(Selector::PseudoClass(name, None));
+        }
+        self.advance(); // Skip '('
+
+        let start = self.position;
+        let mut depth = 0usize;
+        loop {
+            match self.current_token()? {
+                CssToken::LeftParen => depth += 1,
+                CssToken::RightParen if depth == 0 => break,
+                CssToken::RightParen => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+        let argument = self.serialize_tokens(start, self.position);
+        self.advance(); // Skip ')'
+
+        Some(Selector::PseudoClass(name, Some(argument)))
+    }
+
+    fn parse_attribute_selector(&mut self) -> Option<Selector> {
+        self.advance(); // Skip '['
+        self.skip_whitespace();
+
+        let name = match self.current_token() {
+            Some(CssToken::Ident(name)) => name.clone(),
+            _ => return None,
+        };
+        self.advance();
+        self.skip_whitespace();
+
+        let mut matcher = None;
+        let mut value = None;
+        let mut case = None;
+
+        if !matches!(self.current_token(), Some(CssToken::RightBracket)) {
+            matcher = Some(match self.current_token() {
+                Some(CssToken::Delim('=')) => AttrMatcher::Equals,
+                Some(CssToken::Delim('~')) => AttrMatcher::Includes,
+                Some(CssToken::Delim('|')) => AttrMatcher::DashMatch,
+                Some(CssToken::Delim('^')) => AttrMatcher::Prefix,
+                Some(CssToken::Delim('$')) => AttrMatcher::Suffix,
+                Some(CssToken::Delim('*')) => AttrMatcher::Substring,
+                _ => return None,
+            });
+            self.advance();
+            if matcher != Some(AttrMatcher::Equals) {
+                if !matches!(self.current_token(), Some(CssToken::Delim('='))) {
+                    return None;
+                }
+                self.advance();
+            }
+            self.skip_whitespace();
+
+            value = match self.current_token() {
+                Some(CssToken::Ident(value)) | Some(CssToken::String(value)) => Some(value.clone()),
+                _ => return None,
+            };
+            self.advance();
+            self.skip_whitespace();
+
+            if let Some(CssToken::Ident(flag)) = self.current_token() {
+                case = match flag.to_ascii_lowercase().as_str() {
+                    "i" => Some(AttrCase::Insensitive),
+                    "s" => Some(AttrCase::Sensitive),
+                    _ => return None,
+                };
+                self.advance();
+                self.skip_whitespace();
+            }
+        }
+
+        if !matches!(self.current_token(), Some(CssToken::RightBracket)) {
+            return None;
+        }
+        self.advance(); // Skip ']'
+
+        Some(Selector::Attribute { name, matcher, value, case })
+    }
+
+    fn starts_compound_selector(&self) -> bool {
+        matches!(
+            self.current_token(),
+            Some(CssToken::Ident(_))
+                | Some(CssToken::Hash(_))
+                | Some(CssToken::Delim('.'))
+                | Some(CssToken::Delim('*'))
+                | Some(CssToken::LeftBracket)
+                | Some(CssToken::Colon)
+        )
+    }
+
+    // Simple selectors written without whitespace between them form one
+    // compound selector; a lone simple selector is returned unwrapped.
+    fn parse_compound_selector(&mut self) -> Option<Selector> {
+        let mut parts = vec![self.parse_simple_selector()?];
+
+        while matches!(
+            self.current_token(),
+            Some(CssToken::Hash(_)) | Some(CssToken::Delim('.')) | Some(CssToken::LeftBracket) | Some(CssToken::Colon)
+        ) {
+            parts.push(self.parse_simple_selector()?);
+        }
+
+        if parts.len() == 1 {
+            parts.pop()
+        } else {
+            Some(Selector::Compound(parts))
+        }
+    }
+
+    fn parse_selector(&mut self) -> Option<Selector> {
+        self.skip_whitespace();
+        let mut selector = self.parse_compound_selector()?;
+        let mut compounds = 1;
+
+        // Combinators associate to the left: `a > b c` is `(a > b) c`.
+        loop {
+            let start = self.position;
+            let had_whitespace = self.skip_whitespace();
+
+            let combinator = match self.current_token() {
+                Some(CssToken::Delim(combinator @ ('>' | '+' | '~'))) => Some(*combinator),
+                _ => None,
+            };
+            let continues = combinator.is_some() || (had_whitespace && self.starts_compound_selector());
+            if continues && compounds == MAX_COMPOUND_SELECTORS {
+                return None;
+            }
+            compounds += 1;
+
+            if let Some(combinator) = combinator {
+                self.advance();
+                self.skip_whitespace();
+                let left = Box::new(selector);
+                let right = Box::new(self.parse_compound_selector()?);
+                selector = match combinator {
+                    '>' => Selector::Child(left, right),
+                    '+' => Selector::Adjacent(left, right),
+                    _ => Selector::GeneralSibling(left, right),
+                };
+            } else if had_whitespace && self.starts_compound_selector() {
+                let right = self.parse_compound_selector()?;
+                selector = Selector::Descendant(Box::new(selector), Box::new(right));
+            } else {
+                self.position = start;
+                break;
+            }
+        }
+
+        Some(selector)
+    }
+
     fn parse_selector_list(&mut self) -> Vec<Selector> {
         let mut selectors = Vec::new();
         
         while let Some(selector) = self.parse_selector() {
             selectors.push(selector);
+            self.skip_whitespace();
             
             if matches!(self.current_token(), Some(CssToken::Comma)) {
                 self.advance(); // Skip comma
@@ -97,77 +532,152 @@ impl CssParser {
         selectors
     }
 
+    fn separated_by_comment(&self, index: usize) -> bool {
+        index > 0 && self.spans[index - 1].end < self.spans[index].start
+    }
+
+    // Whitespace collapses to a single separator, as does a comment sitting
+    // between two tokens. Leading and trailing whitespace is dropped.
+    fn serialize_tokens(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut pending_separator = false;
+
+        for index in start..end {
+            if self.tokens[index] == CssToken::Whitespace {
+                pending_separator = true;
+                continue;
+            }
+            let separated = pending_separator || self.separated_by_comment(index);
+            if !text.is_empty() && separated {
+                text.push(' ');
+            }
+            // A number written right after another name or number had an
+            // explicit sign, which keeps `2n+1` from reading as `2n1`.
+            let adjacent = index > start && !separated && is_name_or_number(&self.tokens[index - 1]);
+            if adjacent && numeric_value(&self.tokens[index]).is_some_and(|value| value.is_sign_positive()) {
+                text.push('+');
+            }
+            pending_separator = false;
+            text.push_str(&self.tokens[index].to_string());
+        }
+
+        text
+    }
+
+    // Skips to the end of the current declaration, leaving a closing brace of
+    // the enclosing block in place.
+    fn skip_declaration(&mut self) {
+        let mut closers = Vec::new();
+
+        while let Some(token) = self.current_token() {
+            match token {
+                CssToken::Semicolon if closers.is_empty() => {
+                    self.advance();
+                    break;
+                }
+                CssToken::RightBrace if closers.is_empty() => break,
+                CssToken::LeftParen => closers.push(CssToken::RightParen),
+                CssToken::LeftBracket => closers.push(CssToken::RightBracket),
+                CssToken::LeftBrace => closers.push(CssToken::RightBrace),
+                token if closers.last() == Some(token) => {
+                    closers.pop();
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
     fn parse_declaration(&mut self) -> Option<Declaration> {
+        self.skip_whitespace();
+
         if let Some(CssToken::Ident(property)) = self.current_token() {
-            let property = property.clone();
+            let property_index = self.position;
+            let raw_property = property.clone();
+            let property = normalize_property(property);
             self.advance();
+            self.skip_whitespace();
             
             if matches!(self.current_token(), Some(CssToken::Colon)) {
                 self.advance(); // Skip colon
+                self.skip_whitespace();
                 
-                let mut value_parts = Vec::new();
-                let mut important = false;
-                
+                let start = self.position;
+                let mut closers = Vec::new();
+
                 while let Some(token) = self.current_token() {
                     match token {
-                        CssToken::Semicolon | CssToken::RightBrace => break,
-                        CssToken::Delim('!') => {
-                            self.advance();
-                            if let Some(CssToken::Ident(ident)) = self.current_token() {
-                                if ident == "important" {
-                                    important = true;
-                                    self.advance();
-                                }
-                            }
-                        }
-                        CssToken::Ident(s) => {
-                            value_parts.push(s.clone());
-                            self.advance();
-                        }
-                        CssToken::String(s) => {
-                            value_parts.push(format!("\"{}\"", s));
-                            self.advance();
-                        }
-                        CssToken::Number(n) => {
-                            value_parts.push(n.to_string());
-                            self.advance();
-                        }
-                        CssToken::Dimension { value, unit } => {
-                            value_parts.push(format!("{}{}", value, unit));
-                            self.advance();
-                        }
-                        CssToken::Percentage(p) => {
-                            value_parts.push(format!("{}%", p));
-                            self.advance();
-                        }
-                        CssToken::Hash(h) => {
-                            value_parts.push(format!("#{}", h));
-                            self.advance();
-                        }
-                        CssToken::Delim(c) => {
-                            value_parts.push(c.to_string());
-                            self.advance();
-                        }
-                        _ => {
-                            self.advance();
+                        CssToken::Semicolon | CssToken::RightBrace if closers.is_empty() => break,
+                        CssToken::LeftParen => closers.push(CssToken::RightParen),
+                        CssToken::LeftBracket => closers.push(CssToken::RightBracket),
+                        CssToken::LeftBrace => closers.push(CssToken::RightBrace),
+                        token if closers.last() == Some(token) => {
+                            closers.pop();
                         }
+                        _ => {}
                     }
+                    self.advance();
                 }
-                
-                let value = if value_parts.len() == 1 {
-                    value_parts[0].clone()
-                } else {
-                    value_parts.join(" ").trim().to_string()
-                };
-                
+
+                let mut end = self.position;
                 if matches!(self.current_token(), Some(CssToken::Semicolon)) {
                     self.advance(); // Skip semicolon
                 }
+
+                let significant: Vec<usize> = (start..end)
+                    .filter(|&index| self.tokens[index] != CssToken::Whitespace)
+                    .collect();
+
+                let span_end = significant.last().map_or(self.spans[property_index], |&last| self.spans[last]);
+                let span = Span {
+                    end: span_end.end,
+                    ..self.spans[property_index]
+                };
+
+                let mut important = false;
+                if let [.., bang, last] = significant[..] {
+                    if let (CssToken::Delim('!'), CssToken::Ident(ident)) = (&self.tokens[bang], &self.tokens[last]) {
+                        if ident.eq_ignore_ascii_case("important") {
+                            important = true;
+                            end = bang;
+                        }
+                    }
+                }
+                while end > start && self.tokens[end - 1] == CssToken::Whitespace {
+                    end -= 1;
+                }
+
+                let value_tokens: Vec<&CssToken> = self.tokens[start..end]
+                    .iter()
+                    .filter(|token| **token != CssToken::Whitespace)
+                    .collect();
+                let misplaced_important = value_tokens.windows(2).any(|pair| {
+                    matches!(pair, [CssToken::Delim('!'), CssToken::Ident(ident)] if ident.eq_ignore_ascii_case("important"))
+                });
+                let bad_token = value_tokens.iter().any(|token| matches!(token, CssToken::BadString | CssToken::BadUrl));
+                if misplaced_important || bad_token {
+                    return None;
+                }
+                if value_tokens.is_empty() && !property.starts_with("--") {
+                    return None;
+                }
+
+                let value = self.serialize_tokens(start, end);
+                let raw_value = self.source.as_ref().map(|source| {
+                    if start < end {
+                        source[self.spans[start].start..self.spans[end - 1].end].to_string()
+                    } else {
+                        String::new()
+                    }
+                });
                 
                 Some(Declaration {
                     property,
+                    raw_property,
                     value,
+                    raw_value,
                     important,
+                    span,
                 })
             } else {
                 None
@@ -177,50 +687,382 @@ impl CssParser {
         }
     }
 
-    fn parse_rule(&mut self) -> Option<Rule> {
-        let selectors = self.parse_selector_list();
-        
-        if selectors.is_empty() {
-            return None;
+    pub fn parse_declaration_list(&mut self) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
+
+        while self.position < self.tokens.len() {
+            match self.current_token() {
+                Some(CssToken::Semicolon) | Some(CssToken::RightBrace) | Some(CssToken::Whitespace) => self.advance(),
+                _ => {
+                    let start = self.position;
+                    if let Some(declaration) = self.parse_declaration() {
+                        declarations.push(declaration);
+                    } else {
+                        self.errors.push(self.declaration_error(start));
+                        self.position = start;
+                        self.skip_declaration();
+                        self.stats.skipped += 1;
+                    }
+                }
+            }
         }
-        
-        if !matches!(self.current_token(), Some(CssToken::LeftBrace)) {
-            return None;
+
+        self.progress.finish();
+        declarations
+    }
+
+    // Skips an unparseable rule: its prelude and, if present, its block. A
+    // closing brace belonging to an enclosing block is left in place.
+    fn skip_rule(&mut self) {
+        while let Some(token) = self.current_token() {
+            match token {
+                CssToken::LeftBrace => {
+                    self.skip_block();
+                    break;
+                }
+                CssToken::Semicolon => {
+                    self.advance();
+                    break;
+                }
+                CssToken::RightBrace => break,
+                _ => self.advance(),
+            }
         }
-        
+    }
+
+    fn skip_block(&mut self) {
         self.advance(); // Skip opening brace
-        
+        let mut depth = 1;
+
+        while let Some(token) = self.current_token() {
+            match token {
+                CssToken::LeftBrace => depth += 1,
+                CssToken::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+
+    // Called just after the opening brace.
+    fn parse_declaration_block(&mut self) -> Vec<Declaration> {
+        let opening_brace = self.span_at(self.position - 1);
         let mut declarations = Vec::new();
-        
-        while !matches!(self.current_token(), Some(CssToken::RightBrace)) && self.position < self.tokens.len() {
+        self.enter_block();
+
+        loop {
+            self.skip_whitespace();
+            if matches!(self.current_token(), Some(CssToken::RightBrace)) || self.position >= self.tokens.len() {
+                break;
+            }
+
+            let start = self.position;
             if let Some(declaration) = self.parse_declaration() {
                 declarations.push(declaration);
             } else {
-                self.advance(); // Skip unknown tokens
+                self.errors.push(self.declaration_error(start));
+                self.position = start;
+                self.skip_declaration();
+                self.stats.skipped += 1;
             }
         }
         
         if matches!(self.current_token(), Some(CssToken::RightBrace)) {
             self.advance(); // Skip closing brace
+        } else {
+            self.errors.push(CssError::UnclosedBlock { span: opening_brace });
+        }
+
+        self.depth -= 1;
+        declarations
+    }
+
+    fn parse_rule(&mut self) -> Option<Rule> {
+        self.skip_whitespace();
+        let start = self.current_span();
+        let selectors = self.parse_selector_list();
+        self.skip_whitespace();
+        
+        if selectors.is_empty() {
+            return None;
+        }
+        
+        if !matches!(self.current_token(), Some(CssToken::LeftBrace)) {
+            return None;
         }
         
+        self.advance(); // Skip opening brace
+        
+        let declarations = self.parse_declaration_block();
+        let end = self.spans[self.position - 1].end;
+
         Some(Rule {
             selectors,
             declarations,
+            span: Some(Span { end, ..start }),
         })
     }
 
-    pub fn parse(&mut self) -> Vec<Rule> {
+    fn contains_rule_list(at_rule_name: &str) -> bool {
+        matches!(
+            at_rule_name.to_ascii_lowercase().as_str(),
+            "media" | "supports" | "document" | "-moz-document" | "layer" | "container" | "scope" | "starting-style"
+        )
+    }
+
+    fn is_keyframes(at_rule_name: &str) -> bool {
+        matches!(
+            at_rule_name.to_ascii_lowercase().as_str(),
+            "keyframes" | "-webkit-keyframes" | "-moz-keyframes" | "-o-keyframes"
+        )
+    }
+
+    // `from`, `to` or a percentage, separated by commas.
+    fn parse_keyframe_selectors(&mut self) -> Option<Vec<KeyframeSelector>> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let selector = match self.current_token()? {
+                CssToken::Ident(ident) if ident.eq_ignore_ascii_case("from") => KeyframeSelector::From,
+                CssToken::Ident(ident) if ident.eq_ignore_ascii_case("to") => KeyframeSelector::To,
+                CssToken::Percentage(percentage) => KeyframeSelector::Percentage(*percentage),
+                _ => return None,
+            };
+            selectors.push(selector);
+            self.advance();
+            self.skip_whitespace();
+            match self.current_token()? {
+                CssToken::Comma => self.advance(),
+                CssToken::LeftBrace => return Some(selectors),
+                _ => return None,
+            }
+        }
+    }
+
+    // Called just after the opening brace of `@keyframes`.
+    fn parse_keyframe_list(&mut self) -> Vec<Keyframe> {
+        let opening_brace = self.span_at(self.position - 1);
+        let mut keyframes = Vec::new();
+        self.enter_block();
+
+        loop {
+            self.skip_whitespace();
+            match self.current_token() {
+                None => {
+                    self.errors.push(CssError::UnclosedBlock { span: opening_brace });
+                    break;
+                }
+                Some(CssToken::RightBrace) => {
+                    self.advance(); // Skip closing brace
+                    break;
+                }
+                _ => {}
+            }
+
+            let start = self.position;
+            if let Some(selectors) = self.parse_keyframe_selectors() {
+                self.advance(); // Skip opening brace
+                let declarations = self.parse_declaration_block();
+                keyframes.push(Keyframe { selectors, declarations });
+            } else {
+                self.errors.push(CssError::InvalidSelector {
+                    found: self.current_text(),
+                    span: self.current_span(),
+                });
+                self.position = start;
+                self.skip_rule();
+                self.stats.skipped += 1;
+            }
+        }
+
+        self.depth -= 1;
+        keyframes
+    }
+
+    fn parse_at_rule(&mut self) -> Option<AtRule> {
+        let name = match self.current_token() {
+            Some(CssToken::AtKeyword(name)) => name.clone(),
+            _ => return None,
+        };
+        self.advance();
+
+        let start = self.position;
+        let mut closers = Vec::new();
+        while let Some(token) = self.current_token() {
+            match token {
+                CssToken::LeftBrace | CssToken::Semicolon | CssToken::RightBrace if closers.is_empty() => break,
+                CssToken::LeftParen => closers.push(CssToken::RightParen),
+                CssToken::LeftBracket => closers.push(CssToken::RightBracket),
+                token if closers.last() == Some(token) => {
+                    closers.pop();
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+        let prelude = self.serialize_tokens(start, self.position);
+
+        let block = match self.current_token() {
+            // Rule lists nest by recursion, so past the limit the whole
+            // at-rule is dropped rather than parsed.
+            Some(CssToken::LeftBrace) if Self::contains_rule_list(&name) && self.depth >= MAX_NESTING => {
+                self.errors.push(CssError::NestingTooDeep { span: self.current_span() });
+                self.skip_block();
+                self.stats.skipped += 1;
+                return None;
+            }
+            Some(CssToken::LeftBrace) => {
+                self.advance(); // Skip opening brace
+                if Self::is_keyframes(&name) {
+                    Some(AtRuleBlock::Keyframes(self.parse_keyframe_list()))
+                } else if Self::contains_rule_list(&name) {
+                    self.enter_block();
+                    let opening_brace = self.span_at(self.position - 1);
+                    let rules = self.parse_rule_list(true);
+                    self.depth -= 1;
+                    if matches!(self.current_token(), Some(CssToken::RightBrace)) {
+                        self.advance(); // Skip closing brace
+                    } else {
+                        self.errors.push(CssError::UnclosedBlock { span: opening_brace });
+                    }
+                    Some(AtRuleBlock::Rules(rules))
+                } else {
+                    Some(AtRuleBlock::Declarations(self.parse_declaration_block()))
+                }
+            }
+            Some(CssToken::Semicolon) => {
+                self.advance(); // Skip semicolon
+                None
+            }
+            _ => None,
+        };
+
+        Some(AtRule { name, prelude, block })
+    }
+
+    fn parse_rule_list(&mut self, nested: bool) -> Vec<CssRule> {
         let mut rules = Vec::new();
         
-        while self.position < self.tokens.len() {
+        loop {
+            self.skip_whitespace();
+            match self.current_token() {
+                None => break,
+                Some(CssToken::RightBrace) if nested => break,
+                Some(CssToken::AtKeyword(_)) => {
+                    if let Some(at_rule) = self.parse_at_rule() {
+                        rules.push(CssRule::At(at_rule));
+                        self.stats.rules += 1;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let start = self.position;
             if let Some(rule) = self.parse_rule() {
-                rules.push(rule);
+                rules.push(CssRule::Style(rule));
+                self.stats.rules += 1;
             } else {
-                self.advance(); // Skip unknown tokens
+                let failed_at = self.position;
+                self.position = start;
+                self.skip_rule(); // Skip unknown tokens
+                self.stats.skipped += 1;
+                if self.position == start {
+                    self.errors.push(CssError::InvalidToken {
+                        found: self.current_text(),
+                        span: self.current_span(),
+                    });
+                    self.advance(); // Skip a stray closing brace
+                } else {
+                    self.errors.push(CssError::InvalidSelector {
+                        found: self.text_at(failed_at),
+                        span: self.span_at(failed_at),
+                    });
+                }
             }
         }
         
         rules
     }
+
+    pub fn parse_stylesheet(&mut self) -> Stylesheet {
+        let rules = self.parse_rule_list(false);
+        self.progress.finish();
+        Stylesheet::new(rules)
+    }
+
+    pub fn parse(&mut self) -> Vec<Rule> {
+        self.parse_stylesheet()
+            .rules
+            .into_iter()
+            .filter_map(|rule| match rule {
+                CssRule::Style(rule) => Some(rule),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Like `parse`, but also returns an error for each piece of input that
+    // was skipped, in source order.
+    pub fn parse_with_diagnostics(&mut self) -> (Vec<Rule>, Vec<CssError>) {
+        let rules = self.parse();
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|error| error.span().start);
+        (rules, errors)
+    }
+}
+
+pub fn parse_declaration_list(input: &str) -> Vec<Declaration> {
+    CssParser::new(input).parse_declaration_list()
+}
+
+pub fn try_parse_selector_list(input: &str) -> Result<Vec<Selector>, CssError> {
+    let mut parser = CssParser::new(input);
+    let mut selectors = Vec::new();
+
+    loop {
+        match parser.parse_selector() {
+            Some(selector) => selectors.push(selector),
+            None => {
+                return Err(CssError::InvalidSelector {
+                    found: parser.current_text(),
+                    span: parser.current_span(),
+                });
+            }
+        }
+
+        parser.skip_whitespace();
+        match parser.current_token() {
+            None => return Ok(selectors),
+            Some(CssToken::Comma) => parser.advance(),
+            Some(_) => {
+                return Err(CssError::InvalidSelector {
+                    found: parser.current_text(),
+                    span: parser.current_span(),
+                });
+            }
+        }
+    }
+}
+
+pub fn try_parse_declaration(input: &str) -> Result<Declaration, CssError> {
+    let mut parser = CssParser::new(input);
+
+    let declaration = parser.parse_declaration().ok_or_else(|| CssError::InvalidDeclaration {
+        found: parser.current_text(),
+        span: parser.current_span(),
+    })?;
+
+    parser.skip_whitespace();
+    if parser.current_token().is_some() {
+        return Err(CssError::InvalidDeclaration {
+            found: parser.current_text(),
+            span: parser.current_span(),
+        });
+    }
+
+    Ok(declaration)
 }
\ No newline at end of file
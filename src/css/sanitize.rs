@@ -0,0 +1,158 @@
+use super::parser::{parse_declaration_list, Declaration};
+use super::serializer::{serialize_declarations, CssWriteOptions};
+use super::tokenizer::CssToken;
+use super::value::{parse_component_values_within_limit, ComponentValue};
+
+// Properties that run code in some browsers, dropped under every policy.
+const SCRIPTABLE_PROPERTIES: &[&str] = &["behavior", "-moz-binding"];
+
+// URL schemes that are never allowed, even when `allow_urls` is set.
+const SCRIPT_SCHEMES: &[&str] = &["javascript:", "vbscript:", "livescript:"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StylePolicy {
+    // Lowercase property names to keep; `None` keeps every property.
+    pub(crate) allowed_properties: Option<Vec<String>>,
+    // `(property, keyword)` pairs dropped even when the property is allowed.
+    pub(crate) denied_values: Vec<(String, String)>,
+    // Keep `url()` values, except those using a script scheme.
+    pub(crate) allow_urls: bool,
+}
+
+impl Default for StylePolicy {
+    fn default() -> Self {
+        Self {
+            allowed_properties: None,
+            denied_values: vec![("position".to_string(), "fixed".to_string())],
+            allow_urls: false,
+        }
+    }
+}
+
+impl StylePolicy {
+    // The default policy restricted to `properties`.
+    pub fn allow_only(properties: &[&str]) -> Self {
+        Self {
+            allowed_properties: Some(properties.iter().map(|property| property.to_ascii_lowercase()).collect()),
+            ..Self::default()
+        }
+    }
+
+    // Also drop `property: keyword`, matched ASCII case-insensitively.
+    pub fn deny_value(mut self, property: &str, keyword: &str) -> Self {
+        self.denied_values.push((property.to_ascii_lowercase(), keyword.to_string()));
+        self
+    }
+
+    pub fn allow_urls(mut self, enabled: bool) -> Self {
+        self.allow_urls = enabled;
+        self
+    }
+
+    pub fn allows(&self, declaration: &Declaration) -> bool {
+        let property = declaration.property.as_str();
+        if SCRIPTABLE_PROPERTIES.contains(&property) {
+            return false;
+        }
+        if let Some(allowed) = &self.allowed_properties {
+            if !allowed.iter().any(|allowed| allowed == property) {
+                return false;
+            }
+        }
+
+        // Escapes are already decoded in component values, so `expr\65 ssion()`
+        // is caught like `expression()`. Values nested too deeply to check are
+        // dropped.
+        let Some(values) = parse_component_values_within_limit(&declaration.value) else {
+            return false;
+        };
+        let denied_keyword = values.iter().any(|value| match value {
+            ComponentValue::Token(CssToken::Ident(keyword)) => self
+                .denied_values
+                .iter()
+                .any(|(denied_property, denied)| denied_property == property && denied.eq_ignore_ascii_case(keyword)),
+            _ => false,
+        });
+
+        !denied_keyword && values.iter().all(|value| self.allows_value(value))
+    }
+
+    fn allows_value(&self, value: &ComponentValue) -> bool {
+        match value {
+            ComponentValue::Token(CssToken::Url(url)) => self.allows_url(url),
+            ComponentValue::Token(_) => true,
+            ComponentValue::Function { name, arguments } => {
+                match unprefixed(&name.to_ascii_lowercase()) {
+                    "expression" => return false,
+                    "url" | "image" | "image-set" if !self.allow_urls => return false,
+                    "url" => {
+                        let url: String = arguments.iter().map(component_text).collect();
+                        if !self.allows_url(url.trim().trim_matches(['"', '\''])) {
+                            return false;
+                        }
+                    }
+                    // These take URLs as plain strings as well.
+                    "image" | "image-set" => {
+                        let allowed = arguments.iter().all(|argument| match argument {
+                            ComponentValue::Token(CssToken::String(url)) => self.allows_url(url),
+                            _ => true,
+                        });
+                        if !allowed {
+                            return false;
+                        }
+                    }
+                    _ => {}
+                }
+                arguments.iter().all(|argument| self.allows_value(argument))
+            }
+            ComponentValue::Block { contents, .. } => contents.iter().all(|value| self.allows_value(value)),
+        }
+    }
+
+    // Browsers drop tabs and newlines from URLs, so `java\9 script:` is
+    // caught too.
+    fn allows_url(&self, url: &str) -> bool {
+        let url = url
+            .chars()
+            .filter(|ch| !matches!(ch, '\t' | '\n' | '\r'))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        self.allow_urls && !SCRIPT_SCHEMES.iter().any(|scheme| url.starts_with(scheme))
+    }
+}
+
+// Browsers read `-webkit-image-set()` as `image-set()`, so the vendor
+// prefix is ignored.
+fn unprefixed(name: &str) -> &str {
+    name.strip_prefix('-')
+        .and_then(|rest| rest.split_once('-'))
+        .map_or(name, |(_, name)| name)
+}
+
+fn component_text(value: &ComponentValue) -> String {
+    match value {
+        ComponentValue::Token(CssToken::String(string)) => string.clone(),
+        ComponentValue::Token(token) => token.to_string(),
+        ComponentValue::Function { name, arguments } => {
+            format!("{}({})", name, arguments.iter().map(component_text).collect::<String>())
+        }
+        ComponentValue::Block { open, contents } => {
+            let close = match open {
+                '(' => ')',
+                '[' => ']',
+                _ => '}',
+            };
+            format!("{}{}{}", open, contents.iter().map(component_text).collect::<String>(), close)
+        }
+    }
+}
+
+// Parses a `style` attribute value and writes back only the declarations the
+// policy allows.
+pub fn sanitize_inline_style(value: &str, policy: &StylePolicy) -> String {
+    let declarations: Vec<Declaration> = parse_declaration_list(value)
+        .into_iter()
+        .filter(|declaration| policy.allows(declaration))
+        .collect();
+    serialize_declarations(&declarations, &CssWriteOptions::default())
+}
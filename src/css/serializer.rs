@@ -0,0 +1,377 @@
+use super::parser::{AttrCase, AttrMatcher, Declaration, Rule, Selector};
+use super::stylesheet::{AtRule, AtRuleBlock, CssRule, Keyframe, KeyframeSelector, Stylesheet};
+use super::tokenizer::CssToken;
+use std::fmt;
+
+pub fn serialize_identifier(ident: &str, dest: &mut String) {
+    if ident == "-" {
+        dest.push_str("\\-");
+        return;
+    }
+
+    let starts_with_dash = ident.starts_with('-');
+
+    for (index, ch) in ident.chars().enumerate() {
+        match ch {
+            '\0' => dest.push('\u{FFFD}'),
+            '\u{1}'..='\u{1f}' | '\u{7f}' => escape_code_point(ch, dest),
+            '0'..='9' if index == 0 || (index == 1 && starts_with_dash) => escape_code_point(ch, dest),
+            ch if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || !ch.is_ascii() => dest.push(ch),
+            ch => {
+                dest.push('\\');
+                dest.push(ch);
+            }
+        }
+    }
+}
+
+pub fn serialize_name(name: &str, dest: &mut String) {
+    for ch in name.chars() {
+        match ch {
+            '\0' => dest.push('\u{FFFD}'),
+            '\u{1}'..='\u{1f}' | '\u{7f}' => escape_code_point(ch, dest),
+            ch if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || !ch.is_ascii() => dest.push(ch),
+            ch => {
+                dest.push('\\');
+                dest.push(ch);
+            }
+        }
+    }
+}
+
+pub fn serialize_string(string: &str, dest: &mut String) {
+    dest.push('"');
+    for ch in string.chars() {
+        match ch {
+            '\0' => dest.push('\u{FFFD}'),
+            '\u{1}'..='\u{1f}' | '\u{7f}' => escape_code_point(ch, dest),
+            '"' | '\\' => {
+                dest.push('\\');
+                dest.push(ch);
+            }
+            ch => dest.push(ch),
+        }
+    }
+    dest.push('"');
+}
+
+// The inside of an unquoted `url()`: whitespace, quotes, parens and
+// backslashes are escaped so the URL reads back as one token.
+pub fn serialize_url(url: &str, dest: &mut String) {
+    for ch in url.chars() {
+        match ch {
+            '\0' => dest.push('\u{FFFD}'),
+            '\u{1}'..='\u{1f}' | '\u{7f}' => escape_code_point(ch, dest),
+            ' ' | '"' | '\'' | '(' | ')' | '\\' => {
+                dest.push('\\');
+                dest.push(ch);
+            }
+            ch => dest.push(ch),
+        }
+    }
+}
+
+fn escape_code_point(ch: char, dest: &mut String) {
+    dest.push_str(&format!("\\{:x} ", ch as u32));
+}
+
+impl fmt::Display for CssToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut dest = String::new();
+        match self {
+            CssToken::Ident(ident) => serialize_identifier(ident, &mut dest),
+            CssToken::String(string) => serialize_string(string, &mut dest),
+            CssToken::Number(number) => dest.push_str(&number.to_string()),
+            CssToken::Dimension { value, unit } => {
+                dest.push_str(&value.to_string());
+                serialize_name(unit, &mut dest);
+            }
+            CssToken::Percentage(percentage) => {
+                dest.push_str(&percentage.to_string());
+                dest.push('%');
+            }
+            CssToken::Hash(hash) => {
+                dest.push('#');
+                serialize_name(hash, &mut dest);
+            }
+            CssToken::Delim(ch) => dest.push(*ch),
+            CssToken::LeftParen => dest.push('('),
+            CssToken::RightParen => dest.push(')'),
+            CssToken::LeftBrace => dest.push('{'),
+            CssToken::RightBrace => dest.push('}'),
+            CssToken::LeftBracket => dest.push('['),
+            CssToken::RightBracket => dest.push(']'),
+            CssToken::Comma => dest.push(','),
+            CssToken::Colon => dest.push(':'),
+            CssToken::Semicolon => dest.push(';'),
+            CssToken::Whitespace => dest.push(' '),
+            CssToken::Comment(comment) => {
+                dest.push_str("/*");
+                dest.push_str(comment);
+                dest.push_str("*/");
+            }
+            CssToken::AtKeyword(keyword) => {
+                dest.push('@');
+                serialize_identifier(keyword, &mut dest);
+            }
+            CssToken::UnicodeRange(range) => {
+                dest.push_str("U+");
+                dest.push_str(range);
+            }
+            CssToken::BadString | CssToken::BadUrl => {}
+            CssToken::Url(url) => {
+                dest.push_str("url(");
+                serialize_url(url, &mut dest);
+                dest.push(')');
+            }
+        }
+        f.write_str(&dest)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CssWriteOptions {
+    pub(crate) strip_important: bool,
+    // Leave out optional whitespace; otherwise rules are written one
+    // declaration per line, indented two spaces per level.
+    pub(crate) minify: bool,
+}
+
+impl CssWriteOptions {
+    pub fn strip_important(mut self, enabled: bool) -> Self {
+        self.strip_important = enabled;
+        self
+    }
+
+    pub fn minify(mut self, enabled: bool) -> Self {
+        self.minify = enabled;
+        self
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut dest = String::new();
+        match self {
+            Selector::Type(name) => serialize_identifier(name, &mut dest),
+            Selector::Class(name) => {
+                dest.push('.');
+                serialize_identifier(name, &mut dest);
+            }
+            Selector::Id(name) => {
+                dest.push('#');
+                serialize_identifier(name, &mut dest);
+            }
+            Selector::Universal => dest.push('*'),
+            Selector::Attribute { name, matcher, value, case } => {
+                dest.push('[');
+                serialize_identifier(name, &mut dest);
+                if let (Some(matcher), Some(value)) = (matcher, value) {
+                    dest.push_str(match matcher {
+                        AttrMatcher::Equals => "=",
+                        AttrMatcher::Includes => "~=",
+                        AttrMatcher::DashMatch => "|=",
+                        AttrMatcher::Prefix => "^=",
+                        AttrMatcher::Suffix => "$=",
+                        AttrMatcher::Substring => "*=",
+                    });
+                    serialize_string(value, &mut dest);
+                    match case {
+                        Some(AttrCase::Insensitive) => dest.push_str(" i"),
+                        Some(AttrCase::Sensitive) => dest.push_str(" s"),
+                        None => {}
+                    }
+                }
+                dest.push(']');
+            }
+            Selector::PseudoClass(name, argument) => {
+                dest.push(':');
+                serialize_identifier(name, &mut dest);
+                if let Some(argument) = argument {
+                    dest.push('(');
+                    dest.push_str(argument);
+                    dest.push(')');
+                }
+            }
+            Selector::PseudoElement(name) => {
+                dest.push_str("::");
+                serialize_identifier(name, &mut dest);
+            }
+            Selector::Compound(parts) => {
+                for part in parts {
+                    dest.push_str(&part.to_string());
+                }
+            }
+            Selector::Descendant(left, right) => dest.push_str(&format!("{} {}", left, right)),
+            Selector::Child(left, right) => dest.push_str(&format!("{} > {}", left, right)),
+            Selector::Adjacent(left, right) => dest.push_str(&format!("{} + {}", left, right)),
+            Selector::GeneralSibling(left, right) => dest.push_str(&format!("{} ~ {}", left, right)),
+        }
+        f.write_str(&dest)
+    }
+}
+
+impl Selector {
+    pub fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Declaration {
+    pub fn to_css(&self) -> String {
+        self.to_css_with(&CssWriteOptions::default())
+    }
+
+    pub fn to_css_with(&self, options: &CssWriteOptions) -> String {
+        let mut dest = String::new();
+        serialize_identifier(&self.property, &mut dest);
+        dest.push(':');
+        if !self.value.is_empty() {
+            if !options.minify {
+                dest.push(' ');
+            }
+            dest.push_str(&self.value);
+        }
+        if self.important && !options.strip_important {
+            dest.push_str(" !important");
+        }
+        dest
+    }
+}
+
+pub fn serialize_declarations(declarations: &[Declaration], options: &CssWriteOptions) -> String {
+    declarations
+        .iter()
+        .map(|declaration| declaration.to_css_with(options))
+        .collect::<Vec<_>>()
+        .join(if options.minify { ";" } else { "; " })
+}
+
+impl Rule {
+    pub fn to_css(&self) -> String {
+        self.to_css_with(&CssWriteOptions::default())
+    }
+
+    pub fn to_css_with(&self, options: &CssWriteOptions) -> String {
+        let mut dest = String::new();
+        write_rule(self, options, 0, &mut dest);
+        dest
+    }
+}
+
+pub fn serialize_rules(rules: &[Rule], options: &CssWriteOptions) -> String {
+    let rules: Vec<String> = rules.iter().map(|rule| rule.to_css_with(options)).collect();
+    rules.join(if options.minify { "" } else { "\n\n" })
+}
+
+impl Stylesheet {
+    pub fn to_css(&self) -> String {
+        self.to_css_with(&CssWriteOptions::default())
+    }
+
+    pub fn to_css_with(&self, options: &CssWriteOptions) -> String {
+        let mut dest = String::new();
+        write_css_rules(&self.rules, options, 0, &mut dest);
+        dest
+    }
+}
+
+fn write_css_rules(rules: &[CssRule], options: &CssWriteOptions, depth: usize, dest: &mut String) {
+    for (index, rule) in rules.iter().enumerate() {
+        if index > 0 && !options.minify {
+            dest.push_str("\n\n");
+        }
+        match rule {
+            CssRule::Style(rule) => write_rule(rule, options, depth, dest),
+            CssRule::At(rule) => write_at_rule(rule, options, depth, dest),
+        }
+    }
+}
+
+fn write_rule(rule: &Rule, options: &CssWriteOptions, depth: usize, dest: &mut String) {
+    let selectors: Vec<String> = rule.selectors.iter().map(Selector::to_string).collect();
+    indent(options, depth, dest);
+    dest.push_str(&selectors.join(if options.minify { "," } else { ", " }));
+    write_declaration_block(&rule.declarations, options, depth, dest);
+}
+
+fn write_at_rule(rule: &AtRule, options: &CssWriteOptions, depth: usize, dest: &mut String) {
+    indent(options, depth, dest);
+    dest.push('@');
+    serialize_identifier(&rule.name, dest);
+    if !rule.prelude.is_empty() {
+        dest.push(' ');
+        dest.push_str(&rule.prelude);
+    }
+
+    match &rule.block {
+        None => dest.push(';'),
+        Some(AtRuleBlock::Declarations(declarations)) => write_declaration_block(declarations, options, depth, dest),
+        Some(AtRuleBlock::Rules(rules)) if options.minify => {
+            dest.push('{');
+            write_css_rules(rules, options, depth + 1, dest);
+            dest.push('}');
+        }
+        Some(AtRuleBlock::Rules(rules)) if rules.is_empty() => dest.push_str(" {}"),
+        Some(AtRuleBlock::Rules(rules)) => {
+            dest.push_str(" {\n");
+            write_css_rules(rules, options, depth + 1, dest);
+            dest.push('\n');
+            indent(options, depth, dest);
+            dest.push('}');
+        }
+        Some(AtRuleBlock::Keyframes(keyframes)) if options.minify => {
+            dest.push('{');
+            keyframes.iter().for_each(|keyframe| write_keyframe(keyframe, options, depth + 1, dest));
+            dest.push('}');
+        }
+        Some(AtRuleBlock::Keyframes(keyframes)) if keyframes.is_empty() => dest.push_str(" {}"),
+        Some(AtRuleBlock::Keyframes(keyframes)) => {
+            dest.push_str(" {\n");
+            for (index, keyframe) in keyframes.iter().enumerate() {
+                if index > 0 {
+                    dest.push_str("\n\n");
+                }
+                write_keyframe(keyframe, options, depth + 1, dest);
+            }
+            dest.push('\n');
+            indent(options, depth, dest);
+            dest.push('}');
+        }
+    }
+}
+
+fn write_keyframe(keyframe: &Keyframe, options: &CssWriteOptions, depth: usize, dest: &mut String) {
+    let selectors: Vec<String> = keyframe.selectors.iter().map(KeyframeSelector::to_string).collect();
+    indent(options, depth, dest);
+    dest.push_str(&selectors.join(if options.minify { "," } else { ", " }));
+    write_declaration_block(&keyframe.declarations, options, depth, dest);
+}
+
+fn write_declaration_block(declarations: &[Declaration], options: &CssWriteOptions, depth: usize, dest: &mut String) {
+    if options.minify {
+        dest.push('{');
+        dest.push_str(&serialize_declarations(declarations, options));
+        dest.push('}');
+        return;
+    }
+    if declarations.is_empty() {
+        dest.push_str(" {}");
+        return;
+    }
+
+    dest.push_str(" {\n");
+    for declaration in declarations {
+        indent(options, depth + 1, dest);
+        dest.push_str(&declaration.to_css_with(options));
+        dest.push_str(";\n");
+    }
+    indent(options, depth, dest);
+    dest.push('}');
+}
+
+fn indent(options: &CssWriteOptions, depth: usize, dest: &mut String) {
+    if !options.minify {
+        dest.push_str(&"  ".repeat(depth));
+    }
+}
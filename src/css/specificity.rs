@@ -0,0 +1,65 @@
+use super::parser::{try_parse_selector_list, Selector};
+
+// Pseudo-elements that may be written with one colon, as in CSS2.
+const LEGACY_PSEUDO_ELEMENTS: &[&str] = &["before", "after", "first-line", "first-letter"];
+
+// Pseudo-class arguments are kept as text and parsed again at each level, so
+// ones nested deeper than this count for nothing rather than costing a parse
+// of the rest of the selector per level.
+const MAX_ARGUMENT_DEPTH: usize = 16;
+
+impl Selector {
+    // `(ids, classes, types)`: ids; classes, attribute selectors and
+    // pseudo-classes; type selectors and pseudo-elements. Compare the
+    // triples in order, as tuples do, to find the more specific selector.
+    pub fn specificity(&self) -> (u32, u32, u32) {
+        self.specificity_at(0)
+    }
+
+    // `depth` is how many pseudo-class arguments this selector is inside.
+    fn specificity_at(&self, depth: usize) -> (u32, u32, u32) {
+        match self {
+            Selector::Id(_) => (1, 0, 0),
+            Selector::Class(_) | Selector::Attribute { .. } => (0, 1, 0),
+            Selector::Type(_) | Selector::PseudoElement(_) => (0, 0, 1),
+            Selector::Universal => (0, 0, 0),
+            Selector::PseudoClass(name, argument) => pseudo_class_specificity(name, argument.as_deref(), depth),
+            Selector::Compound(parts) => parts.iter().map(|part| part.specificity_at(depth)).fold((0, 0, 0), add),
+            Selector::Descendant(left, right)
+            | Selector::Child(left, right)
+            | Selector::Adjacent(left, right)
+            | Selector::GeneralSibling(left, right) => add(left.specificity_at(depth), right.specificity_at(depth)),
+        }
+    }
+}
+
+fn add(a: (u32, u32, u32), b: (u32, u32, u32)) -> (u32, u32, u32) {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+// `:is()`, `:not()` and `:has()` count as their most specific argument and
+// `:where()` as nothing. `:nth-child(2n of .a)` adds its selector list to a
+// pseudo-class's own weight.
+fn pseudo_class_specificity(name: &str, argument: Option<&str>, depth: usize) -> (u32, u32, u32) {
+    let name = name.to_ascii_lowercase();
+    match name.as_str() {
+        name if LEGACY_PSEUDO_ELEMENTS.contains(&name) => (0, 0, 1),
+        "where" => (0, 0, 0),
+        "is" | "not" | "has" | "matches" | "any" => most_specific(argument.unwrap_or_default(), depth),
+        "nth-child" | "nth-last-child" => {
+            let of = argument.and_then(|argument| argument.split_once(" of "));
+            add((0, 1, 0), of.map_or((0, 0, 0), |(_, selectors)| most_specific(selectors, depth)))
+        }
+        _ => (0, 1, 0),
+    }
+}
+
+fn most_specific(selectors: &str, depth: usize) -> (u32, u32, u32) {
+    if depth >= MAX_ARGUMENT_DEPTH {
+        return (0, 0, 0);
+    }
+    try_parse_selector_list(selectors)
+        .ok()
+        .and_then(|selectors| selectors.iter().map(|selector| selector.specificity_at(depth + 1)).max())
+        .unwrap_or_default()
+}
@@ -0,0 +1,166 @@
+use super::media::{parse_media_query_list, MediaEnv};
+use super::parser::{Declaration, Rule};
+use super::value::{equivalent_values, EquivalenceOptions};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum CssRule {
+    Style(Rule),
+    At(AtRule),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtRule {
+    pub name: String,
+    pub prelude: String,
+    // `None` for statement at-rules such as `@import "a.css";`.
+    pub block: Option<AtRuleBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AtRuleBlock {
+    Rules(Vec<CssRule>),
+    Declarations(Vec<Declaration>),
+    // The frames of `@keyframes` and its vendor-prefixed forms.
+    Keyframes(Vec<Keyframe>),
+}
+
+// One frame of a `@keyframes` rule, such as `from, 50% { opacity: 0 }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub selectors: Vec<KeyframeSelector>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyframeSelector {
+    From,
+    To,
+    Percentage(f64),
+}
+
+impl KeyframeSelector {
+    // Where in the animation the frame applies, from 0 to 100.
+    pub fn percentage(&self) -> f64 {
+        match self {
+            KeyframeSelector::From => 0.0,
+            KeyframeSelector::To => 100.0,
+            KeyframeSelector::Percentage(percentage) => *percentage,
+        }
+    }
+}
+
+impl fmt::Display for KeyframeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyframeSelector::From => write!(f, "from"),
+            KeyframeSelector::To => write!(f, "to"),
+            KeyframeSelector::Percentage(percentage) => write!(f, "{}%", percentage),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stylesheet {
+    pub rules: Vec<CssRule>,
+}
+
+impl Stylesheet {
+    pub fn new(rules: Vec<CssRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn style_rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().filter_map(|rule| match rule {
+            CssRule::Style(rule) => Some(rule),
+            _ => None,
+        })
+    }
+
+    // The style rules that apply under `env`, in source order. `@media` blocks
+    // are entered only when their query matches; other grouping rules don't
+    // depend on the environment and are always entered.
+    pub fn rules_for(&self, env: &MediaEnv) -> Vec<&Rule> {
+        let mut rules = Vec::new();
+        collect_rules_for(&self.rules, env, &mut rules);
+        rules
+    }
+
+    pub fn merge(mut self, other: Stylesheet) -> Stylesheet {
+        self.rules.extend(other.rules);
+        self
+    }
+
+    // Like `merge`, but drops rules that duplicate a later rule, comparing
+    // declaration values by their canonical form. Keeping the last occurrence
+    // leaves the cascade order unchanged.
+    pub fn merge_dedup(self, other: Stylesheet) -> Stylesheet {
+        let merged = self.merge(other);
+        let options = EquivalenceOptions::default();
+        let mut rules: Vec<CssRule> = Vec::with_capacity(merged.rules.len());
+
+        for rule in merged.rules.into_iter().rev() {
+            if !rules.iter().any(|kept| rules_equivalent(kept, &rule, &options)) {
+                rules.push(rule);
+            }
+        }
+        rules.reverse();
+
+        Stylesheet { rules }
+    }
+}
+
+fn collect_rules_for<'a>(rules: &'a [CssRule], env: &MediaEnv, dest: &mut Vec<&'a Rule>) {
+    for rule in rules {
+        match rule {
+            CssRule::Style(rule) => dest.push(rule),
+            CssRule::At(AtRule { name, prelude, block: Some(AtRuleBlock::Rules(rules)) }) => {
+                if name.eq_ignore_ascii_case("media") && !parse_media_query_list(prelude).evaluate(env) {
+                    continue;
+                }
+                collect_rules_for(rules, env, dest);
+            }
+            CssRule::At(_) => {}
+        }
+    }
+}
+
+fn rules_equivalent(a: &CssRule, b: &CssRule, options: &EquivalenceOptions) -> bool {
+    match (a, b) {
+        (CssRule::Style(a), CssRule::Style(b)) => {
+            a.selectors == b.selectors && declarations_equivalent(&a.declarations, &b.declarations, options)
+        }
+        (CssRule::At(a), CssRule::At(b)) => {
+            a.name.eq_ignore_ascii_case(&b.name)
+                && a.prelude == b.prelude
+                && match (&a.block, &b.block) {
+                    (Some(AtRuleBlock::Rules(a)), Some(AtRuleBlock::Rules(b))) => {
+                        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| rules_equivalent(a, b, options))
+                    }
+                    (Some(AtRuleBlock::Declarations(a)), Some(AtRuleBlock::Declarations(b))) => {
+                        declarations_equivalent(a, b, options)
+                    }
+                    (Some(AtRuleBlock::Keyframes(a)), Some(AtRuleBlock::Keyframes(b))) => {
+                        a.len() == b.len()
+                            && a.iter().zip(b).all(|(a, b)| {
+                                a.selectors == b.selectors && declarations_equivalent(&a.declarations, &b.declarations, options)
+                            })
+                    }
+                    (None, None) => true,
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
+
+fn declarations_equivalent(a: &[Declaration], b: &[Declaration], options: &EquivalenceOptions) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.property.eq_ignore_ascii_case(&b.property)
+                && a.important == b.important
+                && equivalent_values(&a.value, &b.value, options)
+        })
+}
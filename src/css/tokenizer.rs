@@ -1,4 +1,9 @@
+use super::error::CssError;
+use crate::span::{LineCounter, Span};
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum CssToken {
     Ident(String),
     String(String),
@@ -19,12 +24,27 @@ pub enum CssToken {
     Whitespace,
     Comment(String),
     AtKeyword(String),
+    UnicodeRange(String),
+    BadString,
+    // An unquoted `url(...)`, with escapes decoded. A quoted one is an
+    // `Ident("url")`, a paren and a `String`, as it is written.
+    Url(String),
+    BadUrl,
+}
+
+// A saved position in a `CssTokenizer`'s input.
+#[derive(Debug, Clone)]
+pub struct CssCheckpoint<'a> {
+    position: usize,
+    current_char: Option<char>,
+    lines: LineCounter<'a>,
 }
 
 pub struct CssTokenizer<'a> {
     input: &'a str,
     position: usize,
     current_char: Option<char>,
+    lines: LineCounter<'a>,
 }
 
 impl<'a> CssTokenizer<'a> {
@@ -33,11 +53,40 @@ impl<'a> CssTokenizer<'a> {
             input,
             position: 0,
             current_char: None,
+            lines: LineCounter::new(input),
         };
         tokenizer.current_char = tokenizer.input.chars().next();
         tokenizer
     }
 
+    pub fn byte_position(&self) -> usize {
+        self.position
+    }
+
+    // Saves the tokenizer's place, to come back to with `restore`.
+    pub fn checkpoint(&self) -> CssCheckpoint<'a> {
+        CssCheckpoint {
+            position: self.position,
+            current_char: self.current_char,
+            lines: self.lines.clone(),
+        }
+    }
+
+    // Goes back to a checkpoint taken from this tokenizer.
+    pub fn restore(&mut self, checkpoint: CssCheckpoint<'a>) {
+        self.position = checkpoint.position;
+        self.current_char = checkpoint.current_char;
+        self.lines = checkpoint.lines;
+    }
+
+    // The token `next_token` would return, without consuming it.
+    pub fn peek_token(&mut self) -> Option<CssToken> {
+        let checkpoint = self.checkpoint();
+        let token = self.next_token();
+        self.restore(checkpoint);
+        token
+    }
+
     fn advance(&mut self) {
         if self.position < self.input.len() {
             self.position += self.current_char.map_or(0, |c| c.len_utf8());
@@ -55,6 +104,33 @@ impl<'a> CssTokenizer<'a> {
         }
     }
 
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.input[self.position..].chars().nth(n)
+    }
+
+    fn starts_escape(&self) -> bool {
+        self.current_char == Some('\\') && !matches!(self.peek(), None | Some('\n') | Some('\r') | Some('\u{c}'))
+    }
+
+    fn starts_ident(&self) -> bool {
+        match self.current_char {
+            Some('-') => match self.peek() {
+                Some(c) if is_name_start(c) || c == '-' => true,
+                Some('\\') => !matches!(self.peek_nth(2), None | Some('\n') | Some('\r') | Some('\u{c}')),
+                _ => false,
+            },
+            Some(c) if is_name_start(c) => true,
+            Some('\\') => self.starts_escape(),
+            _ => false,
+        }
+    }
+
+    fn starts_unicode_range(&self) -> bool {
+        matches!(self.current_char, Some('u') | Some('U'))
+            && self.peek() == Some('+')
+            && matches!(self.peek_nth(2), Some(c) if c.is_ascii_hexdigit() || c == '?')
+    }
+
     fn consume_while<F>(&mut self, predicate: F) -> String
     where
         F: Fn(char) -> bool,
@@ -71,7 +147,44 @@ impl<'a> CssTokenizer<'a> {
         result
     }
 
-    fn parse_string(&mut self, quote: char) -> String {
+    fn parse_escape(&mut self) -> char {
+        self.advance(); // Skip '\\'
+
+        match self.current_char {
+            Some(ch) if ch.is_ascii_hexdigit() => {
+                let mut hex = String::new();
+                while let Some(ch) = self.current_char {
+                    if ch.is_ascii_hexdigit() && hex.len() < 6 {
+                        hex.push(ch);
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                if matches!(self.current_char, Some(' ') | Some('\t') | Some('\n')) {
+                    self.advance();
+                } else if self.current_char == Some('\r') {
+                    self.advance();
+                    if self.current_char == Some('\n') {
+                        self.advance();
+                    }
+                }
+
+                let code = u32::from_str_radix(&hex, 16).unwrap_or(0);
+                match char::from_u32(code) {
+                    Some(ch) if code != 0 => ch,
+                    _ => '\u{FFFD}',
+                }
+            }
+            Some(ch) => {
+                self.advance();
+                ch
+            }
+            None => '\u{FFFD}',
+        }
+    }
+
+    fn parse_string(&mut self, quote: char) -> Option<String> {
         let mut result = String::new();
         self.advance(); // Skip opening quote
         
@@ -79,11 +192,25 @@ impl<'a> CssTokenizer<'a> {
             if ch == quote {
                 self.advance(); // Skip closing quote
                 break;
+            } else if ch == '\n' || ch == '\r' || ch == '\u{c}' {
+                // An unescaped newline ends the string as a bad string; the
+                // newline itself is left for the whitespace token.
+                return None;
             } else if ch == '\\' {
-                self.advance();
-                if let Some(escaped) = self.current_char {
-                    result.push(escaped);
-                    self.advance();
+                match self.peek() {
+                    None => self.advance(),
+                    Some('\n') | Some('\u{c}') => {
+                        self.advance();
+                        self.advance();
+                    }
+                    Some('\r') => {
+                        self.advance();
+                        self.advance();
+                        if self.current_char == Some('\n') {
+                            self.advance();
+                        }
+                    }
+                    Some(_) => result.push(self.parse_escape()),
                 }
             } else {
                 result.push(ch);
@@ -91,16 +218,140 @@ impl<'a> CssTokenizer<'a> {
             }
         }
         
-        result
+        Some(result)
+    }
+
+    // A sign only starts a number when a digit follows, so `-webkit-box` and
+    // `--x` stay idents and a `+` between selectors stays a delimiter.
+    fn starts_number(&self) -> bool {
+        let digit_after_dot = |n| self.peek_nth(n) == Some('.') && self.peek_nth(n + 1).is_some_and(|c| c.is_ascii_digit());
+        match self.current_char {
+            Some('+' | '-') => self.peek().is_some_and(|c| c.is_ascii_digit()) || digit_after_dot(1),
+            Some(c) => c.is_ascii_digit() || digit_after_dot(0),
+            None => false,
+        }
     }
 
     fn parse_number(&mut self) -> f64 {
-        let number_str = self.consume_while(|c| c.is_ascii_digit() || c == '.');
+        let mut number_str = String::new();
+        if let Some(sign @ ('+' | '-')) = self.current_char {
+            number_str.push(sign);
+            self.advance();
+        }
+        number_str.push_str(&self.consume_while(|c| c.is_ascii_digit()));
+        if self.current_char == Some('.') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            number_str.push('.');
+            self.advance(); // Skip '.'
+            number_str.push_str(&self.consume_while(|c| c.is_ascii_digit()));
+        }
+        // An exponent needs a digit, so the `e` of `1em` is left for the unit.
+        if matches!(self.current_char, Some('e' | 'E')) {
+            let exponent_digits = match self.peek() {
+                Some('+' | '-') => 2,
+                _ => 1,
+            };
+            if self.peek_nth(exponent_digits).is_some_and(|c| c.is_ascii_digit()) {
+                for _ in 0..exponent_digits {
+                    number_str.extend(self.current_char);
+                    self.advance();
+                }
+                number_str.push_str(&self.consume_while(|c| c.is_ascii_digit()));
+            }
+        }
         number_str.parse().unwrap_or(0.0)
     }
 
     fn parse_ident(&mut self) -> String {
-        self.consume_while(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        let mut result = String::new();
+        while let Some(ch) = self.current_char {
+            if is_name_char(ch) {
+                result.push(ch);
+                self.advance();
+            } else if self.starts_escape() {
+                result.push(self.parse_escape());
+            } else {
+                break;
+            }
+        }
+        result
+    }
+
+    fn parse_unicode_range(&mut self) -> String {
+        self.advance(); // Skip 'u'
+        self.advance(); // Skip '+'
+
+        let mut range = self.consume_while(|c| c.is_ascii_hexdigit());
+        while self.current_char == Some('?') && range.len() < 6 {
+            range.push('?');
+            self.advance();
+        }
+        if !range.contains('?')
+            && self.current_char == Some('-')
+            && matches!(self.peek(), Some(c) if c.is_ascii_hexdigit())
+        {
+            self.advance(); // Skip '-'
+            range.push('-');
+            range.push_str(&self.consume_while(|c| c.is_ascii_hexdigit()));
+        }
+        range.to_ascii_uppercase()
+    }
+
+    // Whether the `(` at the current position opens a quoted URL, which is
+    // left to be tokenized as a function taking a string.
+    fn url_is_quoted(&self) -> bool {
+        let rest = self.input[self.position + 1..].trim_start_matches(is_url_whitespace);
+        rest.starts_with(['"', '\''])
+    }
+
+    // Consumes an unquoted URL from its `(` through the closing `)`.
+    // Whitespace may surround the URL but not split it.
+    fn parse_url(&mut self) -> CssToken {
+        self.advance(); // Skip '('
+        self.consume_while(is_url_whitespace);
+
+        let mut url = String::new();
+        while let Some(ch) = self.current_char {
+            match ch {
+                ')' => {
+                    self.advance();
+                    break;
+                }
+                _ if is_url_whitespace(ch) => {
+                    self.consume_while(is_url_whitespace);
+                    match self.current_char {
+                        Some(')') => self.advance(),
+                        Some(_) => return self.parse_bad_url(),
+                        None => {}
+                    }
+                    break;
+                }
+                '"' | '\'' | '(' => return self.parse_bad_url(),
+                _ if is_non_printable(ch) => return self.parse_bad_url(),
+                '\\' if self.starts_escape() => url.push(self.parse_escape()),
+                '\\' => return self.parse_bad_url(),
+                _ => {
+                    url.push(ch);
+                    self.advance();
+                }
+            }
+        }
+        CssToken::Url(url)
+    }
+
+    // Skips the rest of a malformed URL, through the next `)` that isn't
+    // escaped.
+    fn parse_bad_url(&mut self) -> CssToken {
+        while let Some(ch) = self.current_char {
+            if ch == ')' {
+                self.advance();
+                break;
+            } else if self.starts_escape() {
+                self.parse_escape();
+            } else {
+                self.advance();
+            }
+        }
+        CssToken::BadUrl
     }
 
     fn parse_comment(&mut self) -> String {
@@ -132,16 +383,19 @@ impl<'a> CssTokenizer<'a> {
                 let comment = self.parse_comment();
                 Some(CssToken::Comment(comment))
             }
-            '"' => {
-                let string = self.parse_string('"');
-                Some(CssToken::String(string))
-            }
-            '\'' => {
-                let string = self.parse_string('\'');
-                Some(CssToken::String(string))
-            }
-            '#' => {
+            '"' => match self.parse_string('"') {
+                Some(string) => Some(CssToken::String(string)),
+                None => Some(CssToken::BadString),
+            },
+            '\'' => match self.parse_string('\'') {
+                Some(string) => Some(CssToken::String(string)),
+                None => Some(CssToken::BadString),
+            },
+            '#' if self.peek().is_some_and(is_name_char) || self.peek() == Some('\\') => {
                 self.advance(); // Skip '#'
+                if self.current_char == Some('\\') && !self.starts_escape() {
+                    return Some(CssToken::Delim('#'));
+                }
                 let hash = self.parse_ident();
                 Some(CssToken::Hash(hash))
             }
@@ -186,7 +440,7 @@ impl<'a> CssTokenizer<'a> {
                 self.advance();
                 Some(CssToken::Semicolon)
             }
-            ch if ch.is_ascii_digit() => {
+            _ if self.starts_number() => {
                 let number = self.parse_number();
                 
                 if self.current_char == Some('%') {
@@ -203,8 +457,15 @@ impl<'a> CssTokenizer<'a> {
                     Some(CssToken::Number(number))
                 }
             }
-            ch if ch.is_alphabetic() || ch == '-' || ch == '_' => {
+            _ if self.starts_unicode_range() => {
+                let range = self.parse_unicode_range();
+                Some(CssToken::UnicodeRange(range))
+            }
+            _ if self.starts_ident() => {
                 let ident = self.parse_ident();
+                if ident.eq_ignore_ascii_case("url") && self.current_char == Some('(') && !self.url_is_quoted() {
+                    return Some(self.parse_url());
+                }
                 Some(CssToken::Ident(ident))
             }
             ch => {
@@ -221,4 +482,191 @@ impl<'a> Iterator for CssTokenizer<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.next_token()
     }
+}
+
+// Pairs each token with where it appears in the input.
+pub struct SpannedCssTokens<'a> {
+    tokenizer: CssTokenizer<'a>,
+}
+
+impl<'a> CssTokenizer<'a> {
+    pub fn next_token_spanned(&mut self) -> Option<(CssToken, Span)> {
+        let start = self.position;
+        let token = self.next_token()?;
+        Some((token, self.lines.span(start, self.position)))
+    }
+
+    pub fn spanned(self) -> SpannedCssTokens<'a> {
+        SpannedCssTokens { tokenizer: self }
+    }
+}
+
+impl<'a> Iterator for SpannedCssTokens<'a> {
+    type Item = (CssToken, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokenizer.next_token_spanned()
+    }
+}
+
+// Skips whitespace and comments as tokens are pulled, without collecting.
+pub struct SignificantTokens<'a> {
+    tokenizer: CssTokenizer<'a>,
+}
+
+impl<'a> SignificantTokens<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            tokenizer: CssTokenizer::new(input),
+        }
+    }
+
+    pub fn byte_position(&self) -> usize {
+        self.tokenizer.byte_position()
+    }
+}
+
+impl<'a> CssTokenizer<'a> {
+    pub fn significant(self) -> SignificantTokens<'a> {
+        SignificantTokens { tokenizer: self }
+    }
+}
+
+impl<'a> Iterator for SignificantTokens<'a> {
+    type Item = CssToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.tokenizer.next_token()? {
+                CssToken::Whitespace | CssToken::Comment(_) => continue,
+                token => return Some(token),
+            }
+        }
+    }
+}
+
+// A token whose text is borrowed from an owned `CssToken`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum CssTokenRef<'a> {
+    Ident(&'a str),
+    String(&'a str),
+    Number(f64),
+    Dimension { value: f64, unit: &'a str },
+    Percentage(f64),
+    Hash(&'a str),
+    Delim(char),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Colon,
+    Semicolon,
+    Whitespace,
+    Comment(&'a str),
+    AtKeyword(&'a str),
+    UnicodeRange(&'a str),
+    BadString,
+    Url(&'a str),
+    BadUrl,
+}
+
+impl<'a> From<&'a CssToken> for CssTokenRef<'a> {
+    fn from(token: &'a CssToken) -> Self {
+        match token {
+            CssToken::Ident(ident) => CssTokenRef::Ident(ident),
+            CssToken::String(string) => CssTokenRef::String(string),
+            CssToken::Number(number) => CssTokenRef::Number(*number),
+            CssToken::Dimension { value, unit } => CssTokenRef::Dimension { value: *value, unit },
+            CssToken::Percentage(percentage) => CssTokenRef::Percentage(*percentage),
+            CssToken::Hash(hash) => CssTokenRef::Hash(hash),
+            CssToken::Delim(ch) => CssTokenRef::Delim(*ch),
+            CssToken::LeftParen => CssTokenRef::LeftParen,
+            CssToken::RightParen => CssTokenRef::RightParen,
+            CssToken::LeftBrace => CssTokenRef::LeftBrace,
+            CssToken::RightBrace => CssTokenRef::RightBrace,
+            CssToken::LeftBracket => CssTokenRef::LeftBracket,
+            CssToken::RightBracket => CssTokenRef::RightBracket,
+            CssToken::Comma => CssTokenRef::Comma,
+            CssToken::Colon => CssTokenRef::Colon,
+            CssToken::Semicolon => CssTokenRef::Semicolon,
+            CssToken::Whitespace => CssTokenRef::Whitespace,
+            CssToken::Comment(comment) => CssTokenRef::Comment(comment),
+            CssToken::AtKeyword(keyword) => CssTokenRef::AtKeyword(keyword),
+            CssToken::UnicodeRange(range) => CssTokenRef::UnicodeRange(range),
+            CssToken::BadString => CssTokenRef::BadString,
+            CssToken::Url(url) => CssTokenRef::Url(url),
+            CssToken::BadUrl => CssTokenRef::BadUrl,
+        }
+    }
+}
+
+impl From<CssTokenRef<'_>> for CssToken {
+    fn from(token: CssTokenRef<'_>) -> Self {
+        match token {
+            CssTokenRef::Ident(ident) => CssToken::Ident(ident.to_string()),
+            CssTokenRef::String(string) => CssToken::String(string.to_string()),
+            CssTokenRef::Number(number) => CssToken::Number(number),
+            CssTokenRef::Dimension { value, unit } => CssToken::Dimension { value, unit: unit.to_string() },
+            CssTokenRef::Percentage(percentage) => CssToken::Percentage(percentage),
+            CssTokenRef::Hash(hash) => CssToken::Hash(hash.to_string()),
+            CssTokenRef::Delim(ch) => CssToken::Delim(ch),
+            CssTokenRef::LeftParen => CssToken::LeftParen,
+            CssTokenRef::RightParen => CssToken::RightParen,
+            CssTokenRef::LeftBrace => CssToken::LeftBrace,
+            CssTokenRef::RightBrace => CssToken::RightBrace,
+            CssTokenRef::LeftBracket => CssToken::LeftBracket,
+            CssTokenRef::RightBracket => CssToken::RightBracket,
+            CssTokenRef::Comma => CssToken::Comma,
+            CssTokenRef::Colon => CssToken::Colon,
+            CssTokenRef::Semicolon => CssToken::Semicolon,
+            CssTokenRef::Whitespace => CssToken::Whitespace,
+            CssTokenRef::Comment(comment) => CssToken::Comment(comment.to_string()),
+            CssTokenRef::AtKeyword(keyword) => CssToken::AtKeyword(keyword.to_string()),
+            CssTokenRef::UnicodeRange(range) => CssToken::UnicodeRange(range.to_string()),
+            CssTokenRef::BadString => CssToken::BadString,
+            CssTokenRef::Url(url) => CssToken::Url(url.to_string()),
+            CssTokenRef::BadUrl => CssToken::BadUrl,
+        }
+    }
+}
+
+// Parses text that must be exactly one token, such as `12px` or `#fff`.
+impl TryFrom<&str> for CssToken {
+    type Error = CssError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        let mut tokenizer = CssTokenizer::new(input);
+        let token = tokenizer.next_token();
+        let end = tokenizer.byte_position();
+        match token {
+            Some(token) if end == input.len() => Ok(token),
+            _ => {
+                let start = if token.is_some() { end } else { 0 };
+                Err(CssError::InvalidToken {
+                    found: input[start..].to_string(),
+                    span: LineCounter::new(input).span(start, input.len()),
+                })
+            }
+        }
+    }
+}
+
+fn is_name_start(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '_' || !ch.is_ascii()
+}
+
+fn is_name_char(ch: char) -> bool {
+    is_name_start(ch) || ch.is_ascii_digit() || ch == '-'
+}
+
+fn is_url_whitespace(ch: char) -> bool {
+    matches!(ch, ' ' | '\t' | '\n' | '\r' | '\u{c}')
+}
+
+fn is_non_printable(ch: char) -> bool {
+    matches!(ch, '\u{0}'..='\u{8}' | '\u{b}' | '\u{e}'..='\u{1f}' | '\u{7f}')
 }
\ No newline at end of file
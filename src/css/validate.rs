@@ -0,0 +1,175 @@
+use super::parser::Declaration;
+use super::stylesheet::{AtRuleBlock, CssRule, Stylesheet};
+use super::tokenizer::CssToken;
+use super::value::ComponentValue;
+use crate::span::Span;
+
+// What a property's numeric values are expected to look like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueKind {
+    // Non-zero numbers need a length unit.
+    Length,
+    // A length, where a small unitless number was probably meant as em/rem.
+    FontSize,
+    // Non-zero numbers need `s` or `ms`.
+    Time,
+    // A number from 0 to 1 or a percentage up to 100%.
+    Alpha,
+    // Unitless numbers are expected.
+    Number,
+}
+
+// Sorted by property name. Properties not listed here aren't checked.
+pub(crate) const PROPERTY_EXPECTATIONS: &[(&str, ValueKind)] = &[
+    ("animation-delay", ValueKind::Time),
+    ("animation-duration", ValueKind::Time),
+    ("border-bottom-width", ValueKind::Length),
+    ("border-left-width", ValueKind::Length),
+    ("border-radius", ValueKind::Length),
+    ("border-right-width", ValueKind::Length),
+    ("border-spacing", ValueKind::Length),
+    ("border-top-width", ValueKind::Length),
+    ("border-width", ValueKind::Length),
+    ("bottom", ValueKind::Length),
+    ("column-count", ValueKind::Number),
+    ("column-gap", ValueKind::Length),
+    ("fill-opacity", ValueKind::Alpha),
+    ("flex", ValueKind::Number),
+    ("flex-basis", ValueKind::Length),
+    ("flex-grow", ValueKind::Number),
+    ("flex-shrink", ValueKind::Number),
+    ("flood-opacity", ValueKind::Alpha),
+    ("font-size", ValueKind::FontSize),
+    ("font-weight", ValueKind::Number),
+    ("gap", ValueKind::Length),
+    ("height", ValueKind::Length),
+    ("inset", ValueKind::Length),
+    ("left", ValueKind::Length),
+    ("letter-spacing", ValueKind::Length),
+    ("line-height", ValueKind::Number),
+    ("margin", ValueKind::Length),
+    ("margin-bottom", ValueKind::Length),
+    ("margin-left", ValueKind::Length),
+    ("margin-right", ValueKind::Length),
+    ("margin-top", ValueKind::Length),
+    ("max-height", ValueKind::Length),
+    ("max-width", ValueKind::Length),
+    ("min-height", ValueKind::Length),
+    ("min-width", ValueKind::Length),
+    ("opacity", ValueKind::Alpha),
+    ("order", ValueKind::Number),
+    ("orphans", ValueKind::Number),
+    ("outline-offset", ValueKind::Length),
+    ("outline-width", ValueKind::Length),
+    ("padding", ValueKind::Length),
+    ("padding-bottom", ValueKind::Length),
+    ("padding-left", ValueKind::Length),
+    ("padding-right", ValueKind::Length),
+    ("padding-top", ValueKind::Length),
+    ("right", ValueKind::Length),
+    ("row-gap", ValueKind::Length),
+    ("stop-opacity", ValueKind::Alpha),
+    ("stroke-opacity", ValueKind::Alpha),
+    ("tab-size", ValueKind::Number),
+    ("text-indent", ValueKind::Length),
+    ("top", ValueKind::Length),
+    ("transition", ValueKind::Time),
+    ("transition-delay", ValueKind::Time),
+    ("transition-duration", ValueKind::Time),
+    ("widows", ValueKind::Number),
+    ("width", ValueKind::Length),
+    ("word-spacing", ValueKind::Length),
+    ("z-index", ValueKind::Number),
+];
+
+pub(crate) fn expected_kind(property: &str) -> Option<ValueKind> {
+    PROPERTY_EXPECTATIONS
+        .binary_search_by(|(name, _)| (*name).cmp(property))
+        .ok()
+        .map(|index| PROPERTY_EXPECTATIONS[index].1)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueWarning {
+    pub property: String,
+    pub value: String,
+    pub message: String,
+    pub span: Span,
+}
+
+// Values that parse but probably don't mean what the author intended, such
+// as `width: 100` or `opacity: 150%`. Only top-level values are checked, so
+// numbers inside functions like `calc()` or `cubic-bezier()` never warn.
+pub fn suspicious_values(stylesheet: &Stylesheet) -> Vec<ValueWarning> {
+    let mut warnings = Vec::new();
+    check_rules(&stylesheet.rules, &mut warnings);
+    warnings
+}
+
+fn check_rules(rules: &[CssRule], warnings: &mut Vec<ValueWarning>) {
+    for rule in rules {
+        match rule {
+            CssRule::Style(rule) => check_declarations(&rule.declarations, warnings),
+            CssRule::At(at_rule) => match &at_rule.block {
+                Some(AtRuleBlock::Rules(rules)) => check_rules(rules, warnings),
+                Some(AtRuleBlock::Declarations(declarations)) => check_declarations(declarations, warnings),
+                Some(AtRuleBlock::Keyframes(keyframes)) => {
+                    keyframes.iter().for_each(|keyframe| check_declarations(&keyframe.declarations, warnings))
+                }
+                None => {}
+            },
+        }
+    }
+}
+
+fn check_declarations(declarations: &[Declaration], warnings: &mut Vec<ValueWarning>) {
+    for declaration in declarations {
+        let Some(kind) = expected_kind(&declaration.property) else {
+            continue;
+        };
+
+        for value in declaration.component_values() {
+            let ComponentValue::Token(token) = value else {
+                continue;
+            };
+            if let Some(message) = check_token(&declaration.property, kind, &token) {
+                warnings.push(ValueWarning {
+                    property: declaration.property.clone(),
+                    value: declaration.value.clone(),
+                    message,
+                    span: declaration.span,
+                });
+            }
+        }
+    }
+}
+
+fn check_token(property: &str, kind: ValueKind, token: &CssToken) -> Option<String> {
+    match (kind, token) {
+        (ValueKind::Length, CssToken::Number(number)) if *number != 0.0 => Some(format!(
+            "`{}: {}` has no unit; only 0 may be written without one (did you mean {}px?)",
+            property, token, token
+        )),
+        // Small sizes read like ems, larger ones like pixels.
+        (ValueKind::FontSize, CssToken::Number(number)) if *number != 0.0 && *number <= 4.0 => Some(format!(
+            "`{}: {}` has no unit (did you mean {}em or {}rem?)",
+            property, token, token, token
+        )),
+        (ValueKind::FontSize, CssToken::Number(number)) if *number != 0.0 => Some(format!(
+            "`{}: {}` has no unit (did you mean {}px?)",
+            property, token, token
+        )),
+        (ValueKind::Time, CssToken::Number(number)) if *number != 0.0 => Some(format!(
+            "`{}: {}` has no unit; durations need `s` or `ms`",
+            property, token
+        )),
+        (ValueKind::Alpha, CssToken::Percentage(percentage)) if *percentage > 100.0 => {
+            Some(format!("`{}: {}` is over 100%", property, token))
+        }
+        (ValueKind::Alpha, CssToken::Number(number)) if *number > 1.0 => Some(format!(
+            "`{}: {}` is over 1 (did you mean {}%?)",
+            property, token, token
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,293 @@
+use super::color::{function_color, hex_color, named_color};
+use super::parser::Declaration;
+use super::serializer::{serialize_identifier, serialize_name};
+use super::tokenizer::{CssToken, CssTokenizer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentValue {
+    Token(CssToken),
+    Function { name: String, arguments: Vec<ComponentValue> },
+    // `open` is one of `(`, `[` or `{`.
+    Block { open: char, contents: Vec<ComponentValue> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EquivalenceOptions {
+    // Treat `red`, `#f00`, `#ff0000` and `rgb(255, 0, 0)` as the same value.
+    pub(crate) color_keywords: bool,
+    // Treat `0px`, `0em`, ... as `0`. Only lengths qualify: `0s` and `0%` are
+    // not interchangeable with a bare zero.
+    pub(crate) zero_units: bool,
+}
+
+impl EquivalenceOptions {
+    // Usable in constants, unlike `default()`.
+    pub const fn new() -> Self {
+        Self { color_keywords: false, zero_units: false }
+    }
+
+    pub const fn color_keywords(mut self, enabled: bool) -> Self {
+        self.color_keywords = enabled;
+        self
+    }
+
+    pub const fn zero_units(mut self, enabled: bool) -> Self {
+        self.zero_units = enabled;
+        self
+    }
+}
+
+// Functions and blocks nest at most this deep. Deeper ones are left as their
+// plain tokens, so code walking the values recursively can't overflow the
+// stack on adversarial input.
+pub(crate) const MAX_NESTING: usize = 256;
+
+pub fn parse_component_values(input: &str) -> Vec<ComponentValue> {
+    parse_nested_values(input).0
+}
+
+// Like `parse_component_values`, but `None` if anything nests deeper than
+// `MAX_NESTING`.
+pub(crate) fn parse_component_values_within_limit(input: &str) -> Option<Vec<ComponentValue>> {
+    match parse_nested_values(input) {
+        (values, false) => Some(values),
+        (_, true) => None,
+    }
+}
+
+fn parse_nested_values(input: &str) -> (Vec<ComponentValue>, bool) {
+    let mut tokenizer = CssTokenizer::new(input);
+    let mut tokens = Vec::new();
+
+    while let Some(token) = tokenizer.next_token() {
+        // A comment separates the tokens around it just like whitespace does.
+        match token {
+            CssToken::Comment(_) => tokens.push(CssToken::Whitespace),
+            token => tokens.push(token),
+        }
+    }
+
+    consume_list(&tokens)
+}
+
+// Returns the values and whether any were too deeply nested to build.
+fn consume_list(tokens: &[CssToken]) -> (Vec<ComponentValue>, bool) {
+    let mut values = Vec::new();
+    // Each open function or block, with the token that closes it and the
+    // values of its parent.
+    let mut open: Vec<(ComponentValue, &CssToken, Vec<ComponentValue>)> = Vec::new();
+    // Functions and blocks opened past the limit and not yet closed.
+    let mut excess = 0;
+    let mut too_deep = false;
+    let mut position = 0;
+
+    loop {
+        let token = tokens.get(position);
+        position += 1;
+
+        let closes = match (token, open.last()) {
+            (None, _) => true,
+            (Some(token), Some((_, close, _))) => token == *close,
+            (Some(_), None) => false,
+        };
+        if closes && excess > 0 && token.is_some() {
+            excess -= 1;
+        } else if closes {
+            let Some((mut value, _, parent)) = open.pop() else {
+                break;
+            };
+            let contents = std::mem::replace(&mut values, parent);
+            match &mut value {
+                ComponentValue::Function { arguments, .. } => *arguments = contents,
+                ComponentValue::Block { contents: inner, .. } => *inner = contents,
+                ComponentValue::Token(_) => {}
+            }
+            values.push(value);
+            continue;
+        }
+        let Some(token) = token else {
+            continue;
+        };
+
+        let opened = match token {
+            CssToken::Ident(name) if tokens.get(position) == Some(&CssToken::LeftParen) => Some((
+                ComponentValue::Function { name: name.clone(), arguments: Vec::new() },
+                &CssToken::RightParen,
+            )),
+            CssToken::LeftParen => Some((ComponentValue::Block { open: '(', contents: Vec::new() }, &CssToken::RightParen)),
+            CssToken::LeftBracket => Some((ComponentValue::Block { open: '[', contents: Vec::new() }, &CssToken::RightBracket)),
+            CssToken::LeftBrace => Some((ComponentValue::Block { open: '{', contents: Vec::new() }, &CssToken::RightBrace)),
+            _ => None,
+        };
+        match opened {
+            Some((value, close)) if open.len() < MAX_NESTING => {
+                if matches!(value, ComponentValue::Function { .. }) {
+                    position += 1; // Skip '('
+                }
+                open.push((value, close, std::mem::take(&mut values)));
+            }
+            Some(_) => {
+                too_deep = true;
+                if !matches!(token, CssToken::Ident(_)) {
+                    excess += 1;
+                }
+                values.push(ComponentValue::Token(token.clone()));
+            }
+            None => values.push(ComponentValue::Token(token.clone())),
+        }
+    }
+
+    (values, too_deep)
+}
+
+impl Declaration {
+    pub fn component_values(&self) -> Vec<ComponentValue> {
+        parse_component_values(&self.value)
+    }
+}
+
+// The canonical form of a value:
+//
+// - whitespace runs become a single space, with none at either end of a list
+//   or before a comma, and exactly one after a comma;
+// - comments are dropped;
+// - numbers are written in their shortest form (`1.50` -> `1.5`, `-0` -> `0`);
+// - units and function names are lowercased;
+// - hex colors are lowercased and contracted when possible (`#FFFFFF` -> `#fff`);
+// - quoted URLs are written unquoted (`url("a.png")` -> `url(a.png)`).
+//
+// Identifiers keep their case, since font families and custom idents are
+// case-sensitive.
+pub fn canonicalize(values: &[ComponentValue]) -> String {
+    canonicalize_with(values, &EquivalenceOptions::default())
+}
+
+pub fn canonicalize_with(values: &[ComponentValue], options: &EquivalenceOptions) -> String {
+    let mut dest = String::new();
+    write_list(values, options, &mut dest);
+    dest
+}
+
+pub fn equivalent(a: &[ComponentValue], b: &[ComponentValue], options: &EquivalenceOptions) -> bool {
+    canonicalize_with(a, options) == canonicalize_with(b, options)
+}
+
+pub(crate) fn equivalent_values(a: &str, b: &str, options: &EquivalenceOptions) -> bool {
+    a == b || equivalent(&parse_component_values(a), &parse_component_values(b), options)
+}
+
+fn write_list(values: &[ComponentValue], options: &EquivalenceOptions, dest: &mut String) {
+    let mut first = true;
+    let mut pending_space = false;
+
+    for value in values {
+        match value {
+            ComponentValue::Token(CssToken::Whitespace) => {
+                pending_space = true;
+                continue;
+            }
+            ComponentValue::Token(CssToken::Comma) => {
+                dest.push(',');
+                first = false;
+                pending_space = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        if pending_space && !first {
+            dest.push(' ');
+        }
+        pending_space = false;
+        first = false;
+        write_value(value, options, dest);
+    }
+}
+
+fn write_value(value: &ComponentValue, options: &EquivalenceOptions, dest: &mut String) {
+    match value {
+        ComponentValue::Token(token) => write_token(token, options, dest),
+        ComponentValue::Function { name, arguments } => {
+            if let Some(url) = quoted_url(name, arguments) {
+                write_token(&CssToken::Url(url.to_string()), options, dest);
+                return;
+            }
+            if options.color_keywords {
+                if let Some(color) = function_color(name, arguments) {
+                    dest.push_str(&color.to_string());
+                    return;
+                }
+            }
+            serialize_identifier(&name.to_ascii_lowercase(), dest);
+            dest.push('(');
+            write_list(arguments, options, dest);
+            dest.push(')');
+        }
+        ComponentValue::Block { open, contents } => {
+            let close = match open {
+                '(' => ')',
+                '[' => ']',
+                _ => '}',
+            };
+            dest.push(*open);
+            write_list(contents, options, dest);
+            dest.push(close);
+        }
+    }
+}
+
+fn write_token(token: &CssToken, options: &EquivalenceOptions, dest: &mut String) {
+    match token {
+        CssToken::Comment(_) => {}
+        CssToken::Number(number) => write_number(*number, dest),
+        CssToken::Dimension { value, unit } => {
+            write_number(*value, dest);
+            if !(options.zero_units && *value == 0.0 && is_length_unit(unit)) {
+                serialize_name(&unit.to_ascii_lowercase(), dest);
+            }
+        }
+        CssToken::Percentage(percentage) => {
+            write_number(*percentage, dest);
+            dest.push('%');
+        }
+        CssToken::Hash(hash) => match hex_color(hash) {
+            Some(color) => dest.push_str(&color.to_string()),
+            None => dest.push_str(&token.to_string()),
+        },
+        CssToken::Ident(ident) if options.color_keywords => match named_color(ident) {
+            Some(color) => dest.push_str(&color.to_string()),
+            None => serialize_identifier(ident, dest),
+        },
+        token => dest.push_str(&token.to_string()),
+    }
+}
+
+// The string of `url("...")`, which means the same as the unquoted form.
+fn quoted_url<'a>(name: &str, arguments: &'a [ComponentValue]) -> Option<&'a str> {
+    let mut values = arguments.iter().filter(|value| **value != ComponentValue::Token(CssToken::Whitespace));
+    match (values.next(), values.next()) {
+        (Some(ComponentValue::Token(CssToken::String(url))), None) if name.eq_ignore_ascii_case("url") => Some(url),
+        _ => None,
+    }
+}
+
+fn write_number(number: f64, dest: &mut String) {
+    if number == 0.0 {
+        dest.push('0');
+    } else {
+        dest.push_str(&number.to_string());
+    }
+}
+
+fn is_length_unit(unit: &str) -> bool {
+    matches!(
+        unit.to_ascii_lowercase().as_str(),
+        "px" | "em" | "rem" | "ex" | "rex" | "ch" | "rch" | "cap" | "rcap" | "ic" | "ric" | "lh" | "rlh"
+            | "vw" | "vh" | "vi" | "vb" | "vmin" | "vmax"
+            | "svw" | "svh" | "svi" | "svb" | "svmin" | "svmax"
+            | "lvw" | "lvh" | "lvi" | "lvb" | "lvmin" | "lvmax"
+            | "dvw" | "dvh" | "dvi" | "dvb" | "dvmin" | "dvmax"
+            | "cqw" | "cqh" | "cqi" | "cqb" | "cqmin" | "cqmax"
+            | "cm" | "mm" | "q" | "in" | "pt" | "pc"
+    )
+}
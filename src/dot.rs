@@ -0,0 +1,111 @@
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotOptions {
+    // Nodes deeper than this are left out; the root is at depth 0.
+    pub(crate) max_depth: Option<usize>,
+    // Labels longer than this many characters are cut and end in "...".
+    pub(crate) max_label_len: usize,
+    // Include text and comment nodes of HTML trees.
+    pub(crate) include_text: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            max_label_len: 40,
+            include_text: true,
+        }
+    }
+}
+
+impl DotOptions {
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn max_label_len(mut self, max_label_len: usize) -> Self {
+        self.max_label_len = max_label_len;
+        self
+    }
+
+    pub fn include_text(mut self, enabled: bool) -> Self {
+        self.include_text = enabled;
+        self
+    }
+}
+
+// Accumulates a `digraph` with numbered nodes.
+pub(crate) struct DotWriter<'a> {
+    output: String,
+    next_id: usize,
+    options: &'a DotOptions,
+}
+
+impl<'a> DotWriter<'a> {
+    pub(crate) fn new(name: &str, options: &'a DotOptions) -> Self {
+        let mut output = String::new();
+        let _ = writeln!(output, "digraph {} {{", name);
+        let _ = writeln!(output, "  node [shape=box, fontname=\"monospace\"];");
+        Self {
+            output,
+            next_id: 0,
+            options,
+        }
+    }
+
+    pub(crate) fn options(&self) -> &DotOptions {
+        self.options
+    }
+
+    pub(crate) fn within_depth(&self, depth: usize) -> bool {
+        self.options.max_depth.is_none_or(|max_depth| depth <= max_depth)
+    }
+
+    pub(crate) fn node(&mut self, label: &str, shape: Option<&str>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let _ = write!(self.output, "  n{} [label=\"{}\"", id, escape_label(&truncate(label, self.options.max_label_len)));
+        if let Some(shape) = shape {
+            let _ = write!(self.output, ", shape={}", shape);
+        }
+        self.output.push_str("];\n");
+        id
+    }
+
+    pub(crate) fn edge(&mut self, from: usize, to: usize) {
+        let _ = writeln!(self.output, "  n{} -> n{};", from, to);
+    }
+
+    pub(crate) fn finish(mut self) -> String {
+        self.output.push_str("}\n");
+        self.output
+    }
+}
+
+fn truncate(label: &str, max_len: usize) -> String {
+    if label.chars().count() <= max_len {
+        return label.to_string();
+    }
+
+    let mut truncated: String = label.chars().take(max_len.saturating_sub(3)).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+fn escape_label(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for ch in label.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' | '\t' => escaped.push(' '),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+// An attribute's value as written. `<input disabled>` has a `Bool` value,
+// while `<input disabled="">` has an empty `Value`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AttrValue {
+    Bool,
+    Value(String),
+}
+
+impl AttrValue {
+    // A boolean attribute reads as the empty string, as it does in the DOM.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AttrValue::Bool => "",
+            AttrValue::Value(value) => value,
+        }
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, AttrValue::Bool)
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(value: String) -> Self {
+        AttrValue::Value(value)
+    }
+}
+
+impl From<&str> for AttrValue {
+    fn from(value: &str) -> Self {
+        AttrValue::Value(value.to_string())
+    }
+}
+
+impl PartialEq<str> for AttrValue {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, AttrValue::Value(value) if value == other)
+    }
+}
+
+impl PartialEq<&str> for AttrValue {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+// An element's attributes in source order. Names are unique: adding a name
+// that's already present replaces its value in place, and collecting from
+// an iterator keeps the first of any duplicates, as HTML does.
+//
+// Equality ignores order, so `<a x y>` equals `<a y x>`.
+#[derive(Debug, Clone, Default, Eq)]
+pub struct Attributes {
+    entries: Vec<(String, AttrValue)>,
+}
+
+impl Attributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Exact name match; `Element::attribute` ignores ASCII case.
+    pub fn get(&self, name: &str) -> Option<&AttrValue> {
+        self.entries.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut AttrValue> {
+        self.entries.iter_mut().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // Returns the value replaced, if any. A new name goes last.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<AttrValue>) -> Option<AttrValue> {
+        let name = name.into();
+        let value = value.into();
+        match self.get_mut(&name) {
+            Some(existing) => Some(std::mem::replace(existing, value)),
+            None => {
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    // Later attributes keep their order.
+    pub fn remove(&mut self, name: &str) -> Option<AttrValue> {
+        let index = self.entries.iter().position(|(key, _)| key == name)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &AttrValue> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl PartialEq for Attributes {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(name, value)| other.get(name) == Some(value))
+    }
+}
+
+impl std::ops::Index<&str> for Attributes {
+    type Output = AttrValue;
+
+    fn index(&self, name: &str) -> &AttrValue {
+        self.get(name).unwrap_or_else(|| panic!("no attribute named {:?}", name))
+    }
+}
+
+impl<N: Into<String>, V: Into<AttrValue>> FromIterator<(N, V)> for Attributes {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        let mut attributes = Attributes::new();
+        attributes.extend(iter);
+        attributes
+    }
+}
+
+// Names already present keep their value.
+impl<N: Into<String>, V: Into<AttrValue>> Extend<(N, V)> for Attributes {
+    fn extend<I: IntoIterator<Item = (N, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            let name = name.into();
+            if !self.contains_key(&name) {
+                self.entries.push((name, value.into()));
+            }
+        }
+    }
+}
+
+impl IntoIterator for Attributes {
+    type Item = (String, AttrValue);
+    type IntoIter = std::vec::IntoIter<(String, AttrValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = (&'a String, &'a AttrValue);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a>(std::slice::Iter<'a, (String, AttrValue)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a AttrValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(name, value)| (name, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
@@ -0,0 +1,208 @@
+use super::options::HtmlParseOptions;
+use super::tokenizer::{HtmlToken, HtmlTokenizer};
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+// How far past a token's end the tokenizer may look to decide where the
+// token ends, as when checking for `</textarea` and the character after it.
+// A token is only returned once this much input follows it.
+const LOOKAHEAD: usize = 16;
+
+const READ_SIZE: usize = 8192;
+
+// Tokenizes input that arrives in pieces, such as reads from a large file,
+// without holding all of it: only the input from the start of the first
+// unfinished token is kept.
+//
+// The tokens are the ones `HtmlTokenizer` gives for the whole input. They
+// carry no spans, and the duplicate attribute callback isn't called.
+#[derive(Debug, Default)]
+pub struct ChunkedHtmlTokenizer {
+    buffer: String,
+    options: HtmlParseOptions,
+    raw_text_end: Option<String>,
+    wait: Wait,
+}
+
+// What a token left unfinished needs before it's worth tokenizing again, so
+// one long token isn't tokenized anew on every feed.
+#[derive(Debug, Default)]
+enum Wait {
+    #[default]
+    Nothing,
+    // The buffer reaching this length, to hold the lookahead after the token.
+    Length(usize),
+    // `end`, matched ASCII case-insensitively, since the token ran to the end
+    // of the buffer and nothing else can end it. `scanned` bytes are already
+    // known not to complete it.
+    Text { end: String, scanned: usize },
+}
+
+impl ChunkedHtmlTokenizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: HtmlParseOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    // Tokens read from `reader`, which must be UTF-8. Input that isn't is
+    // reported as an `InvalidData` error, which ends the iteration.
+    pub fn from_reader<R: Read>(reader: R) -> ReaderTokens<R> {
+        Self::from_reader_with_options(reader, HtmlParseOptions::default())
+    }
+
+    pub fn from_reader_with_options<R: Read>(reader: R, options: HtmlParseOptions) -> ReaderTokens<R> {
+        ReaderTokens {
+            reader,
+            tokenizer: Some(Self::with_options(options)),
+            tokens: VecDeque::new(),
+            undecoded: Vec::new(),
+        }
+    }
+
+    // Adds `chunk` to the input and returns the tokens it completes.
+    pub fn feed(&mut self, chunk: &str) -> Vec<HtmlToken> {
+        self.buffer.push_str(chunk);
+        if !self.may_complete() {
+            return Vec::new();
+        }
+        self.tokenize(false)
+    }
+
+    // Ends the input and returns the tokens left in it, including any cut
+    // off by the end, as `HtmlTokenizer` would.
+    pub fn finish(mut self) -> Vec<HtmlToken> {
+        self.tokenize(true)
+    }
+
+    // Bytes of input held for tokens not yet complete.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn may_complete(&mut self) -> bool {
+        let buffer = self.buffer.as_bytes();
+        match &mut self.wait {
+            Wait::Nothing => true,
+            Wait::Length(length) => buffer.len() >= *length,
+            Wait::Text { end, scanned } => {
+                // The end may straddle the bytes already scanned, or have
+                // needed the bytes after it to tell.
+                let start = scanned.saturating_sub(end.len() + LOOKAHEAD);
+                *scanned = buffer.len();
+                buffer[start..]
+                    .windows(end.len())
+                    .any(|window| window.eq_ignore_ascii_case(end.as_bytes()))
+            }
+        }
+    }
+
+    // What ends the token at the start of the buffer: `</name` for the text
+    // of a raw-text element, the `--` of `-->` for a comment, `>` for other
+    // markup and `<` for other text.
+    fn token_end(&self) -> String {
+        if let Some(name) = &self.raw_text_end {
+            return format!("</{}", name);
+        }
+        match self.buffer.trim_start().as_bytes() {
+            [b'<', b'!', b'-', b'-', ..] => "--".to_string(),
+            [b'<'] => ">".to_string(),
+            [b'<', next, ..] if next.is_ascii_alphabetic() || matches!(next, b'/' | b'!' | b'?') => ">".to_string(),
+            _ => "<".to_string(),
+        }
+    }
+
+    fn tokenize(&mut self, at_end: bool) -> Vec<HtmlToken> {
+        let mut tokenizer = HtmlTokenizer::resume(&self.buffer, &self.options, self.raw_text_end.clone());
+        let mut tokens = Vec::new();
+        let mut consumed = 0;
+        let mut raw_text_end = self.raw_text_end.clone();
+        let mut unfinished = None;
+
+        while let Some(token) = tokenizer.next_token() {
+            if !at_end && tokenizer.byte_position() + LOOKAHEAD > self.buffer.len() {
+                unfinished = Some(tokenizer.byte_position());
+                break;
+            }
+            tokens.push(token);
+            consumed = tokenizer.byte_position();
+            raw_text_end = tokenizer.raw_text_end().map(str::to_string);
+        }
+
+        self.buffer.drain(..consumed);
+        self.raw_text_end = raw_text_end;
+        // A token that ran to the end of the buffer was likely cut off by it,
+        // unless it ends as markup does.
+        self.wait = match unfinished.map(|end| end - consumed) {
+            None => Wait::Nothing,
+            Some(end) if end < self.buffer.len() || self.buffer.ends_with('>') => Wait::Length(end + LOOKAHEAD),
+            Some(_) => Wait::Text {
+                end: self.token_end(),
+                scanned: self.buffer.len(),
+            },
+        };
+        tokens
+    }
+}
+
+pub struct ReaderTokens<R> {
+    reader: R,
+    // `None` once the input has ended or failed.
+    tokenizer: Option<ChunkedHtmlTokenizer>,
+    tokens: VecDeque<HtmlToken>,
+    // The start of a character split across reads.
+    undecoded: Vec<u8>,
+}
+
+impl<R: Read> Iterator for ReaderTokens<R> {
+    type Item = io::Result<HtmlToken>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.tokens.pop_front() {
+                return Some(Ok(token));
+            }
+            let tokenizer = self.tokenizer.as_mut()?;
+
+            let mut chunk = [0; READ_SIZE];
+            let read = match self.reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    self.tokenizer = None;
+                    return Some(Err(error));
+                }
+            };
+
+            if read == 0 {
+                let tokenizer = self.tokenizer.take()?;
+                if !self.undecoded.is_empty() {
+                    let error = io::Error::new(io::ErrorKind::InvalidData, "input ends inside a UTF-8 character");
+                    return Some(Err(error));
+                }
+                self.tokens.extend(tokenizer.finish());
+                continue;
+            }
+
+            self.undecoded.extend_from_slice(&chunk[..read]);
+            let valid = match std::str::from_utf8(&self.undecoded) {
+                Ok(text) => text.len(),
+                // The rest of the character is still to be read.
+                Err(error) if error.error_len().is_none() => error.valid_up_to(),
+                Err(error) => {
+                    self.tokenizer = None;
+                    return Some(Err(io::Error::new(io::ErrorKind::InvalidData, error)));
+                }
+            };
+            // Only the valid prefix is decoded, so nothing is replaced.
+            let text = String::from_utf8_lossy(&self.undecoded[..valid]);
+            self.tokens.extend(tokenizer.feed(&text));
+            self.undecoded.drain(..valid);
+        }
+    }
+}
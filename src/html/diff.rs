@@ -0,0 +1,201 @@
+use super::attr::AttrValue;
+use super::error::HtmlError;
+use super::options::HtmlParseOptions;
+use super::parser::{HtmlParser, Node};
+use super::tree::{NodeData, NodeId, Tree};
+
+// An edit to a `Tree`. Ids refer to nodes of the tree being patched; an
+// `InsertNode` index is a position among the parent's children at the time
+// the operation is applied.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PatchOp {
+    // Replaces the content of a text or comment node.
+    ReplaceText(NodeId, String),
+    SetAttribute(NodeId, String, AttrValue),
+    RemoveAttribute(NodeId, String),
+    RemoveNode(NodeId),
+    // Parses the HTML and inserts the resulting nodes under the parent. Text
+    // is kept as written, so whitespace-only text nodes can be inserted.
+    InsertNode(NodeId, usize, String),
+}
+
+// Operations that turn `old` into `new`. Children are matched by `id`
+// attribute where present, then as unchanged subtrees, and finally by
+// position among nodes of the same kind and tag, so small edits produce
+// small patches.
+pub fn diff_patch(old: &Tree, new: &Tree) -> Vec<PatchOp> {
+    let mut ops = Vec::new();
+    diff_children(old, old.root(), new, new.root(), &mut ops);
+    ops
+}
+
+fn diff_children(old: &Tree, old_parent: NodeId, new: &Tree, new_parent: NodeId, ops: &mut Vec<PatchOp>) {
+    let old_children = old.children(old_parent);
+    let new_children = new.children(new_parent);
+    let old_nodes: Vec<Node> = old_children.iter().filter_map(|&id| old.node(id)).collect();
+    let new_nodes: Vec<Node> = new_children.iter().filter_map(|&id| new.node(id)).collect();
+    let pairs = match_children(&old_nodes, &new_nodes);
+
+    let mut old_matched = vec![false; old_nodes.len()];
+    let mut new_matched = vec![false; new_nodes.len()];
+    for &(old_index, new_index) in &pairs {
+        old_matched[old_index] = true;
+        new_matched[new_index] = true;
+    }
+
+    // Once the unmatched children are gone, inserting in ascending order puts
+    // each new child at its final index.
+    for (index, _) in old_matched.iter().enumerate().filter(|(_, matched)| !**matched) {
+        ops.push(PatchOp::RemoveNode(old_children[index]));
+    }
+    for (index, _) in new_matched.iter().enumerate().filter(|(_, matched)| !**matched) {
+        ops.push(PatchOp::InsertNode(old_parent, index, new_nodes[index].to_html()));
+    }
+
+    for (old_index, new_index) in pairs {
+        if old_nodes[old_index] != new_nodes[new_index] {
+            diff_node(old, old_children[old_index], new, new_children[new_index], ops);
+        }
+    }
+}
+
+fn diff_node(old: &Tree, old_id: NodeId, new: &Tree, new_id: NodeId, ops: &mut Vec<PatchOp>) {
+    match (old.get(old_id), new.get(new_id)) {
+        (Some(NodeData::Text(old_text)), Some(NodeData::Text(new_text)))
+        | (Some(NodeData::Comment(old_text)), Some(NodeData::Comment(new_text)))
+            if old_text != new_text =>
+        {
+            ops.push(PatchOp::ReplaceText(old_id, new_text.clone()));
+        }
+        (
+            Some(NodeData::Element { attributes: old_attributes, .. }),
+            Some(NodeData::Element { attributes: new_attributes, .. }),
+        ) => {
+            for (name, value) in new_attributes {
+                if old_attributes.get(name) != Some(value) {
+                    ops.push(PatchOp::SetAttribute(old_id, name.clone(), value.clone()));
+                }
+            }
+
+            for name in old_attributes.keys().filter(|name| !new_attributes.contains_key(name)) {
+                ops.push(PatchOp::RemoveAttribute(old_id, name.clone()));
+            }
+
+            diff_children(old, old_id, new, new_id, ops);
+        }
+        _ => {}
+    }
+}
+
+fn id_attribute(node: &Node) -> Option<(&str, &str)> {
+    match node {
+        Node::Element(element) => element
+            .attributes
+            .get("id")
+            .map(|id| (element.tag_name.as_str(), id.as_str())),
+        _ => None,
+    }
+}
+
+// Whether `a` could be edited into `b` in place.
+fn compatible(a: &Node, b: &Node) -> bool {
+    match (a, b) {
+        (Node::Element(a), Node::Element(b)) => a.tag_name == b.tag_name,
+        (Node::Text(_), Node::Text(_)) | (Node::Comment(_), Node::Comment(_)) => true,
+        _ => false,
+    }
+}
+
+// Pairs of `(old index, new index)`, increasing in both.
+fn match_children(old: &[Node], new: &[Node]) -> Vec<(usize, usize)> {
+    let anchored = |a: &Node, b: &Node| match (id_attribute(a), id_attribute(b)) {
+        (Some(a), Some(b)) => a == b,
+        (None, None) => a == b,
+        _ => false,
+    };
+
+    // Longest common subsequence of anchored pairs.
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if anchored(&old[i], &new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut anchors = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if anchored(&old[i], &new[j]) {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    // Within each gap between anchors, pair up compatible nodes in order.
+    let mut pairs = Vec::new();
+    let mut gap_start = (0, 0);
+    for anchor in anchors.iter().copied().chain([(old.len(), new.len())]) {
+        let mut next_old = gap_start.0;
+        for (new_index, new_node) in new.iter().enumerate().take(anchor.1).skip(gap_start.1) {
+            if let Some(old_index) = (next_old..anchor.0).find(|&index| compatible(&old[index], new_node)) {
+                pairs.push((old_index, new_index));
+                next_old = old_index + 1;
+            }
+        }
+        if anchor.0 < old.len() {
+            pairs.push(anchor);
+        }
+        gap_start = (anchor.0 + 1, anchor.1 + 1);
+    }
+    pairs
+}
+
+impl Tree {
+    // Applies `ops` in order, stopping at the first one that doesn't apply.
+    pub fn apply(&mut self, ops: &[PatchOp]) -> Result<(), HtmlError> {
+        for (index, op) in ops.iter().enumerate() {
+            if !self.apply_op(op) {
+                return Err(HtmlError::PatchFailed { index });
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_op(&mut self, op: &PatchOp) -> bool {
+        match op {
+            PatchOp::ReplaceText(id, text) => match self.data_mut(*id) {
+                Some(NodeData::Text(content) | NodeData::Comment(content)) => {
+                    *content = text.clone();
+                    true
+                }
+                _ => false,
+            },
+            PatchOp::SetAttribute(id, name, value) => match self.data_mut(*id) {
+                Some(NodeData::Element { attributes, .. }) => {
+                    attributes.insert(name.clone(), value.clone());
+                    true
+                }
+                _ => false,
+            },
+            PatchOp::RemoveAttribute(id, name) => match self.data_mut(*id) {
+                Some(NodeData::Element { attributes, .. }) => attributes.remove(name).is_some(),
+                _ => false,
+            },
+            PatchOp::RemoveNode(id) => self.remove(*id),
+            PatchOp::InsertNode(parent, index, html) => {
+                let options = HtmlParseOptions::default().trim_text(false);
+                self.insert_nodes(*parent, *index, HtmlParser::with_options(html, options).parse())
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use super::attr::Attributes;
+use super::parser::{Element, Node};
+use super::quirks::{quirks_mode_for_doctype, QuirksMode};
+use super::tokenizer::HtmlToken;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Doctype {
+    // Lowercased; empty if the doctype has no name.
+    pub name: String,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+}
+
+impl Doctype {
+    pub(crate) fn from_token(token: &HtmlToken) -> Option<Self> {
+        match token {
+            HtmlToken::Doctype { name, public_id, system_id } => Some(Self {
+                name: name.clone(),
+                public_id: public_id.clone(),
+                system_id: system_id.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// A parsed page: its doctype and top-level nodes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    pub doctype: Option<Doctype>,
+    pub children: Vec<Node>,
+}
+
+impl Document {
+    pub fn new(children: Vec<Node>) -> Self {
+        Self { doctype: None, children }
+    }
+
+    // Quirks mode as the doctype sets it; without one, a page is in quirks
+    // mode.
+    pub fn quirks_mode(&self) -> QuirksMode {
+        quirks_mode_for_doctype(self.doctype.as_ref())
+    }
+
+    // The `<html>` element, or failing that the first top-level element.
+    pub fn root(&self) -> Option<&Element> {
+        let mut elements = self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        });
+        let first = elements.clone().next();
+        elements
+            .find(|element| element.tag_name.eq_ignore_ascii_case("html"))
+            .or(first)
+    }
+
+    // The `<head>` and `<body>` of the root element.
+    pub fn head(&self) -> Option<&Element> {
+        self.root()?.find_child("head")
+    }
+
+    pub fn body(&self) -> Option<&Element> {
+        self.root()?.find_child("body")
+    }
+}
+
+impl Element {
+    fn find_child(&self, tag_name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) if element.tag_name.eq_ignore_ascii_case(tag_name) => Some(element),
+            _ => None,
+        })
+    }
+}
+
+// Gives top-level nodes the structure browsers build for every page: one
+// `html` element holding a `head` and a `body`, with only comments outside
+// it. Metadata before any content goes in the head, and everything else in
+// the body, including text before the head and elements after the body.
+// Explicit `html`, `head` and `body` elements are merged into the ones
+// built, keeping their attributes.
+pub(crate) fn with_implied_structure(nodes: Vec<Node>) -> Vec<Node> {
+    let mut structure = Structure::default();
+    let mut leading = Vec::new();
+    let mut trailing = Vec::new();
+
+    for node in nodes {
+        match node {
+            Node::Comment(_) if structure.is_empty() => leading.push(node),
+            Node::Comment(_) if structure.after_html => trailing.push(node),
+            Node::Element(element) if element.tag_name.eq_ignore_ascii_case("html") => {
+                structure.add(Node::Element(element));
+                structure.after_html = true;
+            }
+            node => structure.add(node),
+        }
+    }
+
+    leading.push(Node::Element(structure.into_html()));
+    leading.append(&mut trailing);
+    leading
+}
+
+#[derive(Default)]
+struct Structure {
+    html_attributes: Attributes,
+    head: Vec<Node>,
+    head_attributes: Attributes,
+    body: Vec<Node>,
+    body_attributes: Attributes,
+    // Set once content that belongs in the body has been seen.
+    in_body: bool,
+    after_html: bool,
+}
+
+impl Structure {
+    fn is_empty(&self) -> bool {
+        self.html_attributes.is_empty() && self.head.is_empty() && self.body.is_empty() && !self.in_body
+    }
+
+    fn add(&mut self, node: Node) {
+        let Node::Element(mut element) = node else {
+            match node {
+                Node::Text(text) if text.trim().is_empty() && !self.in_body => {}
+                Node::Comment(_) if !self.in_body => self.head.push(node),
+                node => {
+                    self.in_body = true;
+                    self.body.push(node);
+                }
+            }
+            return;
+        };
+
+        match element.tag_name.to_ascii_lowercase().as_str() {
+            "html" => {
+                self.html_attributes.extend(std::mem::take(&mut element.attributes));
+                std::mem::take(&mut element.children).into_iter().for_each(|child| self.add(child));
+            }
+            // A head's content is sorted like any other, since the parser
+            // leaves a body that follows a head without `</head>` inside it.
+            // A head after content only contributes its content, to the body.
+            "head" => {
+                if !self.in_body {
+                    self.head_attributes.extend(std::mem::take(&mut element.attributes));
+                }
+                std::mem::take(&mut element.children).into_iter().for_each(|child| self.add(child));
+            }
+            "body" => {
+                self.in_body = true;
+                self.body_attributes.extend(std::mem::take(&mut element.attributes));
+                std::mem::take(&mut element.children).into_iter().for_each(|child| self.add(child));
+            }
+            "base" | "link" | "meta" | "noscript" | "script" | "style" | "template" | "title" if !self.in_body => {
+                self.head.push(Node::Element(element));
+            }
+            _ => {
+                self.in_body = true;
+                self.body.push(Node::Element(element));
+            }
+        }
+    }
+
+    fn into_html(self) -> Element {
+        let element = |tag_name: &str, attributes, children| Element {
+            tag_name: tag_name.to_string(),
+            attributes,
+            children,
+            source_span: None,
+        };
+        let head = element("head", self.head_attributes, self.head);
+        let body = element("body", self.body_attributes, self.body);
+        element("html", self.html_attributes, vec![Node::Element(head), Node::Element(body)])
+    }
+}
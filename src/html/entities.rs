@@ -0,0 +1,103 @@
+use super::entity_table::{LEGACY_REFERENCES, NAMED_REFERENCES};
+
+// Character references recognized in text and attribute values: `&name;`
+// for any name in the HTML standard, the legacy names such as `&amp` and
+// `&copy` without a semicolon when no letter or digit follows, and the
+// `&#digits;` and `&#xhex;` forms, whose semicolon is optional too. Anything
+// else, including a lone `&` or an unknown `&name;`, is kept literally.
+pub(crate) fn decode(text: &str) -> String {
+    decode_references(text, false)
+}
+
+pub(crate) fn decode_attribute(value: &str) -> String {
+    decode_references(value, true)
+}
+
+fn decode_references(text: &str, in_attribute: bool) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find('&') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        match decode_reference(&rest[1..], in_attribute) {
+            Some((replacement, length)) => {
+                decoded.push_str(&replacement);
+                rest = &rest[1 + length..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+// Decodes the reference following an `&`, returning the replacement and the
+// number of bytes it spans.
+fn decode_reference(input: &str, in_attribute: bool) -> Option<(String, usize)> {
+    if let Some(number) = input.strip_prefix('#') {
+        let (radix, digits_start) = match number.chars().next() {
+            Some('x' | 'X') => (16, 2),
+            _ => (10, 1),
+        };
+        let digits = &input[digits_start..];
+        let digits_end = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+        if digits_end == 0 {
+            return None;
+        }
+        // Saturates so that overlong references become U+FFFD below.
+        let code_point = digits[..digits_end].chars().fold(0u32, |value, digit| {
+            value.saturating_mul(radix).saturating_add(digit.to_digit(radix).unwrap_or(0))
+        });
+        let length = digits_start + digits_end + usize::from(digits[digits_end..].starts_with(';'));
+        return Some((numeric_replacement(code_point).to_string(), length));
+    }
+
+    let name_end = input
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(input.len());
+    let name = &input[..name_end];
+    let index = NAMED_REFERENCES
+        .binary_search_by(|(candidate, _)| candidate.cmp(&name))
+        .ok()?;
+    let replacement = NAMED_REFERENCES[index].1.to_string();
+
+    let next = input[name_end..].chars().next();
+    if next == Some(';') {
+        return Some((replacement, name_end + 1));
+    }
+    // Without the semicolon only the legacy names decode, and in attributes
+    // not before `=`, so query strings like `?a=1&copy=2` survive.
+    let legacy = LEGACY_REFERENCES.binary_search(&name).is_ok();
+    if !legacy || (in_attribute && next == Some('=')) {
+        return None;
+    }
+    Some((replacement, name_end))
+}
+
+// The character a numeric reference stands for. NUL, surrogates and values
+// past U+10FFFF become U+FFFD, and the C1 controls that Windows-1252 maps to
+// printable characters take those characters, as browsers do.
+fn numeric_replacement(code_point: u32) -> char {
+    const WINDOWS_1252: [char; 32] = [
+        '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+        '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+        '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+        '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+    ];
+
+    match code_point {
+        0 => '\u{fffd}',
+        0x80..=0x9f => WINDOWS_1252[(code_point - 0x80) as usize],
+        _ => char::from_u32(code_point).unwrap_or('\u{fffd}'),
+    }
+}
@@ -0,0 +1,156 @@
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum HtmlError {
+    NoRootElement,
+    UnexpectedEndTag { name: String, span: Span },
+    // An element closed by the end of input or by an end tag other than its
+    // own; the span is its start tag.
+    UnclosedElement { name: String, span: Span },
+    // The first element nested past `max_depth`; it and any after it were
+    // added beside their parent's other children instead.
+    NestingTooDeep { name: String, span: Span },
+    Tokenize(HtmlTokenizeError),
+    // The patch operation at `index` refers to a node the tree doesn't have.
+    PatchFailed { index: usize },
+}
+
+impl HtmlError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            HtmlError::NoRootElement | HtmlError::PatchFailed { .. } => None,
+            HtmlError::UnexpectedEndTag { span, .. }
+            | HtmlError::UnclosedElement { span, .. }
+            | HtmlError::NestingTooDeep { span, .. } => Some(*span),
+            HtmlError::Tokenize(error) => Some(error.span),
+        }
+    }
+}
+
+impl fmt::Display for HtmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HtmlError::NoRootElement => write!(f, "document has no root element"),
+            HtmlError::PatchFailed { index } => write!(f, "patch operation {} doesn't apply to this tree", index),
+            HtmlError::UnexpectedEndTag { name, span } => write!(
+                f,
+                "unexpected end tag </{}> at line {}, column {}",
+                name, span.line, span.column
+            ),
+            HtmlError::UnclosedElement { name, span } => write!(
+                f,
+                "<{}> at line {}, column {} is never closed",
+                name, span.line, span.column
+            ),
+            HtmlError::NestingTooDeep { name, span } => write!(
+                f,
+                "<{}> at line {}, column {} is nested too deeply and was flattened",
+                name, span.line, span.column
+            ),
+            HtmlError::Tokenize(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for HtmlError {}
+
+// Problems the tokenizer recovers from, named after the HTML standard's
+// parse errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TokenizeErrorKind {
+    EofInTag,
+    EofInComment,
+    EofInDoctype,
+    AbruptClosingOfEmptyComment,
+    IncorrectlyClosedComment,
+    InvalidFirstCharacterOfTagName,
+    MissingEndTagName,
+    UnexpectedQuestionMarkInsteadOfTagName,
+    IncorrectlyOpenedComment,
+    UnexpectedCharacterInAttributeName,
+    UnexpectedCharacterInUnquotedAttributeValue,
+    MissingAttributeValue,
+    MissingWhitespaceBetweenAttributes,
+    DuplicateAttribute,
+    EndTagWithAttributes,
+    UnexpectedNullCharacter,
+    ControlCharacterInInputStream,
+}
+
+impl TokenizeErrorKind {
+    // The error's name in the standard, e.g. `eof-in-tag`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TokenizeErrorKind::EofInTag => "eof-in-tag",
+            TokenizeErrorKind::EofInComment => "eof-in-comment",
+            TokenizeErrorKind::EofInDoctype => "eof-in-doctype",
+            TokenizeErrorKind::AbruptClosingOfEmptyComment => "abrupt-closing-of-empty-comment",
+            TokenizeErrorKind::IncorrectlyClosedComment => "incorrectly-closed-comment",
+            TokenizeErrorKind::InvalidFirstCharacterOfTagName => "invalid-first-character-of-tag-name",
+            TokenizeErrorKind::MissingEndTagName => "missing-end-tag-name",
+            TokenizeErrorKind::UnexpectedQuestionMarkInsteadOfTagName => "unexpected-question-mark-instead-of-tag-name",
+            TokenizeErrorKind::IncorrectlyOpenedComment => "incorrectly-opened-comment",
+            TokenizeErrorKind::UnexpectedCharacterInAttributeName => "unexpected-character-in-attribute-name",
+            TokenizeErrorKind::UnexpectedCharacterInUnquotedAttributeValue => {
+                "unexpected-character-in-unquoted-attribute-value"
+            }
+            TokenizeErrorKind::MissingAttributeValue => "missing-attribute-value",
+            TokenizeErrorKind::MissingWhitespaceBetweenAttributes => "missing-whitespace-between-attributes",
+            TokenizeErrorKind::DuplicateAttribute => "duplicate-attribute",
+            TokenizeErrorKind::EndTagWithAttributes => "end-tag-with-attributes",
+            TokenizeErrorKind::UnexpectedNullCharacter => "unexpected-null-character",
+            TokenizeErrorKind::ControlCharacterInInputStream => "control-character-in-input-stream",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            TokenizeErrorKind::EofInTag => "input ends inside a tag",
+            TokenizeErrorKind::EofInComment => "comment is never closed with -->",
+            TokenizeErrorKind::EofInDoctype => "input ends inside a doctype",
+            TokenizeErrorKind::AbruptClosingOfEmptyComment => "comment is closed by '>' instead of '-->'",
+            TokenizeErrorKind::IncorrectlyClosedComment => "comment is closed by '--!>' instead of '-->'",
+            TokenizeErrorKind::InvalidFirstCharacterOfTagName => "'<' is not followed by a tag name",
+            TokenizeErrorKind::MissingEndTagName => "end tag has no name",
+            TokenizeErrorKind::UnexpectedQuestionMarkInsteadOfTagName => {
+                "HTML has no processing instructions; this one is left out of the tree"
+            }
+            TokenizeErrorKind::IncorrectlyOpenedComment => "'<!' is not followed by '--' or a doctype",
+            TokenizeErrorKind::UnexpectedCharacterInAttributeName => "character can't start an attribute name",
+            TokenizeErrorKind::UnexpectedCharacterInUnquotedAttributeValue => {
+                "unquoted attribute value contains a quote, '<', '=' or '`'"
+            }
+            TokenizeErrorKind::MissingAttributeValue => "attribute has '=' but no value",
+            TokenizeErrorKind::MissingWhitespaceBetweenAttributes => "attributes aren't separated by whitespace",
+            TokenizeErrorKind::DuplicateAttribute => "attribute appears more than once; the first value is kept",
+            TokenizeErrorKind::EndTagWithAttributes => "end tag has attributes, which are ignored",
+            TokenizeErrorKind::UnexpectedNullCharacter => "NUL character, replaced with U+FFFD",
+            TokenizeErrorKind::ControlCharacterInInputStream => "control character in input",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlTokenizeError {
+    pub kind: TokenizeErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for HtmlTokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) at line {}, column {}",
+            self.kind.message(),
+            self.kind.code(),
+            self.span.line,
+            self.span.column
+        )
+    }
+}
+
+impl Error for HtmlTokenizeError {}
@@ -0,0 +1,50 @@
+use super::parser::{Element, Node};
+use crate::dot::DotWriter;
+
+pub use crate::dot::DotOptions;
+
+pub fn to_dot(node: &Node, options: &DotOptions) -> String {
+    let mut writer = DotWriter::new("html", options);
+    write_node(&mut writer, node, None, 0);
+    writer.finish()
+}
+
+fn write_node(writer: &mut DotWriter, node: &Node, parent: Option<usize>, depth: usize) {
+    if !writer.within_depth(depth) {
+        return;
+    }
+
+    let id = match node {
+        Node::Element(element) => writer.node(&element_label(element), None),
+        Node::Text(text) if writer.options().include_text => writer.node(text, Some("plaintext")),
+        Node::Comment(comment) if writer.options().include_text => {
+            writer.node(&format!("<!--{}-->", comment), Some("note"))
+        }
+        _ => return,
+    };
+    if let Some(parent) = parent {
+        writer.edge(parent, id);
+    }
+
+    if let Node::Element(element) = node {
+        for child in &element.children {
+            write_node(writer, child, Some(id), depth + 1);
+        }
+    }
+}
+
+// `tag#id.class1.class2`
+fn element_label(element: &Element) -> String {
+    let mut label = element.tag_name.clone();
+    if let Some(id) = element.attribute("id") {
+        label.push('#');
+        label.push_str(id);
+    }
+    if let Some(classes) = element.attribute("class") {
+        for class in classes.split_ascii_whitespace() {
+            label.push('.');
+            label.push_str(class);
+        }
+    }
+    label
+}
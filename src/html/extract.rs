@@ -0,0 +1,173 @@
+use super::parser::{Element, HtmlParser, Node};
+use super::tokenizer::{HtmlToken, HtmlTokenizer};
+use crate::css::{parse_media_query_list, AtRule, AtRuleBlock, CssParser, CssRule, MediaQueryList, Stylesheet};
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum StylesheetSource {
+    // The text of a `<style>` element.
+    Inline(String),
+    // A `<link rel="stylesheet">`; its rules aren't available.
+    Link { href: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedStylesheet {
+    pub source: StylesheetSource,
+    // `None` when the element has no `media` attribute. Queries in the
+    // attribute that fail to parse become `not all`, which never matches.
+    pub media: Option<MediaQueryList>,
+    pub stylesheet: Stylesheet,
+}
+
+impl ExtractedStylesheet {
+    // The rules wrapped in an `@media` block carrying the element's media
+    // query, so they only apply where the element would.
+    pub fn to_stylesheet(&self) -> Stylesheet {
+        match &self.media {
+            None => self.stylesheet.clone(),
+            Some(media) => Stylesheet::new(vec![CssRule::At(AtRule {
+                name: "media".to_string(),
+                prelude: media.to_string(),
+                block: Some(AtRuleBlock::Rules(self.stylesheet.rules.clone())),
+            })]),
+        }
+    }
+}
+
+pub fn stylesheets(nodes: &[Node]) -> Vec<ExtractedStylesheet> {
+    let mut extracted = Vec::new();
+    collect_stylesheets(nodes, &mut extracted);
+    extracted
+}
+
+// All extracted stylesheets merged in document order, each wrapped in its
+// media context.
+pub fn combined_stylesheet(nodes: &[Node]) -> Stylesheet {
+    stylesheets(nodes)
+        .iter()
+        .fold(Stylesheet::default(), |combined, extracted| combined.merge(extracted.to_stylesheet()))
+}
+
+// Every element whose tag is in `tags` (compared ASCII case-insensitively),
+// in document order, with its attributes and its own text children but none
+// of its child elements. Nested matches are returned as separate elements.
+// Markup outside matched elements is skipped without building tokens.
+pub fn only_tags(input: &str, tags: &[&str]) -> Vec<Element> {
+    let wanted = |name: &str| tags.iter().any(|tag| tag.eq_ignore_ascii_case(name));
+    let mut tokenizer = HtmlTokenizer::new(input);
+    let mut elements: Vec<Element> = Vec::new();
+    // Elements open inside a matched element, with the index in `elements`
+    // of those that were matched themselves.
+    let mut open: Vec<(String, Option<usize>)> = Vec::new();
+
+    loop {
+        let token = if open.is_empty() {
+            tokenizer.skip_to_start_tag(wanted)
+        } else {
+            tokenizer.next_token()
+        };
+        let Some(token) = token else {
+            break;
+        };
+
+        match token {
+            HtmlToken::StartTag { name, attributes, self_closing } => {
+                while let Some(closed) = HtmlParser::implicitly_closed(open.iter().map(|(open_name, _)| open_name.as_str()), &name) {
+                    open.truncate(closed);
+                }
+                let index = wanted(&name).then(|| {
+                    elements.push(Element {
+                        tag_name: name.clone(),
+                        attributes: attributes.into_iter().collect(),
+                        children: Vec::new(),
+                        source_span: None,
+                    });
+                    elements.len() - 1
+                });
+                if !self_closing && !HtmlParser::is_void_element(&name) {
+                    open.push((name, index));
+                }
+            }
+            // Like the parser, an end tag closes every element opened after
+            // the one it names. One naming none of them may name an element
+            // open outside the matched one, so it closes them all; the
+            // parser, which sees every open element, ignores an end tag
+            // naming none. Formatting elements aren't reopened either, so
+            // misnested markup can give different results than a full parse.
+            HtmlToken::EndTag { name } => {
+                let closed = open
+                    .iter()
+                    .rposition(|(open_name, _)| open_name.eq_ignore_ascii_case(&name))
+                    .unwrap_or(0);
+                open.truncate(closed);
+            }
+            HtmlToken::Text(text) => {
+                let text = text.trim();
+                if let (false, Some((_, Some(index)))) = (text.is_empty(), open.last()) {
+                    elements[*index].children.push(Node::Text(text.to_string()));
+                }
+            }
+            HtmlToken::Comment(_) | HtmlToken::ProcessingInstruction(_) | HtmlToken::Doctype { .. } => {}
+        }
+    }
+
+    elements
+}
+
+fn collect_stylesheets(nodes: &[Node], extracted: &mut Vec<ExtractedStylesheet>) {
+    for node in nodes {
+        let Node::Element(element) = node else {
+            continue;
+        };
+
+        match element.tag_name.to_ascii_lowercase().as_str() {
+            "style" => {
+                let css = text_of(element);
+                extracted.push(ExtractedStylesheet {
+                    stylesheet: CssParser::new(&css).parse_stylesheet(),
+                    source: StylesheetSource::Inline(css),
+                    media: media_of(element),
+                });
+            }
+            "link" if is_stylesheet_link(element) => {
+                extracted.push(ExtractedStylesheet {
+                    source: StylesheetSource::Link {
+                        href: element.attribute("href").unwrap_or_default().to_string(),
+                    },
+                    media: media_of(element),
+                    stylesheet: Stylesheet::default(),
+                });
+            }
+            _ => collect_stylesheets(&element.children, extracted),
+        }
+    }
+}
+
+
+fn is_stylesheet_link(element: &Element) -> bool {
+    element.attribute("rel").is_some_and(|rel| {
+        rel.split_ascii_whitespace()
+            .any(|keyword| keyword.eq_ignore_ascii_case("stylesheet"))
+    })
+}
+
+fn media_of(element: &Element) -> Option<MediaQueryList> {
+    let media = element.attribute("media")?.trim();
+    if media.is_empty() {
+        return None;
+    }
+
+    Some(parse_media_query_list(media))
+}
+
+fn text_of(element: &Element) -> String {
+    element
+        .children
+        .iter()
+        .filter_map(|child| match child {
+            Node::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,288 @@
+use super::attr::AttrValue;
+use super::parser::{Element, HtmlParser, Node};
+use super::render::{default_display, Display};
+use super::serializer::{escape_attribute, escape_text};
+
+// Each transformation can be turned off on its own; all are on by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinifyOptions {
+    // Collapse runs of whitespace in text to one space, and drop whitespace
+    // next to the start or end of a block element, where it isn't rendered.
+    pub(crate) collapse_whitespace: bool,
+    pub(crate) remove_comments: bool,
+    // Keep `<!--[if IE]>...<![endif]-->` and the like when removing comments.
+    pub(crate) keep_conditional_comments: bool,
+    // Write `class=a` for values that can go unquoted.
+    pub(crate) remove_attribute_quotes: bool,
+    // Leave out end tags, such as `</li>` and `</p>`, that the next sibling
+    // or the parent's end tag implies.
+    pub(crate) omit_optional_end_tags: bool,
+}
+
+impl Default for MinifyOptions {
+    fn default() -> Self {
+        Self {
+            collapse_whitespace: true,
+            remove_comments: true,
+            keep_conditional_comments: true,
+            remove_attribute_quotes: true,
+            omit_optional_end_tags: true,
+        }
+    }
+}
+
+impl MinifyOptions {
+    pub fn collapse_whitespace(mut self, enabled: bool) -> Self {
+        self.collapse_whitespace = enabled;
+        self
+    }
+
+    pub fn remove_comments(mut self, enabled: bool) -> Self {
+        self.remove_comments = enabled;
+        self
+    }
+
+    pub fn keep_conditional_comments(mut self, enabled: bool) -> Self {
+        self.keep_conditional_comments = enabled;
+        self
+    }
+
+    pub fn remove_attribute_quotes(mut self, enabled: bool) -> Self {
+        self.remove_attribute_quotes = enabled;
+        self
+    }
+
+    pub fn omit_optional_end_tags(mut self, enabled: bool) -> Self {
+        self.omit_optional_end_tags = enabled;
+        self
+    }
+
+    fn keeps_comment(&self, comment: &str) -> bool {
+        !self.remove_comments || (self.keep_conditional_comments && is_conditional_comment(comment))
+    }
+}
+
+// Elements whose whitespace is kept as written.
+fn preserves_whitespace(tag_name: &str) -> bool {
+    matches!(tag_name.to_ascii_lowercase().as_str(), "pre" | "textarea" | "listing" | "script" | "style")
+}
+
+// Elements at whose edges whitespace isn't rendered.
+fn is_block(element: &Element) -> bool {
+    let tag_name = element.tag_name.to_ascii_lowercase();
+    match default_display(&tag_name) {
+        Display::Block | Display::TableCell => true,
+        Display::None => tag_name == "head",
+        Display::Inline => matches!(tag_name.as_str(), "title" | "meta" | "link" | "base"),
+    }
+}
+
+fn is_conditional_comment(comment: &str) -> bool {
+    comment.starts_with("[if") || comment.ends_with("[endif]")
+}
+
+// Values that need no quotes: no whitespace or characters that would end or
+// confuse an unquoted value, and no trailing `/` that could read as `/>`.
+fn can_unquote(value: &str) -> bool {
+    !value.is_empty()
+        && !value.ends_with('/')
+        && !value
+            .chars()
+            .any(|ch| ch.is_ascii_whitespace() || matches!(ch, '"' | '\'' | '=' | '<' | '>' | '`'))
+}
+
+// Elements a `<p>` can't leave its end tag out at the end of.
+fn keeps_p_end_tag(parent: &str) -> bool {
+    matches!(
+        parent.to_ascii_lowercase().as_str(),
+        "a" | "audio" | "del" | "ins" | "map" | "noscript" | "video"
+    )
+}
+
+enum Item<'a> {
+    Element(&'a Element),
+    Text(String),
+    Comment(&'a str),
+}
+
+struct Minifier<'a> {
+    options: &'a MinifyOptions,
+    output: String,
+}
+
+impl Minifier<'_> {
+    // Writes `element`, leaving its end tag out if `may_omit_end_tag` and
+    // the end of its last child is written. Returns whether the end tag was.
+    fn element(&mut self, element: &Element, preserve: bool, may_omit_end_tag: bool) -> bool {
+        self.output.push('<');
+        self.output.push_str(&element.tag_name);
+        for (name, value) in &element.attributes {
+            self.output.push(' ');
+            self.output.push_str(name);
+            if let AttrValue::Value(value) = value {
+                self.output.push('=');
+                let quote = !(self.options.remove_attribute_quotes && can_unquote(value));
+                if quote {
+                    self.output.push('"');
+                }
+                let _ = escape_attribute(value, &mut self.output);
+                if quote {
+                    self.output.push('"');
+                }
+            }
+        }
+        self.output.push('>');
+        if HtmlParser::is_void_element(&element.tag_name) {
+            return true;
+        }
+
+        let preserve = preserve || preserves_whitespace(&element.tag_name);
+        if preserve && element.children.iter().all(|child| matches!(child, Node::Text(_))) {
+            let _ = element.write_contents(&mut self.output);
+        } else {
+            let last_closed = self.children(element, preserve, !may_omit_end_tag);
+            if may_omit_end_tag && last_closed {
+                return false;
+            }
+        }
+
+        self.output.push_str("</");
+        self.output.push_str(&element.tag_name);
+        self.output.push('>');
+        true
+    }
+
+    // Writes the children of `parent`. Returns whether the last one is
+    // closed by its own end tag, if it's an element.
+    fn children(&mut self, parent: &Element, preserve: bool, parent_end_tag_written: bool) -> bool {
+        let items = self.items(parent, preserve);
+        let mut last_closed = true;
+
+        for (index, item) in items.iter().enumerate() {
+            last_closed = true;
+            match item {
+                Item::Text(text) => self.output.push_str(&escaped(text)),
+                Item::Comment(comment) => {
+                    self.output.push_str("<!--");
+                    self.output.push_str(comment);
+                    self.output.push_str("-->");
+                }
+                Item::Element(element) => {
+                    let may_omit = self.options.omit_optional_end_tags
+                        && HtmlParser::has_optional_end_tag(&element.tag_name)
+                        && match items.get(index + 1) {
+                            Some(Item::Element(next)) => HtmlParser::closes_implicitly(&element.tag_name, &next.tag_name),
+                            Some(_) => false,
+                            None => {
+                                parent_end_tag_written
+                                    && !(element.tag_name.eq_ignore_ascii_case("p") && keeps_p_end_tag(&parent.tag_name))
+                            }
+                        };
+                    last_closed = self.element(element, preserve, may_omit);
+                }
+            }
+        }
+        last_closed
+    }
+
+    // The children to write: comments dropped, neighbouring text joined and
+    // whitespace collapsed as the options say.
+    fn items<'a>(&self, parent: &'a Element, preserve: bool) -> Vec<Item<'a>> {
+        let mut items: Vec<Item<'a>> = Vec::new();
+        for child in &parent.children {
+            match child {
+                Node::Comment(comment) if self.options.keeps_comment(comment) => items.push(Item::Comment(comment)),
+                Node::Comment(_) => {}
+                Node::Text(text) => match items.last_mut() {
+                    Some(Item::Text(last)) => last.push_str(text),
+                    _ => items.push(Item::Text(text.clone())),
+                },
+                Node::Element(element) => items.push(Item::Element(element)),
+            }
+        }
+        if preserve || !self.options.collapse_whitespace {
+            return items;
+        }
+
+        // Whitespace is dropped after a block boundary or another space,
+        // then before a block boundary. Comments aren't rendered, so they're
+        // passed over.
+        let block_parent = is_block(parent);
+        for item in &mut items {
+            if let Item::Text(text) = item {
+                *text = collapse(text);
+            }
+        }
+        let mut after_boundary = block_parent;
+        for item in items.iter_mut() {
+            match item {
+                Item::Text(text) => {
+                    if after_boundary {
+                        *text = text.trim_start_matches(' ').to_string();
+                    }
+                    if !text.is_empty() {
+                        after_boundary = text.ends_with(' ');
+                    }
+                }
+                Item::Element(element) => after_boundary = is_block(element),
+                Item::Comment(_) => {}
+            }
+        }
+        let mut before_boundary = block_parent;
+        for item in items.iter_mut().rev() {
+            match item {
+                Item::Text(text) => {
+                    if before_boundary {
+                        text.truncate(text.trim_end_matches(' ').len());
+                    }
+                    if !text.is_empty() {
+                        before_boundary = false;
+                    }
+                }
+                Item::Element(element) => before_boundary = is_block(element),
+                Item::Comment(_) => {}
+            }
+        }
+        items.retain(|item| !matches!(item, Item::Text(text) if text.is_empty()));
+        items
+    }
+}
+
+fn escaped(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let _ = escape_text(text, &mut escaped);
+    escaped
+}
+
+// Replaces each run of ASCII whitespace with a single space.
+fn collapse(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if !ch.is_ascii_whitespace() {
+            collapsed.push(ch);
+        } else if !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+    }
+    collapsed
+}
+
+// `node` as compact HTML that renders the same. Text inside `<pre>`,
+// `<textarea>`, `<script>` and `<style>` is left as it is, and a space at
+// the edge of an inline element is kept.
+pub fn minify(node: &Node, options: &MinifyOptions) -> String {
+    let mut minifier = Minifier {
+        options,
+        output: String::new(),
+    };
+    match node {
+        Node::Element(element) => {
+            minifier.element(element, false, false);
+        }
+        Node::Text(text) if options.collapse_whitespace => minifier.output = escaped(&collapse(text)),
+        Node::Text(text) => minifier.output = escaped(text),
+        Node::Comment(comment) if options.keeps_comment(comment) => minifier.output = format!("<!--{}-->", comment),
+        Node::Comment(_) => {}
+    }
+    minifier.output
+}
@@ -1,5 +1,39 @@
 pub mod tokenizer;
+pub mod attr;
 pub mod parser;
+pub mod document;
+pub mod sink;
+pub mod error;
+pub mod extract;
+pub mod quirks;
+pub mod serializer;
+pub mod pretty;
+pub mod minify;
+pub mod options;
+pub mod export;
+pub mod tree;
+pub mod diff;
+pub mod render;
+pub mod streaming;
+pub mod chunked;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod entities;
+mod entity_table;
 
-pub use tokenizer::{HtmlTokenizer, HtmlToken};
-pub use parser::{HtmlParser, Element, Node};
\ No newline at end of file
+pub use tokenizer::{Checkpoint, HtmlTokenizer, HtmlToken, HtmlTokenRef, SpannedTokens};
+pub use attr::{AttrValue, Attributes};
+pub use parser::{HtmlParser, HtmlStats, Element, Node, ChildRef};
+pub use streaming::StreamingHtmlParser;
+pub use chunked::{ChunkedHtmlTokenizer, ReaderTokens};
+pub use document::{Doctype, Document};
+pub use sink::{TreeSink, NodeSink};
+pub use options::{DuplicateAttributeCallback, HtmlParseOptions};
+pub use quirks::QuirksMode;
+pub use serializer::{escape_text, escape_attribute};
+pub use pretty::{to_html_pretty, PrettyOptions};
+pub use minify::{minify, MinifyOptions};
+pub use tree::{NodeData, NodeId, Tree};
+pub use diff::{diff_patch, PatchOp};
+pub use render::rendered_text;
+pub use error::{HtmlError, HtmlTokenizeError, TokenizeErrorKind};
\ No newline at end of file
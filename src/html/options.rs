@@ -0,0 +1,122 @@
+// Characters that template syntaxes put in attribute names: Vue's `@click`
+// and `#slot`, Angular's `[value]`, `(click)` and `*ngIf`, and modifiers like
+// `@submit.prevent`.
+const FRAMEWORK_ATTRIBUTE_CHARS: &[char] = &['@', '#', '[', ']', '(', ')', '.', '*'];
+
+use crate::span::Span;
+
+// Called with the name and position of an attribute dropped because an
+// earlier attribute on the same tag has that name.
+pub type DuplicateAttributeCallback = fn(name: &str, span: Span);
+
+// Fields are private so new options can be added without breaking callers;
+// build values with `default()` and the methods below.
+#[derive(Debug, Clone)]
+pub struct HtmlParseOptions {
+    pub(crate) framework_attributes: bool,
+    // Further characters to accept in attribute names.
+    pub(crate) extra_attribute_chars: Vec<char>,
+    // Collapse runs of ASCII whitespace in text to a single space, as a
+    // browser renders them, except inside `<pre>`, `<textarea>`, `<script>`
+    // and `<style>`.
+    pub(crate) collapse_whitespace: bool,
+    // Decode `&amp;`, `&#8217;` and the like in text and attribute values.
+    // On by default; turn off to keep the source text as written.
+    pub(crate) decode_character_references: bool,
+    // Drop whitespace between tags and trim text nodes. On by default; turn
+    // off to keep every character of text, e.g. `Hello <b>world</b>!`.
+    pub(crate) trim_text: bool,
+    pub(crate) on_duplicate_attribute: Option<DuplicateAttributeCallback>,
+    // Give documents the `html`, `head` and `body` elements browsers imply
+    // when the markup leaves them out.
+    pub(crate) fix_document_structure: bool,
+    // How deeply elements nest, top-level elements being at depth 1.
+    // Elements at the limit are left empty, their contents following them
+    // instead, as browsers do, so adversarial nesting can't overflow the
+    // stack of code that walks the tree recursively.
+    pub(crate) max_depth: usize,
+}
+
+impl Default for HtmlParseOptions {
+    fn default() -> Self {
+        Self {
+            framework_attributes: false,
+            extra_attribute_chars: Vec::new(),
+            collapse_whitespace: false,
+            decode_character_references: true,
+            trim_text: true,
+            on_duplicate_attribute: None,
+            fix_document_structure: false,
+            max_depth: 512,
+        }
+    }
+}
+
+// Written out because derived comparisons of function pointers are linted.
+impl PartialEq for HtmlParseOptions {
+    fn eq(&self, other: &Self) -> bool {
+        let same_callback = match (self.on_duplicate_attribute, other.on_duplicate_attribute) {
+            (Some(a), Some(b)) => std::ptr::fn_addr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        self.framework_attributes == other.framework_attributes
+            && self.extra_attribute_chars == other.extra_attribute_chars
+            && self.collapse_whitespace == other.collapse_whitespace
+            && self.decode_character_references == other.decode_character_references
+            && self.trim_text == other.trim_text
+            && self.fix_document_structure == other.fix_document_structure
+            && self.max_depth == other.max_depth
+            && same_callback
+    }
+}
+
+impl HtmlParseOptions {
+    pub fn framework_attributes(mut self, enabled: bool) -> Self {
+        self.framework_attributes = enabled;
+        self
+    }
+
+    pub fn extra_attribute_chars(mut self, chars: impl IntoIterator<Item = char>) -> Self {
+        self.extra_attribute_chars.extend(chars);
+        self
+    }
+
+    pub fn collapse_whitespace(mut self, enabled: bool) -> Self {
+        self.collapse_whitespace = enabled;
+        self
+    }
+
+    pub fn decode_character_references(mut self, enabled: bool) -> Self {
+        self.decode_character_references = enabled;
+        self
+    }
+
+    pub fn trim_text(mut self, enabled: bool) -> Self {
+        self.trim_text = enabled;
+        self
+    }
+
+    pub fn on_duplicate_attribute(mut self, callback: DuplicateAttributeCallback) -> Self {
+        self.on_duplicate_attribute = Some(callback);
+        self
+    }
+
+    pub fn fix_document_structure(mut self, enabled: bool) -> Self {
+        self.fix_document_structure = enabled;
+        self
+    }
+
+    // At least 2, so elements at the limit have a parent to be added to.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth.max(2);
+        self
+    }
+
+    pub(crate) fn attribute_name_chars(&self) -> Vec<char> {
+        let mut chars = self.extra_attribute_chars.clone();
+        if self.framework_attributes {
+            chars.extend_from_slice(FRAMEWORK_ATTRIBUTE_CHARS);
+        }
+        chars
+    }
+}
@@ -1,11 +1,347 @@
+use super::attr::{AttrValue, Attributes};
+use super::document::{with_implied_structure, Doctype, Document};
+use super::error::{HtmlError, HtmlTokenizeError};
+use super::options::HtmlParseOptions;
+use super::quirks::{quirks_mode_for_doctype, QuirksMode};
+use crate::progress::{Progress, ProgressCallback};
+use super::sink::{NodeSink, TreeSink};
+use super::tree::Tree;
 use super::tokenizer::{HtmlTokenizer, HtmlToken};
-use std::collections::HashMap;
+use crate::span::Span;
+use std::borrow::Cow;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Element {
     pub tag_name: String,
-    pub attributes: HashMap<String, String>,
+    pub attributes: Attributes,
     pub children: Vec<Node>,
+    // From the start tag to the end tag, or to the end of the last child if
+    // the end tag is missing. `None` for elements that weren't parsed.
+    pub source_span: Option<Span>,
+}
+
+// Descendants are dropped one at a time from a stack, as the derived drop
+// would recurse once per level and overflow on deep trees.
+impl Drop for Element {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(node) = pending.pop() {
+            if let Node::Element(mut element) = node {
+                pending.append(&mut element.children);
+            }
+        }
+    }
+}
+
+// Elements compare by content; where they were parsed doesn't matter.
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag_name == other.tag_name && self.attributes == other.attributes && self.children == other.children
+    }
+}
+
+// Building elements in code, as in
+// `Element::new("p").attr("class", "note").text("Hi ").child(Element::new("b").text("there"))`.
+impl Element {
+    pub fn new(tag_name: impl Into<String>) -> Self {
+        Self {
+            tag_name: tag_name.into(),
+            attributes: Attributes::new(),
+            children: Vec::new(),
+            source_span: None,
+        }
+    }
+
+    // Setting a name again replaces its value. `AttrValue::Bool` gives a
+    // boolean attribute.
+    pub fn attr(mut self, name: impl Into<String>, value: impl Into<AttrValue>) -> Self {
+        self.attributes.insert(name, value);
+        self
+    }
+
+    pub fn child(mut self, node: impl Into<Node>) -> Self {
+        self.children.push(node.into());
+        self
+    }
+
+    // Joined to a text child just before it, so the tree is one the parser
+    // could give.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        match self.children.last_mut() {
+            Some(Node::Text(last)) => last.push_str(&text),
+            _ => self.children.push(Node::Text(text)),
+        }
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.children.push(Node::Comment(comment.into()));
+        self
+    }
+}
+
+impl Element {
+    // Attribute names are ASCII case-insensitive on HTML elements.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attribute_value(name).map(AttrValue::as_str)
+    }
+
+    // The DOM's name for `attribute`.
+    pub fn get_attribute(&self, name: &str) -> Option<&str> {
+        self.attribute(name)
+    }
+
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attribute_value(name).is_some()
+    }
+
+    // Like `attribute`, but tells `<input disabled>` from `disabled=""`.
+    pub fn attribute_value(&self, name: &str) -> Option<&AttrValue> {
+        self.attributes.get(name).or_else(|| {
+            self.attributes
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+        })
+    }
+
+    // These lookups search this element and its descendants in document
+    // order. Ids and classes compare case-sensitively, tag names don't.
+    pub fn get_element_by_id(&self, id: &str) -> Option<&Element> {
+        if self.attribute("id") == Some(id) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| match child {
+            Node::Element(element) => element.get_element_by_id(id),
+            _ => None,
+        })
+    }
+
+    // `*` matches every element.
+    pub fn get_elements_by_tag_name(&self, tag: &str) -> Vec<&Element> {
+        let mut found = Vec::new();
+        self.collect_elements(&|element| tag == "*" || element.tag_name.eq_ignore_ascii_case(tag), &mut found);
+        found
+    }
+
+    // Elements having every class in the whitespace-separated `classes`.
+    pub fn get_elements_by_class_name(&self, classes: &str) -> Vec<&Element> {
+        let wanted: Vec<&str> = classes.split_ascii_whitespace().collect();
+        let mut found = Vec::new();
+        if wanted.is_empty() {
+            return found;
+        }
+        self.collect_elements(
+            &|element| {
+                let class = element.attribute("class").unwrap_or_default();
+                wanted.iter().all(|wanted| class.split_ascii_whitespace().any(|class| class == *wanted))
+            },
+            &mut found,
+        );
+        found
+    }
+
+    fn collect_elements<'a>(&'a self, predicate: &impl Fn(&Element) -> bool, found: &mut Vec<&'a Element>) {
+        if predicate(self) {
+            found.push(self);
+        }
+        for child in &self.children {
+            if let Node::Element(element) = child {
+                element.collect_elements(predicate, found);
+            }
+        }
+    }
+
+    // The text of every descendant text node, in document order.
+    pub fn text_content(&self) -> String {
+        self.descendant_nodes()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Every node below this one, depth-first in document order.
+    pub fn descendant_nodes(&self) -> impl Iterator<Item = &Node> {
+        DescendantNodes {
+            stack: vec![self.children.iter()],
+        }
+    }
+
+    // The children that are elements.
+    pub fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    // Calls `visit` with every node below this one, depth-first in document
+    // order. A node's children are visited after `visit` returns, so any it
+    // adds are visited too. Iterators can't hand out a node and then its
+    // children mutably, hence the callback.
+    pub fn for_each_descendant_mut(&mut self, mut visit: impl FnMut(&mut Node)) {
+        let mut stack = vec![self.children.iter_mut()];
+        while let Some(children) = stack.last_mut() {
+            let Some(node) = children.next() else {
+                stack.pop();
+                continue;
+            };
+            visit(node);
+            if let Node::Element(element) = node {
+                stack.push(element.children.iter_mut());
+            }
+        }
+    }
+
+    // Every element below this one, depth-first in document order.
+    pub fn descendants(&self) -> impl Iterator<Item = &Element> {
+        self.descendant_nodes().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn is_custom_element(&self) -> bool {
+        let name = self.tag_name.as_str();
+
+        // Hyphenated names reserved by SVG and MathML can never be custom elements.
+        if matches!(
+            name.to_ascii_lowercase().as_str(),
+            "annotation-xml" | "color-profile" | "font-face" | "font-face-src" |
+            "font-face-uri" | "font-face-format" | "font-face-name" | "missing-glyph"
+        ) {
+            return false;
+        }
+
+        name.contains('-') && name.starts_with(|c: char| c.is_ascii_alphabetic())
+    }
+
+    fn child_index(&self, reference: ChildRef<'_>) -> Option<usize> {
+        match reference {
+            ChildRef::Index(index) => (index < self.children.len()).then_some(index),
+            ChildRef::Node(node) => self.children.iter().position(|child| child == node),
+        }
+    }
+
+    // Returns false, leaving the children untouched, if the reference doesn't
+    // name a child.
+    pub fn insert_before<'a>(&mut self, reference: impl Into<ChildRef<'a>>, nodes: impl IntoIterator<Item = Node>) -> bool {
+        let Some(index) = self.child_index(reference.into()) else {
+            return false;
+        };
+        self.children.splice(index..index, nodes);
+        true
+    }
+
+    // Replaces the referenced child with `nodes`, returning the removed child.
+    pub fn replace_with<'a>(
+        &mut self,
+        reference: impl Into<ChildRef<'a>>,
+        nodes: impl IntoIterator<Item = Node>,
+    ) -> Option<Node> {
+        let index = self.child_index(reference.into())?;
+        self.children.splice(index..=index, nodes).next()
+    }
+
+    pub fn append_child(&mut self, node: Node) {
+        self.children.push(node);
+    }
+
+    // Panics if `index` is past the end, as `Vec::insert` does.
+    pub fn insert_child(&mut self, index: usize, node: Node) {
+        self.children.insert(index, node);
+    }
+
+    // Panics if `index` isn't a child's, as `Vec::remove` does.
+    pub fn remove_child(&mut self, index: usize) -> Node {
+        self.children.remove(index)
+    }
+
+    pub fn retain_children(&mut self, keep: impl FnMut(&Node) -> bool) {
+        self.children.retain(keep);
+    }
+
+    // Replaces the value of an attribute whose name matches ignoring ASCII
+    // case, keeping its place, or adds the attribute last.
+    pub fn set_attribute(&mut self, name: &str, value: impl Into<AttrValue>) {
+        let name = self
+            .attributes
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(name))
+            .map_or_else(|| name.to_string(), String::clone);
+        self.attributes.insert(name, value);
+    }
+
+    // Returns the removed value; a boolean attribute's is empty.
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        let name = self.attributes.keys().find(|key| key.eq_ignore_ascii_case(name))?.clone();
+        self.attributes.remove(&name).map(|value| value.as_str().to_string())
+    }
+
+    // `add_class` and `remove_class` take one or more whitespace-separated
+    // classes. As with the DOM's `classList`, changing the list rewrites
+    // `class` with each class once, separated by single spaces.
+    pub fn add_class(&mut self, classes: &str) {
+        let mut list = self.class_list();
+        for class in classes.split_ascii_whitespace() {
+            if !list.contains(&class) {
+                list.push(class);
+            }
+        }
+        let class = list.join(" ");
+        self.set_attribute("class", class);
+    }
+
+    // Removes every occurrence of each class. An element without `class`
+    // doesn't gain one, while one left with no classes keeps `class=""`.
+    pub fn remove_class(&mut self, classes: &str) {
+        if !self.has_attribute("class") {
+            return;
+        }
+        let removed: Vec<&str> = classes.split_ascii_whitespace().collect();
+        let mut list = self.class_list();
+        list.retain(|class| !removed.contains(class));
+        let class = list.join(" ");
+        self.set_attribute("class", class);
+    }
+
+    // The classes in `class`, without duplicates.
+    fn class_list(&self) -> Vec<&str> {
+        let mut list = Vec::new();
+        for class in self.attribute("class").unwrap_or_default().split_ascii_whitespace() {
+            if !list.contains(&class) {
+                list.push(class);
+            }
+        }
+        list
+    }
+
+    // Replaces the children with `html` parsed as this element's contents.
+    pub fn set_inner_html(&mut self, html: &str) {
+        self.children = HtmlParser::for_fragment(html, &self.tag_name, HtmlParseOptions::default()).parse();
+    }
+}
+
+// Locates a child either by position or by the first child equal to a node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChildRef<'a> {
+    Index(usize),
+    Node(&'a Node),
+}
+
+impl From<usize> for ChildRef<'_> {
+    fn from(index: usize) -> Self {
+        ChildRef::Index(index)
+    }
+}
+
+impl<'a> From<&'a Node> for ChildRef<'a> {
+    fn from(node: &'a Node) -> Self {
+        ChildRef::Node(node)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,145 +351,733 @@ pub enum Node {
     Comment(String),
 }
 
-pub struct HtmlParser {
-    tokens: Vec<HtmlToken>,
-    position: usize,
+impl From<Element> for Node {
+    fn from(element: Element) -> Self {
+        Node::Element(element)
+    }
 }
 
-impl HtmlParser {
-    pub fn new(input: &str) -> Self {
-        let tokenizer = HtmlTokenizer::new(input);
-        let tokens: Vec<HtmlToken> = tokenizer.collect();
-        
+impl Node {
+    // Every node below this one, depth-first in document order; none for
+    // text and comments.
+    pub fn descendants(&self) -> impl Iterator<Item = &Node> {
+        let stack = match self {
+            Node::Element(element) => vec![element.children.iter()],
+            _ => Vec::new(),
+        };
+        DescendantNodes { stack }
+    }
+
+    pub fn text_content(&self) -> String {
+        match self {
+            Node::Element(element) => element.text_content(),
+            Node::Text(text) => text.clone(),
+            Node::Comment(_) => String::new(),
+        }
+    }
+}
+
+// Replaces each run of ASCII whitespace with a single space.
+fn collapse_ascii_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for ch in text.chars() {
+        if ch.is_ascii_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(ch);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+// The children of each element yet to be finished, innermost last.
+struct DescendantNodes<'a> {
+    stack: Vec<std::slice::Iter<'a, Node>>,
+}
+
+impl<'a> Iterator for DescendantNodes<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            let Some(node) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+            if let Node::Element(element) = node {
+                if !element.children.is_empty() {
+                    self.stack.push(element.children.iter());
+                }
+            }
+            return Some(node);
+        }
+    }
+}
+
+// Elements whose text keeps its whitespace when collapsing is enabled.
+fn preserves_whitespace(tag_name: &str) -> bool {
+    matches!(tag_name.to_ascii_lowercase().as_str(), "pre" | "textarea" | "listing" | "script" | "style")
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HtmlStats {
+    pub tokens: usize,
+    pub nodes: usize,
+    pub bytes: usize,
+    pub max_depth: usize,
+    // Tokens dropped or elements closed implicitly to recover from bad markup.
+    pub recoveries: usize,
+}
+
+// Tree-building state shared by `HtmlParser` and `StreamingHtmlParser`.
+#[derive(Debug, Default)]
+pub(crate) struct BuildState {
+    pub(crate) stats: HtmlStats,
+    pub(crate) errors: Vec<HtmlError>,
+    // Names of the elements being built, outermost first.
+    open: Vec<String>,
+    // Formatting elements closed by an ancestor's end tag, to be reopened
+    // around the next content, with the depth each was open at. Kept in
+    // order of depth.
+    reopen: Vec<(usize, HtmlToken)>,
+    collapse_whitespace: bool,
+    trim_text: bool,
+    // How many open elements preserve whitespace.
+    preserve_depth: usize,
+    max_depth: usize,
+    // Whether an element past `max_depth` has been reported.
+    too_deep: bool,
+}
+
+impl BuildState {
+    pub(crate) fn new(options: &HtmlParseOptions) -> Self {
         Self {
-            tokens,
-            position: 0,
+            collapse_whitespace: options.collapse_whitespace,
+            trim_text: options.trim_text,
+            max_depth: options.max_depth,
+            ..Self::default()
         }
     }
 
-    fn current_token(&self) -> Option<&HtmlToken> {
-        self.tokens.get(self.position)
+    // Text as it's stored in a text node; `None` if no node should be created.
+    fn text_value<'t>(&self, text: &'t str) -> Option<Cow<'t, str>> {
+        let collapse = self.collapse_whitespace && self.preserve_depth == 0;
+        if !self.trim_text {
+            return match text {
+                "" => None,
+                text if collapse => Some(Cow::Owned(collapse_ascii_whitespace(text))),
+                text => Some(Cow::Borrowed(text)),
+            };
+        }
+
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            None
+        } else if collapse {
+            Some(Cow::Owned(collapse_ascii_whitespace(trimmed)))
+        } else {
+            Some(Cow::Borrowed(trimmed))
+        }
     }
+}
+
+// Builds nodes from a token source: the parser's buffer, or a tokenizer read
+// one token at a time.
+pub(crate) trait TreeBuilder {
+    fn current_token(&self) -> Option<&HtmlToken>;
+
+    // Moves past the current token and returns it.
+    fn take_token(&mut self) -> Option<HtmlToken>;
 
     fn advance(&mut self) {
-        if self.position < self.tokens.len() {
-            self.position += 1;
+        self.take_token();
+    }
+
+    // The span of the token last moved past.
+    fn previous_span(&self) -> Span;
+
+    fn state(&self) -> &BuildState;
+
+    fn state_mut(&mut self) -> &mut BuildState;
+
+    // The span of the current token.
+    fn current_span(&self) -> Span;
+
+    // Drops an end tag that matches no open element.
+    fn skip_stray_end_tag(&mut self) {
+        let Some(HtmlToken::EndTag { name }) = self.current_token() else {
+            return;
+        };
+        let name = name.clone();
+        let span = self.current_span();
+        let state = self.state_mut();
+        // A formatting element waiting to be reopened stays closed.
+        let waiting = state
+            .reopen
+            .iter()
+            .rposition(|(_, tag)| matches!(tag, HtmlToken::StartTag { name: open, .. } if open.eq_ignore_ascii_case(&name)));
+        if let Some(index) = waiting {
+            state.reopen.remove(index);
         }
+        state.errors.push(HtmlError::UnexpectedEndTag { name, span });
+        state.stats.recoveries += 1;
+        self.advance();
     }
 
-    fn is_void_element(tag_name: &str) -> bool {
-        matches!(
-            tag_name.to_lowercase().as_str(),
-            "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" |
-            "link" | "meta" | "param" | "source" | "track" | "wbr"
-        )
+    // Records that the element `name` being parsed ends with an ancestor,
+    // without an end tag of its own. A formatting element is reopened around
+    // the content that follows.
+    fn close_with_ancestor(&mut self, name: &str, reopen_attributes: Option<Vec<(String, AttrValue)>>) {
+        if !HtmlParser::has_optional_end_tag(name) {
+            self.state_mut().stats.recoveries += 1;
+        }
+        if let Some(attributes) = reopen_attributes {
+            let state = self.state_mut();
+            let depth = state.open.len();
+            let index = state.reopen.partition_point(|(open_depth, _)| *open_depth <= depth);
+            let tag = HtmlToken::StartTag { name: name.to_string(), attributes, self_closing: false };
+            state.reopen.insert(index, (depth, tag));
+        }
     }
 
-    fn parse_element(&mut self, start_tag: HtmlToken) -> Option<Node> {
-        if let HtmlToken::StartTag { name, attributes, self_closing } = start_tag {
-            let mut attr_map = HashMap::new();
-            for (key, value) in attributes {
-                attr_map.insert(key, value);
+    // The start tag of the outermost formatting element waiting to be
+    // reopened, if the current token is content it should wrap.
+    fn take_reopened(&mut self) -> Option<HtmlToken> {
+        if self.state().reopen.is_empty() {
+            return None;
+        }
+        let wraps = match self.current_token()? {
+            // Links and `<nobr>` don't nest, so a new one isn't put inside
+            // one waiting to be reopened.
+            HtmlToken::StartTag { name, .. } if name.eq_ignore_ascii_case("a") || name.eq_ignore_ascii_case("nobr") => {
+                let name = name.clone();
+                let state = self.state_mut();
+                state.reopen.retain(|(_, tag)| !matches!(tag, HtmlToken::StartTag { name: open, .. } if open.eq_ignore_ascii_case(&name)));
+                !state.reopen.is_empty()
+            }
+            // Block-level content is left alone; its own content is wrapped.
+            HtmlToken::StartTag { name, .. } => {
+                !HtmlParser::closes_implicitly("p", name) && !HtmlParser::has_optional_end_tag(name)
             }
+            HtmlToken::Text(text) => self.state().text_value(text).is_some(),
+            _ => false,
+        };
+        wraps.then(|| self.state_mut().reopen.remove(0).1)
+    }
 
-            let mut element = Element {
-                tag_name: name.clone(),
-                attributes: attr_map,
-                children: Vec::new(),
-            };
+    // At `max_depth`, start tags don't nest: their element is added to
+    // `parent`, the element just above the limit, and their contents follow
+    // it there. Their names stay open, after `parent`'s at `depth`, so their
+    // end tags are matched. Returns whether the current token was handled.
+    fn flatten<S: TreeSink>(&mut self, depth: usize, parent: &mut S::Handle, sink: &mut S) -> bool {
+        let state = self.state();
+        let flattened = state.open.len() > depth;
+        match self.current_token() {
+            Some(HtmlToken::StartTag { name, .. }) if state.open.len() + 1 >= state.max_depth => {
+                if flattened && state.open.last().is_some_and(|open| HtmlParser::closes_implicitly(open, name)) {
+                    self.state_mut().open.pop();
+                }
+                let Some(HtmlToken::StartTag { name, attributes, self_closing }) = self.take_token() else {
+                    return false;
+                };
+                let span = self.previous_span();
+                let mut element = sink.create_element(&name);
+                sink.set_attributes(&mut element, attributes);
+                sink.set_source_span(&mut element, span);
+                sink.append_child(Some(parent), element);
 
-            if self_closing || Self::is_void_element(&name) {
-                return Some(Node::Element(element));
+                let state = self.state_mut();
+                state.stats.nodes += 1;
+                state.stats.recoveries += 1;
+                state.stats.max_depth = state.stats.max_depth.max(depth + 1);
+                if !state.too_deep {
+                    state.too_deep = true;
+                    state.errors.push(HtmlError::NestingTooDeep { name: name.clone(), span });
+                }
+                if !self_closing && !HtmlParser::is_void_element(&name) {
+                    state.open.push(name);
+                }
+                true
             }
+            Some(HtmlToken::EndTag { name }) if flattened => {
+                let matches = |open: &String| open.eq_ignore_ascii_case(name);
+                if let Some(index) = state.open[depth..].iter().rposition(matches) {
+                    self.state_mut().open.truncate(depth + index);
+                    self.advance();
+                    return true;
+                }
+                // Closing `parent` or an ancestor closes the flattened
+                // elements too; a stray end tag is dropped as usual.
+                if state.open[..depth].iter().any(matches) {
+                    self.state_mut().open.truncate(depth);
+                }
+                false
+            }
+            _ => false,
+        }
+    }
 
-            while let Some(token) = self.current_token() {
-                match token {
-                    HtmlToken::EndTag { name: end_name } if end_name == &name => {
-                        self.advance();
-                        break;
-                    }
-                    HtmlToken::StartTag { .. } => {
-                        if let Some(child) = self.parse_node() {
-                            element.children.push(child);
-                        }
-                    }
-                    HtmlToken::Text(text) => {
-                        let trimmed = text.trim();
-                        if !trimmed.is_empty() {
-                            element.children.push(Node::Text(trimmed.to_string()));
+    // Called with the start tag just consumed.
+    //
+    // An end tag closes the elements up to the nearest open one it names, or
+    // is dropped if none does, so `<div><p>x</div>` closes the `<p>` and
+    // `<p>x</div>` ignores the `</div>`. Formatting elements closed this way
+    // are reopened around the content that follows, so in
+    // `<b><i>x</b>y</i>` the `y` is italic too.
+    //
+    // The elements being built are kept on a stack of their own rather than
+    // the call stack, so deep nesting can't overflow it.
+    fn parse_element<S: TreeSink>(&mut self, start_tag: HtmlToken, sink: &mut S) -> Option<S::Handle> {
+        let mut stack: Vec<OpenElement<S::Handle>> = Vec::new();
+        let mut start_tag = Some(start_tag);
+        loop {
+            if let Some(HtmlToken::StartTag { name, attributes, self_closing }) = start_tag.take() {
+                let start_span = self.previous_span();
+                let mut handle = sink.create_element(&name);
+                let reopen_attributes = is_formatting_element(&name).then(|| attributes.clone());
+                sink.set_attributes(&mut handle, attributes);
+                self.state_mut().stats.nodes += 1;
+
+                if self_closing || HtmlParser::is_void_element(&name) {
+                    sink.set_source_span(&mut handle, start_span);
+                    match stack.last_mut() {
+                        Some(parent) => {
+                            sink.append_child(Some(&mut parent.handle), handle);
+                            continue;
                         }
-                        self.advance();
-                    }
-                    HtmlToken::Comment(comment) => {
-                        element.children.push(Node::Comment(comment.clone()));
-                        self.advance();
+                        None => return Some(handle),
                     }
-                    HtmlToken::EndTag { .. } => {
-                        break;
+                }
+
+                let preserves = preserves_whitespace(&name);
+                let state = self.state_mut();
+                state.open.push(name.clone());
+                let depth = state.open.len();
+                state.stats.max_depth = state.stats.max_depth.max(depth);
+                if preserves {
+                    state.preserve_depth += 1;
+                }
+                stack.push(OpenElement { handle, name, start_span, reopen_attributes, preserves, depth });
+            }
+            let element = stack.last_mut()?;
+
+            if self.state().open.len() + 1 >= self.state().max_depth && self.flatten(element.depth, &mut element.handle, sink) {
+                continue;
+            }
+            // `Some(closed)` once the element ends, `closed` if by its own
+            // end tag or implicitly rather than with an ancestor.
+            let ended = match self.current_token() {
+                None => Some(false),
+                Some(HtmlToken::EndTag { name: end_name }) if end_name.eq_ignore_ascii_case(&element.name) => {
+                    self.advance();
+                    Some(true)
+                }
+                Some(HtmlToken::EndTag { name: end_name }) => {
+                    let ancestor = self.state().open.iter().any(|open| open.eq_ignore_ascii_case(end_name));
+                    if ancestor {
+                        self.close_with_ancestor(&element.name, element.reopen_attributes.take());
+                        Some(false)
+                    } else {
+                        self.skip_stray_end_tag();
+                        None
                     }
-                    HtmlToken::Doctype(_) => {
-                        self.advance();
+                }
+                Some(HtmlToken::StartTag { name: start_name, .. }) if HtmlParser::closes_implicitly(&element.name, start_name) => {
+                    Some(true)
+                }
+                // Ends an ancestor whose end tag was left out, as `<li>` does
+                // in `<li><b>A<li>B`.
+                Some(HtmlToken::StartTag { name: start_name, .. })
+                    if HtmlParser::implicitly_closed(self.state().open.iter().map(String::as_str), start_name).is_some() =>
+                {
+                    self.close_with_ancestor(&element.name, element.reopen_attributes.take());
+                    Some(false)
+                }
+                Some(HtmlToken::StartTag { .. }) => {
+                    start_tag = self.take_reopened().or_else(|| self.take_token());
+                    None
+                }
+                Some(HtmlToken::Text(text)) if !self.state().reopen.is_empty() && self.state().text_value(text).is_some() => {
+                    start_tag = Some(self.state_mut().reopen.remove(0).1);
+                    None
+                }
+                Some(HtmlToken::Text(text)) => {
+                    let child = self.state().text_value(text).map(|text| sink.create_text(&text));
+                    if let Some(child) = child {
+                        sink.append_child(Some(&mut element.handle), child);
+                        self.state_mut().stats.nodes += 1;
                     }
+                    self.advance();
+                    None
+                }
+                Some(HtmlToken::Comment(comment)) => {
+                    let child = sink.create_comment(comment);
+                    sink.append_child(Some(&mut element.handle), child);
+                    self.state_mut().stats.nodes += 1;
+                    self.advance();
+                    None
+                }
+                Some(HtmlToken::Doctype { .. }) => {
+                    self.state_mut().stats.recoveries += 1;
+                    self.advance();
+                    None
                 }
+                // Processing instructions have no place in the tree.
+                Some(HtmlToken::ProcessingInstruction(_)) => {
+                    self.advance();
+                    None
+                }
+            };
+
+            let Some(closed) = ended else {
+                continue;
+            };
+            let element = stack.pop()?;
+            let handle = self.finish_element(element, closed, sink);
+            match stack.last_mut() {
+                Some(parent) => sink.append_child(Some(&mut parent.handle), handle),
+                None => return Some(handle),
             }
+        }
+    }
 
-            Some(Node::Element(element))
-        } else {
-            None
+    fn finish_element<S: TreeSink>(&mut self, element: OpenElement<S::Handle>, closed: bool, sink: &mut S) -> S::Handle {
+        let OpenElement { mut handle, name, start_span, preserves, depth, .. } = element;
+        let state = self.state_mut();
+        state.open.truncate(depth - 1);
+        if preserves {
+            state.preserve_depth -= 1;
+        }
+        // Formatting elements left open inside a cell aren't reopened
+        // outside it.
+        if matches!(name.to_ascii_lowercase().as_str(), "applet" | "caption" | "marquee" | "object" | "td" | "template" | "th") {
+            let depth = state.open.len();
+            state.reopen.retain(|(open_depth, _)| *open_depth <= depth);
+        }
+        if !closed && !HtmlParser::has_optional_end_tag(&name) {
+            state.errors.push(HtmlError::UnclosedElement { name, span: start_span });
         }
+        let end = self.previous_span().end;
+        sink.set_source_span(&mut handle, Span { end, ..start_span });
+        handle
     }
 
-    fn parse_node(&mut self) -> Option<Node> {
-        match self.current_token()?.clone() {
-            HtmlToken::StartTag { .. } => {
-                let token = self.current_token()?.clone();
-                self.advance();
-                self.parse_element(token)
+    // `None` at an end tag, which is left for the caller, or at the end of
+    // the input.
+    fn parse_node<S: TreeSink>(&mut self, sink: &mut S) -> Option<S::Handle> {
+        loop {
+            if let HtmlToken::EndTag { .. } = self.current_token()? {
+                return None;
             }
-            HtmlToken::Text(text) => {
-                self.advance();
-                let trimmed = text.trim();
-                if !trimmed.is_empty() {
-                    Some(Node::Text(trimmed.to_string()))
-                } else {
-                    self.parse_node()
+            if let Some(tag) = self.take_reopened() {
+                return self.parse_element(tag, sink);
+            }
+
+            match self.take_token()? {
+                token @ HtmlToken::StartTag { .. } => return self.parse_element(token, sink),
+                HtmlToken::Text(text) => {
+                    let node = self.state().text_value(&text).map(|text| sink.create_text(&text));
+                    if node.is_some() {
+                        self.state_mut().stats.nodes += 1;
+                        return node;
+                    }
+                }
+                HtmlToken::Comment(comment) => {
+                    self.state_mut().stats.nodes += 1;
+                    return Some(sink.create_comment(&comment));
                 }
+                HtmlToken::EndTag { .. } | HtmlToken::Doctype { .. } | HtmlToken::ProcessingInstruction(_) => {}
             }
-            HtmlToken::Comment(comment) => {
-                self.advance();
-                Some(Node::Comment(comment))
+        }
+    }
+}
+
+// An element `parse_element` is building.
+pub(crate) struct OpenElement<H> {
+    handle: H,
+    name: String,
+    start_span: Span,
+    // The start tag's attributes, for a formatting element reopened after
+    // an ancestor's end tag closes it.
+    reopen_attributes: Option<Vec<(String, AttrValue)>>,
+    preserves: bool,
+    // Its place in `BuildState::open`, counting from 1.
+    depth: usize,
+}
+
+// Elements the HTML standard reopens when they're closed by an ancestor's
+// end tag.
+fn is_formatting_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name.to_ascii_lowercase().as_str(),
+        "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s" | "small" | "strike" | "strong" | "tt" | "u"
+    )
+}
+
+// Non-void elements the HTML standard's parser treats specially.
+fn is_special_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name,
+        "address" | "applet" | "article" | "aside" | "blockquote" | "body" | "button" | "caption" |
+        "center" | "colgroup" | "dd" | "details" | "dir" | "div" | "dl" | "dt" | "fieldset" |
+        "figcaption" | "figure" | "footer" | "form" | "frameset" | "h1" | "h2" | "h3" | "h4" |
+        "h5" | "h6" | "head" | "header" | "hgroup" | "html" | "iframe" | "li" | "listing" | "main" |
+        "marquee" | "menu" | "nav" | "noembed" | "noframes" | "noscript" | "object" | "ol" | "p" |
+        "plaintext" | "pre" | "script" | "search" | "section" | "select" | "style" | "summary" |
+        "table" | "tbody" | "td" | "template" | "textarea" | "tfoot" | "th" | "thead" | "title" |
+        "tr" | "ul" | "xmp"
+    )
+}
+
+pub struct HtmlParser {
+    tokens: Vec<HtmlToken>,
+    spans: Vec<Span>,
+    position: usize,
+    progress: Progress,
+    build: BuildState,
+    tokenize_errors: Vec<HtmlTokenizeError>,
+    fix_document_structure: bool,
+}
+
+impl HtmlParser {
+    pub fn new(input: &str) -> Self {
+        Self::with_options(input, HtmlParseOptions::default())
+    }
+
+    pub fn with_options(input: &str, options: HtmlParseOptions) -> Self {
+        Self::from_tokenizer(HtmlTokenizer::with_options(input, &options), input.len(), options)
+    }
+
+    // Parses `input` as the contents of a `context` element, as setting
+    // `innerHTML` does. In `<script>`, `<style>`, `<textarea>` and `<title>`
+    // it's text up to the context's end tag.
+    pub fn for_fragment(input: &str, context: &str, options: HtmlParseOptions) -> Self {
+        let tokenizer = HtmlTokenizer::with_options(input, &options).in_context(context);
+        Self::from_tokenizer(tokenizer, input.len(), options)
+    }
+
+    fn from_tokenizer(mut tokenizer: HtmlTokenizer, input_len: usize, options: HtmlParseOptions) -> Self {
+        let mut tokens = Vec::new();
+        let mut spans = Vec::new();
+        while let Some((token, span)) = tokenizer.next_token_spanned() {
+            tokens.push(token);
+            spans.push(span);
+        }
+        
+        Self {
+            tokens,
+            spans,
+            position: 0,
+            progress: Progress::new(input_len),
+            build: BuildState::new(&options),
+            tokenize_errors: tokenizer.errors().to_vec(),
+            fix_document_structure: options.fix_document_structure,
+        }
+    }
+
+    // Calls `callback` every `interval` tokens while parsing, and once more
+    // when parsing completes.
+    pub fn on_progress(mut self, interval: usize, callback: ProgressCallback) -> Self {
+        self.progress.set_callback(interval, callback);
+        self
+    }
+
+    pub fn stats(&self) -> HtmlStats {
+        HtmlStats {
+            tokens: self.position,
+            bytes: self.position.checked_sub(1).map_or(0, |last| self.spans[last].end),
+            ..self.build.stats.clone()
+        }
+    }
+
+    pub(crate) fn is_void_element(tag_name: &str) -> bool {
+        matches!(
+            tag_name.to_ascii_lowercase().as_str(),
+            "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" |
+            "link" | "meta" | "param" | "source" | "track" | "wbr"
+        )
+    }
+
+    // Whether a `start` tag ends an open `open` element whose end tag was
+    // left out, as in `<li>A<li>B` or `<p>one<p>two`.
+    pub(crate) fn closes_implicitly(open: &str, start: &str) -> bool {
+        let start = start.to_ascii_lowercase();
+        let start = start.as_str();
+        match open.to_ascii_lowercase().as_str() {
+            "li" => start == "li",
+            "dt" | "dd" => matches!(start, "dt" | "dd"),
+            "p" => matches!(
+                start,
+                "address" | "article" | "aside" | "blockquote" | "center" | "dd" | "details" |
+                "dialog" | "dir" | "div" | "dl" | "dt" | "fieldset" | "figcaption" | "figure" |
+                "footer" | "form" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "header" |
+                "hgroup" | "hr" | "li" | "listing" | "main" | "menu" | "nav" | "ol" | "p" |
+                "plaintext" | "pre" | "search" | "section" | "summary" | "table" | "ul" | "xmp"
+            ),
+            "option" => matches!(start, "option" | "optgroup"),
+            "optgroup" => start == "optgroup",
+            "tr" => matches!(start, "tr" | "thead" | "tbody" | "tfoot"),
+            "td" | "th" => matches!(start, "td" | "th" | "tr" | "thead" | "tbody" | "tfoot"),
+            "thead" | "tbody" => matches!(start, "tbody" | "tfoot"),
+            "tfoot" => start == "tbody",
+            _ => false,
+        }
+    }
+
+    // The index in `open`, outermost first, of the element a `start` tag
+    // ends implicitly. The search from the innermost element stops where
+    // that element couldn't be closed from, so in `<li><ul><li>` the outer
+    // `<li>` stays open, while in `<li><b><li>` it's closed along with the
+    // `<b>`.
+    pub(crate) fn implicitly_closed<'a>(
+        open: impl DoubleEndedIterator<Item = &'a str> + ExactSizeIterator,
+        start: &str,
+    ) -> Option<usize> {
+        for (index, open) in open.enumerate().rev() {
+            if Self::closes_implicitly(open, start) {
+                return Some(index);
             }
-            HtmlToken::EndTag { .. } => None,
-            HtmlToken::Doctype(_) => {
-                self.advance();
-                self.parse_node()
+            if Self::bounds_implied_end(open, start) {
+                return None;
             }
         }
+        None
+    }
+
+    // Whether an open `open` element keeps a `start` tag from ending the
+    // elements outside it. These are the HTML standard's scopes.
+    fn bounds_implied_end(open: &str, start: &str) -> bool {
+        let open = open.to_ascii_lowercase();
+        let open = open.as_str();
+        match start.to_ascii_lowercase().as_str() {
+            "li" | "dt" | "dd" => is_special_element(open) && !matches!(open, "address" | "div" | "p"),
+            "td" | "th" | "tr" | "thead" | "tbody" | "tfoot" => matches!(open, "html" | "table" | "template"),
+            start if Self::closes_implicitly("p", start) => matches!(
+                open,
+                "applet" | "button" | "caption" | "html" | "marquee" | "object" | "table" | "td" | "th" | "template"
+            ),
+            _ => true,
+        }
+    }
+
+    // Elements whose end tag may be left out when the parent's end tag
+    // follows.
+    pub(crate) fn has_optional_end_tag(tag_name: &str) -> bool {
+        matches!(
+            tag_name.to_ascii_lowercase().as_str(),
+            "li" | "dt" | "dd" | "p" | "option" | "optgroup" | "tr" | "td" | "th" | "thead" | "tbody" | "tfoot"
+        )
+    }
+
+    // Determined by the first doctype in the input, if any.
+    pub fn quirks_mode(&self) -> QuirksMode {
+        quirks_mode_for_doctype(self.doctype().as_ref())
+    }
+
+    // The first doctype in the input, if any.
+    fn doctype(&self) -> Option<Doctype> {
+        self.tokens.iter().find_map(Doctype::from_token)
     }
 
     pub fn parse(&mut self) -> Vec<Node> {
-        let mut nodes = Vec::new();
+        self.parse_with_sink(NodeSink::new())
+    }
 
+    pub fn parse_with_sink<S: TreeSink>(&mut self, mut sink: S) -> S::Output {
         while self.position < self.tokens.len() {
-            if let Some(node) = self.parse_node() {
-                nodes.push(node);
+            if let Some(node) = self.parse_node(&mut sink) {
+                sink.append_child(None, node);
+            } else {
+                self.skip_stray_end_tag();
             }
         }
 
-        nodes
+        self.progress.finish();
+        sink.finish()
     }
 
-    pub fn parse_document(&mut self) -> Option<Node> {
+    // The nodes of `parse` as an arena with parent and sibling links, built
+    // without an intermediate `Vec<Node>`.
+    pub fn parse_tree(&mut self) -> Tree {
+        self.parse_with_sink(Tree::new(Vec::new()))
+    }
+
+    // Like `parse`, but also returns an error for each recovery the tokenizer
+    // or the tree builder made, in source order.
+    pub fn parse_with_diagnostics(&mut self) -> (Vec<Node>, Vec<HtmlError>) {
         let nodes = self.parse();
-        
-        for node in &nodes {
-            if let Node::Element(element) = node {
-                if element.tag_name.to_lowercase() == "html" {
-                    return Some(node.clone());
-                }
-            }
+        let mut errors: Vec<HtmlError> = self.tokenize_errors.drain(..).map(HtmlError::Tokenize).collect();
+        errors.append(&mut self.build.errors);
+        errors.sort_by_key(|error| error.span().map_or(0, |span| span.start));
+        (nodes, errors)
+    }
+
+    // Fails at the first end tag that matches no open element.
+    pub fn try_parse(&mut self) -> Result<Vec<Node>, HtmlError> {
+        let nodes = self.parse();
+        match self.build.errors.iter().find(|error| matches!(error, HtmlError::UnexpectedEndTag { .. })) {
+            Some(error) => Err(error.clone()),
+            None => Ok(nodes),
+        }
+    }
+
+    pub fn try_parse_document(&mut self) -> Result<Node, HtmlError> {
+        self.parse_document().ok_or(HtmlError::NoRootElement)
+    }
+
+    // The root element of `parse_full_document`, as a node.
+    pub fn parse_document(&mut self) -> Option<Node> {
+        self.parse_full_document().root().cloned().map(Node::Element)
+    }
+
+    // Every top-level node, including comments before and after the root
+    // element, along with the doctype. With `fix_document_structure` set,
+    // the nodes are put in implied `html`, `head` and `body` elements.
+    pub fn parse_full_document(&mut self) -> Document {
+        let mut children = self.parse();
+        if self.fix_document_structure {
+            children = with_implied_structure(children);
+        }
+        Document { doctype: self.doctype(), children }
+    }
+}
+
+impl TreeBuilder for HtmlParser {
+    fn current_token(&self) -> Option<&HtmlToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn take_token(&mut self) -> Option<HtmlToken> {
+        let token = self.current_token()?.clone();
+        self.advance();
+        Some(token)
+    }
+
+    fn advance(&mut self) {
+        if self.position < self.tokens.len() {
+            self.position += 1;
+            self.progress.update(self.position, self.spans[self.position - 1].end);
         }
+    }
 
-        nodes.into_iter().find(|n| matches!(n, Node::Element(_)))
+    fn previous_span(&self) -> Span {
+        self.spans[self.position - 1]
     }
-}
\ No newline at end of file
+
+    fn current_span(&self) -> Span {
+        self.spans[self.position]
+    }
+
+    fn state(&self) -> &BuildState {
+        &self.build
+    }
+
+    fn state_mut(&mut self) -> &mut BuildState {
+        &mut self.build
+    }
+}
@@ -0,0 +1,222 @@
+use super::document::Document;
+use super::parser::{Element, HtmlParser, Node};
+use super::render::{default_display, Display};
+use super::serializer::write_attribute;
+
+// Fields are private so new options can be added without breaking callers;
+// build values with `default()` and the methods below.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrettyOptions {
+    pub(crate) indent_width: usize,
+    pub(crate) indent_char: char,
+    // Start tags longer than this, indentation included, put each attribute
+    // on its own line, and inline content that doesn't fit beside its tags
+    // goes on a line of its own.
+    pub(crate) max_line_width: usize,
+    // Put the text of block elements on its own line even when it fits
+    // between the tags.
+    pub(crate) text_on_own_line: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            indent_char: ' ',
+            max_line_width: 80,
+            text_on_own_line: false,
+        }
+    }
+}
+
+impl PrettyOptions {
+    pub fn indent_width(mut self, width: usize) -> Self {
+        self.indent_width = width;
+        self
+    }
+
+    pub fn indent_char(mut self, ch: char) -> Self {
+        self.indent_char = ch;
+        self
+    }
+
+    pub fn max_line_width(mut self, width: usize) -> Self {
+        self.max_line_width = width;
+        self
+    }
+
+    pub fn text_on_own_line(mut self, enabled: bool) -> Self {
+        self.text_on_own_line = enabled;
+        self
+    }
+}
+
+// Elements put on lines of their own. Whitespace next to them isn't
+// rendered, so the line breaks don't change how the page looks.
+fn is_block(element: &Element) -> bool {
+    let tag_name = element.tag_name.to_ascii_lowercase();
+    default_display(&tag_name) != Display::Inline
+        || matches!(
+            tag_name.as_str(),
+            "title" | "meta" | "link" | "base" | "caption" | "colgroup" | "col" | "optgroup" | "option"
+        )
+}
+
+// Elements whose contents are written exactly as they are, since whitespace
+// in them is kept.
+fn is_verbatim(element: &Element) -> bool {
+    matches!(
+        element.tag_name.to_ascii_lowercase().as_str(),
+        "pre" | "textarea" | "listing" | "script" | "style"
+    )
+}
+
+fn is_inline(node: &Node) -> bool {
+    match node {
+        Node::Element(element) => !is_block(element),
+        Node::Text(_) | Node::Comment(_) => true,
+    }
+}
+
+struct PrettyWriter<'a> {
+    options: &'a PrettyOptions,
+    output: String,
+}
+
+impl PrettyWriter<'_> {
+    fn line_width(&self) -> usize {
+        let start = self.output.rfind('\n').map_or(0, |newline| newline + 1);
+        self.output[start..].chars().count()
+    }
+
+    fn start_line(&mut self, depth: usize) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        let indent = depth * self.options.indent_width;
+        self.output.extend(std::iter::repeat_n(self.options.indent_char, indent));
+    }
+
+    // Inline nodes stay together on one line, however long, since breaking
+    // between them would add visible whitespace.
+    fn nodes(&mut self, nodes: &[Node], depth: usize) {
+        let mut rest = nodes;
+        while let Some(first) = rest.first() {
+            if let Node::Element(element) = first {
+                if is_block(element) {
+                    self.block(element, depth);
+                    rest = &rest[1..];
+                    continue;
+                }
+            }
+            let run = rest.iter().take_while(|node| is_inline(node)).count();
+            self.start_line(depth);
+            for node in &rest[..run] {
+                let _ = node.write_html(&mut self.output);
+            }
+            rest = &rest[run..];
+        }
+    }
+
+    fn block(&mut self, element: &Element, depth: usize) {
+        self.start_line(depth);
+        let wrapped = self.start_tag(element, depth);
+        if HtmlParser::is_void_element(&element.tag_name) {
+            return;
+        }
+
+        let end_tag = format!("</{}>", element.tag_name);
+        if element.children.is_empty() || is_verbatim(element) {
+            let _ = element.write_contents(&mut self.output);
+            self.output.push_str(&end_tag);
+            return;
+        }
+
+        if !wrapped && element.children.iter().all(is_inline) {
+            let has_text = element.children.iter().any(|child| matches!(child, Node::Text(_)));
+            let mut contents = String::new();
+            let _ = element.write_contents(&mut contents);
+            let width = self.line_width() + contents.chars().count() + end_tag.len();
+            if width <= self.options.max_line_width && !(has_text && self.options.text_on_own_line) {
+                self.output.push_str(&contents);
+                self.output.push_str(&end_tag);
+                return;
+            }
+        }
+
+        self.nodes(&element.children, depth + 1);
+        self.start_line(depth);
+        self.output.push_str(&end_tag);
+    }
+
+    // Writes the start tag, with one attribute per line if it's too long for
+    // one line. Returns whether it was.
+    fn start_tag(&mut self, element: &Element, depth: usize) -> bool {
+        let mut tag = format!("<{}", element.tag_name);
+        for (name, value) in &element.attributes {
+            tag.push(' ');
+            let _ = write_attribute(name, value, &mut tag);
+        }
+        tag.push('>');
+
+        if element.attributes.is_empty() || self.line_width() + tag.chars().count() <= self.options.max_line_width {
+            self.output.push_str(&tag);
+            return false;
+        }
+
+        self.output.push('<');
+        self.output.push_str(&element.tag_name);
+        for (name, value) in &element.attributes {
+            self.start_line(depth + 1);
+            let _ = write_attribute(name, value, &mut self.output);
+        }
+        self.start_line(depth);
+        self.output.push('>');
+        true
+    }
+}
+
+// Formats `nodes` as indented HTML, one block element per line. Parsing the
+// result with text trimming on, as by default, gives `nodes` back, so
+// formatting it again changes nothing.
+pub fn to_html_pretty(nodes: &[Node], options: &PrettyOptions) -> String {
+    let mut writer = PrettyWriter {
+        options,
+        output: String::new(),
+    };
+    writer.nodes(nodes, 0);
+    writer.output
+}
+
+impl Node {
+    pub fn to_html_pretty(&self, options: &PrettyOptions) -> String {
+        to_html_pretty(std::slice::from_ref(self), options)
+    }
+}
+
+impl Element {
+    pub fn to_html_pretty(&self, options: &PrettyOptions) -> String {
+        if !is_block(self) {
+            return self.to_html();
+        }
+        let mut writer = PrettyWriter {
+            options,
+            output: String::new(),
+        };
+        writer.block(self, 0);
+        writer.output
+    }
+}
+
+impl Document {
+    // The doctype, if any, on the first line, then the nodes formatted as
+    // by `to_html_pretty`.
+    pub fn to_html_pretty(&self, options: &PrettyOptions) -> String {
+        let nodes = to_html_pretty(&self.children, options);
+        match &self.doctype {
+            Some(doctype) if nodes.is_empty() => doctype.to_html(),
+            Some(doctype) => format!("{}\n{}", doctype.to_html(), nodes),
+            None => nodes,
+        }
+    }
+}
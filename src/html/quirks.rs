@@ -0,0 +1,60 @@
+use super::document::Doctype;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuirksMode {
+    #[default]
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
+}
+
+// Public identifiers that put a document in quirks mode, matched as ASCII
+// case-insensitive prefixes. This covers the legacy doctypes seen in practice
+// rather than the full list from the HTML spec.
+const QUIRKY_PUBLIC_ID_PREFIXES: &[&str] = &[
+    "-//w3c//dtd html 3.2",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 3",
+    "-//w3o//dtd w3 html",
+    "-//ietf//dtd html",
+    "-//netscape comm. corp.//dtd html//",
+    "-//microsoft//dtd internet explorer",
+    "-//softquad",
+    "-//webtechs//dtd mozilla html",
+    "+//silmaril//dtd html pro",
+];
+
+// `None` when the document has no doctype.
+pub(crate) fn quirks_mode_for_doctype(doctype: Option<&Doctype>) -> QuirksMode {
+    let Some(Doctype { name, public_id, system_id }) = doctype else {
+        return QuirksMode::Quirks;
+    };
+
+    if name != "html" {
+        return QuirksMode::Quirks;
+    }
+
+    let has_system_id = system_id.is_some();
+    let Some(public_id) = public_id.as_deref().map(str::to_ascii_lowercase) else {
+        return QuirksMode::NoQuirks;
+    };
+
+    if QUIRKY_PUBLIC_ID_PREFIXES.iter().any(|prefix| public_id.starts_with(prefix)) {
+        return QuirksMode::Quirks;
+    }
+
+    let html4_transitional = public_id.starts_with("-//w3c//dtd html 4.01 transitional//")
+        || public_id.starts_with("-//w3c//dtd html 4.01 frameset//");
+    if html4_transitional {
+        return if has_system_id { QuirksMode::LimitedQuirks } else { QuirksMode::Quirks };
+    }
+
+    if public_id.starts_with("-//w3c//dtd xhtml 1.0 transitional//")
+        || public_id.starts_with("-//w3c//dtd xhtml 1.0 frameset//")
+    {
+        return QuirksMode::LimitedQuirks;
+    }
+
+    QuirksMode::NoQuirks
+}
@@ -0,0 +1,157 @@
+use super::parser::{Element, Node};
+use crate::css::ComputedStyles;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Display {
+    None,
+    Inline,
+    Block,
+    TableCell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WhiteSpace {
+    // `normal` and `nowrap`: all whitespace collapses.
+    Collapse,
+    // `pre-line`: spaces collapse but newlines are kept.
+    PreserveNewlines,
+    // `pre`, `pre-wrap` and `break-spaces`.
+    Preserve,
+}
+
+pub(crate) fn default_display(tag_name: &str) -> Display {
+    match tag_name.to_ascii_lowercase().as_str() {
+        "head" | "script" | "style" | "template" | "noscript" => Display::None,
+        "address" | "article" | "aside" | "blockquote" | "body" | "dd" | "details" | "dialog" | "div" | "dl"
+        | "dt" | "fieldset" | "figcaption" | "figure" | "footer" | "form" | "h1" | "h2" | "h3" | "h4" | "h5"
+        | "h6" | "header" | "hr" | "html" | "li" | "listing" | "main" | "nav" | "ol" | "p" | "pre" | "section"
+        | "summary" | "table" | "tbody" | "tfoot" | "thead" | "tr" | "ul" => Display::Block,
+        "td" | "th" => Display::TableCell,
+        _ => Display::Inline,
+    }
+}
+
+pub(crate) fn parse_display(value: &str) -> Display {
+    match value.to_ascii_lowercase().as_str() {
+        "none" => Display::None,
+        "inline" | "inline-block" | "inline-flex" | "inline-grid" | "contents" => Display::Inline,
+        "table-cell" => Display::TableCell,
+        _ => Display::Block,
+    }
+}
+
+fn parse_white_space(value: &str) -> Option<WhiteSpace> {
+    match value.to_ascii_lowercase().as_str() {
+        "normal" | "nowrap" => Some(WhiteSpace::Collapse),
+        "pre-line" => Some(WhiteSpace::PreserveNewlines),
+        "pre" | "pre-wrap" | "break-spaces" => Some(WhiteSpace::Preserve),
+        _ => None,
+    }
+}
+
+// The text a browser would render for `node`: whitespace collapses, block
+// elements start new lines and hidden elements are left out. `display` and
+// `white-space` come from `styles` when given, addressed by child index
+// paths from `node`; otherwise, or where they aren't set, elements use their
+// default display and `<pre>`, `<textarea>` and `<listing>` keep whitespace.
+pub fn rendered_text(node: &Node, styles: Option<&ComputedStyles>) -> String {
+    let mut renderer = TextRenderer {
+        styles,
+        output: String::new(),
+        pending_space: false,
+    };
+    renderer.render(node, WhiteSpace::Collapse, &mut Vec::new());
+    renderer.output.trim_end().to_string()
+}
+
+struct TextRenderer<'a> {
+    styles: Option<&'a ComputedStyles>,
+    output: String,
+    // Collapsed whitespace, written only if more text follows on the line.
+    pending_space: bool,
+}
+
+impl TextRenderer<'_> {
+    fn render(&mut self, node: &Node, white_space: WhiteSpace, path: &mut Vec<usize>) {
+        match node {
+            Node::Text(text) => self.text(text, white_space),
+            Node::Comment(_) => {}
+            Node::Element(element) => self.element(element, white_space, path),
+        }
+    }
+
+    fn element(&mut self, element: &Element, white_space: WhiteSpace, path: &mut Vec<usize>) {
+        let style = |property: &str| self.styles.and_then(|styles| styles.get(path, property));
+        let display = style("display").map_or_else(|| default_display(&element.tag_name), parse_display);
+        let white_space = style("white-space").and_then(parse_white_space).unwrap_or_else(|| {
+            match element.tag_name.to_ascii_lowercase().as_str() {
+                "pre" | "textarea" | "listing" => WhiteSpace::Preserve,
+                _ => white_space,
+            }
+        });
+
+        if element.tag_name.eq_ignore_ascii_case("br") {
+            self.output.push('\n');
+            self.pending_space = false;
+            return;
+        }
+
+        match display {
+            Display::None => return,
+            Display::Block => self.line_break(),
+            Display::Inline | Display::TableCell => {}
+        }
+
+        for (index, child) in element.children.iter().enumerate() {
+            path.push(index);
+            self.render(child, white_space, path);
+            path.pop();
+        }
+
+        match display {
+            Display::Block => self.line_break(),
+            // Adjacent cells are separated like words.
+            Display::TableCell => self.pending_space = true,
+            Display::None | Display::Inline => {}
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.output.is_empty() || self.output.ends_with('\n')
+    }
+
+    fn line_break(&mut self) {
+        self.pending_space = false;
+        if !self.at_line_start() {
+            self.output.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str, white_space: WhiteSpace) {
+        if white_space == WhiteSpace::Preserve {
+            if self.pending_space && !self.at_line_start() && !text.is_empty() {
+                self.output.push(' ');
+            }
+            self.pending_space = false;
+            self.output.push_str(text);
+            return;
+        }
+
+        for ch in text.chars() {
+            match ch {
+                '\n' if white_space == WhiteSpace::PreserveNewlines => {
+                    self.output.push('\n');
+                    self.pending_space = false;
+                }
+                ' ' | '\t' | '\n' | '\r' | '\x0c' => self.pending_space = true,
+                ch => {
+                    if self.pending_space && !self.at_line_start() {
+                        self.output.push(' ');
+                    }
+                    self.pending_space = false;
+                    self.output.push(ch);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use super::attr::{AttrValue, Attributes};
+use super::parser::{Element, Node};
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// Nodes are objects tagged by `type`:
+//
+//     {"type": "element", "tag": "p", "attributes": {"class": "x", "hidden": true}, "children": [...]}
+//     {"type": "text", "text": "Hi"}
+//     {"type": "comment", "text": " note "}
+//
+// An element serializes as its node does. Source spans aren't included.
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (kind, text) = match self {
+            Node::Element(element) => return element.serialize(serializer),
+            Node::Text(text) => ("text", text),
+            Node::Comment(comment) => ("comment", comment),
+        };
+        let mut node = serializer.serialize_struct("Node", 2)?;
+        node.serialize_field("type", kind)?;
+        node.serialize_field("text", text)?;
+        node.end()
+    }
+}
+
+impl Serialize for Element {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut node = serializer.serialize_struct("Node", 4)?;
+        node.serialize_field("type", "element")?;
+        node.serialize_field("tag", &self.tag_name)?;
+        node.serialize_field("attributes", &self.attributes)?;
+        node.serialize_field("children", &self.children)?;
+        node.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum NodeRepr {
+    Element {
+        tag: String,
+        #[serde(default)]
+        attributes: Attributes,
+        #[serde(default)]
+        children: Vec<Node>,
+    },
+    Text {
+        text: String,
+    },
+    Comment {
+        text: String,
+    },
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match NodeRepr::deserialize(deserializer)? {
+            NodeRepr::Element { tag, attributes, children } => Node::Element(Element {
+                tag_name: tag,
+                attributes,
+                children,
+                source_span: None,
+            }),
+            NodeRepr::Text { text } => Node::Text(text),
+            NodeRepr::Comment { text } => Node::Comment(text),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Node::deserialize(deserializer)? {
+            Node::Element(element) => Ok(element),
+            _ => Err(de::Error::custom("expected a node of type \"element\"")),
+        }
+    }
+}
+
+// An object in source order, with `true` for boolean attributes.
+impl Serialize for Attributes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (name, value) in self {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Attributes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AttributesVisitor;
+
+        impl<'de> Visitor<'de> for AttributesVisitor {
+            type Value = Attributes;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of attribute names to values")
+            }
+
+            // The first of any duplicate names wins, as in HTML.
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Attributes, A::Error> {
+                let mut attributes = Attributes::new();
+                while let Some((name, value)) = map.next_entry::<String, AttrValue>()? {
+                    attributes.extend([(name, value)]);
+                }
+                Ok(attributes)
+            }
+        }
+
+        deserializer.deserialize_map(AttributesVisitor)
+    }
+}
+
+impl Serialize for AttrValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AttrValue::Bool => serializer.serialize_bool(true),
+            AttrValue::Value(value) => serializer.serialize_str(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AttrValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AttrValueVisitor;
+
+        impl Visitor<'_> for AttrValueVisitor {
+            type Value = AttrValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string, or true for a boolean attribute")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<AttrValue, E> {
+                if value {
+                    Ok(AttrValue::Bool)
+                } else {
+                    Err(E::invalid_value(de::Unexpected::Bool(false), &self))
+                }
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<AttrValue, E> {
+                Ok(AttrValue::Value(value.to_string()))
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<AttrValue, E> {
+                Ok(AttrValue::Value(value))
+            }
+        }
+
+        deserializer.deserialize_any(AttrValueVisitor)
+    }
+}
@@ -0,0 +1,150 @@
+use super::attr::AttrValue;
+use super::document::Doctype;
+use super::parser::{Element, HtmlParser, Node};
+use std::fmt;
+
+// Elements whose text content is written without escaping, matching the
+// tokenizer's raw-text handling.
+fn is_raw_text_element(tag_name: &str) -> bool {
+    matches!(tag_name.to_ascii_lowercase().as_str(), "script" | "style")
+}
+
+pub fn escape_text(text: &str, dest: &mut impl fmt::Write) -> fmt::Result {
+    for ch in text.chars() {
+        match ch {
+            '&' => dest.write_str("&amp;")?,
+            '<' => dest.write_str("&lt;")?,
+            '>' => dest.write_str("&gt;")?,
+            '\u{a0}' => dest.write_str("&nbsp;")?,
+            ch => dest.write_char(ch)?,
+        }
+    }
+    Ok(())
+}
+
+pub fn escape_attribute(value: &str, dest: &mut impl fmt::Write) -> fmt::Result {
+    for ch in value.chars() {
+        match ch {
+            '&' => dest.write_str("&amp;")?,
+            '"' => dest.write_str("&quot;")?,
+            '<' => dest.write_str("&lt;")?,
+            '\u{a0}' => dest.write_str("&nbsp;")?,
+            ch => dest.write_char(ch)?,
+        }
+    }
+    Ok(())
+}
+
+impl Node {
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        // Writing to a String can't fail.
+        let _ = self.write_html(&mut html);
+        html
+    }
+
+    pub fn write_html(&self, dest: &mut impl fmt::Write) -> fmt::Result {
+        match self {
+            Node::Element(element) => element.write_html(dest),
+            Node::Text(text) => escape_text(text, dest),
+            Node::Comment(comment) => write!(dest, "<!--{}-->", comment),
+        }
+    }
+}
+
+impl Element {
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = self.write_html(&mut html);
+        html
+    }
+
+    // The DOM names: the element with its tags, and its children alone.
+    pub fn outer_html(&self) -> String {
+        self.to_html()
+    }
+
+    pub fn inner_html(&self) -> String {
+        let mut html = String::new();
+        let _ = self.write_contents(&mut html);
+        html
+    }
+
+    pub fn write_html(&self, dest: &mut impl fmt::Write) -> fmt::Result {
+        self.write_start_tag(dest)?;
+        if HtmlParser::is_void_element(&self.tag_name) {
+            return Ok(());
+        }
+        self.write_contents(dest)?;
+        write!(dest, "</{}>", self.tag_name)
+    }
+
+    fn write_start_tag(&self, dest: &mut impl fmt::Write) -> fmt::Result {
+        write!(dest, "<{}", self.tag_name)?;
+        for (name, value) in &self.attributes {
+            dest.write_char(' ')?;
+            write_attribute(name, value, dest)?;
+        }
+        dest.write_char('>')
+    }
+
+    // The children, written as they are inside the element's tags. The
+    // elements being written are kept on a stack of their own, so trees
+    // of any depth can be written.
+    pub(crate) fn write_contents(&self, dest: &mut impl fmt::Write) -> fmt::Result {
+        let mut open = vec![(self, self.children.iter())];
+        while let Some((element, children)) = open.last_mut() {
+            let element: &Element = element;
+            match children.next() {
+                None => {
+                    open.pop();
+                    if !open.is_empty() {
+                        write!(dest, "</{}>", element.tag_name)?;
+                    }
+                }
+                Some(Node::Text(text)) if is_raw_text_element(&element.tag_name) => dest.write_str(text)?,
+                Some(Node::Element(child)) => {
+                    child.write_start_tag(dest)?;
+                    if !HtmlParser::is_void_element(&child.tag_name) {
+                        open.push((child, child.children.iter()));
+                    }
+                }
+                Some(child) => child.write_html(dest)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// `name="value"`, or just the name for an attribute without a value.
+pub(crate) fn write_attribute(name: &str, value: &AttrValue, dest: &mut impl fmt::Write) -> fmt::Result {
+    dest.write_str(name)?;
+    if let AttrValue::Value(value) = value {
+        dest.write_str("=\"")?;
+        escape_attribute(value, dest)?;
+        dest.write_char('"')?;
+    }
+    Ok(())
+}
+
+impl Doctype {
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<!DOCTYPE");
+        if !self.name.is_empty() {
+            html.push(' ');
+            html.push_str(&self.name);
+        }
+        match (&self.public_id, &self.system_id) {
+            (Some(public_id), system_id) => {
+                html.push_str(&format!(" PUBLIC \"{}\"", public_id));
+                if let Some(system_id) = system_id {
+                    html.push_str(&format!(" \"{}\"", system_id));
+                }
+            }
+            (None, Some(system_id)) => html.push_str(&format!(" SYSTEM \"{}\"", system_id)),
+            (None, None) => {}
+        }
+        html.push('>');
+        html
+    }
+}
@@ -0,0 +1,88 @@
+use super::attr::{AttrValue, Attributes};
+use super::parser::{Element, Node};
+use crate::span::Span;
+
+pub trait TreeSink {
+    type Handle;
+    type Output;
+
+    fn create_element(&mut self, name: &str) -> Self::Handle;
+
+    fn create_text(&mut self, text: &str) -> Self::Handle;
+
+    fn create_comment(&mut self, text: &str) -> Self::Handle;
+
+    fn set_attributes(&mut self, element: &mut Self::Handle, attributes: Vec<(String, AttrValue)>);
+
+    // Where the element appears in the input. Sinks that don't track
+    // positions can ignore it.
+    fn set_source_span(&mut self, _element: &mut Self::Handle, _span: Span) {}
+
+    // A `None` parent appends the child at the top level of the document.
+    fn append_child(&mut self, parent: Option<&mut Self::Handle>, child: Self::Handle);
+
+    fn finish(self) -> Self::Output;
+}
+
+#[derive(Debug, Default)]
+pub struct NodeSink {
+    nodes: Vec<Node>,
+}
+
+impl NodeSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TreeSink for NodeSink {
+    type Handle = Node;
+    type Output = Vec<Node>;
+
+    fn create_element(&mut self, name: &str) -> Node {
+        Node::Element(Element {
+            tag_name: name.to_string(),
+            attributes: Attributes::new(),
+            children: Vec::new(),
+            source_span: None,
+        })
+    }
+
+    fn create_text(&mut self, text: &str) -> Node {
+        Node::Text(text.to_string())
+    }
+
+    fn create_comment(&mut self, text: &str) -> Node {
+        Node::Comment(text.to_string())
+    }
+
+    fn set_attributes(&mut self, element: &mut Node, attributes: Vec<(String, AttrValue)>) {
+        if let Node::Element(element) = element {
+            element.attributes.extend(attributes);
+        }
+    }
+
+    fn set_source_span(&mut self, element: &mut Node, span: Span) {
+        if let Node::Element(element) = element {
+            element.source_span = Some(span);
+        }
+    }
+
+    fn append_child(&mut self, parent: Option<&mut Node>, child: Node) {
+        let siblings = match parent {
+            Some(Node::Element(element)) => &mut element.children,
+            Some(_) => return,
+            None => &mut self.nodes,
+        };
+        // Text split by markup the tree leaves out, such as a stray end tag,
+        // is joined into one node, as it would be when parsed again.
+        match (siblings.last_mut(), child) {
+            (Some(Node::Text(last)), Node::Text(text)) => last.push_str(&text),
+            (_, child) => siblings.push(child),
+        }
+    }
+
+    fn finish(self) -> Vec<Node> {
+        self.nodes
+    }
+}
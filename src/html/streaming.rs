@@ -0,0 +1,107 @@
+use super::options::HtmlParseOptions;
+use super::parser::{BuildState, HtmlStats, Node, TreeBuilder};
+use super::sink::NodeSink;
+use super::tokenizer::{HtmlToken, HtmlTokenizer};
+use crate::span::Span;
+
+// Parses like `HtmlParser`, but reads tokens from the tokenizer as it goes
+// instead of tokenizing the whole input first, and yields each top-level
+// node once it's complete. Beyond the node being built, memory use depends
+// on nesting depth rather than input size.
+pub struct StreamingHtmlParser<'a> {
+    tokenizer: HtmlTokenizer<'a>,
+    current: Option<(HtmlToken, Span)>,
+    previous_span: Span,
+    tokens: usize,
+    build: BuildState,
+    // A node read past the end of a text node, to see whether it was more
+    // text to join to it.
+    pending: Option<Node>,
+}
+
+impl<'a> StreamingHtmlParser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_options(input, HtmlParseOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: HtmlParseOptions) -> Self {
+        let mut tokenizer = HtmlTokenizer::with_options(input, &options);
+        let current = tokenizer.next_token_spanned();
+        Self {
+            tokenizer,
+            current,
+            previous_span: Span::default(),
+            tokens: 0,
+            build: BuildState::new(&options),
+            pending: None,
+        }
+    }
+
+    // For the nodes yielded so far.
+    pub fn stats(&self) -> HtmlStats {
+        HtmlStats {
+            tokens: self.tokens,
+            bytes: self.previous_span.end,
+            ..self.build.stats.clone()
+        }
+    }
+
+    fn next_node(&mut self) -> Option<Node> {
+        while self.current.is_some() {
+            if let Some(node) = self.parse_node(&mut NodeSink::new()) {
+                return Some(node);
+            }
+            self.skip_stray_end_tag();
+        }
+        None
+    }
+}
+
+impl TreeBuilder for StreamingHtmlParser<'_> {
+    fn current_token(&self) -> Option<&HtmlToken> {
+        self.current.as_ref().map(|(token, _)| token)
+    }
+
+    fn take_token(&mut self) -> Option<HtmlToken> {
+        let (token, span) = self.current.take()?;
+        self.current = self.tokenizer.next_token_spanned();
+        self.previous_span = span;
+        self.tokens += 1;
+        Some(token)
+    }
+
+    fn previous_span(&self) -> Span {
+        self.previous_span
+    }
+
+    fn current_span(&self) -> Span {
+        self.current.as_ref().map_or(self.previous_span, |(_, span)| *span)
+    }
+
+    fn state(&self) -> &BuildState {
+        &self.build
+    }
+
+    fn state_mut(&mut self) -> &mut BuildState {
+        &mut self.build
+    }
+}
+
+impl Iterator for StreamingHtmlParser<'_> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        let mut node = self.pending.take().or_else(|| self.next_node())?;
+        // Joins text as `NodeSink` does.
+        while let Node::Text(text) = &mut node {
+            match self.next_node() {
+                Some(Node::Text(more)) => text.push_str(&more),
+                next => {
+                    self.pending = next;
+                    break;
+                }
+            }
+        }
+        Some(node)
+    }
+}
@@ -1,8 +1,16 @@
+use super::attr::AttrValue;
+use super::entities::{decode, decode_attribute};
+use super::error::{HtmlTokenizeError, TokenizeErrorKind};
+use super::options::{DuplicateAttributeCallback, HtmlParseOptions};
+use crate::span::{LineCounter, Span};
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum HtmlToken {
     StartTag {
         name: String,
-        attributes: Vec<(String, String)>,
+        attributes: Vec<(String, AttrValue)>,
         self_closing: bool,
     },
     EndTag {
@@ -10,13 +18,136 @@ pub enum HtmlToken {
     },
     Text(String),
     Comment(String),
-    Doctype(String),
+    // The text between `<?` and `?>`, as in `<?xml version="1.0"?>`.
+    ProcessingInstruction(String),
+    Doctype {
+        // Lowercased; empty if the doctype has no name.
+        name: String,
+        public_id: Option<String>,
+        system_id: Option<String>,
+    },
+}
+
+// Elements whose contents are text up to the matching end tag. Only
+// `textarea` and `title` decode character references in that text.
+fn is_raw_text_element(name: &str) -> bool {
+    matches!(name.to_ascii_lowercase().as_str(), "script" | "style" | "textarea" | "title")
+}
+
+fn is_escapable_raw_text_element(name: &str) -> bool {
+    name.eq_ignore_ascii_case("textarea") || name.eq_ignore_ascii_case("title")
+}
+
+fn is_tag_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || c == '\0'
+}
+
+// C0 and C1 controls other than ASCII whitespace and NUL, which the HTML
+// standard calls parse errors but keeps.
+fn is_control_character(c: char) -> bool {
+    matches!(c, '\u{1}'..='\u{8}' | '\u{B}' | '\u{E}'..='\u{1F}' | '\u{7F}'..='\u{9F}')
+}
+
+// NUL never reaches a token: like the standard does in most states, each
+// one becomes U+FFFD.
+fn replace_null_characters(token: &mut HtmlToken) {
+    let replace = |text: &mut String| {
+        if text.contains('\0') {
+            *text = text.replace('\0', "\u{FFFD}");
+        }
+    };
+    match token {
+        HtmlToken::StartTag { name, attributes, .. } => {
+            replace(name);
+            for (name, value) in attributes {
+                replace(name);
+                if let AttrValue::Value(value) = value {
+                    replace(value);
+                }
+            }
+        }
+        HtmlToken::EndTag { name } => replace(name),
+        HtmlToken::Text(text) | HtmlToken::Comment(text) | HtmlToken::ProcessingInstruction(text) => replace(text),
+        HtmlToken::Doctype { name, public_id, system_id } => {
+            replace(name);
+            public_id.iter_mut().chain(system_id.iter_mut()).for_each(replace);
+        }
+    }
+}
+
+// For the text after `<?`: the length of the instruction's content and the
+// bytes it takes up with its closing `?>`. An instruction cut off by the end
+// of the input runs to the end.
+fn processing_instruction_extent(rest: &str) -> (usize, usize) {
+    match rest.find("?>") {
+        Some(length) => (length, length + 2),
+        None => (rest.len(), rest.len()),
+    }
+}
+
+// For the text after `<!--`: the length of the comment's content and the
+// bytes it takes up with its closing `-->` or `--!>`. Other `--` sequences
+// are part of the content. `<!-->` and `<!--->` are empty comments. A
+// comment cut off by the end of the input runs to the end, less the start
+// of an unfinished close.
+fn comment_extent(rest: &str) -> (usize, usize) {
+    if rest.starts_with('>') {
+        return (0, 1);
+    }
+    if rest.starts_with("->") {
+        return (0, 2);
+    }
+    let bytes = rest.as_bytes();
+    let close = (0..bytes.len()).find(|&i| bytes[i..].starts_with(b"-->") || bytes[i..].starts_with(b"--!>"));
+    match close {
+        Some(end) if rest[end + 2..].starts_with('!') => (end, end + 4),
+        Some(end) => (end, end + 3),
+        None => {
+            let content = rest
+                .strip_suffix("--!")
+                .or_else(|| rest.strip_suffix("--"))
+                .or_else(|| rest.strip_suffix('-'))
+                .unwrap_or(rest);
+            (content.len(), rest.len())
+        }
+    }
+}
+
+// A saved position in an `HtmlTokenizer`'s input.
+#[derive(Debug, Clone)]
+pub struct Checkpoint<'a> {
+    position: usize,
+    current_char: Option<char>,
+    raw_text_end: Option<String>,
+    token_start: usize,
+    lines: LineCounter<'a>,
+    errors: usize,
+    error_lines: LineCounter<'a>,
+    character_lines: LineCounter<'a>,
 }
 
 pub struct HtmlTokenizer<'a> {
     input: &'a str,
     position: usize,
     current_char: Option<char>,
+    // Set after a `<script>`, `<style>`, `<textarea>` or `<title>` start
+    // tag: everything up to the matching end tag is text.
+    raw_text_end: Option<String>,
+    attribute_name_chars: Vec<char>,
+    decode_references: bool,
+    // Emit whitespace between tags as text instead of skipping it.
+    keep_whitespace: bool,
+    on_duplicate_attribute: Option<DuplicateAttributeCallback>,
+    // Where the last token returned by `next_token` begins.
+    token_start: usize,
+    lines: LineCounter<'a>,
+    errors: Vec<HtmlTokenizeError>,
+    // Kept apart from `lines`, which only moves forward and is behind the
+    // errors found while reading a token.
+    error_lines: LineCounter<'a>,
+    // The same again for errors found in a token's characters once it's
+    // been read, which are behind those found while reading it.
+    character_lines: LineCounter<'a>,
 }
 
 impl<'a> HtmlTokenizer<'a> {
@@ -25,11 +156,115 @@ impl<'a> HtmlTokenizer<'a> {
             input,
             position: 0,
             current_char: None,
+            raw_text_end: None,
+            attribute_name_chars: Vec::new(),
+            decode_references: true,
+            keep_whitespace: false,
+            on_duplicate_attribute: None,
+            token_start: 0,
+            lines: LineCounter::new(input),
+            errors: Vec::new(),
+            error_lines: LineCounter::new(input),
+            character_lines: LineCounter::new(input),
         };
         tokenizer.current_char = tokenizer.input.chars().next();
         tokenizer
     }
 
+    pub fn with_options(input: &'a str, options: &HtmlParseOptions) -> Self {
+        let mut tokenizer = Self::new(input);
+        tokenizer.attribute_name_chars = options.attribute_name_chars();
+        tokenizer.decode_references = options.decode_character_references;
+        tokenizer.keep_whitespace = !options.trim_text;
+        tokenizer.on_duplicate_attribute = options.on_duplicate_attribute;
+        tokenizer
+    }
+
+    // Continues tokenizing input that follows a token boundary, inside the
+    // raw-text element `raw_text_end` names, if any.
+    pub(crate) fn resume(input: &'a str, options: &HtmlParseOptions, raw_text_end: Option<String>) -> Self {
+        let mut tokenizer = Self::with_options(input, options);
+        tokenizer.raw_text_end = raw_text_end;
+        tokenizer.on_duplicate_attribute = None;
+        tokenizer
+    }
+
+    // Starts inside a `context` element, as when tokenizing its contents on
+    // their own: all text up to its end tag if it's a raw-text element.
+    pub(crate) fn in_context(mut self, context: &str) -> Self {
+        if is_raw_text_element(context) {
+            self.raw_text_end = Some(context.to_ascii_lowercase());
+        }
+        self
+    }
+
+    // The raw-text element whose contents come next, if any.
+    pub(crate) fn raw_text_end(&self) -> Option<&str> {
+        self.raw_text_end.as_deref()
+    }
+
+    // Tokenizes all of `input`, collecting the parse errors recovered from
+    // along the way. The tokens are the same as iterating would give.
+    pub fn tokenize_with_errors(input: &'a str) -> (Vec<HtmlToken>, Vec<HtmlTokenizeError>) {
+        let mut tokenizer = Self::new(input);
+        let tokens = tokenizer.by_ref().collect();
+        (tokens, tokenizer.errors)
+    }
+
+    // Where the next token's input starts, just past the last token
+    // returned; `peek_token` doesn't move it.
+    pub fn byte_position(&self) -> usize {
+        self.position
+    }
+
+    // Errors found in the tokens returned so far, in input order.
+    pub fn errors(&self) -> &[HtmlTokenizeError] {
+        &self.errors
+    }
+
+    // Saves the tokenizer's place, to come back to with `restore`.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            position: self.position,
+            current_char: self.current_char,
+            raw_text_end: self.raw_text_end.clone(),
+            token_start: self.token_start,
+            lines: self.lines.clone(),
+            errors: self.errors.len(),
+            error_lines: self.error_lines.clone(),
+            character_lines: self.character_lines.clone(),
+        }
+    }
+
+    // Goes back to a checkpoint taken from this tokenizer. Errors found since
+    // are forgotten, and are found again if the same input is read again.
+    pub fn restore(&mut self, checkpoint: Checkpoint<'a>) {
+        self.position = checkpoint.position;
+        self.current_char = checkpoint.current_char;
+        self.raw_text_end = checkpoint.raw_text_end;
+        self.token_start = checkpoint.token_start;
+        self.lines = checkpoint.lines;
+        self.errors.truncate(checkpoint.errors);
+        self.error_lines = checkpoint.error_lines;
+        self.character_lines = checkpoint.character_lines;
+    }
+
+    // The token `next_token` would return, without consuming it. The
+    // duplicate attribute callback isn't called for it.
+    pub fn peek_token(&mut self) -> Option<HtmlToken> {
+        let checkpoint = self.checkpoint();
+        let callback = self.on_duplicate_attribute.take();
+        let token = self.next_token();
+        self.on_duplicate_attribute = callback;
+        self.restore(checkpoint);
+        token
+    }
+
+    fn report(&mut self, kind: TokenizeErrorKind, start: usize, end: usize) {
+        let span = self.error_lines.span(start, end);
+        self.errors.push(HtmlTokenizeError { kind, span });
+    }
+
     fn advance(&mut self) {
         if self.position < self.input.len() {
             self.position += self.current_char.map_or(0, |c| c.len_utf8());
@@ -39,6 +274,11 @@ impl<'a> HtmlTokenizer<'a> {
         }
     }
 
+    fn jump_to(&mut self, position: usize) {
+        self.position = position.min(self.input.len());
+        self.current_char = self.input[self.position..].chars().next();
+    }
+
     fn peek(&self) -> Option<char> {
         if self.position < self.input.len() {
             self.input[self.position..].chars().nth(1)
@@ -67,12 +307,22 @@ impl<'a> HtmlTokenizer<'a> {
         self.consume_while(|c| c.is_whitespace());
     }
 
+    // NUL stays part of a name, to be replaced once the token is read.
     fn parse_tag_name(&mut self) -> String {
-        self.consume_while(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        self.consume_while(is_tag_name_char)
+    }
+
+    fn is_attribute_name_char(&self, c: char) -> bool {
+        is_tag_name_char(c) || c == ':' || self.attribute_name_chars.contains(&c)
     }
 
     fn parse_attribute_name(&mut self) -> String {
-        self.consume_while(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ':')
+        let mut name = String::new();
+        while let Some(ch) = self.current_char.filter(|&ch| self.is_attribute_name_char(ch)) {
+            name.push(ch);
+            self.advance();
+        }
+        name
     }
 
     fn parse_attribute_value(&mut self) -> String {
@@ -83,6 +333,7 @@ impl<'a> HtmlTokenizer<'a> {
             let value = self.consume_while(|c| c != '"');
             if self.current_char == Some('"') {
                 self.advance(); // Skip closing quote
+                self.check_after_quoted_value();
             }
             value
         } else if self.current_char == Some('\'') {
@@ -90,131 +341,540 @@ impl<'a> HtmlTokenizer<'a> {
             let value = self.consume_while(|c| c != '\'');
             if self.current_char == Some('\'') {
                 self.advance(); // Skip closing quote
+                self.check_after_quoted_value();
             }
             value
         } else {
-            self.consume_while(|c| !c.is_whitespace() && c != '>' && c != '/')
+            let start = self.position;
+            let value = self.consume_while(|c| !c.is_whitespace() && c != '>');
+            if value.is_empty() {
+                self.report(TokenizeErrorKind::MissingAttributeValue, start, start);
+            } else if let Some(offset) = value.find(['"', '\'', '<', '=', '`']) {
+                self.report(
+                    TokenizeErrorKind::UnexpectedCharacterInUnquotedAttributeValue,
+                    start + offset,
+                    start + offset + 1,
+                );
+            }
+            value
+        }
+    }
+
+    fn check_after_quoted_value(&mut self) {
+        if self.current_char.is_some_and(|c| !c.is_whitespace() && c != '>' && c != '/') {
+            self.report(TokenizeErrorKind::MissingWhitespaceBetweenAttributes, self.position, self.position);
         }
     }
 
-    fn parse_attributes(&mut self) -> Vec<(String, String)> {
-        let mut attributes = Vec::new();
+    fn parse_attributes(&mut self) -> Vec<(String, AttrValue)> {
+        let mut attributes: Vec<(String, AttrValue)> = Vec::new();
         
-        while let Some(ch) = self.current_char {
-            if ch == '>' || ch == '/' {
-                break;
-            }
-            
+        loop {
             self.skip_whitespace();
             
-            if self.current_char.is_none() || self.current_char == Some('>') || self.current_char == Some('/') {
-                break;
+            match self.current_char {
+                None | Some('>') => break,
+                Some('/') if self.peek() == Some('>') => break,
+                Some('/') => {
+                    self.advance(); // Skip stray '/' that doesn't close the tag
+                    continue;
+                }
+                _ => {}
             }
             
+            let name_start = self.position;
             let name = self.parse_attribute_name();
             if name.is_empty() {
-                break;
+                self.advance(); // Skip a character that can't start an attribute name
+                self.report(TokenizeErrorKind::UnexpectedCharacterInAttributeName, name_start, self.position);
+                continue;
+            }
+            // Only the first of several attributes with the same name counts.
+            let duplicate = attributes.iter().any(|(existing, _)| existing.eq_ignore_ascii_case(&name));
+            if duplicate {
+                self.report(TokenizeErrorKind::DuplicateAttribute, name_start, self.position);
+                if let Some(callback) = self.on_duplicate_attribute {
+                    callback(&name, self.errors[self.errors.len() - 1].span);
+                }
             }
             
             self.skip_whitespace();
             
             let value = if self.current_char == Some('=') {
                 self.advance(); // Skip '='
-                self.parse_attribute_value()
+                let value = self.parse_attribute_value();
+                if self.decode_references {
+                    AttrValue::Value(decode_attribute(&value))
+                } else {
+                    AttrValue::Value(value)
+                }
             } else {
-                String::new()
+                AttrValue::Bool
             };
             
-            attributes.push((name, value));
+            if !duplicate {
+                attributes.push((name, value));
+            }
         }
         
         attributes
     }
 
+    // The comment ends at the first `-->`, or at the end of input.
     fn parse_comment(&mut self) -> String {
-        let mut comment = String::new();
-        
-        while let Some(ch) = self.current_char {
-            if ch == '-' && self.peek() == Some('-') {
-                self.advance(); // Skip first '-'
-                self.advance(); // Skip second '-'
-                if self.current_char == Some('>') {
-                    self.advance(); // Skip '>'
-                    break;
+        let rest = &self.input[self.position..];
+        let (length, consumed) = comment_extent(rest);
+        if rest.starts_with('>') || rest.starts_with("->") {
+            self.report(TokenizeErrorKind::AbruptClosingOfEmptyComment, self.position, self.position + consumed);
+        } else if rest[length..consumed].starts_with("--!>") {
+            let end = self.position + length;
+            self.report(TokenizeErrorKind::IncorrectlyClosedComment, end, end + 4);
+        } else if !rest[length..consumed].ends_with('>') {
+            self.report(TokenizeErrorKind::EofInComment, self.input.len(), self.input.len());
+        }
+
+        let comment = rest[..length].to_string();
+        self.jump_to(self.position + consumed);
+        comment
+    }
+
+    fn parse_raw_text(&mut self, end_tag: &str) -> String {
+        let end = self.raw_text_len(end_tag);
+        let text = self.input[self.position..self.position + end].to_string();
+        self.jump_to(self.position + end);
+        text
+    }
+
+    // Bytes from the current position up to the end tag closing a raw-text
+    // element, or to the end of input.
+    fn raw_text_len(&self, end_tag: &str) -> usize {
+        let rest = &self.input[self.position..];
+        let bytes = rest.as_bytes();
+
+        let mut search_from = 0;
+        while let Some(offset) = rest[search_from..].find("</") {
+            let start = search_from + offset;
+            let name_end = start + 2 + end_tag.len();
+            let closes = rest
+                .get(start + 2..name_end)
+                .is_some_and(|name| name.eq_ignore_ascii_case(end_tag))
+                && bytes
+                    .get(name_end)
+                    .is_none_or(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>');
+            if closes {
+                return start;
+            }
+            search_from = start + 2;
+        }
+        rest.len()
+    }
+
+    fn skip_past(&mut self, c: char) {
+        match self.input[self.position..].find(c) {
+            Some(offset) => self.jump_to(self.position + offset + c.len_utf8()),
+            None => self.jump_to(self.input.len()),
+        }
+    }
+
+    // Mirrors `parse_attributes` without building anything.
+    fn skip_attributes(&mut self) {
+        loop {
+            self.skip_past_whitespace();
+
+            match self.current_char {
+                None | Some('>') => break,
+                Some('/') if self.peek() == Some('>') => break,
+                _ => {}
+            }
+
+            let name_start = self.position;
+            while self.current_char.is_some_and(|ch| self.is_attribute_name_char(ch)) {
+                self.advance();
+            }
+            if self.position == name_start {
+                self.advance();
+                continue;
+            }
+
+            self.skip_past_whitespace();
+            if self.current_char != Some('=') {
+                continue;
+            }
+            self.advance();
+            self.skip_past_whitespace();
+            match self.current_char {
+                Some(quote @ ('"' | '\'')) => {
+                    self.advance();
+                    self.skip_past(quote);
                 }
-                comment.push_str("--");
-            } else {
-                comment.push(ch);
+                _ => {
+                    while self.current_char.is_some_and(|c| !c.is_whitespace() && c != '>') {
+                        self.advance();
+                    }
+                }
+            }
+        }
+    }
+
+    fn skip_past_whitespace(&mut self) {
+        while self.current_char.is_some_and(char::is_whitespace) {
+            self.advance();
+        }
+    }
+
+    // Skips markup without allocating until a start tag whose name satisfies
+    // `wanted`, which is then tokenized as usual. The contents of skipped
+    // raw-text elements such as `<script>` are skipped unparsed.
+    pub(crate) fn skip_to_start_tag(&mut self, wanted: impl Fn(&str) -> bool) -> Option<HtmlToken> {
+        if let Some(end_tag) = self.raw_text_end.take() {
+            self.jump_to(self.position + self.raw_text_len(&end_tag));
+        }
+
+        loop {
+            let offset = self.input[self.position..].find('<')?;
+            self.jump_to(self.position + offset);
+            if !self.at_markup_start() {
                 self.advance();
+                continue;
+            }
+
+            let tag_start = self.position;
+            self.advance(); // Skip '<'
+            match self.current_char {
+                Some('!') if self.input[self.position..].starts_with("!--") => {
+                    self.jump_to(self.position + 3);
+                    let (_, consumed) = comment_extent(&self.input[self.position..]);
+                    self.jump_to(self.position + consumed);
+                }
+                Some('?') => {
+                    self.advance(); // Skip '?'
+                    let (_, consumed) = processing_instruction_extent(&self.input[self.position..]);
+                    self.jump_to(self.position + consumed);
+                }
+                Some('!') | Some('/') => self.skip_past('>'),
+                _ => {
+                    let name_start = self.position;
+                    while self.current_char.is_some_and(is_tag_name_char) {
+                        self.advance();
+                    }
+                    let name = &self.input[name_start..self.position];
+                    if wanted(name) {
+                        self.jump_to(tag_start);
+                        return self.next_token();
+                    }
+
+                    let raw_text = is_raw_text_element(name);
+                    let name_end = self.position;
+                    self.skip_attributes();
+                    let self_closing = self.current_char == Some('/');
+                    self.skip_past('>');
+                    if raw_text && !self_closing {
+                        let name = &self.input[name_start..name_end];
+                        self.jump_to(self.position + self.raw_text_len(name));
+                    }
+                }
             }
         }
-        
-        comment
     }
 
-    fn parse_doctype(&mut self) -> String {
-        self.consume_while(|c| c != '>')
+    // A `<` only opens markup when followed by a tag name, `/` and a tag
+    // name, or `!`; otherwise it is text.
+    fn at_markup_start(&self) -> bool {
+        if self.current_char != Some('<') {
+            return false;
+        }
+
+        let mut rest = self.input[self.position + 1..].chars();
+        match rest.next() {
+            Some('!') | Some('?') => true,
+            Some('/') => rest.next().is_some(),
+            Some(c) => c.is_ascii_alphabetic(),
+            None => false,
+        }
     }
 
-    pub fn next_token(&mut self) -> Option<HtmlToken> {
+    // Everything up to the next `>`, which is skipped.
+    fn parse_bogus_comment(&mut self) -> HtmlToken {
+        let comment = self.consume_while(|c| c != '>');
+        if self.current_char == Some('>') {
+            self.advance(); // Skip '>'
+        }
+        HtmlToken::Comment(comment)
+    }
+
+    // Called after `<!DOCTYPE`; stops before the closing `>`.
+    fn parse_doctype(&mut self) -> HtmlToken {
+        self.skip_whitespace();
+        let name = self
+            .consume_while(|c| !c.is_whitespace() && c != '>')
+            .to_ascii_lowercase();
+        self.skip_whitespace();
+
+        let keyword = self.consume_while(|c| !c.is_whitespace() && c != '>' && c != '"' && c != '\'');
+        let (public_id, system_id) = if keyword.eq_ignore_ascii_case("public") {
+            let public_id = self.parse_doctype_identifier();
+            (public_id, self.parse_doctype_identifier())
+        } else if keyword.eq_ignore_ascii_case("system") {
+            (None, self.parse_doctype_identifier())
+        } else {
+            (None, None)
+        };
+
+        self.consume_while(|c| c != '>');
+        HtmlToken::Doctype { name, public_id, system_id }
+    }
+
+    // A quoted identifier; a `>` inside the quotes ends the doctype early.
+    fn parse_doctype_identifier(&mut self) -> Option<String> {
         self.skip_whitespace();
+        let quote = self.current_char.filter(|&c| c == '"' || c == '\'')?;
+        self.advance();
+
+        let identifier = self.consume_while(|c| c != quote && c != '>');
+        if self.current_char == Some(quote) {
+            self.advance();
+        }
+        Some(identifier)
+    }
+
+    fn at_doctype_keyword(&self) -> bool {
+        self.input[self.position..]
+            .get(..7)
+            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("doctype"))
+    }
+
+    pub fn next_token(&mut self) -> Option<HtmlToken> {
+        let errors = self.errors.len();
+        let mut token = self.read_token()?;
+        self.check_characters(self.token_start, self.position);
+        self.errors[errors..].sort_by_key(|error| error.span.start);
+        replace_null_characters(&mut token);
+        Some(token)
+    }
+
+    // Reports the NULs and control characters in `input[start..end]`. The
+    // token read from there has its NULs replaced.
+    fn check_characters(&mut self, start: usize, end: usize) {
+        let found = self.input[start..end]
+            .char_indices()
+            .filter(|&(_, c)| c == '\0' || is_control_character(c));
+        for (index, c) in found {
+            let kind = if c == '\0' {
+                TokenizeErrorKind::UnexpectedNullCharacter
+            } else {
+                TokenizeErrorKind::ControlCharacterInInputStream
+            };
+            let start = start + index;
+            let span = self.character_lines.span(start, start + c.len_utf8());
+            self.errors.push(HtmlTokenizeError { kind, span });
+        }
+    }
+
+    fn read_token(&mut self) -> Option<HtmlToken> {
+        // `</>` gives no token, so reading starts over after it.
+        loop {
+            if let Some(end_tag) = self.raw_text_end.take() {
+                self.token_start = self.position;
+                let text = self.parse_raw_text(&end_tag);
+                let empty = if self.keep_whitespace { text.is_empty() } else { text.trim().is_empty() };
+                if !empty {
+                    if self.decode_references && is_escapable_raw_text_element(&end_tag) {
+                        return Some(HtmlToken::Text(decode(&text)));
+                    }
+                    return Some(HtmlToken::Text(text));
+                }
+            }
+
+            if !self.keep_whitespace {
+                self.skip_whitespace();
+            }
+            self.token_start = self.position;
         
-        match self.current_char? {
-            '<' => {
-                self.advance(); // Skip '<'
+            return match self.current_char? {
+                '<' if self.at_markup_start() => {
+                    self.advance(); // Skip '<'
                 
-                if self.current_char == Some('!') {
-                    self.advance(); // Skip '!'
+                    if self.current_char == Some('!') {
+                        self.advance(); // Skip '!'
                     
-                    if self.current_char == Some('-') && self.peek() == Some('-') {
-                        self.advance(); // Skip first '-'
-                        self.advance(); // Skip second '-'
-                        let comment = self.parse_comment();
-                        Some(HtmlToken::Comment(comment))
+                        if self.current_char == Some('-') && self.peek() == Some('-') {
+                            self.advance(); // Skip first '-'
+                            self.advance(); // Skip second '-'
+                            let comment = self.parse_comment();
+                            Some(HtmlToken::Comment(comment))
+                        } else if self.at_doctype_keyword() {
+                            self.jump_to(self.position + 7); // Skip 'DOCTYPE'
+                            let doctype = self.parse_doctype();
+                            if self.current_char == Some('>') {
+                                self.advance(); // Skip '>'
+                            } else {
+                                self.report(TokenizeErrorKind::EofInDoctype, self.position, self.position);
+                            }
+                            Some(doctype)
+                        } else {
+                            // Any other `<!...>` is a bogus comment.
+                            self.report(TokenizeErrorKind::IncorrectlyOpenedComment, self.token_start, self.position);
+                            Some(self.parse_bogus_comment())
+                        }
+                    } else if self.current_char == Some('?') {
+                        // HTML has no processing instructions, so this is still
+                        // an error, but they're kept whole: `<?php $a->b ?>`
+                        // ends at the `?>`, not the first `>`.
+                        self.report(
+                            TokenizeErrorKind::UnexpectedQuestionMarkInsteadOfTagName,
+                            self.position,
+                            self.position + 1,
+                        );
+                        self.advance(); // Skip '?'
+                        let (length, consumed) = processing_instruction_extent(&self.input[self.position..]);
+                        let content = self.input[self.position..self.position + length].to_string();
+                        self.jump_to(self.position + consumed);
+                        Some(HtmlToken::ProcessingInstruction(content))
+                    } else if self.current_char == Some('/') {
+                        self.advance(); // Skip '/'
+                        match self.current_char {
+                            Some(c) if c.is_ascii_alphabetic() => {
+                                let name = self.parse_tag_name();
+                                self.skip_whitespace();
+                                match self.current_char {
+                                    Some('>') => self.advance(), // Skip '>'
+                                    Some(_) => self.report(
+                                        TokenizeErrorKind::EndTagWithAttributes,
+                                        self.position,
+                                        self.position,
+                                    ),
+                                    None => self.report(TokenizeErrorKind::EofInTag, self.position, self.position),
+                                }
+                                Some(HtmlToken::EndTag { name })
+                            }
+                            Some('>') => {
+                                // `</>` is dropped.
+                                self.report(TokenizeErrorKind::MissingEndTagName, self.token_start, self.position + 1);
+                                self.advance();
+                                continue;
+                            }
+                            // An end tag whose name doesn't start with a letter,
+                            // like `</ 3>`, is a bogus comment.
+                            _ => {
+                                self.report(
+                                    TokenizeErrorKind::InvalidFirstCharacterOfTagName,
+                                    self.position,
+                                    self.position + self.current_char.map_or(0, char::len_utf8),
+                                );
+                                Some(self.parse_bogus_comment())
+                            }
+                        }
                     } else {
-                        let doctype = self.parse_doctype();
+                        let name = self.parse_tag_name();
+                        let attributes = self.parse_attributes();
+                    
+                        let mut self_closing = false;
+                        if self.current_char == Some('/') {
+                            self_closing = true;
+                            self.advance(); // Skip '/'
+                        }
+                    
                         if self.current_char == Some('>') {
                             self.advance(); // Skip '>'
+                        } else {
+                            self.report(TokenizeErrorKind::EofInTag, self.position, self.position);
+                        }
+
+                        if !self_closing && is_raw_text_element(&name) {
+                            self.raw_text_end = Some(name.clone());
                         }
-                        Some(HtmlToken::Doctype(doctype))
-                    }
-                } else if self.current_char == Some('/') {
-                    self.advance(); // Skip '/'
-                    let name = self.parse_tag_name();
-                    self.skip_whitespace();
-                    if self.current_char == Some('>') {
-                        self.advance(); // Skip '>'
-                    }
-                    Some(HtmlToken::EndTag { name })
-                } else {
-                    let name = self.parse_tag_name();
-                    let attributes = self.parse_attributes();
-                    
-                    let mut self_closing = false;
-                    if self.current_char == Some('/') {
-                        self_closing = true;
-                        self.advance(); // Skip '/'
-                    }
                     
-                    if self.current_char == Some('>') {
-                        self.advance(); // Skip '>'
+                        Some(HtmlToken::StartTag {
+                            name,
+                            attributes,
+                            self_closing,
+                        })
                     }
-                    
-                    Some(HtmlToken::StartTag {
-                        name,
-                        attributes,
-                        self_closing,
-                    })
                 }
-            }
-            _ => {
-                let text = self.consume_while(|c| c != '<');
-                if !text.is_empty() {
-                    Some(HtmlToken::Text(text))
-                } else {
-                    None
+                _ => {
+                    let mut text = String::new();
+                    loop {
+                        text.push_str(&self.consume_while(|c| c != '<'));
+                        if self.current_char.is_none() || self.at_markup_start() {
+                            break;
+                        }
+                        text.push('<');
+                        self.advance();
+                    }
+                    if text.is_empty() {
+                        None
+                    } else if self.decode_references {
+                        Some(HtmlToken::Text(decode(&text)))
+                    } else {
+                        Some(HtmlToken::Text(text))
+                    }
                 }
-            }
+            };
+        }
+    }
+}
+
+
+// A token whose text is borrowed from an owned `HtmlToken`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum HtmlTokenRef<'a> {
+    StartTag {
+        name: &'a str,
+        attributes: &'a [(String, AttrValue)],
+        self_closing: bool,
+    },
+    EndTag {
+        name: &'a str,
+    },
+    Text(&'a str),
+    Comment(&'a str),
+    ProcessingInstruction(&'a str),
+    Doctype {
+        name: &'a str,
+        public_id: Option<&'a str>,
+        system_id: Option<&'a str>,
+    },
+}
+
+impl<'a> From<&'a HtmlToken> for HtmlTokenRef<'a> {
+    fn from(token: &'a HtmlToken) -> Self {
+        match token {
+            HtmlToken::StartTag { name, attributes, self_closing } => HtmlTokenRef::StartTag {
+                name,
+                attributes,
+                self_closing: *self_closing,
+            },
+            HtmlToken::EndTag { name } => HtmlTokenRef::EndTag { name },
+            HtmlToken::Text(text) => HtmlTokenRef::Text(text),
+            HtmlToken::Comment(comment) => HtmlTokenRef::Comment(comment),
+            HtmlToken::ProcessingInstruction(instruction) => HtmlTokenRef::ProcessingInstruction(instruction),
+            HtmlToken::Doctype { name, public_id, system_id } => HtmlTokenRef::Doctype {
+                name,
+                public_id: public_id.as_deref(),
+                system_id: system_id.as_deref(),
+            },
+        }
+    }
+}
+
+impl From<HtmlTokenRef<'_>> for HtmlToken {
+    fn from(token: HtmlTokenRef<'_>) -> Self {
+        match token {
+            HtmlTokenRef::StartTag { name, attributes, self_closing } => HtmlToken::StartTag {
+                name: name.to_string(),
+                attributes: attributes.to_vec(),
+                self_closing,
+            },
+            HtmlTokenRef::EndTag { name } => HtmlToken::EndTag { name: name.to_string() },
+            HtmlTokenRef::Text(text) => HtmlToken::Text(text.to_string()),
+            HtmlTokenRef::Comment(comment) => HtmlToken::Comment(comment.to_string()),
+            HtmlTokenRef::ProcessingInstruction(instruction) => HtmlToken::ProcessingInstruction(instruction.to_string()),
+            HtmlTokenRef::Doctype { name, public_id, system_id } => HtmlToken::Doctype {
+                name: name.to_string(),
+                public_id: public_id.map(str::to_string),
+                system_id: system_id.map(str::to_string),
+            },
         }
     }
 }
@@ -225,4 +885,29 @@ impl<'a> Iterator for HtmlTokenizer<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.next_token()
     }
+}
+
+// Pairs each token with where it appears in the input. Whitespace skipped
+// before a token isn't part of its span.
+pub struct SpannedTokens<'a> {
+    tokenizer: HtmlTokenizer<'a>,
+}
+
+impl<'a> HtmlTokenizer<'a> {
+    pub fn next_token_spanned(&mut self) -> Option<(HtmlToken, Span)> {
+        let token = self.next_token()?;
+        Some((token, self.lines.span(self.token_start, self.position)))
+    }
+
+    pub fn spanned(self) -> SpannedTokens<'a> {
+        SpannedTokens { tokenizer: self }
+    }
+}
+
+impl<'a> Iterator for SpannedTokens<'a> {
+    type Item = (HtmlToken, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokenizer.next_token_spanned()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,294 @@
+use super::attr::{AttrValue, Attributes};
+use super::parser::{Element, HtmlParser, Node};
+use super::sink::TreeSink;
+
+// Identifies a node within one `Tree`. Ids stay valid while other nodes are
+// inserted or removed and are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum NodeData {
+    // The parent of the top-level nodes.
+    Document,
+    Element {
+        tag_name: String,
+        attributes: Attributes,
+    },
+    Text(String),
+    Comment(String),
+}
+
+#[derive(Debug, Clone)]
+struct TreeNode {
+    data: NodeData,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+// A document stored as an arena of nodes addressed by `NodeId`, for
+// tooling that needs to refer to nodes across edits.
+#[derive(Debug, Clone)]
+pub struct Tree {
+    // Removed nodes leave an empty slot so ids aren't reused.
+    nodes: Vec<Option<TreeNode>>,
+}
+
+impl Tree {
+    pub fn new(nodes: Vec<Node>) -> Self {
+        let mut tree = Self {
+            nodes: vec![Some(TreeNode {
+                data: NodeData::Document,
+                parent: None,
+                children: Vec::new(),
+            })],
+        };
+        tree.insert_nodes(tree.root(), 0, nodes);
+        tree
+    }
+
+    pub fn parse(input: &str) -> Self {
+        HtmlParser::new(input).parse_tree()
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&NodeData> {
+        self.slot(id).map(|node| &node.data)
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.slot(id).map_or(&[], |node| &node.children)
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let (siblings, index) = self.siblings(id)?;
+        siblings.get(index + 1).copied()
+    }
+
+    pub fn prev_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let (siblings, index) = self.siblings(id)?;
+        siblings.get(index.checked_sub(1)?).copied()
+    }
+
+    // The parent of `id`, its parent and so on, up to and including the root.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), |&id| self.parent(id))
+    }
+
+    // `None` for nodes other than elements.
+    pub fn tag_name(&self, id: NodeId) -> Option<&str> {
+        match self.get(id)? {
+            NodeData::Element { tag_name, .. } => Some(tag_name),
+            _ => None,
+        }
+    }
+
+    // Looked up as `Element::attribute` does.
+    pub fn attribute(&self, id: NodeId, name: &str) -> Option<&str> {
+        let NodeData::Element { attributes, .. } = self.get(id)? else {
+            return None;
+        };
+        attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The text of `id` and every text node below it, in document order.
+    pub fn text_content(&self, id: NodeId) -> String {
+        let mut text = String::new();
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            match self.get(id) {
+                Some(NodeData::Text(content)) => text.push_str(content),
+                Some(NodeData::Document | NodeData::Element { .. }) => pending.extend(self.children(id).iter().rev()),
+                _ => {}
+            }
+        }
+        text
+    }
+
+    // The subtree at `id` as a `Node`; `None` for the root or a removed id.
+    pub fn node(&self, id: NodeId) -> Option<Node> {
+        let node = self.slot(id)?;
+        match &node.data {
+            NodeData::Document => None,
+            NodeData::Element { tag_name, attributes } => Some(Node::Element(Element {
+                tag_name: tag_name.clone(),
+                attributes: attributes.clone(),
+                children: node.children.iter().filter_map(|&child| self.node(child)).collect(),
+                source_span: None,
+            })),
+            NodeData::Text(text) => Some(Node::Text(text.clone())),
+            NodeData::Comment(comment) => Some(Node::Comment(comment.clone())),
+        }
+    }
+
+    // The top-level nodes.
+    pub fn to_nodes(&self) -> Vec<Node> {
+        self.children(self.root()).iter().filter_map(|&id| self.node(id)).collect()
+    }
+
+    fn slot(&self, id: NodeId) -> Option<&TreeNode> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    fn siblings(&self, id: NodeId) -> Option<(&[NodeId], usize)> {
+        let siblings = self.children(self.parent(id)?);
+        let index = siblings.iter().position(|&sibling| sibling == id)?;
+        Some((siblings, index))
+    }
+
+    // A node not yet in the tree, for `TreeSink`.
+    fn detached(&mut self, data: NodeData) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(TreeNode {
+            data,
+            parent: None,
+            children: Vec::new(),
+        }));
+        id
+    }
+
+    pub(crate) fn data_mut(&mut self, id: NodeId) -> Option<&mut NodeData> {
+        self.nodes.get_mut(id.0)?.as_mut().map(|node| &mut node.data)
+    }
+
+    // Returns false if `parent` doesn't exist, can't have children, or
+    // `index` is past its last child.
+    pub(crate) fn insert_nodes(&mut self, parent: NodeId, index: usize, nodes: Vec<Node>) -> bool {
+        match self.get(parent) {
+            Some(NodeData::Document | NodeData::Element { .. }) if index <= self.children(parent).len() => {}
+            _ => return false,
+        }
+
+        let ids: Vec<NodeId> = nodes.into_iter().map(|node| self.add(node, parent)).collect();
+        if let Some(Some(parent)) = self.nodes.get_mut(parent.0) {
+            parent.children.splice(index..index, ids);
+        }
+        true
+    }
+
+    fn add(&mut self, node: Node, parent: NodeId) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        let (data, children) = match node {
+            Node::Element(mut element) => (
+                NodeData::Element {
+                    tag_name: std::mem::take(&mut element.tag_name),
+                    attributes: std::mem::take(&mut element.attributes),
+                },
+                std::mem::take(&mut element.children),
+            ),
+            Node::Text(text) => (NodeData::Text(text), Vec::new()),
+            Node::Comment(comment) => (NodeData::Comment(comment), Vec::new()),
+        };
+        self.nodes.push(Some(TreeNode {
+            data,
+            parent: Some(parent),
+            children: Vec::new(),
+        }));
+
+        let children: Vec<NodeId> = children.into_iter().map(|child| self.add(child, id)).collect();
+        if let Some(Some(node)) = self.nodes.get_mut(id.0) {
+            node.children = children;
+        }
+        id
+    }
+
+    // Removes `id` and its descendants. The root can't be removed.
+    pub(crate) fn remove(&mut self, id: NodeId) -> bool {
+        let Some(parent) = self.parent(id) else {
+            return false;
+        };
+        if let Some(Some(parent)) = self.nodes.get_mut(parent.0) {
+            parent.children.retain(|&child| child != id);
+        }
+
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            if let Some(node) = self.nodes.get_mut(id.0).and_then(Option::take) {
+                pending.extend(node.children);
+            }
+        }
+        true
+    }
+}
+
+// Lets the parser build a tree directly, as `HtmlParser::parse_tree` does.
+// Start from an empty `Tree::new(Vec::new())`.
+impl TreeSink for Tree {
+    type Handle = NodeId;
+    type Output = Tree;
+
+    fn create_element(&mut self, name: &str) -> NodeId {
+        self.detached(NodeData::Element {
+            tag_name: name.to_string(),
+            attributes: Attributes::new(),
+        })
+    }
+
+    fn create_text(&mut self, text: &str) -> NodeId {
+        self.detached(NodeData::Text(text.to_string()))
+    }
+
+    fn create_comment(&mut self, text: &str) -> NodeId {
+        self.detached(NodeData::Comment(text.to_string()))
+    }
+
+    fn set_attributes(&mut self, element: &mut NodeId, attributes: Vec<(String, AttrValue)>) {
+        if let Some(NodeData::Element { attributes: existing, .. }) = self.data_mut(*element) {
+            existing.extend(attributes);
+        }
+    }
+
+    fn append_child(&mut self, parent: Option<&mut NodeId>, child: NodeId) {
+        let parent = parent.map_or(self.root(), |parent| *parent);
+        if !matches!(self.get(parent), Some(NodeData::Document | NodeData::Element { .. })) {
+            return;
+        }
+
+        // Adjacent text is joined, as `NodeSink` does; the later node's slot
+        // is left empty.
+        let last = self.children(parent).last().copied();
+        if let (Some(last), Some(NodeData::Text(text))) = (last, self.get(child)) {
+            let text = text.clone();
+            if let Some(NodeData::Text(last)) = self.data_mut(last) {
+                last.push_str(&text);
+                self.nodes[child.0] = None;
+                return;
+            }
+        }
+
+        if let Some(Some(node)) = self.nodes.get_mut(child.0) {
+            node.parent = Some(parent);
+        }
+        if let Some(Some(parent)) = self.nodes.get_mut(parent.0) {
+            parent.children.push(child);
+        }
+    }
+
+    fn finish(self) -> Tree {
+        self
+    }
+}
+
+impl From<Vec<Node>> for Tree {
+    fn from(nodes: Vec<Node>) -> Self {
+        Self::new(nodes)
+    }
+}
+
+// Trees are equal when they hold the same nodes, whatever their ids.
+impl PartialEq for Tree {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_nodes() == other.to_nodes()
+    }
+}
@@ -1,5 +1,50 @@
+//! HTML and CSS tokenizers and parsers.
+//!
+//! Enums that are expected to grow are `#[non_exhaustive]`, so a match on
+//! them needs a wildcard arm:
+//!
+//! ```compile_fail
+//! use html_css_parser::HtmlToken;
+//!
+//! fn describe(token: &HtmlToken) -> &'static str {
+//!     match token {
+//!         HtmlToken::StartTag { .. } => "start tag",
+//!         HtmlToken::EndTag { .. } => "end tag",
+//!         HtmlToken::Text(_) => "text",
+//!         HtmlToken::Comment(_) => "comment",
+//!         HtmlToken::ProcessingInstruction(_) => "processing instruction",
+//!         HtmlToken::Doctype { .. } => "doctype",
+//!     }
+//! }
+//! ```
+//!
+//! Options structs have private fields and are built from `default()`:
+//!
+//! ```
+//! use html_css_parser::html::HtmlParseOptions;
+//! use html_css_parser::{HtmlToken, HtmlTokenizer};
+//!
+//! let options = HtmlParseOptions::default().framework_attributes(true);
+//! let tokens: Vec<_> = HtmlTokenizer::with_options("<a @click=\"go\">", &options).collect();
+//! let names: Vec<&str> = tokens
+//!     .iter()
+//!     .map(|token| match token {
+//!         HtmlToken::StartTag { name, .. } => name.as_str(),
+//!         _ => "other",
+//!     })
+//!     .collect();
+//! assert_eq!(names, ["a"]);
+//! ```
+
 pub mod html;
 pub mod css;
+pub mod span;
+pub mod progress;
+pub mod dot;
+pub mod page;
+pub mod prelude;
 
-pub use html::{HtmlTokenizer, HtmlParser, HtmlToken, Element, Node};
-pub use css::{CssTokenizer, CssParser, CssToken, Rule, Selector, Declaration};
\ No newline at end of file
+pub use html::{HtmlTokenizer, HtmlParser, HtmlToken, Element, Node, HtmlError};
+pub use css::{CssTokenizer, CssParser, CssToken, Rule, Selector, Declaration, CssError};
+pub use span::Span;
+pub use page::parse_page;
\ No newline at end of file
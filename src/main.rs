@@ -1,5 +1,7 @@
 use html_css_parser::html::{HtmlParser, HtmlTokenizer, Node};
 use html_css_parser::css::{CssParser, CssTokenizer, Selector};
+use html_css_parser::dot::DotOptions;
+use html_css_parser::{css, html};
 use std::env;
 use std::fs;
 use std::process;
@@ -14,6 +16,7 @@ fn main() {
     
     let command = &args[1];
     let file_path = &args[2];
+    let dot = args[3..].iter().any(|arg| arg == "--dot");
     
     let content = match fs::read_to_string(file_path) {
         Ok(content) => content,
@@ -25,8 +28,11 @@ fn main() {
     
     match command.as_str() {
         "html-tokenize" => tokenize_html(&content),
+        "html-parse" if dot => print_html_dot(&content),
         "html-parse" => parse_html(&content),
+        "html-format" => format_html(&content),
         "css-tokenize" => tokenize_css(&content),
+        "css-parse" if dot => print_css_dot(&content),
         "css-parse" => parse_css(&content),
         "demo" => run_demo(),
         _ => {
@@ -41,15 +47,19 @@ fn print_usage() {
     println!("HTML & CSS Parser CLI");
     println!();
     println!("Usage:");
-    println!("  {} <command> <file>", env::args().next().unwrap_or_else(|| "html-css-parser".to_string()));
+    println!("  {} <command> <file> [--dot]", env::args().next().unwrap_or_else(|| "html-css-parser".to_string()));
     println!();
     println!("Commands:");
     println!("  html-tokenize <file>  Tokenize HTML file");
     println!("  html-parse <file>     Parse HTML file into DOM tree");
+    println!("  html-format <file>    Print HTML file indented, one block element per line");
     println!("  css-tokenize <file>   Tokenize CSS file");
     println!("  css-parse <file>      Parse CSS file into rules");
     println!("  demo                  Run built-in demo (no file needed)");
     println!();
+    println!("Options:");
+    println!("  --dot                 Print the html-parse or css-parse tree as Graphviz DOT");
+    println!();
     println!("Examples:");
     println!("  {} html-parse index.html", env::args().next().unwrap_or_else(|| "html-css-parser".to_string()));
     println!("  {} css-parse styles.css", env::args().next().unwrap_or_else(|| "html-css-parser".to_string()));
@@ -76,6 +86,7 @@ fn parse_html(content: &str) {
         Some(document) => {
             println!("Successfully parsed HTML document!");
             print_node(&document, 0);
+            print_html_stats(&parser);
         }
         None => {
             println!("Failed to parse HTML document");
@@ -86,10 +97,37 @@ fn parse_html(content: &str) {
                     print_node(node, 0);
                 }
             }
+            print_html_stats(&parser);
         }
     }
 }
 
+fn format_html(content: &str) {
+    let document = HtmlParser::new(content).parse_full_document();
+    println!("{}", document.to_html_pretty(&html::PrettyOptions::default()));
+}
+
+fn print_html_dot(content: &str) {
+    let mut parser = HtmlParser::new(content);
+    let options = DotOptions::default();
+
+    match parser.parse_document() {
+        Some(document) => print!("{}", html::export::to_dot(&document, &options)),
+        None => {
+            eprintln!("Failed to parse HTML document");
+            process::exit(1);
+        }
+    }
+}
+
+fn print_html_stats(parser: &HtmlParser) {
+    let stats = parser.stats();
+    println!(
+        "\nTokens: {}, nodes: {}, bytes: {}, max depth: {}, recoveries: {}",
+        stats.tokens, stats.nodes, stats.bytes, stats.max_depth, stats.recoveries
+    );
+}
+
 fn tokenize_css(content: &str) {
     println!("=== CSS Tokenization ===");
     let tokenizer = CssTokenizer::new(content);
@@ -107,8 +145,13 @@ fn parse_css(content: &str) {
     println!("=== CSS Parsing ===");
     let mut parser = CssParser::new(content);
     let rules = parser.parse();
+    let stats = parser.stats();
     
     println!("Parsed {} CSS rules:", rules.len());
+    println!(
+        "Tokens: {}, rules: {}, bytes: {}, max depth: {}, skipped: {}",
+        stats.tokens, stats.rules, stats.bytes, stats.max_depth, stats.skipped
+    );
     
     for (i, rule) in rules.iter().enumerate() {
         println!("\nRule #{}: {} selector(s)", i + 1, rule.selectors.len());
@@ -135,6 +178,11 @@ fn parse_css(content: &str) {
     }
 }
 
+fn print_css_dot(content: &str) {
+    let stylesheet = CssParser::new(content).parse_stylesheet();
+    print!("{}", css::export::to_dot(&stylesheet));
+}
+
 fn print_node(node: &Node, depth: usize) {
     let indent = "  ".repeat(depth);
     
@@ -143,7 +191,11 @@ fn print_node(node: &Node, depth: usize) {
             print!("{}<{}", indent, element.tag_name);
             
             for (name, value) in &element.attributes {
-                print!(" {}=\"{}\"", name, value);
+                if value.is_bool() {
+                    print!(" {}", name);
+                } else {
+                    print!(" {}=\"{}\"", name, value);
+                }
             }
             
             if element.children.is_empty() {
@@ -0,0 +1,52 @@
+use crate::css::{parse_declaration_list, AttrMatcher, CssRule, Rule, Selector, Stylesheet};
+use crate::html::extract::combined_stylesheet;
+use crate::html::{Document, Element, HtmlParser, Node};
+
+// Parses a page and gathers its CSS: the `<style>` and `<link>` stylesheets
+// in document order, followed by one rule per `style` attribute.
+pub fn parse_page(html: &str) -> (Document, Stylesheet) {
+    let document = HtmlParser::new(html).parse_full_document();
+
+    let mut stylesheet = combined_stylesheet(&document.children);
+    collect_inline_styles(&document.children, &mut stylesheet.rules);
+
+    (document, stylesheet)
+}
+
+// An inline style becomes a rule whose selector matches elements with the
+// same tag and `style` attribute, e.g. `p[style="color: red"]`.
+fn collect_inline_styles(nodes: &[Node], rules: &mut Vec<CssRule>) {
+    for node in nodes {
+        let Node::Element(element) = node else {
+            continue;
+        };
+
+        if let Some(rule) = inline_style_rule(element) {
+            rules.push(CssRule::Style(rule));
+        }
+        collect_inline_styles(&element.children, rules);
+    }
+}
+
+fn inline_style_rule(element: &Element) -> Option<Rule> {
+    let style = element.attribute("style")?;
+    let declarations = parse_declaration_list(style);
+    if declarations.is_empty() {
+        return None;
+    }
+
+    let selector = Selector::Compound(vec![
+        Selector::Type(element.tag_name.clone()),
+        Selector::Attribute {
+            name: "style".to_string(),
+            matcher: Some(AttrMatcher::Equals),
+            value: Some(style.to_string()),
+            case: None,
+        },
+    ]);
+    Some(Rule {
+        selectors: vec![selector],
+        declarations,
+        span: None,
+    })
+}
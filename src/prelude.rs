@@ -0,0 +1,33 @@
+//! Commonly used types, for glob import.
+//!
+//! ```
+//! use html_css_parser::prelude::*;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let html = r#"<html><head><style>.title { color: red; }</style></head>
+//!         <body><h1 class="title">Hello</h1></body></html>"#;
+//!
+//!     let document = HtmlParser::new(html).try_parse_document()?;
+//!     let Node::Element(root) = document else {
+//!         return Err("expected an element".into());
+//!     };
+//!     assert_eq!(root.tag_name, "html");
+//!
+//!     let selectors = try_parse_selector_list(".title")?;
+//!     assert_eq!(selectors, vec![Selector::Class("title".to_string())]);
+//!
+//!     let declaration = try_parse_declaration("color: red")?;
+//!     assert_eq!(declaration.to_css(), "color: red");
+//!
+//!     let rules: Vec<Rule> = CssParser::new(".title { color: red; }").parse();
+//!     assert_eq!(rules[0].declarations[0], declaration);
+//!     Ok(())
+//! }
+//! ```
+
+pub use crate::html::{Element, HtmlError, HtmlParseOptions, HtmlParser, HtmlToken, HtmlTokenizer, Node, NodeSink, TreeSink};
+pub use crate::css::{
+    parse_declaration_list, ComponentValue, EquivalenceOptions, try_parse_declaration, try_parse_selector_list, CssError, CssParser, CssToken,
+    CssParseOptions, CssRule, CssTokenizer, CssWriteOptions, Declaration, Rule, Selector, Stylesheet,
+};
+pub use crate::span::Span;
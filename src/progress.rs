@@ -0,0 +1,45 @@
+pub type ProgressCallback = fn(bytes_done: usize, total_bytes: usize);
+
+// Fires a callback every `interval` tokens. Parsers may revisit tokens while
+// backtracking, so the next threshold only ever moves forward.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Progress {
+    callback: Option<ProgressCallback>,
+    interval: usize,
+    next: usize,
+    total_bytes: usize,
+    finished: bool,
+}
+
+impl Progress {
+    pub(crate) fn new(total_bytes: usize) -> Self {
+        Self {
+            total_bytes,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn set_callback(&mut self, interval: usize, callback: ProgressCallback) {
+        self.interval = interval.max(1);
+        self.next = self.interval;
+        self.callback = Some(callback);
+    }
+
+    pub(crate) fn update(&mut self, tokens_done: usize, bytes_done: usize) {
+        let Some(callback) = self.callback else {
+            return;
+        };
+        if tokens_done >= self.next {
+            self.next = tokens_done + self.interval;
+            callback(bytes_done, self.total_bytes);
+        }
+    }
+
+    // Reports completion once, however many times parsing is resumed.
+    pub(crate) fn finish(&mut self) {
+        if let (Some(callback), false) = (self.callback, self.finished) {
+            self.finished = true;
+            callback(self.total_bytes, self.total_bytes);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self { start, end, line, column }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+// Turns increasing byte offsets into spans carrying 1-based line and column
+// numbers. Columns count characters, and `\r\n` counts as a single line break.
+#[derive(Debug, Clone)]
+pub(crate) struct LineCounter<'a> {
+    input: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> LineCounter<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn advance_to(&mut self, offset: usize) {
+        if offset <= self.offset {
+            return;
+        }
+
+        let bytes = self.input.as_bytes();
+        for (index, ch) in self.input[self.offset..offset].char_indices() {
+            match ch {
+                '\n' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                '\r' if bytes.get(self.offset + index + 1) != Some(&b'\n') => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                '\r' => {}
+                _ => self.column += 1,
+            }
+        }
+        self.offset = offset;
+    }
+
+    pub(crate) fn span(&mut self, start: usize, end: usize) -> Span {
+        self.advance_to(start);
+        Span::new(start, end, self.line, self.column)
+    }
+}
@@ -1,4 +1,4 @@
-use html_css_parser::css::{CssTokenizer, CssParser, CssToken, Rule, Selector, Declaration};
+use html_css_parser::css::{CssTokenizer, SignificantTokens, CssParser, CssToken, CssError, CssWriteOptions, Selector, AtRuleBlock, CssRule, parse_declaration_list, serialize_declarations, try_parse_declaration, try_parse_selector_list};
 
 #[test]
 fn test_css_tokenizer_basic() {
@@ -47,6 +47,45 @@ fn test_css_tokenizer_numbers() {
     }
 }
 
+#[test]
+fn test_css_tokenizer_signed_and_exponent_numbers() {
+    let significant = |css: &str| -> Vec<CssToken> { SignificantTokens::new(css).collect() };
+    let dimension = |value: f64, unit: &str| CssToken::Dimension { value, unit: unit.to_string() };
+
+    assert_eq!(significant("-5px +10 1e3 -.5em +2.5% 1E+2 4e-1px"), vec![
+        dimension(-5.0, "px"),
+        CssToken::Number(10.0),
+        CssToken::Number(1000.0),
+        dimension(-0.5, "em"),
+        CssToken::Percentage(2.5),
+        CssToken::Number(100.0),
+        dimension(0.4, "px"),
+    ]);
+    // Without a digit after it, `e` starts the unit.
+    assert_eq!(significant("1em 2e 3e+"), vec![dimension(1.0, "em"), dimension(2.0, "e"), dimension(3.0, "e"), CssToken::Delim('+')]);
+    // A sign not followed by a number is an ident or a delimiter.
+    assert_eq!(significant("-webkit-box --x - + a+b"), vec![
+        CssToken::Ident("-webkit-box".to_string()),
+        CssToken::Ident("--x".to_string()),
+        CssToken::Delim('-'),
+        CssToken::Delim('+'),
+        CssToken::Ident("a".to_string()),
+        CssToken::Delim('+'),
+        CssToken::Ident("b".to_string()),
+    ]);
+
+    let declarations = parse_declaration_list("margin: -5px; z-index: +2; opacity: 1e-1");
+    let values: Vec<&str> = declarations.iter().map(|declaration| declaration.value.as_str()).collect();
+    assert_eq!(values, ["-5px", "2", "0.1"]);
+    assert_eq!(declarations[0].component_values(), [html_css_parser::css::ComponentValue::Token(dimension(-5.0, "px"))]);
+
+    // A sign that separates two numbers is kept.
+    let rules = CssParser::new("li:nth-child(2n+1), li:nth-child(-n+3) { margin: 1px -2px }").parse();
+    assert_eq!(rules[0].selectors[0].to_string(), "li:nth-child(2n+1)");
+    assert_eq!(rules[0].selectors[1].to_string(), "li:nth-child(-n+3)");
+    assert_eq!(rules[0].declarations[0].value, "1px -2px");
+}
+
 #[test]
 fn test_css_tokenizer_strings() {
     let css = r#"content: "Hello World"; font-family: 'Arial';"#;
@@ -115,6 +154,37 @@ fn test_css_tokenizer_at_keyword() {
     }
 }
 
+#[test]
+fn test_css_tokenizer_url() {
+    let significant = |css: &str| -> Vec<CssToken> { SignificantTokens::new(css).collect() };
+    let url = |url: &str| CssToken::Url(url.to_string());
+
+    assert_eq!(significant("url(image.jpg)"), [url("image.jpg")]);
+    assert_eq!(significant("url( spaced.png )"), [url("spaced.png")]);
+    assert_eq!(significant("url(data:image/png;base64,AAA)"), [url("data:image/png;base64,AAA")]);
+    assert_eq!(significant("URL(a.png) url()"), [url("a.png"), url("")]);
+    // Escapes are decoded, and an unclosed URL runs to the end.
+    assert_eq!(significant(r"url(a\ b\).png) u\72l(x"), [url("a b).png"), url("x")]);
+
+    // Quoted URLs stay a function taking a string.
+    assert_eq!(significant("url( \"a.png\")"), [
+        CssToken::Ident("url".to_string()),
+        CssToken::LeftParen,
+        CssToken::String("a.png".to_string()),
+        CssToken::RightParen,
+    ]);
+
+    // Whitespace inside, quotes, parens and bad escapes make a bad URL,
+    // which runs to the next `)`.
+    for css in ["url(a b)", "url(a\"b)", "url(a'b)", "url(a(b)", "url(a\\\nb)", "url(a\u{1}b)"] {
+        assert_eq!(significant(&format!("{} x", css)), [CssToken::BadUrl, CssToken::Ident("x".to_string())], "{:?}", css);
+    }
+    assert_eq!(significant(r"url(a b\) c) x"), [CssToken::BadUrl, CssToken::Ident("x".to_string())]);
+
+    assert_eq!(url("a b(\"c\").png").to_string(), r#"url(a\ b\(\"c\"\).png)"#);
+    assert_eq!(CssToken::try_from("url( x.png )").unwrap(), url("x.png"));
+}
+
 #[test]
 fn test_css_parser_simple_rule() {
     let css = "body { color: red; font-size: 16px; }";
@@ -258,15 +328,1481 @@ fn test_css_parser_complex_values() {
     
     let background_decl = &rule.declarations[0];
     assert_eq!(background_decl.property, "background");
-    assert!(background_decl.value.contains("url"));
-    assert!(background_decl.value.contains("no-repeat"));
-    assert!(background_decl.value.contains("center"));
+    assert_eq!(background_decl.value, r#"url("image.jpg") no-repeat center"#);
     
     let box_shadow_decl = &rule.declarations[1];
     assert_eq!(box_shadow_decl.property, "box-shadow");
-    assert!(box_shadow_decl.value.contains("rgba"));
+    assert_eq!(box_shadow_decl.value, "0 2px 4px rgba(0, 0, 0, 0.1)");
     
     let font_family_decl = &rule.declarations[2];
     assert_eq!(font_family_decl.property, "font-family");
-    assert!(font_family_decl.value.contains("Helvetica Neue"));
-}
\ No newline at end of file
+    assert_eq!(font_family_decl.value, r#""Helvetica Neue", Arial, sans-serif"#);
+}
+
+#[test]
+fn test_css_function_values_keep_their_structure() {
+    use html_css_parser::css::ComponentValue;
+
+    // Values come back as written, commas, parentheses and quotes included.
+    for value in [
+        "0 2px 4px rgba(0, 0, 0, 0.1)",
+        "linear-gradient(135deg, #667eea 0%, #764ba2 100%)",
+        r#"url("x.jpg")"#,
+        "url(http://a.com/x.png?a=1&b=2) no-repeat",
+        "url(data:image/png;base64,iV==)",
+        "0 2px 4px rgba(0, 0, 0, 0.1), inset 0 0 0 1px #fff",
+        "var(--x, calc(100% - (2 * 1em)))",
+        r#""a, b" c"#,
+    ] {
+        let declarations = parse_declaration_list(&format!("x: {}; y: z", value));
+        assert_eq!(declarations.len(), 2, "{}", value);
+        assert_eq!(declarations[0].value, value);
+        assert_eq!(declarations[0].to_css_with(&CssWriteOptions::default()), format!("x: {}", value));
+    }
+
+    // The function calls are there in `component_values`.
+    let declarations = parse_declaration_list("box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1)");
+    let values = declarations[0].component_values();
+    let Some(ComponentValue::Function { name, arguments }) = values.last() else {
+        panic!("expected a function: {:?}", values);
+    };
+    assert_eq!(name, "rgba");
+    let arguments: Vec<&ComponentValue> = arguments
+        .iter()
+        .filter(|value| !matches!(value, ComponentValue::Token(CssToken::Whitespace | CssToken::Comma)))
+        .collect();
+    assert_eq!(
+        arguments,
+        [0.0, 0.0, 0.0, 0.1].map(|number| ComponentValue::Token(CssToken::Number(number))).iter().collect::<Vec<_>>()
+    );
+
+    let declarations = parse_declaration_list("background: linear-gradient(135deg, #667eea 0%, #764ba2 100%)");
+    let values = declarations[0].component_values();
+    assert!(matches!(values.as_slice(), [ComponentValue::Function { name, .. }] if name == "linear-gradient"));
+}
+#[test]
+fn test_css_serializer_strip_important() {
+    let declarations = parse_declaration_list("color: red !important; margin: 0");
+
+    let default_options = CssWriteOptions::default();
+    assert_eq!(declarations[0].to_css_with(&default_options), "color: red !important");
+
+    let options = CssWriteOptions::default().strip_important(true);
+    assert_eq!(declarations[0].to_css_with(&options), "color: red");
+    assert_eq!(serialize_declarations(&declarations, &options), "color: red; margin: 0");
+    assert!(declarations[0].important);
+}
+
+#[test]
+fn test_css_try_parse_errors() {
+    assert_eq!(
+        try_parse_selector_list("h1, .title").unwrap(),
+        vec![Selector::Type("h1".to_string()), Selector::Class("title".to_string())]
+    );
+
+    let error = try_parse_selector_list("h1,\n  }").unwrap_err();
+    assert_eq!(error.span().line, 2);
+    assert_eq!(error.to_string(), "invalid selector: unexpected \"}\" at line 2, column 3");
+
+    let error = try_parse_selector_list("h1,").unwrap_err();
+    assert_eq!(error.to_string(), "invalid selector: unexpected end of input at line 1, column 4");
+
+    let error = try_parse_declaration("color red").unwrap_err();
+    assert!(matches!(error, CssError::InvalidDeclaration { .. }));
+    assert_eq!(error.to_string(), "invalid declaration: unexpected \"red\" at line 1, column 7");
+
+    let declaration = try_parse_declaration("color: red !important").unwrap();
+    assert!(declaration.important);
+}
+
+fn first_selector(css: &str) -> Selector {
+    let rules = CssParser::new(css).parse();
+    assert_eq!(rules.len(), 1, "expected one rule for {:?}", css);
+    rules[0].selectors[0].clone()
+}
+
+// The first selector of a selector list, parsed on its own.
+fn parse_selector(css: &str) -> Selector {
+    try_parse_selector_list(css).unwrap().remove(0)
+}
+
+#[test]
+fn test_css_parser_whitespace_between_selectors() {
+    let descendant = Selector::Descendant(
+        Box::new(Selector::Type("a".to_string())),
+        Box::new(Selector::Class("b".to_string())),
+    );
+    let compound = Selector::Compound(vec![
+        Selector::Type("a".to_string()),
+        Selector::Class("b".to_string()),
+    ]);
+
+    assert_eq!(first_selector("a .b { color: red; }"), descendant);
+    assert_eq!(first_selector("a\n.b { color: red; }"), descendant);
+    assert_eq!(first_selector("a /* comment */ .b { color: red; }"), descendant);
+    assert_eq!(first_selector("a.b { color: red; }"), compound);
+    assert_eq!(first_selector("a/**/.b { color: red; }"), compound);
+    assert_ne!(descendant, compound);
+}
+
+#[test]
+fn test_css_parser_insignificant_whitespace() {
+    let css = "  h1 ,\n h2\t{\n  color :\n red ;\n  margin : 0  auto  !important ;}\n\n";
+    let rules = CssParser::new(css).parse();
+
+    assert_eq!(rules.len(), 1);
+    assert_eq!(
+        rules[0].selectors,
+        vec![Selector::Type("h1".to_string()), Selector::Type("h2".to_string())]
+    );
+    assert_eq!(rules[0].declarations.len(), 2);
+    assert_eq!(rules[0].declarations[0].value, "red");
+    assert_eq!(rules[0].declarations[1].value, "0 auto");
+    assert!(rules[0].declarations[1].important);
+}
+
+#[test]
+fn test_css_parser_skips_rule_with_invalid_selector() {
+    let css = "a..b { color: red; } p { margin: 0; }";
+    let rules = CssParser::new(css).parse();
+
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].selectors, vec![Selector::Type("p".to_string())]);
+    assert_eq!(rules[0].declarations[0].property, "margin");
+}
+
+#[test]
+fn test_stylesheet_merge() {
+    let base = CssParser::new("@import \"reset.css\"; h1 { color: red; } @media print { h1 { color: black; } }").parse_stylesheet();
+    let theme = CssParser::new("h1 { color: blue; } p { margin: 0; }").parse_stylesheet();
+
+    let merged = base.merge(theme);
+    assert_eq!(merged.rules.len(), 5);
+
+    match &merged.rules[0] {
+        CssRule::At(rule) => {
+            assert_eq!(rule.name, "import");
+            assert_eq!(rule.prelude, "\"reset.css\"");
+            assert_eq!(rule.block, None);
+        }
+        other => panic!("expected @import, got {:?}", other),
+    }
+    match &merged.rules[2] {
+        CssRule::At(rule) => {
+            assert_eq!(rule.name, "media");
+            assert_eq!(rule.prelude, "print");
+            assert!(matches!(&rule.block, Some(AtRuleBlock::Rules(rules)) if rules.len() == 1));
+        }
+        other => panic!("expected @media, got {:?}", other),
+    }
+
+    // Rules from the second stylesheet come later, so they win the cascade.
+    let colors: Vec<&str> = merged
+        .style_rules()
+        .flat_map(|rule| rule.declarations.iter())
+        .filter(|declaration| declaration.property == "color")
+        .map(|declaration| declaration.value.as_str())
+        .collect();
+    assert_eq!(colors, vec!["red", "blue"]);
+}
+
+#[test]
+fn test_stylesheet_merge_dedup() {
+    let first = CssParser::new("a { color: red; } p { margin: 0; }").parse_stylesheet();
+    let second = CssParser::new("p { margin: 0; } a { color: red; }").parse_stylesheet();
+
+    let merged = first.merge_dedup(second);
+    let selectors: Vec<&Selector> = merged.style_rules().map(|rule| &rule.selectors[0]).collect();
+    assert_eq!(
+        selectors,
+        vec![&Selector::Type("p".to_string()), &Selector::Type("a".to_string())]
+    );
+}
+
+#[test]
+fn test_parse_skips_at_rules() {
+    let rules = CssParser::new("@font-face { font-family: x; } a { color: red; } } b { color: blue; }").parse();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[1].selectors, vec![Selector::Type("b".to_string())]);
+}
+
+#[test]
+fn test_statement_at_rules() {
+    let css = r#"@charset "utf-8";
+@import url("a.css");
+@import "reset.css" screen;
+@supports (display: grid) { .grid { display: grid; } }
+h1 { color: red; }
+@namespace svg url(http://www.w3.org/2000/svg)"#;
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    let at_rules: Vec<(&str, &str, bool)> = stylesheet
+        .rules
+        .iter()
+        .filter_map(|rule| match rule {
+            CssRule::At(rule) => Some((rule.name.as_str(), rule.prelude.as_str(), rule.block.is_some())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(at_rules, [
+        ("charset", "\"utf-8\"", false),
+        ("import", "url(\"a.css\")", false),
+        ("import", "\"reset.css\" screen", false),
+        ("supports", "(display: grid)", true),
+        ("namespace", "svg url(http://www.w3.org/2000/svg)", false),
+    ]);
+
+    // Statement at-rules don't swallow the rules after them.
+    let rules = CssParser::new(css).parse();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].selectors, vec![Selector::Type("h1".to_string())]);
+}
+
+#[test]
+fn test_keyframes() {
+    use html_css_parser::css::KeyframeSelector;
+
+    let css = "@keyframes spin { 0% { transform: rotate(0); } 100% { transform: rotate(360deg); } } .x { animation: spin 1s; }";
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    assert_eq!(stylesheet.rules.len(), 2);
+    let CssRule::At(spin) = &stylesheet.rules[0] else {
+        panic!("expected @keyframes, got {:?}", stylesheet.rules[0]);
+    };
+    assert_eq!((spin.name.as_str(), spin.prelude.as_str()), ("keyframes", "spin"));
+    let Some(AtRuleBlock::Keyframes(frames)) = &spin.block else {
+        panic!("expected keyframes, got {:?}", spin.block);
+    };
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].selectors, [KeyframeSelector::Percentage(0.0)]);
+    assert_eq!(frames[0].declarations[0].property, "transform");
+    assert_eq!(frames[0].declarations[0].value, "rotate(0)");
+    assert_eq!(frames[1].selectors, [KeyframeSelector::Percentage(100.0)]);
+    assert_eq!(frames[1].declarations[0].value, "rotate(360deg)");
+    assert_eq!(stylesheet.style_rules().count(), 1);
+
+    // Keywords, selector lists, vendor prefixes, and a bad frame that's
+    // skipped.
+    let css = "@-webkit-keyframes fade { from, 50% { opacity: 0 } .bad { opacity: 1 } to { opacity: 1 } }";
+    let (rules, errors) = CssParser::new(css).parse_with_diagnostics();
+    assert!(rules.is_empty());
+    assert!(matches!(&errors[..], [CssError::InvalidSelector { found, .. }] if found == "."));
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    let CssRule::At(fade) = &stylesheet.rules[0] else {
+        panic!("expected @-webkit-keyframes");
+    };
+    let Some(AtRuleBlock::Keyframes(frames)) = &fade.block else {
+        panic!("expected keyframes, got {:?}", fade.block);
+    };
+    let selectors: Vec<Vec<f64>> = frames
+        .iter()
+        .map(|frame| frame.selectors.iter().map(KeyframeSelector::percentage).collect())
+        .collect();
+    assert_eq!(selectors, [vec![0.0, 50.0], vec![100.0]]);
+
+    assert_eq!(
+        stylesheet.to_css(),
+        "@-webkit-keyframes fade {\n  from, 50% {\n    opacity: 0;\n  }\n\n  to {\n    opacity: 1;\n  }\n}"
+    );
+    let minified = stylesheet.to_css_with(&CssWriteOptions::default().minify(true));
+    assert_eq!(minified, "@-webkit-keyframes fade{from,50%{opacity:0}to{opacity:1}}");
+    assert_eq!(CssParser::new(&minified).parse_stylesheet(), stylesheet);
+}
+
+#[test]
+fn test_media_query_evaluate() {
+    use html_css_parser::css::{parse_media_query, MediaEnv};
+
+    let narrow = MediaEnv::screen(500.0, 800.0);
+    let wide = MediaEnv::screen(1280.0, 800.0);
+    let cases = [
+        ("screen", true, true),
+        ("print", false, false),
+        ("all", true, true),
+        ("not print", true, true),
+        ("only screen and (max-width: 600px)", true, false),
+        ("(min-width: 40em)", false, true),
+        ("screen and (orientation: portrait)", true, false),
+        ("(min-width: 400px) and (max-width: 1000px)", true, false),
+        ("(hover: hover)", false, false),
+    ];
+
+    for (input, on_narrow, on_wide) in cases {
+        let query = parse_media_query(input).unwrap_or_else(|| panic!("failed to parse {:?}", input));
+        assert_eq!(query.evaluate(&narrow), on_narrow, "{} on a narrow screen", input);
+        assert_eq!(query.evaluate(&wide), on_wide, "{} on a wide screen", input);
+        assert_eq!(query.to_string(), input);
+    }
+
+    assert_eq!(parse_media_query("screen and"), None);
+    assert_eq!(parse_media_query("(max-width: 600px"), None);
+}
+
+#[test]
+fn test_selector_matching_case_rules() {
+    use html_css_parser::css::MatchOptions;
+    use html_css_parser::html::{HtmlParser, Node, QuirksMode};
+
+    fn element(html: &str) -> html_css_parser::Element {
+        match HtmlParser::new(html).parse().remove(0) {
+            Node::Element(element) => element,
+            other => panic!("expected an element, got {:?}", other),
+        }
+    }
+
+    let standards = HtmlParser::new("<!DOCTYPE html><p></p>");
+    let quirks = HtmlParser::new("<p></p>");
+    assert_eq!(standards.quirks_mode(), QuirksMode::NoQuirks);
+    assert_eq!(quirks.quirks_mode(), QuirksMode::Quirks);
+    let standards = MatchOptions::from(standards.quirks_mode());
+    let quirks = MatchOptions::from(quirks.quirks_mode());
+
+    // Class and id matching depends on the document mode.
+    let div = element(r#"<div class="foo bar" id="Main"></div>"#);
+    assert!(!parse_selector(".Foo").matches_with(&div, &standards));
+    assert!(parse_selector(".Foo").matches_with(&div, &quirks));
+    assert!(parse_selector(".foo").matches_with(&div, &standards));
+    assert!(!parse_selector("#main").matches_with(&div, &standards));
+    assert!(parse_selector("#main").matches_with(&div, &quirks));
+    assert!(parse_selector("DIV.bar#Main").matches(&div));
+
+    // Attribute names are case-insensitive, and so are `type` values.
+    let input = element(r#"<input type="text" data-kind="Primary">"#);
+    assert!(parse_selector("[TYPE=text]").matches(&input));
+    assert!(parse_selector("[type=TEXT]").matches(&input));
+    assert!(!parse_selector("[data-kind=primary]").matches(&input));
+
+    // Explicit flags override the defaults.
+    assert!(parse_selector("[data-kind=primary i]").matches(&input));
+    assert!(parse_selector("[data-kind^=\"PRI\" I]").matches(&input));
+    assert!(!parse_selector("[type=TEXT s]").matches(&input));
+    assert!(parse_selector("[type=text s]").matches(&input));
+}
+
+#[test]
+fn test_quirks_mode_from_doctype() {
+    use html_css_parser::html::{HtmlParser, QuirksMode};
+
+    let cases = [
+        ("<!DOCTYPE html>", QuirksMode::NoQuirks),
+        ("<!doctype HTML>", QuirksMode::NoQuirks),
+        ("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"http://www.w3.org/TR/html4/strict.dtd\">", QuirksMode::NoQuirks),
+        ("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\">", QuirksMode::Quirks),
+        ("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\">", QuirksMode::LimitedQuirks),
+        ("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">", QuirksMode::LimitedQuirks),
+        ("<!DOCTYPE svg>", QuirksMode::Quirks),
+        ("", QuirksMode::Quirks),
+    ];
+
+    for (doctype, expected) in cases {
+        let html = format!("{}<p></p>", doctype);
+        assert_eq!(HtmlParser::new(&html).quirks_mode(), expected, "{}", doctype);
+    }
+}
+
+#[test]
+fn test_css_parser_stats() {
+    use html_css_parser::css::CssStats;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn progress(bytes_done: usize, total: usize) {
+        assert!(bytes_done <= total);
+        CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let css = "a{color:red;bad}@media print{b{x:1}}}";
+    let mut parser = CssParser::new(css).on_progress(10, progress);
+    let stylesheet = parser.parse_stylesheet();
+    assert_eq!(stylesheet.rules.len(), 2);
+
+    assert_eq!(
+        parser.stats(),
+        CssStats {
+            tokens: 20,
+            rules: 3,
+            bytes: css.len(),
+            max_depth: 2,
+            skipped: 2,
+        }
+    );
+    // Every tenth token, plus completion.
+    assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_media_query_list() {
+    use html_css_parser::css::{parse_media_query_list, MediaEnv};
+
+    let screen = MediaEnv::screen(1024.0, 768.0);
+    let print = MediaEnv::print();
+
+    let list = parse_media_query_list("screen, print");
+    assert_eq!(list.queries.len(), 2);
+    assert!(list.evaluate(&screen));
+    assert!(list.evaluate(&print));
+    assert_eq!(list.to_string(), "screen, print");
+
+    // A malformed query only invalidates itself.
+    let list = parse_media_query_list("screen and, (min-width: 10px) and (max-width: 2000px)");
+    assert_eq!(list.to_string(), "not all, (min-width: 10px) and (max-width: 2000px)");
+    assert!(list.evaluate(&screen));
+    assert!(!parse_media_query_list("screen and, print").evaluate(&screen));
+
+    // Commas inside parentheses don't split the list.
+    assert_eq!(parse_media_query_list("(foo: a, b), print").queries.len(), 2);
+    // An empty list matches everything.
+    assert!(parse_media_query_list("").evaluate(&print));
+
+    let stylesheet = CssParser::new("@media screen, print { a { color: red; } } @media tv, speech { b { color: blue; } }")
+        .parse_stylesheet();
+    assert_eq!(stylesheet.rules_for(&screen).len(), 1);
+}
+
+#[test]
+fn test_significant_tokens() {
+    let inputs = [
+        "a > b { color : red ; }",
+        "/* lead */ .x/* mid */.y{margin:0 auto}  ",
+        "@media screen and (max-width: 600px) { a { b: c } }",
+        "   ",
+        "",
+    ];
+
+    for input in inputs {
+        let filtered: Vec<CssToken> = CssTokenizer::new(input)
+            .filter(|token| !matches!(token, CssToken::Whitespace | CssToken::Comment(_)))
+            .collect();
+        let significant: Vec<CssToken> = SignificantTokens::new(input).collect();
+        assert_eq!(significant, filtered, "{:?}", input);
+        assert_eq!(CssTokenizer::new(input).significant().collect::<Vec<_>>(), filtered);
+    }
+}
+
+#[test]
+fn test_css_property_names_are_normalized() {
+    let rules = CssParser::new("a { COLOR : red; Margin:0; /* c */ padding /* d */ : 1px; --Brand-Color: blue; }").parse();
+    let rule = &rules[0];
+
+    let properties: Vec<(&str, &str)> = rule
+        .declarations
+        .iter()
+        .map(|declaration| (declaration.property.as_str(), declaration.raw_property.as_str()))
+        .collect();
+    assert_eq!(
+        properties,
+        vec![
+            ("color", "COLOR"),
+            ("margin", "Margin"),
+            ("padding", "padding"),
+            ("--Brand-Color", "--Brand-Color"),
+        ]
+    );
+
+    assert_eq!(rule.get("color").map(|declaration| declaration.value.as_str()), Some("red"));
+    assert_eq!(rule.get("Color").map(|declaration| declaration.value.as_str()), Some("red"));
+    assert_eq!(rule.get("--Brand-Color").map(|declaration| declaration.value.as_str()), Some("blue"));
+    assert_eq!(rule.get("--brand-color"), None);
+}
+
+#[test]
+fn test_rule_get_honors_important() {
+    let rules = CssParser::new("a { color: red !important; color: blue; margin: 0; margin: 1px; }").parse();
+    assert_eq!(rules[0].get("color").map(|declaration| declaration.value.as_str()), Some("red"));
+    assert_eq!(rules[0].get("margin").map(|declaration| declaration.value.as_str()), Some("1px"));
+    assert_eq!(rules[0].get("padding"), None);
+}
+
+#[test]
+fn test_rule_dedup_declarations() {
+    let mut rules = CssParser::new("a { color: red; color: blue; }").parse();
+    rules[0].dedup_declarations();
+    assert_eq!(rules[0].declarations.len(), 1);
+    assert_eq!(rules[0].declarations[0].property, "color");
+    assert_eq!(rules[0].declarations[0].value, "blue");
+
+    let mut rules =
+        CssParser::new("a { margin: 0; Color: red !important; padding: 1px; color: blue; margin: 2px; }").parse();
+    rules[0].dedup_declarations();
+    let kept: Vec<(&str, &str)> = rules[0]
+        .declarations
+        .iter()
+        .map(|declaration| (declaration.property.as_str(), declaration.value.as_str()))
+        .collect();
+    assert_eq!(kept, [("color", "red"), ("padding", "1px"), ("margin", "2px")]);
+}
+
+#[test]
+fn test_css_parser_preserves_raw_values() {
+    use html_css_parser::css::CssParseOptions;
+
+    let css = "a { margin:0    auto; font: 12px /* size */ serif  !important; --empty:; }";
+    let options = CssParseOptions::default().preserve_raw_values(true);
+    let rules = CssParser::with_options(css, options).parse();
+    let declarations = &rules[0].declarations;
+
+    assert_eq!(declarations[0].value, "0 auto");
+    assert_eq!(declarations[0].raw_value.as_deref(), Some("0    auto"));
+    assert_eq!(declarations[1].value, "12px serif");
+    assert_eq!(declarations[1].raw_value.as_deref(), Some("12px /* size */ serif"));
+    assert!(declarations[1].important);
+    assert_eq!(declarations[2].raw_value.as_deref(), Some(""));
+
+    // Raw values are only captured on request.
+    let rules = CssParser::new(css).parse();
+    assert_eq!(rules[0].declarations[0].raw_value, None);
+}
+
+#[test]
+fn test_sanitize_inline_style() {
+    use html_css_parser::css::{sanitize_inline_style, StylePolicy};
+
+    let only_color = StylePolicy::allow_only(&["color"]);
+    assert_eq!(sanitize_inline_style("color: red; position: fixed", &only_color), "color: red");
+
+    let policy = StylePolicy::default();
+    assert_eq!(sanitize_inline_style("position: fixed; top: 0", &policy), "top: 0");
+    assert_eq!(sanitize_inline_style("position: absolute", &policy), "position: absolute");
+    assert_eq!(sanitize_inline_style("width: expression(alert(1)); color: red", &policy), "color: red");
+    assert_eq!(sanitize_inline_style("background: url(a.png) red", &policy), "");
+    assert_eq!(sanitize_inline_style("-moz-binding: url(x.xml#y); behavior: url(x.htc)", &policy), "");
+    assert_eq!(sanitize_inline_style("color: r\\65 d; width: expr\\65 ssion(alert(1))", &policy), "color: red");
+    assert_eq!(sanitize_inline_style("background: -webkit-image-set(\"https://evil.example/t.png\" 1x)", &policy), "");
+
+    let urls = StylePolicy::default().allow_urls(true);
+    assert_eq!(sanitize_inline_style("background: url('a.png')", &urls), "background: url(\"a.png\")");
+    assert_eq!(sanitize_inline_style("background: url(\"JavaScript:alert(1)\")", &urls), "");
+    assert_eq!(sanitize_inline_style("background: url(b.png)", &urls), "background: url(b.png)");
+    assert_eq!(sanitize_inline_style("background: url(javascript:alert)", &urls), "");
+    assert_eq!(sanitize_inline_style("background: url(java\\9 script:alert)", &urls), "");
+    assert_eq!(sanitize_inline_style("background: image-set(url(javascript:alert) 1x)", &urls), "");
+    assert_eq!(sanitize_inline_style("background: image-set(\"javascript:alert(1)\" 1x)", &urls), "");
+    assert_eq!(sanitize_inline_style("background: -webkit-image-set(\"javascript:alert(1)\" 1x)", &urls), "");
+    assert_eq!(
+        sanitize_inline_style("background: -webkit-image-set(\"a.png\" 1x, \"b.png\" 2x)", &urls),
+        "background: -webkit-image-set(\"a.png\" 1x, \"b.png\" 2x)"
+    );
+}
+
+#[test]
+fn test_sanitize_deeply_nested_values() {
+    use html_css_parser::css::value::parse_component_values;
+    use html_css_parser::css::{sanitize_inline_style, ComponentValue, StylePolicy};
+
+    // How deeply functions and blocks nest, walked without recursion.
+    fn nesting_depth(values: &[ComponentValue]) -> usize {
+        let mut stack: Vec<(&[ComponentValue], usize)> = vec![(values, 0)];
+        let mut deepest = 0;
+        while let Some((values, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            for value in values {
+                match value {
+                    ComponentValue::Function { arguments: inner, .. } | ComponentValue::Block { contents: inner, .. } => {
+                        stack.push((inner, depth + 1))
+                    }
+                    ComponentValue::Token(_) => {}
+                }
+            }
+        }
+        deepest
+    }
+
+    let policy = StylePolicy::default();
+    for depth in [50_000, 1_000_000] {
+        let css = format!("color: {}; margin: 0", "(".repeat(depth));
+        assert_eq!(sanitize_inline_style(&css, &policy), "");
+        assert_eq!(nesting_depth(&parse_component_values(&"(".repeat(depth))), 256);
+    }
+
+    // Nesting within the limit is kept and checked as usual.
+    let nested = |depth: usize, inner: &str| format!("{}{}{}", "a(".repeat(depth), inner, ")".repeat(depth));
+    assert_eq!(nesting_depth(&parse_component_values(&nested(256, "x"))), 256);
+    assert_eq!(sanitize_inline_style(&format!("width: {}", nested(200, "1px")), &policy), format!("width: {}", nested(200, "1px")));
+    assert_eq!(sanitize_inline_style(&format!("width: {}", nested(200, "expression(1)")), &policy), "");
+    assert_eq!(sanitize_inline_style(&format!("width: {}; color: red", nested(300, "1px")), &policy), "color: red");
+
+    // Past the limit, tokens follow the innermost function in order.
+    let values = parse_component_values(&nested(257, "x"));
+    assert_eq!(nesting_depth(&values), 256);
+    assert_eq!(html_css_parser::css::value::canonicalize(&values), nested(257, "x"));
+}
+
+#[test]
+fn test_suspicious_values() {
+    use html_css_parser::css::validate::suspicious_values;
+
+    let warnings = |css: &str| -> Vec<String> {
+        suspicious_values(&CssParser::new(css).parse_stylesheet())
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    };
+
+    assert_eq!(warnings("a { width: 100; }"), ["`width: 100` has no unit; only 0 may be written without one (did you mean 100px?)"]);
+    assert_eq!(warnings("a { font-size: 0.5; }"), ["`font-size: 0.5` has no unit (did you mean 0.5em or 0.5rem?)"]);
+    assert_eq!(warnings("a { font-size: 14; }"), ["`font-size: 14` has no unit (did you mean 14px?)"]);
+    assert_eq!(warnings("a { transition-duration: 300; }"), ["`transition-duration: 300` has no unit; durations need `s` or `ms`"]);
+    assert_eq!(warnings("a { transition: opacity 200 ease-in; }"), ["`transition: 200` has no unit; durations need `s` or `ms`"]);
+    assert_eq!(warnings("a { animation-delay: 2; }").len(), 1);
+    assert_eq!(warnings("a { opacity: 150%; }"), ["`opacity: 150%` is over 100%"]);
+    assert_eq!(warnings("a { fill-opacity: 50; }"), ["`fill-opacity: 50` is over 1 (did you mean 50%?)"]);
+    assert_eq!(warnings("a { margin: 0 10 0 auto; }").len(), 1);
+
+    // Warnings inside at-rules carry the declaration's span.
+    let css = "@media print {\n  p { padding: 4; }\n}";
+    let found = suspicious_values(&CssParser::new(css).parse_stylesheet());
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].property, "padding");
+    assert_eq!(found[0].value, "4");
+    assert_eq!(&css[found[0].span.start..found[0].span.end], "padding: 4");
+    assert_eq!((found[0].span.line, found[0].span.column), (2, 7));
+
+    // Known-good values.
+    for css in [
+        "a { line-height: 1.5; }",
+        "a { z-index: 100; }",
+        "a { flex: 1 1 0; order: 2; font-weight: 700; }",
+        "a { width: 0; margin: 0 auto; transition-duration: 0; }",
+        "a { width: 100px; font-size: 1.2em; transition: opacity 0.3s cubic-bezier(0.1, 0.7, 1, 0.1); }",
+        "a { width: calc(100% - 10px); }",
+        "a { opacity: 0.5; opacity: 100%; }",
+        "a { animation: spin 1s infinite 2; }",
+        "a { unknown-property: 100; }",
+    ] {
+        assert_eq!(warnings(css), Vec::<String>::new(), "{}", css);
+    }
+}
+
+#[test]
+fn test_custom_property_graph() {
+    use html_css_parser::css::analyze::custom_property_graph;
+
+    let css = "html { --color: var(--brand); --brand: var(--blue); --blue: #00f; --spare: 1px; }
+        @media print { a { color: var(--color); border: 1px solid var(--missing, var(--fallback)); } }
+        p { --fallback: red; }";
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    let graph = custom_property_graph(&stylesheet);
+
+    assert_eq!(graph.dependencies("--color"), ["--brand"]);
+    assert_eq!(graph.resolution_order().unwrap(), ["--blue", "--brand", "--color", "--spare", "--fallback"]);
+    assert_eq!(graph.unused_properties(&stylesheet), ["--spare"]);
+
+    let css = "html { --a: calc(var(--b) * 2); --b: var(--missing, var(--a)); --c: 0; }";
+    let graph = custom_property_graph(&CssParser::new(css).parse_stylesheet());
+    assert_eq!(graph.dependencies("--b"), ["--missing", "--a"]);
+    let error = graph.resolution_order().unwrap_err();
+    assert_eq!(error.cycle, ["--a", "--b", "--a"]);
+    assert_eq!(error.to_string(), "custom properties reference each other in a cycle: --a -> --b -> --a");
+}
+
+#[test]
+fn test_resolve_var_references() {
+    use html_css_parser::css::analyze::resolve_var_references;
+
+    let css = ".card { --brand-color: #667eea; --Gap: 4px; color: var(--brand-color); \
+        margin: calc(var(--Gap) * 2) var(--gap, 1px); border: 1px solid var(--missing, var(--brand-color)); \
+        outline: var(--missing); --a: var(--b); --b: var(--a); width: var(--a, 10px); height: var(--a); }";
+    let rules = CssParser::new(css).parse();
+    let resolved = resolve_var_references(&rules[0]);
+
+    // The custom property names keep their case and round-trip unchanged.
+    assert_eq!(resolved.get("--brand-color").unwrap().value, "#667eea");
+    assert_eq!(resolved.get("--Gap").unwrap().raw_property, "--Gap");
+    assert_eq!(resolved.get("color").unwrap().value, "#667eea");
+    assert_eq!(resolved.get("margin").unwrap().value, "calc(4px * 2) 1px");
+    assert_eq!(resolved.get("border").unwrap().value, "1px solid #667eea");
+    assert_eq!(resolved.get("outline").unwrap().value, "var(--missing)");
+    assert_eq!(resolved.get("width").unwrap().value, "10px");
+    assert_eq!(resolved.get("height").unwrap().value, "var(--a)");
+    assert_eq!(resolved.get("--a").unwrap().value, "var(--b)");
+
+    let rules = CssParser::new("a { --x: red; --x: blue !important; --x: green; color: var(--x) }").parse();
+    assert_eq!(resolve_var_references(&rules[0]).get("color").unwrap().value, "blue");
+}
+
+#[test]
+fn test_resolve_var_references_doubling_chain() {
+    use html_css_parser::css::analyze::resolve_var_references;
+
+    // Each property is referenced twice by the next, so expanding the chain
+    // naively takes 2^30 steps and a gigabyte of text.
+    let mut css = String::from("a { --v0: xxxxxxxx;");
+    for n in 1..=30 {
+        css.push_str(&format!(" --v{n}: var(--v{}) var(--v{});", n - 1, n - 1));
+    }
+    css.push_str(" width: var(--v2); height: var(--v30); color: var(--v30, red) }");
+    let rules = CssParser::new(&css).parse();
+    let resolved = resolve_var_references(&rules[0]);
+
+    assert_eq!(resolved.get("width").unwrap().value, ["xxxxxxxx"; 4].join(" "));
+    // Substitutions this long are invalid, so the fallback is used if any.
+    assert_eq!(resolved.get("height").unwrap().value, "var(--v30)");
+    assert_eq!(resolved.get("color").unwrap().value, "red");
+}
+
+#[test]
+fn test_parse_color() {
+    use html_css_parser::css::{parse_color, Color};
+
+    assert_eq!(parse_color("#abc"), Some(Color::rgb(0xaa, 0xbb, 0xcc)));
+    assert_eq!(parse_color("#ABCD"), Some(Color::rgba(0xaa, 0xbb, 0xcc, 1.0 - 0x22 as f32 / 255.0)));
+    assert_eq!(parse_color("#ff0000"), Some(Color::rgb(255, 0, 0)));
+    let translucent = parse_color("#ff000080").unwrap();
+    assert_eq!((translucent.r, translucent.g, translucent.b, translucent.alpha()), (255, 0, 0, 0x80));
+    assert!((translucent.a - 0.502).abs() < 0.001);
+
+    assert_eq!(parse_color("rgba(0,0,0,0.1)"), Some(Color::rgba(0, 0, 0, 0.1)));
+    assert_eq!(parse_color("rgb(255, 128, 0)"), Some(Color::rgb(255, 128, 0)));
+    assert_eq!(parse_color("rgb(100% 50% 0% / 25%)"), Some(Color::rgba(255, 128, 0, 0.25)));
+    assert_eq!(parse_color("RGBA(300, 0, 0, 2)"), Some(Color::rgb(255, 0, 0)));
+
+    assert_eq!(parse_color(" Red "), Some(Color::rgb(255, 0, 0)));
+    assert_eq!(parse_color("white"), Some(Color::rgb(255, 255, 255)));
+    assert_eq!(parse_color("rebeccapurple"), Some(Color::rgb(0x66, 0x33, 0x99)));
+    assert_eq!(parse_color("transparent"), Some(Color::TRANSPARENT));
+
+    for invalid in ["#abcde", "#ggg", "notacolor", "currentcolor", "red blue", "rgb(1, 2)", "hsl(0, 100%, 50%)", "10px"] {
+        assert_eq!(parse_color(invalid), None, "{invalid}");
+    }
+
+    // Written back in the shortest hex form.
+    assert_eq!(Color::rgb(0xaa, 0xbb, 0xcc).to_string(), "#abc");
+    assert_eq!(parse_color("rgba(0,0,0,0.1)").unwrap().to_string(), "#0000001a");
+    assert_eq!(Color::TRANSPARENT.to_string(), "#0000");
+
+    let rules = CssParser::new("a { color: #abc; background-color: rgba(0, 0, 0, .5); margin: 0 }").parse();
+    assert_eq!(rules[0].get("color").unwrap().color_value(), Some(Color::rgb(0xaa, 0xbb, 0xcc)));
+    assert_eq!(rules[0].get("background-color").unwrap().color_value().unwrap().a, 0.5);
+    assert_eq!(rules[0].get("margin").unwrap().color_value(), None);
+}
+
+#[test]
+fn test_calc_expressions() {
+    use html_css_parser::css::{parse_calc, CalcExpr, CalcOp};
+
+    let dimension = |value: f64, unit: &str| Box::new(CalcExpr::Dimension { value, unit: unit.to_string() });
+
+    let rules = CssParser::new("div { width: calc(100% - 20px); height: calc(2 * (10px + 5px)); color: red }").parse();
+    let width = rules[0].get("width").unwrap().calc_value().unwrap();
+    assert_eq!(
+        width,
+        CalcExpr::Binary { op: CalcOp::Subtract, left: Box::new(CalcExpr::Percentage(100.0)), right: dimension(20.0, "px") }
+    );
+    assert_eq!(width.evaluate(300.0), Some(280.0));
+
+    let height = rules[0].get("height").unwrap().calc_value().unwrap();
+    assert_eq!(
+        height,
+        CalcExpr::Binary {
+            op: CalcOp::Multiply,
+            left: Box::new(CalcExpr::Number(2.0)),
+            right: Box::new(CalcExpr::Binary { op: CalcOp::Add, left: dimension(10.0, "px"), right: dimension(5.0, "px") }),
+        }
+    );
+    assert_eq!(height.evaluate(0.0), Some(30.0));
+    assert_eq!(rules[0].get("color").unwrap().calc_value(), None);
+
+    // Multiplication binds tighter, operators associate to the left, and
+    // nested `calc()` calls group like parentheses.
+    let expr = parse_calc("calc(1em + 2px * 3 - calc(10px - 4px) / 2)").unwrap();
+    assert_eq!(expr.evaluate(0.0), Some(19.0));
+    assert_eq!(expr.to_string(), "calc(1em + 2px * 3 - (10px - 4px) / 2)");
+    assert_eq!(parse_calc(&expr.to_string()), Some(expr));
+    assert_eq!(parse_calc(" CALC( 50% / 4 ) ").unwrap().evaluate(200.0), Some(25.0));
+
+    for invalid in [
+        "calc()",
+        "calc(10px +)",
+        "calc(1 + 2px)",
+        "calc(2px * 3px)",
+        "calc(2 / 1px)",
+        "calc(1px 2px)",
+        "calc(10px + 5px) 1px",
+        "min(1px, 2px)",
+        "calc(1px + red)",
+    ] {
+        assert_eq!(parse_calc(invalid), None, "{invalid}");
+    }
+    assert_eq!(parse_calc("calc(1px / 0)").unwrap().evaluate(0.0), None);
+    assert_eq!(parse_calc("calc(1vw + 1px)").unwrap().evaluate(0.0), None);
+}
+
+#[test]
+fn test_selector_display_round_trip() {
+    for css in [
+        "input[type=\"text\"]:focus::placeholder",
+        "a:hover",
+        "p::before",
+        "li:nth-child(2n+1)",
+        ".x:not(.y)",
+        "[data-state|=\"open\" i]",
+        "#main.active *",
+        "nav a[href^=\"https\"]",
+    ] {
+        let selectors = try_parse_selector_list(css).unwrap();
+        assert_eq!(selectors[0].to_string(), css);
+        assert_eq!(try_parse_selector_list(&selectors[0].to_css()).unwrap(), selectors);
+    }
+
+    // Equivalent spellings serialize the same way.
+    let selectors = try_parse_selector_list("input[type=text]:FOCUS:before").unwrap();
+    assert_eq!(selectors[0].to_string(), "input[type=\"text\"]:focus::before");
+}
+
+#[test]
+fn test_css_token_ref_conversions() {
+    use html_css_parser::css::CssTokenRef;
+
+    let tokens: Vec<CssToken> = CssTokenizer::new("a { width: 12px; }").collect();
+    let refs: Vec<CssTokenRef> = tokens.iter().map(CssTokenRef::from).collect();
+    assert_eq!(refs[0], CssTokenRef::Ident("a"));
+    assert!(refs.contains(&CssTokenRef::Dimension { value: 12.0, unit: "px" }));
+
+    let owned: Vec<CssToken> = refs.into_iter().map(CssToken::from).collect();
+    assert_eq!(owned, tokens);
+}
+
+#[test]
+fn test_css_token_try_from_str() {
+    assert_eq!(CssToken::try_from("12px"), Ok(CssToken::Dimension { value: 12.0, unit: "px".to_string() }));
+    assert_eq!(CssToken::try_from("#fff"), Ok(CssToken::Hash("fff".to_string())));
+
+    let error = CssToken::try_from("12px solid").unwrap_err();
+    assert!(matches!(&error, CssError::InvalidToken { found, .. } if found == " solid"));
+    assert_eq!(error.span().start, 4);
+    assert!(CssToken::try_from("").is_err());
+}
+
+#[test]
+fn test_box_tree() {
+    use html_css_parser::css::{box_tree, BoxNode, ComputedStyles, MediaEnv};
+    use html_css_parser::html::HtmlParser;
+
+    let html = r#"<body>
+        <div class="ad">Buy <b>now</b></div>
+        <div>Intro <span>one</span><em>two</em><p>Block</p>tail</div>
+        <p>Read <a href="/">more</a> here</p>
+    </body>"#;
+    let document = HtmlParser::new(html).parse_document().unwrap();
+    let stylesheet = CssParser::new(".ad { display: none; } em { display: block; }").parse_stylesheet();
+    let styles = ComputedStyles::compute(&document, &stylesheet, &MediaEnv::default());
+    let tree = box_tree(&document, &styles);
+
+    // The hidden div and its descendants are gone.
+    let blocks = tree.children();
+    assert_eq!(blocks.len(), 2);
+    assert!(!tree.to_string().contains("Buy"));
+
+    // Inline content before and after the block children is wrapped in
+    // anonymous blocks; `em` is a block because of the stylesheet.
+    let BoxNode::Block { tag_name, children, .. } = &blocks[0] else {
+        panic!("expected a block, got {:?}", blocks[0]);
+    };
+    assert_eq!(tag_name, "div");
+    assert_eq!(children.len(), 4);
+    let BoxNode::AnonymousBlock { children: intro } = &children[0] else {
+        panic!("expected an anonymous block, got {:?}", children[0]);
+    };
+    assert_eq!(intro[0], BoxNode::Text("Intro".to_string()));
+    assert!(matches!(&intro[1], BoxNode::Inline { tag_name, .. } if tag_name == "span"));
+    assert!(matches!(&children[1], BoxNode::Block { tag_name, path, .. } if tag_name == "em" && path == &[1, 2]));
+    assert!(matches!(&children[2], BoxNode::Block { tag_name, .. } if tag_name == "p"));
+    assert!(matches!(&children[3], BoxNode::AnonymousBlock { children } if children == &[BoxNode::Text("tail".to_string())]));
+
+    // A paragraph with only inline content keeps its text and inline boxes
+    // as direct children.
+    assert_eq!(
+        blocks[1].to_string(),
+        "block <p>\n  text \"Read\"\n  inline <a>\n    text \"more\"\n  text \"here\"\n"
+    );
+
+    let hidden = ComputedStyles::compute(&document, &CssParser::new("body { display: none }").parse_stylesheet(), &MediaEnv::default());
+    assert!(box_tree(&document, &hidden).children().is_empty());
+}
+
+#[test]
+fn test_computed_styles_combinators() {
+    use html_css_parser::css::{box_tree, resolve_styles, ComputedStyles, MediaEnv};
+    use html_css_parser::html::{rendered_text, HtmlParser, Node};
+
+    let html = "<body><div><p>Hidden</p><span>Red</span><h1>Title</h1><p>Shown</p></div><p>Plain</p><span>Black</span></body>";
+    let document = HtmlParser::new(html).parse_document().unwrap();
+    let stylesheet = CssParser::new(
+        "p { color: blue } div p { display: none; color: green } div > span { color: red } h1 + p { display: block } body > p ~ span { color: black }",
+    )
+    .parse_stylesheet();
+    let styles = ComputedStyles::compute(&document, &stylesheet, &MediaEnv::default());
+
+    // body > div > p, span, h1, p; then body > p, span.
+    assert_eq!(styles.get(&[0, 0], "display"), Some("none"));
+    assert_eq!(styles.get(&[0, 0], "color"), Some("green"));
+    assert_eq!(styles.get(&[0, 1], "color"), Some("red"));
+    assert_eq!(styles.get(&[0, 3], "display"), Some("block"));
+    assert_eq!(styles.get(&[1], "display"), None);
+    assert_eq!(styles.get(&[1], "color"), Some("blue"));
+    assert_eq!(styles.get(&[2], "color"), Some("black"));
+
+    assert_eq!(rendered_text(&document, Some(&styles)), "Red\nTitle\nShown\nPlain\nBlack");
+    assert!(!box_tree(&document, &styles).to_string().contains("Hidden"));
+
+    // A lone element has no ancestors for combinators to match.
+    let Node::Element(body) = &document else { panic!("expected an element") };
+    let Node::Element(div) = &body.children[0] else { panic!("expected an element") };
+    let Node::Element(span) = &div.children[1] else { panic!("expected an element") };
+    assert!(resolve_styles(span, &CssParser::new("div > span { color: red }").parse()).is_empty());
+}
+
+#[test]
+fn test_compound_selectors() {
+    let selectors = try_parse_selector_list("a.nav-link#home").unwrap();
+    assert_eq!(
+        selectors,
+        vec![Selector::Compound(vec![
+            Selector::Type("a".to_string()),
+            Selector::Class("nav-link".to_string()),
+            Selector::Id("home".to_string()),
+        ])]
+    );
+
+    for css in ["p.a.b", "#x.y", "*.foo", "div.active#main"] {
+        let selectors = try_parse_selector_list(css).unwrap();
+        assert_eq!(selectors.len(), 1);
+        assert!(matches!(selectors[0], Selector::Compound(_)), "{css}");
+        assert_eq!(selectors[0].to_string(), css);
+        assert_eq!(try_parse_selector_list(&selectors[0].to_string()).unwrap(), selectors);
+    }
+
+    // Whitespace ends the compound and starts a descendant combinator.
+    let selectors = try_parse_selector_list("div.a .b").unwrap();
+    let Selector::Descendant(left, right) = &selectors[0] else {
+        panic!("expected a descendant selector, got {:?}", selectors[0]);
+    };
+    assert_eq!(left.to_string(), "div.a");
+    assert_eq!(**right, Selector::Class("b".to_string()));
+
+    let rules = CssParser::new("div.highlight { color: red; }").parse();
+    assert_eq!(rules[0].selectors[0].to_string(), "div.highlight");
+    assert_eq!(rules[0].declarations.len(), 1);
+}
+
+#[test]
+fn test_combinator_selectors() {
+    let ty = |name: &str| Box::new(Selector::Type(name.to_string()));
+
+    assert_eq!(parse_selector("div p"), Selector::Descendant(ty("div"), ty("p")));
+    assert_eq!(parse_selector("ul > li"), Selector::Child(ty("ul"), ty("li")));
+    assert_eq!(parse_selector("h1 + p"), Selector::Adjacent(ty("h1"), ty("p")));
+    assert_eq!(parse_selector("h1 ~ p"), Selector::GeneralSibling(ty("h1"), ty("p")));
+
+    // Whitespace around `>`, `+` and `~` is optional.
+    assert_eq!(parse_selector("ul>li"), parse_selector("ul > li"));
+    assert_eq!(parse_selector("h1+p"), parse_selector("h1 + p"));
+    assert_eq!(parse_selector("h1~p"), parse_selector("h1 ~ p"));
+
+    assert_eq!(parse_selector("a b c"), Selector::Descendant(Box::new(Selector::Descendant(ty("a"), ty("b"))), ty("c")));
+    assert_eq!(
+        parse_selector("nav > ul li.active + li ~ a"),
+        Selector::GeneralSibling(
+            Box::new(Selector::Adjacent(
+                Box::new(Selector::Descendant(
+                    Box::new(Selector::Child(ty("nav"), ty("ul"))),
+                    Box::new(Selector::Compound(vec![Selector::Type("li".to_string()), Selector::Class("active".to_string())])),
+                )),
+                ty("li"),
+            )),
+            ty("a"),
+        )
+    );
+    assert_eq!(parse_selector("nav > ul li.active + li ~ a").to_string(), "nav > ul li.active + li ~ a");
+
+    let rules = CssParser::new("ul > li, h1 + p { margin: 0 }").parse();
+    assert_eq!(rules[0].selectors.len(), 2);
+    assert!(try_parse_selector_list("ul >").is_err());
+
+    // Complex selectors are limited to 256 compounds.
+    let chain = |compounds: usize| format!("a{}", " > a".repeat(compounds - 1));
+    assert_eq!(parse_selector(&chain(256)).specificity(), (0, 0, 256));
+    assert!(matches!(
+        try_parse_selector_list(&chain(257)),
+        Err(CssError::InvalidSelector { found, .. }) if found == ">"
+    ));
+    let css = format!("{} {{ color: red }} b {{ color: blue }}", "a ".repeat(100_000));
+    let (rules, errors) = CssParser::new(&css).parse_with_diagnostics();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].selectors, [parse_selector("b")]);
+    assert!(matches!(errors[..], [CssError::InvalidSelector { .. }]));
+}
+
+#[test]
+fn test_minified_stylesheet_selectors() {
+    let selectors = |css: &str| -> Vec<Selector> {
+        let (mut rules, errors) = CssParser::new(css).parse_with_diagnostics();
+        assert!(errors.is_empty(), "{}: {:?}", css, errors);
+        assert_eq!(rules.len(), 1, "{}", css);
+        rules.remove(0).selectors
+    };
+    let ty = |name: &str| Box::new(Selector::Type(name.to_string()));
+    let class = |name: &str| Box::new(Selector::Class(name.to_string()));
+
+    let rules = CssParser::new("a>b{color:red}").parse();
+    assert_eq!(rules[0].selectors, [Selector::Child(ty("a"), ty("b"))]);
+    assert_eq!(rules[0].declarations[0].property, "color");
+    assert_eq!(rules[0].declarations[0].value, "red");
+    assert_eq!(selectors(".x,.y{margin:0}"), [*class("x"), *class("y")]);
+
+    assert_eq!(selectors(".a+.b{x:y}"), [Selector::Adjacent(class("a"), class("b"))]);
+    assert_eq!(selectors("a~.b{x:y}"), [Selector::GeneralSibling(ty("a"), class("b"))]);
+    assert_eq!(selectors("*>*{x:y}"), [Selector::Child(Box::new(Selector::Universal), Box::new(Selector::Universal))]);
+    assert_eq!(
+        selectors("a>b,c>d{x:y}"),
+        [Selector::Child(ty("a"), ty("b")), Selector::Child(ty("c"), ty("d"))]
+    );
+    // `+` inside an argument isn't a combinator.
+    assert_eq!(
+        selectors("li:nth-child(2n+1)+a{x:y}"),
+        [Selector::Adjacent(
+            Box::new(Selector::Compound(vec![
+                Selector::Type("li".to_string()),
+                Selector::PseudoClass("nth-child".to_string(), Some("2n+1".to_string())),
+            ])),
+            ty("a"),
+        )]
+    );
+
+    // The same selectors as with whitespace, written back the same way.
+    for (minified, spaced) in [
+        ("div>p:first-child+p", "div > p:first-child + p"),
+        ("a[href^=\"http\"]>img~span", "a[href^=\"http\"] > img ~ span"),
+        (".a>.b>.c,#d+e", ".a > .b > .c, #d + e"),
+    ] {
+        let minified = selectors(&format!("{}{{x:y}}", minified));
+        assert_eq!(minified, selectors(&format!("{} {{ x: y }}", spaced)));
+        let written: Vec<String> = minified.iter().map(Selector::to_string).collect();
+        assert_eq!(written.join(", "), spaced);
+    }
+
+    // Rules nested in at-rules too.
+    let stylesheet = CssParser::new("@media (min-width:600px){nav>ul+p{x:y}}").parse_stylesheet();
+    let [CssRule::At(media)] = stylesheet.rules.as_slice() else {
+        panic!("expected one at-rule: {:?}", stylesheet.rules);
+    };
+    let Some(AtRuleBlock::Rules(nested)) = &media.block else {
+        panic!("expected nested rules: {:?}", media.block);
+    };
+    let [CssRule::Style(rule)] = nested.as_slice() else {
+        panic!("expected one style rule: {:?}", nested);
+    };
+    assert_eq!(rule.selectors, [Selector::Adjacent(Box::new(Selector::Child(ty("nav"), ty("ul"))), ty("p"))]);
+}
+
+#[test]
+fn test_attribute_selectors() {
+    use html_css_parser::css::{AttrCase, AttrMatcher};
+
+    let attribute = |name: &str, matcher: Option<AttrMatcher>, value: Option<&str>| Selector::Attribute {
+        name: name.to_string(),
+        matcher,
+        value: value.map(str::to_string),
+        case: None,
+    };
+
+    assert_eq!(parse_selector("[disabled]"), attribute("disabled", None, None));
+    assert_eq!(parse_selector("[class~=\"btn\"]"), attribute("class", Some(AttrMatcher::Includes), Some("btn")));
+    assert_eq!(parse_selector("[href^=\"https\"]"), attribute("href", Some(AttrMatcher::Prefix), Some("https")));
+    assert_eq!(parse_selector("[ lang |= en ]"), attribute("lang", Some(AttrMatcher::DashMatch), Some("en")));
+    assert_eq!(parse_selector("[src$='.png']"), attribute("src", Some(AttrMatcher::Suffix), Some(".png")));
+    assert_eq!(parse_selector("[title*=hello]"), attribute("title", Some(AttrMatcher::Substring), Some("hello")));
+
+    assert_eq!(
+        parse_selector("input[type=\"text\"]"),
+        Selector::Compound(vec![Selector::Type("input".to_string()), attribute("type", Some(AttrMatcher::Equals), Some("text"))])
+    );
+    assert!(matches!(parse_selector("[type=\"x\" i]"), Selector::Attribute { case: Some(AttrCase::Insensitive), .. }));
+
+    let rules = CssParser::new("a[target] { color: red } [data-x=\"1\"] { color: blue }").parse();
+    assert_eq!(rules.len(), 2);
+    assert!(try_parse_selector_list("[=x]").is_err());
+    assert!(try_parse_selector_list("[href^=]").is_err());
+}
+
+#[test]
+fn test_pseudo_selectors() {
+    assert_eq!(
+        parse_selector("a:hover"),
+        Selector::Compound(vec![Selector::Type("a".to_string()), Selector::PseudoClass("hover".to_string(), None)])
+    );
+    assert_eq!(parse_selector(":hover"), Selector::PseudoClass("hover".to_string(), None));
+    assert_eq!(
+        parse_selector("li:nth-child(odd)"),
+        Selector::Compound(vec![
+            Selector::Type("li".to_string()),
+            Selector::PseudoClass("nth-child".to_string(), Some("odd".to_string())),
+        ])
+    );
+    assert_eq!(parse_selector(":nth-child(2n+1)").to_string(), ":nth-child(2n+1)");
+    assert_eq!(
+        parse_selector("p::after"),
+        Selector::Compound(vec![Selector::Type("p".to_string()), Selector::PseudoElement("after".to_string())])
+    );
+    // CSS2 pseudo-elements keep working with a single colon.
+    assert_eq!(parse_selector(":first-line"), Selector::PseudoElement("first-line".to_string()));
+
+    let Selector::Descendant(_, right) = parse_selector(".nav-menu a:hover") else {
+        panic!("expected a descendant selector");
+    };
+    assert_eq!(right.to_string(), "a:hover");
+}
+
+#[test]
+fn test_rule_to_css() {
+    use html_css_parser::css::serialize_rules;
+
+    let minify = CssWriteOptions::default().minify(true);
+    let rules = CssParser::new("a,b{color:red !important}").parse();
+    assert_eq!(rules[0].to_css_with(&minify), "a,b{color:red !important}");
+    assert_eq!(rules[0].to_css(), "a, b {\n  color: red !important;\n}");
+
+    let css = "ul > li.item, #nav :hover { margin: 0 auto; padding: 0 } p {}";
+    let rules = CssParser::new(css).parse();
+    assert_eq!(serialize_rules(&rules, &minify), "ul > li.item,#nav :hover{margin:0 auto;padding:0}p{}");
+    assert_eq!(
+        serialize_rules(&rules, &CssWriteOptions::default()),
+        "ul > li.item, #nav :hover {\n  margin: 0 auto;\n  padding: 0;\n}\n\np {}"
+    );
+    for options in [&minify, &CssWriteOptions::default()] {
+        assert_eq!(CssParser::new(&serialize_rules(&rules, options)).parse(), rules);
+    }
+
+    let css = "@import \"a.css\"; @media screen and (min-width: 600px) { .a { color: red } } @font-face { font-family: X }";
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    assert_eq!(
+        stylesheet.to_css(),
+        "@import \"a.css\";\n\n@media screen and (min-width: 600px) {\n  .a {\n    color: red;\n  }\n}\n\n@font-face {\n  font-family: X;\n}"
+    );
+    let minified = stylesheet.to_css_with(&minify);
+    assert_eq!(minified, "@import \"a.css\";@media screen and (min-width: 600px){.a{color:red}}@font-face{font-family:X}");
+    assert_eq!(CssParser::new(&minified).parse_stylesheet(), stylesheet);
+}
+
+#[test]
+fn test_css_rule_and_token_spans() {
+    use html_css_parser::Span;
+
+    let css = "a {\n  color: red;\n}\n\n/* note */\n.b, .c {\r\n    margin: 0 auto !important;\r\n}";
+    let rules = CssParser::new(css).parse();
+    assert_eq!(rules[0].span, Some(Span::new(0, 19, 1, 1)));
+
+    let span = rules[1].span.unwrap();
+    assert_eq!((span.line, span.column), (6, 1));
+    assert_eq!(&css[span.start..span.end], ".b, .c {\r\n    margin: 0 auto !important;\r\n}");
+
+    let declaration = &rules[1].declarations[0];
+    assert_eq!((declaration.span.line, declaration.span.column), (7, 5));
+    assert_eq!(&css[declaration.span.start..declaration.span.end], "margin: 0 auto !important");
+
+    let tokens: Vec<(CssToken, Span)> = CssTokenizer::new("a{\n  b: 1é;}").spanned().collect();
+    assert_eq!(tokens[0], (CssToken::Ident("a".to_string()), Span::new(0, 1, 1, 1)));
+    assert_eq!(tokens[4], (CssToken::Colon, Span::new(6, 7, 2, 4)));
+    assert_eq!(tokens[6], (CssToken::Dimension { value: 1.0, unit: "é".to_string() }, Span::new(8, 11, 2, 6)));
+    assert_eq!(tokens[7].1, Span::new(11, 12, 2, 8));
+
+    // Spans don't affect equality.
+    assert_eq!(CssParser::new("a{color:red}").parse(), CssParser::new("\n\na { color: red }").parse());
+}
+
+#[test]
+fn test_query_selector() {
+    use html_css_parser::html::{Element, HtmlParser, Node};
+
+    let html = r#"
+        <div class="features">
+            <h3>Intro</h3>
+            <div class="feature"><h3>Fast</h3><p>One</p></div>
+            <div class="feature wide"><section><h3>Small</h3></section><p>Two</p></div>
+            <div class="feature"><h3 id="safe">Safe</h3><p>Three</p><p>Four</p></div>
+        </div>
+    "#;
+    let Some(Node::Element(root)) = HtmlParser::new(html).parse_document() else {
+        panic!("expected a root element");
+    };
+    let select = |root: &Element, selectors: &str| -> Vec<String> {
+        root.query_selector_all(selectors).unwrap().iter().map(|element| element.text_content()).collect()
+    };
+
+    assert_eq!(select(&root, ".feature h3"), ["Fast", "Small", "Safe"]);
+    assert_eq!(select(&root, ".feature > h3"), ["Fast", "Safe"]);
+    assert_eq!(select(&root, ".features > h3"), ["Intro"]);
+    assert_eq!(select(&root, "h3 + p"), ["One", "Three"]);
+    assert_eq!(select(&root, "h3 ~ p"), ["One", "Three", "Four"]);
+    assert_eq!(select(&root, "section + p"), ["Two"]);
+    assert_eq!(select(&root, ".wide"), ["SmallTwo"]);
+    assert_eq!(select(&root, "div.feature #safe"), ["Safe"]);
+    assert_eq!(select(&root, "*").len(), 13);
+    assert!(select(&root, "p h3").is_empty());
+
+    // The element itself can match, but nothing above it is considered.
+    assert_eq!(select(&root, ".features").len(), 1);
+    let Node::Element(feature) = &root.children[1] else {
+        panic!("expected an element");
+    };
+    assert!(select(feature, ".features h3").is_empty());
+    assert_eq!(select(feature, "div h3"), ["Fast"]);
+
+    assert_eq!(root.query_selector("p").unwrap().map(Element::text_content).as_deref(), Some("One"));
+    assert!(root.query_selector("table").unwrap().is_none());
+    let selector = &parse_selector("p");
+    assert_eq!(root.first_matching(selector).map(Element::text_content).as_deref(), Some("One"));
+    assert_eq!(root.all_matching(selector).len(), 4);
+
+    // Lists match in document order, each element once.
+    assert_eq!(select(&root, "p, #safe, .feature > h3"), ["Fast", "One", "Two", "Safe", "Three", "Four"]);
+    assert_eq!(root.query_selector("table, p, h3").unwrap().map(Element::text_content).as_deref(), Some("Intro"));
+
+    for invalid in ["", "div,", "h3 >", ".", "p { color: red }"] {
+        assert!(
+            matches!(root.query_selector_all(invalid), Err(CssError::InvalidSelector { .. })),
+            "{:?}",
+            invalid
+        );
+        assert!(root.query_selector(invalid).is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn test_query_selector_deep_descendants() {
+    use html_css_parser::html::{HtmlParser, Node};
+
+    // Without `p` above them, every choice of twelve ancestors for the divs
+    // fails, and there are more than a billion of them.
+    let html = format!("{}<span></span>{}", "<div>".repeat(36), "</div>".repeat(36));
+    let Some(Node::Element(root)) = HtmlParser::new(&html).parse_document() else {
+        panic!("expected a root element");
+    };
+    let selector = format!("p{}", " div".repeat(12));
+    assert!(root.query_selector_all(&selector).unwrap().is_empty());
+
+    let selector = format!("{} span", "div ".repeat(12));
+    assert_eq!(root.query_selector_all(&selector).unwrap().len(), 1);
+    assert_eq!(root.query_selector_all(&"div ".repeat(12)).unwrap().len(), 25);
+}
+
+#[test]
+fn test_query_selector_example_document() {
+    use html_css_parser::html::{HtmlParser, Node};
+
+    let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/html_parser.rs")).unwrap();
+    let html = &source[source.find("<!DOCTYPE").unwrap()..source.find("</html>").unwrap()];
+    let Some(Node::Element(root)) = HtmlParser::new(html).parse_document() else {
+        panic!("expected a root element");
+    };
+
+    let headings: Vec<String> = root
+        .query_selector_all("div.feature h3")
+        .unwrap()
+        .iter()
+        .map(|element| element.text_content())
+        .collect();
+    assert_eq!(headings, ["Fast", "Accurate", "Memory Efficient"]);
+    assert_eq!(root.query_selector("#main-title").unwrap().unwrap().text_content(), "Welcome to HTML Parser");
+}
+
+#[test]
+fn test_css_parse_with_diagnostics() {
+    let (rules, errors) = CssParser::new("a { color red }").parse_with_diagnostics();
+    assert_eq!(rules.len(), 1);
+    assert!(rules[0].declarations.is_empty());
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], CssError::MissingColon { property, .. } if property == "color"));
+    assert_eq!((errors[0].span().line, errors[0].span().column), (1, 11));
+    assert!(errors[0].to_string().starts_with("missing colon"));
+
+    let css = "p < q { color: red }\nh1 { margin: ; color: blue }\n} div { width: 1px";
+    let (rules, errors) = CssParser::new(css).parse_with_diagnostics();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[1].declarations.len(), 1);
+    let kinds: Vec<(&str, usize)> = errors
+        .iter()
+        .map(|error| {
+            let kind = match error {
+                CssError::InvalidSelector { .. } => "selector",
+                CssError::InvalidDeclaration { .. } => "declaration",
+                CssError::InvalidToken { .. } => "token",
+                CssError::UnclosedBlock { .. } => "unclosed",
+                _ => "other",
+            };
+            (kind, error.span().line)
+        })
+        .collect();
+    assert_eq!(kinds, [("selector", 1), ("declaration", 2), ("token", 3), ("unclosed", 3)]);
+    assert!(matches!(&errors[0], CssError::InvalidSelector { found, .. } if found == "<"));
+
+    // The lenient parse is unchanged.
+    assert_eq!(CssParser::new(css).parse(), rules);
+    let (_, errors) = CssParser::new("a { color: red } @media print { b { color: blue } }").parse_with_diagnostics();
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_css_deeply_nested_at_rules() {
+    use html_css_parser::css::MediaEnv;
+
+    // Unclosed, so every block runs to the end of input.
+    let css = "@media screen{".repeat(100_000);
+    let mut parser = CssParser::new(&css);
+    let (rules, errors) = parser.parse_with_diagnostics();
+    assert!(rules.is_empty());
+    assert_eq!(parser.stats().max_depth, 256);
+    let too_deep: Vec<&CssError> =
+        errors.iter().filter(|error| matches!(error, CssError::NestingTooDeep { .. })).collect();
+    assert_eq!(too_deep.len(), 1);
+    assert_eq!((too_deep[0].span().line, too_deep[0].span().column), (1, 256 * 14 + 14));
+    assert!(too_deep[0].to_string().starts_with("block nested too deeply"));
+    assert_eq!(errors.len(), 257);
+
+    // Only the block past the limit is dropped, and parsing carries on after it.
+    let css = format!("{}a{{color:red}}{} b{{color:blue}}", "@media screen{".repeat(300), "}".repeat(300));
+    let (rules, errors) = CssParser::new(&css).parse_with_diagnostics();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0], CssError::NestingTooDeep { .. }));
+    assert_eq!(rules.len(), 1);
+    let stylesheet = CssParser::new(&css).parse_stylesheet();
+    assert_eq!(stylesheet.rules_for(&MediaEnv::default()).len(), 1);
+}
+
+#[test]
+fn test_css_tokenizer_checkpoint() {
+    let css = ".café::before { content: \"☃\"; } /* ü */ a{b:c}";
+    let expected: Vec<(CssToken, html_css_parser::Span)> = CssTokenizer::new(css).spanned().collect();
+
+    for split in 0..=expected.len() {
+        let mut tokenizer = CssTokenizer::new(css);
+        for _ in 0..split {
+            tokenizer.next_token_spanned();
+        }
+        let checkpoint = tokenizer.checkpoint();
+
+        let rest: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_spanned()).collect();
+        assert_eq!(rest, expected[split..]);
+        assert_eq!(tokenizer.peek_token(), None);
+        tokenizer.restore(checkpoint);
+
+        assert_eq!(tokenizer.peek_token(), expected.get(split).map(|(token, _)| token.clone()));
+        let rest: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_spanned()).collect();
+        assert_eq!(rest, expected[split..]);
+    }
+}
+
+#[test]
+fn test_element_matches() {
+    use html_css_parser::html::{Element, HtmlParser, Node};
+
+    let html = r#"<DIV class="foo  bar" id="main"><section><P class="x">a</P></section><h2>t</h2><p id="b">b</p></DIV>"#;
+    let Some(Node::Element(div)) = HtmlParser::new(html).parse().into_iter().next() else {
+        panic!("expected an element");
+    };
+    let elements: Vec<&Element> = div.descendants().collect();
+    let (section, p, h2, last) = (elements[0], elements[1], elements[2], elements[3]);
+
+    assert!(div.matches(&parse_selector("div")));
+    assert!(div.matches(&parse_selector("*")));
+    assert!(div.matches(&parse_selector(".bar")));
+    assert!(div.matches(&parse_selector("div.foo.bar#main")));
+    assert!(!div.matches(&parse_selector(".fo")));
+    assert!(!div.matches(&parse_selector("#MAIN")));
+    assert!(p.matches(&parse_selector("p.x")));
+
+    // Without ancestors, combinators can't match.
+    assert!(!p.matches(&parse_selector("div p")));
+    assert!(p.matches_with_ancestors(&[&div, section], &parse_selector("div p")));
+    assert!(p.matches_with_ancestors(&[&div, section], &parse_selector("section > p")));
+    assert!(!p.matches_with_ancestors(&[&div, section], &parse_selector("div > p")));
+    assert!(p.matches_with_ancestors(&[&div, section], &parse_selector("#main > section > .x")));
+    assert!(!p.matches_with_ancestors(&[&div, section], &parse_selector("h2 p")));
+    assert!(last.matches_with_ancestors(&[&div], &parse_selector("h2 + p")));
+    assert!(last.matches_with_ancestors(&[&div], &parse_selector("section ~ #b")));
+    assert!(!h2.matches_with_ancestors(&[&div], &parse_selector("p ~ h2")));
+    assert!(h2.matches_with_ancestors(&[], &parse_selector("h2")));
+
+    assert_eq!(div.matches_str("div.foo"), Ok(true));
+    assert_eq!(div.matches_str("span, #main"), Ok(true));
+    assert_eq!(div.matches_str("span, .baz"), Ok(false));
+    assert!(matches!(div.matches_str("div."), Err(CssError::InvalidSelector { .. })));
+}
+
+#[test]
+fn test_selector_specificity() {
+    let specificity = |text: &str| parse_selector(text).specificity();
+
+    assert_eq!(specificity("#id"), (1, 0, 0));
+    assert_eq!(specificity(".a.b"), (0, 2, 0));
+    assert_eq!(specificity("div p"), (0, 0, 2));
+    assert_eq!(specificity("*"), (0, 0, 0));
+    assert_eq!(specificity("* > *"), (0, 0, 0));
+    assert_eq!(specificity("a[href]"), (0, 1, 1));
+    assert_eq!(specificity("ul#nav li.active > a:hover"), (1, 2, 3));
+    assert_eq!(specificity("h1 + p ~ span::first-line"), (0, 0, 4));
+    assert_eq!(specificity("p:first-letter"), (0, 0, 2));
+    assert_eq!(specificity("li:nth-child(2n+1)"), (0, 1, 1));
+    assert_eq!(specificity("li:nth-child(2n of .item)"), (0, 2, 1));
+
+    // The most specific argument counts, except in `:where()`.
+    assert_eq!(specificity(":is(#a, .b, c)"), (1, 0, 0));
+    assert_eq!(specificity("p:not(.a.b, div)"), (0, 2, 1));
+    assert_eq!(specificity(":where(#a, .b)"), (0, 0, 0));
+
+    assert!(specificity("#a") > specificity(".a.b.c.d"));
+    assert!(specificity(".a") > specificity("html body div p"));
+
+    // Arguments are weighed through a few levels of nesting, but not
+    // without limit, since each level parses the rest of the selector again.
+    let nested = |levels: usize| format!("{}#a{}", ":is(".repeat(levels), ")".repeat(levels));
+    assert_eq!(specificity(&nested(10)), (1, 0, 0));
+    assert_eq!(specificity(&nested(5_000)), (0, 0, 0));
+}
+
+#[test]
+fn test_resolve_styles() {
+    use html_css_parser::css::{resolve_styles, ComputedStyles, MediaEnv};
+    use html_css_parser::html::{HtmlParser, Node};
+
+    let element = |html: &str| match HtmlParser::new(html).parse().remove(0) {
+        Node::Element(element) => element,
+        other => panic!("expected an element, got {:?}", other),
+    };
+    let p = element(r#"<p id="id" class="a b">x</p>"#);
+    let value = |css: &str, property: &str| {
+        let styles = resolve_styles(&p, &CssParser::new(css).parse());
+        styles.get(property).map(|declaration| declaration.value.clone())
+    };
+
+    // The id wins whatever the order.
+    assert_eq!(value("#id { color: blue } .a { color: red }", "color").as_deref(), Some("blue"));
+    assert_eq!(value(".a { color: red } #id { color: blue }", "color").as_deref(), Some("blue"));
+    assert_eq!(value("p.a { color: red } .a { color: green }", "color").as_deref(), Some("red"));
+    // Equal specificity: the later rule.
+    assert_eq!(value(".a { color: red } .b { color: green }", "color").as_deref(), Some("green"));
+    // A rule counts with its most specific matching selector.
+    assert_eq!(value("#id { color: blue } div, .a.b { color: red }", "color").as_deref(), Some("blue"));
+    assert_eq!(value(".a { color: blue } span, #id { color: red }", "color").as_deref(), Some("red"));
+
+    // `!important` beats specificity.
+    assert_eq!(value(".a { color: red !important } #id { color: blue }", "color").as_deref(), Some("red"));
+    assert_eq!(value(".a { color: red !important } #id { color: blue !important }", "color").as_deref(), Some("blue"));
+    let styles = resolve_styles(&p, &CssParser::new(".a { color: red !important; margin: 0 } #id { color: blue }").parse());
+    assert!(styles["color"].important);
+    assert_eq!(styles["margin"].value, "0");
+    assert_eq!(styles.len(), 2);
+
+    assert!(resolve_styles(&p, &CssParser::new("div { color: red } .c { margin: 0 }").parse()).is_empty());
+
+    // Inline styles win over normal stylesheet declarations only.
+    let styled = element(r#"<p id="id" style="color: green; margin: 1px">x</p>"#);
+    let rules = CssParser::new("#id { color: blue; margin: 0 !important }").parse();
+    let styles = resolve_styles(&styled, &rules);
+    assert_eq!(styles["color"].value, "green");
+    assert_eq!(styles["margin"].value, "0");
+
+    // Computed styles follow the same order.
+    let stylesheet = CssParser::new(".a { color: red } #id { color: blue } .a { color: green }").parse_stylesheet();
+    let styles = ComputedStyles::compute(&Node::Element(p.clone()), &stylesheet, &MediaEnv::default());
+    assert_eq!(styles.get(&[], "color"), Some("blue"));
+}
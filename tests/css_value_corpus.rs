@@ -0,0 +1,184 @@
+use html_css_parser::css::parse_declaration_list;
+
+// Marks an input that must not produce any declaration.
+const INVALID: &str = "<invalid>";
+
+// (input declaration list, expected serialization). Multiple declarations
+// serialize joined by "; ". Add new cases anywhere in the table.
+const CORPUS: &[(&str, &str)] = &[
+    // Whitespace and basic shapes
+    ("color: red", "color: red"),
+    ("color:red", "color: red"),
+    ("color : red ;", "color: red"),
+    ("  color:   red  ", "color: red"),
+    ("color:\tred\n", "color: red"),
+    ("margin: 0 auto", "margin: 0 auto"),
+    ("margin:0    auto", "margin: 0 auto"),
+    ("margin: 0 0 0 0", "margin: 0 0 0 0"),
+    ("counter-reset: a 1 b 2", "counter-reset: a 1 b 2"),
+    ("color: red; color: blue", "color: red; color: blue"),
+    ("color: red;; margin: 0", "color: red; margin: 0"),
+    // Commas
+    ("font-family: \"Helvetica Neue\", Arial, sans-serif", "font-family: \"Helvetica Neue\", Arial, sans-serif"),
+    ("font-family:'Times New Roman',serif", "font-family: \"Times New Roman\",serif"),
+    ("font-family: a,b , c", "font-family: a,b , c"),
+    ("transition: opacity 0.3s ease, transform 0.3s", "transition: opacity 0.3s ease, transform 0.3s"),
+    ("will-change: transform , opacity", "will-change: transform , opacity"),
+    // Functions
+    ("box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1)", "box-shadow: 0 2px 4px rgba(0, 0, 0, 0.1)"),
+    ("color: rgba(0,0,0,.5)", "color: rgba(0,0,0,0.5)"),
+    ("color: rgb( 1 , 2 , 3 )", "color: rgb( 1 , 2 , 3 )"),
+    ("width: calc(100% - 20px)", "width: calc(100% - 20px)"),
+    ("width: calc(1px + min(2px, 3px))", "width: calc(1px + min(2px, 3px))"),
+    ("grid-template-columns: repeat(auto-fit, minmax(300px, 1fr))", "grid-template-columns: repeat(auto-fit, minmax(300px, 1fr))"),
+    ("background: linear-gradient(135deg, #667eea 0%, #764ba2 100%)", "background: linear-gradient(135deg, #667eea 0%, #764ba2 100%)"),
+    ("transform: translate(-50%, -50%) rotate(45deg)", "transform: translate(-50%, -50%) rotate(45deg)"),
+    ("content: attr(data-x)", "content: attr(data-x)"),
+    ("filter: progid:DXImageTransform.Microsoft.gradient(startColorstr='#1e5799')", "filter: progid:DXImageTransform.Microsoft.gradient(startColorstr=\"#1e5799\")"),
+    // Stray and unbalanced closers
+    ("width: rgb(1,2,3))", "width: rgb(1,2,3))"),
+    ("color: rgb(1, 2; margin: 0", "color: rgb(1, 2; margin: 0"),
+    ("color: (a; b); margin: 0", "color: (a; b); margin: 0"),
+    ("x: [a] [b]", "x: [a] [b]"),
+    ("color: red } margin: 0", "color: red; margin: 0"),
+    ("margin: 0; }", "margin: 0"),
+    // URLs
+    ("background: url(\"image.jpg\") no-repeat center", "background: url(\"image.jpg\") no-repeat center"),
+    ("background: url(image.jpg)", "background: url(image.jpg)"),
+    ("background-image: url(data:image/png;base64,AAA)", "background-image: url(data:image/png;base64,AAA)"),
+    ("background:url('a b.png')", "background: url(\"a b.png\")"),
+    ("background: url( spaced.png )", "background: url(spaced.png)"),
+    ("background: url(a\\ b\\).png)", "background: url(a\\ b\\).png)"),
+    ("background: url(a;b.png); color: red", "background: url(a;b.png); color: red"),
+    ("background: url(a b.png); color: red", "color: red"),
+    ("background: url(a\"b.png); color: red", "color: red"),
+    // Strings and escapes
+    ("content: \"\"", "content: \"\""),
+    ("content: \"a\\\"b\"", "content: \"a\\\"b\""),
+    ("content: 'it\\'s'", "content: \"it's\""),
+    ("quotes: '\"' '\"'", "quotes: \"\\\"\" \"\\\"\""),
+    ("content: \"\\201C\"", "content: \"\u{201C}\""),
+    ("content: \"\\26 B\"", "content: \"&B\""),
+    ("content: \"line\\\nbreak\"", "content: \"linebreak\""),
+    ("content: \"tab\\9 x\"", "content: \"tab\\9 x\""),
+    ("content: \"\\0\"", "content: \"\u{FFFD}\""),
+    ("content: \"#\"", "content: \"#\""),
+    ("content: \"\u{1F600}\"", "content: \"\u{1F600}\""),
+    ("content: 'x", "content: \"x\""),
+    ("font-family: Font\\ Name", "font-family: Font\\ Name"),
+    ("font-family: \\66 oo", "font-family: foo"),
+    ("font-family: \u{65E5}\u{672C}\u{8A9E}", "font-family: \u{65E5}\u{672C}\u{8A9E}"),
+    ("--my\\.var: 1", "--my\\.var: 1"),
+    // Bad strings
+    ("content: \"abc\ncolor: red", INVALID),
+    ("content: 'abc\n; margin: 0", "margin: 0"),
+    ("content: \"ok\"; content: \"bad\n; color: red", "content: \"ok\"; color: red"),
+    // unicode-range
+    ("unicode-range: U+0025-00FF", "unicode-range: U+0025-00FF"),
+    ("unicode-range: u+4??", "unicode-range: U+4??"),
+    ("unicode-range: U+0-7F, U+0100-024F", "unicode-range: U+0-7F, U+0100-024F"),
+    ("unicode-range: U+26", "unicode-range: U+26"),
+    ("unicode-range: u+00ff-01ab", "unicode-range: U+00FF-01AB"),
+    // Custom properties
+    ("--brand-color: #667eea", "--brand-color: #667eea"),
+    ("--spacing:  1rem  2rem ", "--spacing: 1rem 2rem"),
+    ("--empty:;", "--empty:"),
+    ("--json: [1, 2, 3]", "--json: [1, 2, 3]"),
+    ("--block: {a: b}", "--block: {a: b}"),
+    ("--Case: 1", "--Case: 1"),
+    ("--x: a !important", "--x: a !important"),
+    ("color: var(--brand-color)", "color: var(--brand-color)"),
+    ("color: var(--x, #fff)", "color: var(--x, #fff)"),
+    // !important placement
+    ("color: red !important", "color: red !important"),
+    ("color: red!important", "color: red !important"),
+    ("color: red ! important", "color: red !important"),
+    ("color: red !IMPORTANT", "color: red !important"),
+    ("color: red !important blue", INVALID),
+    ("color: !important", INVALID),
+    ("color: red !important !important", INVALID),
+    ("color: red !important; margin: 0", "color: red !important; margin: 0"),
+    // Comments
+    ("color: red /* c */", "color: red"),
+    ("margin: 1px/**/2px", "margin: 1px 2px"),
+    ("/* a */ color: red", "color: red"),
+    ("color: /* x */ red", "color: red"),
+    // Malformed declarations and recovery
+    ("color red; margin: 0", "margin: 0"),
+    ("color; margin: 0", "margin: 0"),
+    (": red; margin: 0", "margin: 0"),
+    ("color: ; margin: 0", "margin: 0"),
+    ("color:", INVALID),
+    ("", INVALID),
+    (";;;", INVALID),
+    ("a b: c; d: e", "d: e"),
+    ("1color: red", INVALID),
+    ("\"color\": red", INVALID),
+    // Numbers, units, hashes and delimiters
+    ("width: 100PX", "width: 100PX"),
+    ("opacity: 0.50", "opacity: 0.5"),
+    ("opacity: .5", "opacity: 0.5"),
+    ("line-height: 1.60", "line-height: 1.6"),
+    ("width: 50%", "width: 50%"),
+    ("z-index: 10", "z-index: 10"),
+    ("margin: -5px", "margin: -5px"),
+    ("flex: 1 1 0%", "flex: 1 1 0%"),
+    ("-webkit-transition: all .3s ease", "-webkit-transition: all 0.3s ease"),
+    ("-moz-box-sizing: border-box", "-moz-box-sizing: border-box"),
+    ("color: #FFF", "color: #FFF"),
+    ("color: #abcdef80", "color: #abcdef80"),
+    ("content: #", "content: #"),
+    ("font: 12px/1.5 serif", "font: 12px/1.5 serif"),
+    ("grid-area: 1 / 2 / 3 / 4", "grid-area: 1 / 2 / 3 / 4"),
+];
+
+fn serialize(input: &str) -> String {
+    let declarations = parse_declaration_list(input);
+    if declarations.is_empty() {
+        return INVALID.to_string();
+    }
+
+    declarations
+        .iter()
+        .map(|declaration| declaration.to_css())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[test]
+fn test_css_value_corpus() {
+    let mut failures = Vec::new();
+
+    for (input, expected) in CORPUS {
+        let actual = serialize(input);
+        if actual != *expected {
+            failures.push(format!("  input:    {:?}\n  expected: {:?}\n  actual:   {:?}", input, expected, actual));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} corpus cases failed:\n{}",
+        failures.len(),
+        CORPUS.len(),
+        failures.join("\n\n")
+    );
+}
+
+#[test]
+fn test_css_value_corpus_is_stable() {
+    // Serialized output must parse back to the same serialization.
+    let mut failures = Vec::new();
+
+    for (input, expected) in CORPUS {
+        if *expected == INVALID {
+            continue;
+        }
+        let reserialized = serialize(expected);
+        if reserialized != *expected {
+            failures.push(format!("  input:    {:?}\n  first:    {:?}\n  second:   {:?}", input, expected, reserialized));
+        }
+    }
+
+    assert!(failures.is_empty(), "unstable serializations:\n{}", failures.join("\n\n"));
+}
@@ -0,0 +1,81 @@
+use html_css_parser::css::value::{canonicalize, equivalent, parse_component_values, EquivalenceOptions};
+use html_css_parser::css::CssParser;
+
+const PLAIN: EquivalenceOptions = EquivalenceOptions::new();
+const COLORS: EquivalenceOptions = EquivalenceOptions::new().color_keywords(true);
+const ZEROS: EquivalenceOptions = EquivalenceOptions::new().zero_units(true);
+const ALL: EquivalenceOptions = EquivalenceOptions::new().color_keywords(true).zero_units(true);
+
+// (a, b, options, expected equivalence)
+const PAIRS: &[(&str, &str, &EquivalenceOptions, bool)] = &[
+    // Canonical form alone
+    ("red", "red", &PLAIN, true),
+    ("0 auto", "0   auto", &PLAIN, true),
+    ("a,b", "a , b", &PLAIN, true),
+    ("rgb( 1 , 2 , 3 )", "rgb(1, 2, 3)", &PLAIN, true),
+    ("RGB(1,2,3)", "rgb(1,2,3)", &PLAIN, true),
+    ("10PX", "10px", &PLAIN, true),
+    ("1.50em", "1.5em", &PLAIN, true),
+    (".5", "0.5", &PLAIN, true),
+    ("#FFF", "#ffffff", &PLAIN, true),
+    ("#AABBCCDD", "#abcd", &PLAIN, true),
+    ("a/**/b", "a b", &PLAIN, true),
+    ("url( a.png )", "URL(\"a.png\")", &PLAIN, true),
+    ("Arial", "arial", &PLAIN, false),
+    ("#fff", "white", &PLAIN, false),
+    ("0px", "0", &PLAIN, false),
+    ("#abcdef", "#abc", &PLAIN, false),
+    ("1px 2px", "1px, 2px", &PLAIN, false),
+    ("url(a.png)", "url(A.png)", &PLAIN, false),
+    // Color equivalence
+    ("red", "#f00", &COLORS, true),
+    ("RED", "#FF0000", &COLORS, true),
+    ("rgb(255, 0, 0)", "red", &COLORS, true),
+    ("rgb(100%, 0%, 0%)", "red", &COLORS, true),
+    ("rgba(0, 0, 0, 0)", "transparent", &COLORS, true),
+    ("rgb(0 0 0 / 50%)", "rgba(0,0,0,.5)", &COLORS, true),
+    ("1px solid white", "1px solid #fff", &COLORS, true),
+    ("red", "#f01", &COLORS, false),
+    ("rgba(255, 0, 0, 0.5)", "red", &COLORS, false),
+    ("0px", "0", &COLORS, false),
+    // Zero-unit equivalence
+    ("0px", "0", &ZEROS, true),
+    ("0 0em", "0px 0", &ZEROS, true),
+    ("0s", "0", &ZEROS, false),
+    ("0%", "0", &ZEROS, false),
+    ("1px", "1", &ZEROS, false),
+    ("red", "#f00", &ZEROS, false),
+    // Both
+    ("0px solid black", "0 solid #000", &ALL, true),
+    ("0px solid black", "0 solid #001", &ALL, false),
+];
+
+#[test]
+fn test_value_equivalence_table() {
+    let mut failures = Vec::new();
+
+    for (a, b, options, expected) in PAIRS {
+        let actual = equivalent(&parse_component_values(a), &parse_component_values(b), options);
+        if actual != *expected {
+            failures.push(format!("  {:?} vs {:?} with {:?}: expected {}", a, b, options, expected));
+        }
+    }
+
+    assert!(failures.is_empty(), "equivalence mismatches:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_canonicalize() {
+    let values = parse_component_values("  1.50PX   Rgba( 0 ,0,0 , .1 ) #FFFFFF  ");
+    assert_eq!(canonicalize(&values), "1.5px rgba(0, 0, 0, 0.1) #fff");
+}
+
+#[test]
+fn test_merge_dedup_uses_canonical_values() {
+    let first = CssParser::new("a { color: #FFF; margin: 0  auto; }").parse_stylesheet();
+    let second = CssParser::new("a { color: #ffffff; margin: 0 auto; }").parse_stylesheet();
+
+    let merged = first.merge_dedup(second);
+    assert_eq!(merged.rules.len(), 1);
+    assert_eq!(merged.style_rules().next().unwrap().declarations[0].value, "#ffffff");
+}
@@ -0,0 +1,131 @@
+use html_css_parser::css::{self, CssParser};
+use html_css_parser::dot::DotOptions;
+use html_css_parser::html::{self, HtmlParser};
+
+// The document from the CLI's `demo` command.
+const DEMO_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+    <title>Demo Page</title>
+</head>
+<body>
+    <div class="container">
+        <h1 id="title">Hello World</h1>
+        <p>This is a <strong>demo</strong> page.</p>
+    </div>
+</body>
+</html>"##;
+
+fn demo_dot(options: &DotOptions) -> String {
+    let document = HtmlParser::new(DEMO_HTML).parse_document().unwrap();
+    html::export::to_dot(&document, options)
+}
+
+// Braces balance outside quoted labels and every label is a closed string.
+fn assert_valid_dot(dot: &str) {
+    assert!(dot.starts_with("digraph "), "{}", dot);
+    assert!(dot.ends_with("}\n"), "{}", dot);
+
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut chars = dot.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                assert!(depth >= 0, "{}", dot);
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(depth, 0, "{}", dot);
+    assert!(!in_string, "{}", dot);
+
+    for line in dot.lines().filter(|line| line.contains("[label=")) {
+        assert!(line.contains("[label=\""), "{}", line);
+        assert!(line.ends_with("];"), "{}", line);
+    }
+}
+
+#[test]
+fn test_html_dot_demo_document() {
+    let dot = demo_dot(&DotOptions::default());
+    assert_valid_dot(&dot);
+
+    let head: Vec<&str> = dot.lines().take(10).collect();
+    assert_eq!(
+        head,
+        [
+            "digraph html {",
+            "  node [shape=box, fontname=\"monospace\"];",
+            "  n0 [label=\"html\"];",
+            "  n1 [label=\"head\"];",
+            "  n0 -> n1;",
+            "  n2 [label=\"title\"];",
+            "  n1 -> n2;",
+            "  n3 [label=\"Demo Page\", shape=plaintext];",
+            "  n2 -> n3;",
+            "  n4 [label=\"body\"];",
+        ]
+    );
+    assert!(dot.contains("[label=\"div.container\"]"));
+    assert!(dot.contains("[label=\"h1#title\"]"));
+}
+
+#[test]
+fn test_html_dot_options() {
+    let dot = demo_dot(&DotOptions::default().include_text(false));
+    assert_valid_dot(&dot);
+    assert!(!dot.contains("plaintext"));
+
+    let dot = demo_dot(&DotOptions::default().max_depth(1));
+    assert_valid_dot(&dot);
+    assert_eq!(dot.matches("[label=").count(), 3);
+
+    let dot = demo_dot(&DotOptions::default().max_label_len(8));
+    assert!(dot.contains("[label=\"div.c...\"]"));
+}
+
+#[test]
+fn test_html_dot_escapes_labels() {
+    let nodes = HtmlParser::new(r#"<p>say "hi" \ bye<!-- a
+b --></p>"#).parse();
+    let dot = html::export::to_dot(&nodes[0], &DotOptions::default());
+    assert_valid_dot(&dot);
+    assert!(dot.contains(r#"[label="say \"hi\" \\ bye", shape=plaintext]"#));
+    assert!(dot.contains(r#"[label="<!-- a\nb -->", shape=note]"#));
+}
+
+#[test]
+fn test_css_dot_at_rule_nesting() {
+    let stylesheet = CssParser::new(
+        "body { margin: 0; } @media screen { @supports (display: grid) { .grid { display: grid; gap: 1em; } } } @import \"a.css\";",
+    )
+    .parse_stylesheet();
+    let dot = css::export::to_dot(&stylesheet);
+    assert_valid_dot(&dot);
+
+    let lines: Vec<&str> = dot.lines().skip(2).collect();
+    assert_eq!(
+        lines,
+        [
+            "  n0 [label=\"stylesheet\", shape=ellipse];",
+            "  n1 [label=\"body (1 declaration)\"];",
+            "  n0 -> n1;",
+            "  n2 [label=\"@media screen\", shape=folder];",
+            "  n0 -> n2;",
+            "  n3 [label=\"@supports (display: grid)\", shape=folder];",
+            "  n2 -> n3;",
+            "  n4 [label=\".grid (2 declarations)\"];",
+            "  n3 -> n4;",
+            "  n5 [label=\"@import \\\"a.css\\\"\", shape=folder];",
+            "  n0 -> n5;",
+            "}",
+        ]
+    );
+}
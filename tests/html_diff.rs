@@ -0,0 +1,197 @@
+use html_css_parser::html::{diff_patch, HtmlError, HtmlParseOptions, HtmlParser, NodeData, PatchOp, Tree};
+
+const BASE: &str = r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="big">Hello</h1>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <div class="aside"><p>Remove <em>me</em></p><!-- note --></div>
+  <p>Footer</p>
+</body></html>"#;
+
+// Edits of BASE, each with the number of operations its patch should need.
+const SCENARIOS: &[(&str, &str, usize)] = &[
+    ("unchanged", BASE, 0),
+    ("text edit", r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="big">Hello, world</h1>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <div class="aside"><p>Remove <em>me</em></p><!-- note --></div>
+  <p>Footer</p>
+</body></html>"#, 1),
+    ("attribute change", r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="small" lang="en">Hello</h1>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <div><p>Remove <em>me</em></p><!-- note --></div>
+  <p>Footer</p>
+</body></html>"#, 3),
+    ("sibling insertion", r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="big">Hello</h1>
+  <ul id="items"><li>One</li><li>One and a half</li><li>Two</li><li>Three</li></ul>
+  <div class="aside"><p>Remove <em>me</em></p><!-- note --></div>
+  <p>Footer</p>
+</body></html>"#, 1),
+    ("subtree removal", r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="big">Hello</h1>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <p>Footer</p>
+</body></html>"#, 1),
+    ("comment edit", r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="big">Hello</h1>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <div class="aside"><p>Remove <em>me</em></p><!-- changed --></div>
+  <p>Footer</p>
+</body></html>"#, 1),
+    ("keyed move", r#"<html><head><title>Preview</title></head><body>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <h1 id="title" class="big">Hello</h1>
+  <div class="aside"><p>Remove <em>me</em></p><!-- note --></div>
+  <p>Footer</p>
+</body></html>"#, 2),
+    ("replaced element", r#"<html><head><title>Preview</title></head><body>
+  <h1 id="title" class="big">Hello</h1>
+  <ul id="items"><li>One</li><li>Two</li><li>Three</li></ul>
+  <section><p>New</p></section>
+  <p>Footer</p>
+</body></html>"#, 2),
+    ("new document", "<p>Something else entirely</p>", 2),
+];
+
+#[test]
+fn test_patch_applies_to_old_tree() {
+    for &(name, edited, expected_ops) in SCENARIOS {
+        let mut old = Tree::parse(BASE);
+        let new = Tree::parse(edited);
+
+        let ops = diff_patch(&old, &new);
+        assert_eq!(ops.len(), expected_ops, "{}: {:#?}", name, ops);
+
+        old.apply(&ops).unwrap_or_else(|err| panic!("{}: {}", name, err));
+        assert!(old == new, "{}: {:#?}", name, old.to_nodes());
+        assert_eq!(diff_patch(&old, &new), Vec::new(), "{}", name);
+    }
+}
+
+// (old, new) pairs parsed keeping whitespace, so whitespace-only text nodes
+// are inserted and removed too.
+const WHITESPACE_SCENARIOS: &[(&str, &str)] = &[
+    ("<p>a</p>", "<p>a</p> <p>b</p>"),
+    ("<p>a</p> <p>b</p>", "<p>a</p>"),
+    ("<ul><li>One</li></ul>", "<ul>\n  <li>One</li>\n  <li>Two</li>\n</ul>"),
+    ("<p>a <b>b</b></p>", "<p> a  <b> b </b> </p>\n"),
+    (BASE, SCENARIOS[3].1),
+];
+
+#[test]
+fn test_patch_keeps_whitespace() {
+    let parse = |html: &str| HtmlParser::with_options(html, HtmlParseOptions::default().trim_text(false)).parse_tree();
+
+    for &(old_html, new_html) in WHITESPACE_SCENARIOS {
+        let mut old = parse(old_html);
+        let new = parse(new_html);
+
+        let ops = diff_patch(&old, &new);
+        old.apply(&ops).unwrap_or_else(|err| panic!("{:?}: {}: {:#?}", new_html, err, ops));
+        assert!(old == new, "{:?}: {:#?}", new_html, old.to_nodes());
+        assert_eq!(diff_patch(&old, &new), Vec::new(), "{:?}", new_html);
+    }
+
+    let ops = diff_patch(&parse("<p>a</p>"), &parse("<p>a</p> <p>b</p>"));
+    assert!(matches!(&ops[0], PatchOp::InsertNode(_, 1, html) if html == " "), "{:?}", ops);
+}
+
+#[test]
+fn test_patch_ops() {
+    let old = Tree::parse(BASE);
+    let new = Tree::parse(SCENARIOS[1].1);
+    let ops = diff_patch(&old, &new);
+
+    let PatchOp::ReplaceText(id, text) = &ops[0] else {
+        panic!("expected a text replacement: {:?}", ops);
+    };
+    assert_eq!(text, "Hello, world");
+    assert_eq!(old.get(*id), Some(&NodeData::Text("Hello".to_string())));
+    let h1 = old.parent(*id).unwrap();
+    assert!(matches!(old.get(h1), Some(NodeData::Element { tag_name, .. }) if tag_name == "h1"));
+
+    let new = Tree::parse(SCENARIOS[3].1);
+    let ops = diff_patch(&old, &new);
+    let PatchOp::InsertNode(parent, index, html) = &ops[0] else {
+        panic!("expected an insertion: {:?}", ops);
+    };
+    assert_eq!((*index, html.as_str()), (1, "<li>One and a half</li>"));
+    assert_eq!(old.children(*parent).len(), 3);
+}
+
+#[test]
+fn test_patch_stale_ids() {
+    let mut tree = Tree::parse(BASE);
+    let new = Tree::parse(SCENARIOS[4].1);
+    let ops = diff_patch(&tree, &new);
+    tree.apply(&ops).unwrap();
+
+    // The removed node is gone, so the same patch no longer applies.
+    assert_eq!(tree.apply(&ops), Err(HtmlError::PatchFailed { index: 0 }));
+    assert_eq!(tree.apply(&[PatchOp::RemoveNode(tree.root())]), Err(HtmlError::PatchFailed { index: 0 }));
+}
+
+#[test]
+fn test_tree_navigation() {
+    use html_css_parser::css::try_parse_selector_list;
+    use html_css_parser::html::HtmlParser;
+
+    let tree = HtmlParser::new(BASE).parse_tree();
+    assert_eq!(tree, Tree::new(HtmlParser::new(BASE).parse()));
+    assert_eq!(tree.to_nodes(), HtmlParser::new(BASE).parse());
+
+    let find = |tag: &str| {
+        let mut pending = vec![tree.root()];
+        while let Some(id) = pending.pop() {
+            if tree.tag_name(id) == Some(tag) {
+                return id;
+            }
+            pending.extend(tree.children(id).iter().rev());
+        }
+        panic!("no <{}>", tag);
+    };
+
+    let html = find("html");
+    assert_eq!(tree.parent(html), Some(tree.root()));
+    assert_eq!(tree.parent(tree.root()), None);
+
+    let ul = find("ul");
+    let items = tree.children(ul);
+    assert_eq!(items.len(), 3);
+    assert_eq!(tree.next_sibling(items[0]), Some(items[1]));
+    assert_eq!(tree.prev_sibling(items[1]), Some(items[0]));
+    assert_eq!(tree.prev_sibling(items[0]), None);
+    assert_eq!(tree.next_sibling(items[2]), None);
+    assert_eq!(tree.text_content(items[1]), "Two");
+    assert_eq!(tree.text_content(ul), "OneTwoThree");
+    assert_eq!(tree.attribute(ul, "ID"), Some("items"));
+    assert_eq!(tree.attribute(ul, "class"), None);
+
+    let body = find("body");
+    let ancestors: Vec<Option<&str>> = tree.ancestors(items[0]).map(|id| tree.tag_name(id)).collect();
+    assert_eq!(ancestors, [Some("ul"), Some("body"), Some("html"), None]);
+
+    // The previous sibling heading of the list.
+    let heading = std::iter::successors(tree.prev_sibling(ul), |&id| tree.prev_sibling(id))
+        .find(|&id| tree.tag_name(id) == Some("h1"));
+    assert_eq!(heading.map(|id| tree.text_content(id)).as_deref(), Some("Hello"));
+
+    let selector = |text: &str| try_parse_selector_list(text).unwrap().remove(0);
+    let em = find("em");
+    let em_text = tree.children(em)[0];
+    assert_eq!(tree.closest(em_text, &selector("div")), Some(find("div")));
+    assert_eq!(tree.closest(em, &selector("em")), Some(em));
+    assert_eq!(tree.closest(em, &selector(".aside > p")), tree.parent(em));
+    assert_eq!(tree.closest(em, &selector("body > *")), Some(find("div")));
+    assert_eq!(tree.closest(em, &selector("ul ~ div")), Some(find("div")));
+    assert_eq!(tree.closest(em, &selector("ul")), None);
+    assert_eq!(tree.closest(items[2], &selector("body")), Some(body));
+    assert_eq!(tree.closest(tree.root(), &selector("*")), None);
+
+    // Text split by dropped markup is one node, as in `parse`.
+    let tree = HtmlParser::new("a</b>b<p>x</i>y</p>").parse_tree();
+    assert_eq!(tree.children(tree.root()).len(), 2);
+    assert_eq!(tree.get(tree.children(tree.root())[0]), Some(&NodeData::Text("ab".to_string())));
+    assert_eq!(tree, Tree::new(HtmlParser::new("a</b>b<p>x</i>y</p>").parse()));
+}
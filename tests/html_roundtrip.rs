@@ -0,0 +1,382 @@
+use html_css_parser::html::{minify, rendered_text, to_html_pretty, AttrValue, Element, HtmlParseOptions, HtmlParser, MinifyOptions, Node, PrettyOptions};
+use std::fs;
+use std::path::Path;
+
+// Inputs that stress escaping and tokenizer edge cases. Add new cases
+// anywhere in the table; every raw string containing markup in tests/,
+// examples/ and benches/ is checked as well.
+const ADVERSARIAL: &[&str] = &[
+    "<p>a < b > c</p>",
+    "<p>1 <2 and 3< 4</p>",
+    "<p>Tom &amp; Jerry & friends</p>",
+    "<p>&lt;b&gt; is not a tag</p>",
+    "<p>x&nbsp;y</p>",
+    r#"<a title="say &quot;hi&quot;" href="?a=1&amp;b=2">x</a>"#,
+    r#"<a title='it"s' data-x="a < b">x</a>"#,
+    "<a href=foo/>x</a>",
+    "<a href=/path/ class=x>x</a>",
+    "<div><!-- a -- b --></div>",
+    "<div><!--a---></div>",
+    "<div><!----></div>",
+    "<div><!--a--!>b<!--[if IE]><p>x</p><![endif]--></div>",
+    "<div><!-- <p>not a tag</p> --></div>",
+    "<script>if (a < b && c > d) { x = '</scrip' + 't>'; }</script>",
+    "<script>document.write('<p>hi</p>')</script><p>after</p>",
+    "<style>div > p { content: \"<\"; } /* </sty */</style>",
+    "<SCRIPT>a < b</SCRIPT>",
+];
+
+fn element(tag_name: &str, attributes: &[(&str, &str)], children: Vec<Node>) -> Node {
+    Node::Element(Element {
+        tag_name: tag_name.to_string(),
+        attributes: attributes.iter().copied().collect(),
+        children,
+        source_span: None,
+    })
+}
+
+fn text(text: &str) -> Node {
+    Node::Text(text.to_string())
+}
+
+// Trees in the shape the parser produces: text is trimmed and never empty,
+// and adjacent text nodes don't occur.
+fn adversarial_trees() -> Vec<Node> {
+    vec![
+        element("p", &[], vec![text("a < b"), element("b", &[], vec![text("<c>")]), text("d & e")]),
+        element("p", &[], vec![text("&amp; is already escaped; &lt;")]),
+        element("a", &[("title", "\"quoted\" & 'single'"), ("href", "a/")], vec![text("x")]),
+        element("a", &[("data-x", "ends with slash/"), ("data-y", "<tag>"), ("data-z", "")], vec![]),
+        element("div", &[], vec![Node::Comment(" a -- b ".to_string()), Node::Comment("-".to_string())]),
+        element("div", &[], vec![Node::Comment("x-".to_string()), Node::Comment("".to_string())]),
+        element("script", &[], vec![text("if (a < b) { s = '</scrip'; t = '<p>'; }")]),
+        element("style", &[], vec![text("a > b { content: '&amp;' }")]),
+        element("ul", &[], vec![element("li", &[], vec![text("1")]), element("li", &[], vec![element("img", &[("src", "x")], vec![])])]),
+    ]
+}
+
+// Raw string literals (`r#"..."#` and `r##"..."##`) that contain markup.
+fn source_fixtures() -> Vec<(String, String)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut fixtures = Vec::new();
+
+    for directory in ["tests", "examples", "benches"] {
+        let Ok(entries) = fs::read_dir(root.join(directory)) else {
+            continue;
+        };
+        let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+        paths.sort();
+
+        for path in paths.into_iter().filter(|path| path.extension().is_some_and(|ext| ext == "rs")) {
+            let source = fs::read_to_string(&path).unwrap();
+            for hashes in ["##", "#"] {
+                let open = format!("r{}\"", hashes);
+                let close = format!("\"{}", hashes);
+                let mut rest = source.as_str();
+                while let Some(start) = rest.find(&open) {
+                    rest = &rest[start + open.len()..];
+                    let Some(end) = rest.find(&close) else {
+                        break;
+                    };
+                    let literal = &rest[..end];
+                    rest = &rest[end + close.len()..];
+
+                    let has_markup = literal
+                        .split('<')
+                        .skip(1)
+                        .any(|after| after.starts_with(|c: char| c.is_ascii_alphabetic()));
+                    if has_markup {
+                        fixtures.push((path.display().to_string(), literal.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    fixtures
+}
+
+fn serialize(nodes: &[Node]) -> String {
+    nodes.iter().map(Node::to_html).collect()
+}
+
+fn check_tree(label: &str, tree: &[Node], failures: &mut Vec<String>) {
+    let html = serialize(tree);
+    let reparsed = HtmlParser::new(&html).parse();
+    if reparsed != tree {
+        failures.push(format!(
+            "  {}\n  serialized: {:?}\n  original:   {:?}\n  reparsed:   {:?}",
+            label, html, tree, reparsed
+        ));
+    }
+}
+
+#[test]
+fn test_html_roundtrip_fixtures() {
+    let fixtures = source_fixtures();
+    assert!(fixtures.len() >= 10, "expected to find the example documents, found {}", fixtures.len());
+
+    let mut failures = Vec::new();
+    for (origin, html) in &fixtures {
+        let tree = HtmlParser::new(html).parse();
+        check_tree(&format!("fixture from {}: {:?}", origin, html), &tree, &mut failures);
+    }
+    for html in ADVERSARIAL {
+        let tree = HtmlParser::new(html).parse();
+        check_tree(&format!("adversarial input {:?}", html), &tree, &mut failures);
+    }
+
+    assert!(failures.is_empty(), "{} round-trip failures:\n{}", failures.len(), failures.join("\n\n"));
+}
+
+#[test]
+fn test_html_roundtrip_built_trees() {
+    let mut failures = Vec::new();
+    for tree in adversarial_trees() {
+        check_tree("built tree", std::slice::from_ref(&tree), &mut failures);
+    }
+
+    assert!(failures.is_empty(), "{} round-trip failures:\n{}", failures.len(), failures.join("\n\n"));
+}
+
+#[test]
+fn test_html_serialization_is_stable() {
+    for html in ADVERSARIAL {
+        let once = serialize(&HtmlParser::new(html).parse());
+        let twice = serialize(&HtmlParser::new(&once).parse());
+        assert_eq!(once, twice, "{:?}", html);
+    }
+}
+
+#[test]
+fn test_html_serialization_escaping() {
+    let tree = element("a", &[("title", "\"x\" & <y>")], vec![text("1 < 2 & 3 > 2"), Node::Comment(" c ".to_string())]);
+    assert_eq!(tree.to_html(), r#"<a title="&quot;x&quot; &amp; &lt;y>">1 &lt; 2 &amp; 3 &gt; 2<!-- c --></a>"#);
+
+    let script = element("script", &[], vec![text("a < b && c")]);
+    assert_eq!(script.to_html(), "<script>a < b && c</script>");
+
+    let img = element("img", &[("src", "x.png")], vec![]);
+    assert_eq!(img.to_html(), r#"<img src="x.png">"#);
+}
+
+#[test]
+fn test_html_to_html_after_parse() {
+    let html = r#"<div class="x"><p>Hi &amp; bye</p></div>"#;
+    let nodes = HtmlParser::new(html).parse();
+    assert_eq!(serialize(&nodes), html);
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(div.to_html(), html);
+
+    let html = r#"<ul id="list"><li>One<br>two</li><!-- more --><li><input disabled="" value="a &quot;b&quot;"></li></ul>"#;
+    assert_eq!(serialize(&HtmlParser::new(html).parse()), html);
+}
+
+#[test]
+fn test_html_parse_serialize_reparse() {
+    let documents = [
+        r#"<!DOCTYPE html><html><head><title>T &amp; U</title><style>p > a { color: red }</style></head><body><p class="a b">One<br>two <b>bold</b> three</p><img src="x.png" alt='say "hi"'><!-- note --><script>if (a < b) {}</script></body></html>"#,
+        "<div>a</b>b</div>",
+        "a</span>b<p>c</p>",
+        "<div>a<?php x ?>b</div>",
+        "<ul><li>one<li>two</ul><p>x<p>y",
+    ];
+
+    for html in documents {
+        let tree = HtmlParser::new(html).parse();
+        let serialized = serialize(&tree);
+        assert_eq!(HtmlParser::new(&serialized).parse(), tree, "{:?} serialized as {:?}", html, serialized);
+
+        let mut written = String::new();
+        for node in &tree {
+            node.write_html(&mut written).unwrap();
+        }
+        assert_eq!(written, serialized);
+    }
+}
+
+#[test]
+fn test_html_element_builder() {
+    let page = Element::new("div")
+        .attr("class", "card")
+        .attr("id", "first")
+        .child(Element::new("h2").text("Fish & chips"))
+        .comment(" price ")
+        .child(
+            Element::new("p")
+                .text("From ")
+                .child(Element::new("b").text("£5"))
+                .text(" only"),
+        )
+        .child(Element::new("img").attr("src", "a.png").attr("alt", "say \"hi\""))
+        .child(Element::new("input").attr("type", "checkbox").attr("checked", AttrValue::Bool))
+        .attr("class", "card wide");
+
+    let html = page.to_html();
+    assert_eq!(
+        html,
+        r#"<div class="card wide" id="first"><h2>Fish &amp; chips</h2><!-- price --><p>From <b>£5</b> only</p><img src="a.png" alt="say &quot;hi&quot;"><input type="checkbox" checked></div>"#
+    );
+    assert_eq!(HtmlParser::with_options(&html, HtmlParseOptions::default().trim_text(false)).parse(), vec![Node::from(page)]);
+
+    // Text added in pieces is one node.
+    let p = Element::new("p").text("a").text("b");
+    assert_eq!(p.children, vec![text("ab")]);
+    assert_eq!(Element::new("br").to_html(), "<br>");
+}
+
+#[test]
+fn test_html_inner_html() {
+    fn normalize(html: &str) -> String {
+        serialize(&HtmlParser::new(html).parse())
+    }
+
+    let cases = [
+        ("ul", "<li>a<li>b"),
+        ("tbody", "<tr><td>x<td>y"),
+        ("select", "<option value=1>One<option selected>Two"),
+        ("div", "Fish &amp; chips <b>now</b><!-- c --><br>"),
+        ("p", "a</span>b"),
+        ("div", ""),
+    ];
+    for (context, html) in cases {
+        let mut element = Element::new(context);
+        element.set_inner_html(html);
+        assert_eq!(element.inner_html(), normalize(html), "{:?} in <{}>", html, context);
+        assert_eq!(element.outer_html(), format!("<{0}>{1}</{0}>", context, normalize(html)));
+        assert_eq!(element.outer_html(), element.to_html());
+    }
+
+    // Raw-text and escapable raw-text contexts keep markup as text.
+    let mut script = Element::new("script");
+    script.set_inner_html("if (a < b && c) { x = '<p>'; }");
+    assert_eq!(script.children, vec![text("if (a < b && c) { x = '<p>'; }")]);
+    assert_eq!(script.inner_html(), "if (a < b && c) { x = '<p>'; }");
+
+    let mut textarea = Element::new("textarea");
+    textarea.set_inner_html("<b>bold</b> &amp; more");
+    assert_eq!(textarea.children, vec![text("<b>bold</b> & more")]);
+    assert_eq!(textarea.inner_html(), "&lt;b&gt;bold&lt;/b&gt; &amp; more");
+
+    // Setting replaces the old children.
+    let mut div = Element::new("div");
+    div.set_inner_html("<p>old</p>");
+    div.set_inner_html("<span>new</span>");
+    assert_eq!(div.to_html(), "<div><span>new</span></div>");
+}
+
+#[test]
+fn test_html_pretty_output() {
+    let html = r#"<div id="main"><h1>Title</h1><p>Some <b>bold</b> and <a href="/x">a link</a>.</p><ul><li>One</li><li>Two</li></ul><pre>  keep
+    this</pre><img src="x.png"></div>"#;
+    let nodes = HtmlParser::new(html).parse();
+    let expected = "<div id=\"main\">\n  <h1>Title</h1>\n  <p>Some<b>bold</b>and<a href=\"/x\">a link</a>.</p>\n  <ul>\n    <li>One</li>\n    <li>Two</li>\n  </ul>\n  <pre>keep\n    this</pre>\n  <img src=\"x.png\">\n</div>";
+    assert_eq!(to_html_pretty(&nodes, &PrettyOptions::default()), expected);
+
+    let options = PrettyOptions::default().indent_width(1).indent_char('\t').max_line_width(30).text_on_own_line(true);
+    let html = r#"<section class="intro wide" data-role="banner"><p>Hi</p><div><span>x</span> y</div></section>"#;
+    let Node::Element(section) = &HtmlParser::new(html).parse()[0] else {
+        panic!("expected an element");
+    };
+    let expected = "<section\n\tclass=\"intro wide\"\n\tdata-role=\"banner\"\n>\n\t<p>\n\t\tHi\n\t</p>\n\t<div>\n\t\t<span>x</span>y\n\t</div>\n</section>";
+    assert_eq!(section.to_html_pretty(&options), expected);
+
+    // Inline elements are never broken, even past the line width.
+    let span = HtmlParser::new("<span>one <b>two</b> three</span>").parse();
+    assert_eq!(span[0].to_html_pretty(&options), span[0].to_html());
+}
+
+#[test]
+fn test_html_pretty_is_idempotent() {
+    let mut inputs: Vec<String> = source_fixtures().into_iter().map(|(_, html)| html).collect();
+    inputs.extend(ADVERSARIAL.iter().map(|html| html.to_string()));
+    inputs.push(
+        r#"<!DOCTYPE html><html><head><title>T</title><meta charset="utf-8"><style>p > a { color: red }</style></head><body><!-- nav --><nav class="a very long class list that will not fit on one line" id="nav" data-x="1"><a href="/">Home</a> | <a href="/about">About</a></nav><table><tr><td>1</td><td><em>2</em></td></tr></table><textarea>  a
+  b</textarea><script>if (a < b) {}</script></body></html>"#
+            .to_string(),
+    );
+
+    let options = [
+        PrettyOptions::default(),
+        PrettyOptions::default().indent_width(4).max_line_width(20).text_on_own_line(true),
+        PrettyOptions::default().indent_width(1).indent_char('\t').max_line_width(0),
+    ];
+    for html in &inputs {
+        let document = HtmlParser::new(html).parse_full_document();
+        for options in &options {
+            let once = document.to_html_pretty(options);
+            let reparsed = HtmlParser::new(&once).parse_full_document();
+            assert_eq!(reparsed, document, "{:?} formatted as {:?}", html, once);
+            assert_eq!(reparsed.to_html_pretty(options), once, "{:?}", html);
+        }
+    }
+}
+
+#[test]
+fn test_html_minify() {
+    let keep_whitespace = HtmlParseOptions::default().trim_text(false);
+    let minified = |html: &str, options: &MinifyOptions| {
+        let nodes = HtmlParser::with_options(html, keep_whitespace.clone()).parse();
+        minify(&nodes[0], options)
+    };
+    let defaults = MinifyOptions::default();
+
+    let html = "<div class=\"a\" id=\"x y\">\n  <ul>\n    <li>One</li>\n    <li>Two <b>bold</b> three</li>\n  </ul>\n  <p>Para</p>\n  <p>Last</p>\n</div>";
+    assert_eq!(minified(html, &defaults), "<div class=a id=\"x y\"><ul><li>One<li>Two <b>bold</b> three</ul><p>Para<p>Last</div>");
+    assert_eq!(
+        minified(html, &defaults.clone().omit_optional_end_tags(false)),
+        "<div class=a id=\"x y\"><ul><li>One</li><li>Two <b>bold</b> three</li></ul><p>Para</p><p>Last</p></div>"
+    );
+    assert_eq!(
+        minified(html, &defaults.clone().remove_attribute_quotes(false)),
+        "<div class=\"a\" id=\"x y\"><ul><li>One<li>Two <b>bold</b> three</ul><p>Para<p>Last</div>"
+    );
+    // Whitespace between the items keeps their end tags.
+    assert_eq!(minified(html, &defaults.clone().collapse_whitespace(false)), html.replace("\"a\"", "a"));
+
+    // A `<p>` at the end of a link keeps its end tag, and one followed by
+    // text does too.
+    assert_eq!(minified("<a href=\"/x/\"><p>x</p></a>", &defaults), "<a href=\"/x/\"><p>x</p></a>");
+    assert_eq!(minified("<div><p>x</p>y</div>", &defaults), "<div><p>x</p>y</div>");
+
+    let html = "<div> <!-- note --> <!--[if IE]><p>old</p><![endif]--> <span> a  b </span> </div>";
+    assert_eq!(minified(html, &defaults), "<div><!--[if IE]><p>old</p><![endif]--><span> a b </span></div>");
+    assert_eq!(minified(html, &defaults.clone().keep_conditional_comments(false)), "<div><span> a b </span></div>");
+    assert_eq!(
+        minified(html, &defaults.clone().remove_comments(false)),
+        "<div><!-- note --><!--[if IE]><p>old</p><![endif]--><span> a b </span></div>"
+    );
+
+    let html = "<div>\n  <pre>  keep\n    this </pre>\n  <textarea> a\n  b </textarea>\n  <script>if (a  <  b) {\n}</script>\n</div>";
+    assert_eq!(
+        minified(html, &defaults),
+        "<div><pre>  keep\n    this </pre><textarea> a\n  b </textarea> <script>if (a  <  b) {\n}</script></div>"
+    );
+}
+
+#[test]
+fn test_html_minify_example_document() {
+    let (_, html) = source_fixtures()
+        .into_iter()
+        .find(|(origin, html)| origin.ends_with("html_parser.rs") && html.contains("<!DOCTYPE"))
+        .expect("the example document");
+
+    for parse_options in [HtmlParseOptions::default(), HtmlParseOptions::default().trim_text(false)] {
+        let parse_options = parse_options.collapse_whitespace(true);
+        let parse = |html: &str| HtmlParser::with_options(html, parse_options.clone()).parse_document().unwrap();
+        let document = parse(&html);
+
+        let minified = minify(&document, &MinifyOptions::default());
+        assert!(minified.len() * 2 < html.len(), "{} of {} bytes: {}", minified.len(), html.len(), minified);
+        assert_eq!(rendered_text(&parse(&minified), None), rendered_text(&document, None));
+
+        // With comments kept and whitespace already trimmed, the tree
+        // itself comes back.
+        if parse_options == HtmlParseOptions::default().collapse_whitespace(true) {
+            let minified = minify(&document, &MinifyOptions::default().remove_comments(false));
+            assert_eq!(parse(&minified), document, "{}", minified);
+        }
+    }
+}
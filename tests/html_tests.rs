@@ -1,10 +1,10 @@
-use html_css_parser::html::{HtmlTokenizer, HtmlParser, HtmlToken, Node, Element};
-use std::collections::HashMap;
+use html_css_parser::html::{AttrValue, HtmlTokenizer, HtmlParser, HtmlToken, HtmlError, Element, TokenizeErrorKind, Node, NodeSink, TreeSink};
+use html_css_parser::Span;
 
 #[test]
 fn test_html_tokenizer_basic() {
     let html = "<div>Hello</div>";
-    let mut tokenizer = HtmlTokenizer::new(html);
+    let tokenizer = HtmlTokenizer::new(html);
     
     let tokens: Vec<HtmlToken> = tokenizer.collect();
     
@@ -33,7 +33,7 @@ fn test_html_tokenizer_basic() {
 #[test]
 fn test_html_tokenizer_attributes() {
     let html = r#"<div class="container" id="main" data-value="test">Content</div>"#;
-    let mut tokenizer = HtmlTokenizer::new(html);
+    let tokenizer = HtmlTokenizer::new(html);
     
     let tokens: Vec<HtmlToken> = tokenizer.collect();
     
@@ -41,9 +41,9 @@ fn test_html_tokenizer_attributes() {
         HtmlToken::StartTag { name, attributes, self_closing } => {
             assert_eq!(name, "div");
             assert_eq!(attributes.len(), 3);
-            assert_eq!(attributes[0], ("class".to_string(), "container".to_string()));
-            assert_eq!(attributes[1], ("id".to_string(), "main".to_string()));
-            assert_eq!(attributes[2], ("data-value".to_string(), "test".to_string()));
+            assert_eq!(attributes[0], ("class".to_string(), "container".into()));
+            assert_eq!(attributes[1], ("id".to_string(), "main".into()));
+            assert_eq!(attributes[2], ("data-value".to_string(), "test".into()));
             assert!(!self_closing);
         }
         _ => panic!("Expected StartTag with attributes"),
@@ -53,7 +53,7 @@ fn test_html_tokenizer_attributes() {
 #[test]
 fn test_html_tokenizer_self_closing() {
     let html = r#"<img src="test.jpg" alt="Test" />"#;
-    let mut tokenizer = HtmlTokenizer::new(html);
+    let tokenizer = HtmlTokenizer::new(html);
     
     let tokens: Vec<HtmlToken> = tokenizer.collect();
     
@@ -72,7 +72,7 @@ fn test_html_tokenizer_self_closing() {
 #[test]
 fn test_html_tokenizer_comment() {
     let html = "<!-- This is a comment --><div>Content</div>";
-    let mut tokenizer = HtmlTokenizer::new(html);
+    let tokenizer = HtmlTokenizer::new(html);
     
     let tokens: Vec<HtmlToken> = tokenizer.collect();
     
@@ -85,14 +85,47 @@ fn test_html_tokenizer_comment() {
 #[test]
 fn test_html_tokenizer_doctype() {
     let html = "<!DOCTYPE html><html></html>";
-    let mut tokenizer = HtmlTokenizer::new(html);
+    let tokenizer = HtmlTokenizer::new(html);
     
     let tokens: Vec<HtmlToken> = tokenizer.collect();
     
-    match &tokens[0] {
-        HtmlToken::Doctype(doctype) => assert_eq!(doctype, "DOCTYPE html"),
-        _ => panic!("Expected Doctype"),
-    }
+    assert_eq!(
+        tokens[0],
+        HtmlToken::Doctype {
+            name: "html".to_string(),
+            public_id: None,
+            system_id: None,
+        }
+    );
+}
+
+#[test]
+fn test_html_tokenizer_doctype_identifiers() {
+    let html = r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"><html></html>"#;
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new(html).collect();
+
+    assert_eq!(
+        tokens[0],
+        HtmlToken::Doctype {
+            name: "html".to_string(),
+            public_id: Some("-//W3C//DTD XHTML 1.0 Strict//EN".to_string()),
+            system_id: Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd".to_string()),
+        }
+    );
+    assert_eq!(tokens[1], HtmlToken::StartTag { name: "html".to_string(), attributes: vec![], self_closing: false });
+
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<!doctype HTML system 'about:legacy-compat'><!ELEMENT br EMPTY>").collect();
+    assert_eq!(
+        tokens,
+        vec![
+            HtmlToken::Doctype {
+                name: "html".to_string(),
+                public_id: None,
+                system_id: Some("about:legacy-compat".to_string()),
+            },
+            HtmlToken::Comment("ELEMENT br EMPTY".to_string()),
+        ]
+    );
 }
 
 #[test]
@@ -130,7 +163,7 @@ fn test_html_parser_nested() {
     match &nodes[0] {
         Node::Element(element) => {
             assert_eq!(element.tag_name, "div");
-            assert_eq!(element.attributes.get("class"), Some(&"container".to_string()));
+            assert_eq!(element.attribute("class"), Some("container"));
             assert_eq!(element.children.len(), 2);
             
             match &element.children[0] {
@@ -228,4 +261,2231 @@ fn test_html_parser_document() {
         }
         _ => panic!("Expected html element"),
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_html_parse_full_document() {
+    use html_css_parser::html::{Doctype, QuirksMode};
+
+    let html = "<!-- generated --><!DOCTYPE html><html><body><p>Hi</p></body></html><!-- trailer -->";
+    let document = HtmlParser::new(html).parse_full_document();
+    assert_eq!(
+        document.doctype,
+        Some(Doctype { name: "html".to_string(), public_id: None, system_id: None })
+    );
+    assert_eq!(document.quirks_mode(), QuirksMode::NoQuirks);
+    assert!(matches!(&document.children[..], [
+        Node::Comment(before),
+        Node::Element(root),
+        Node::Comment(after),
+    ] if before == " generated " && root.tag_name == "html" && after == " trailer "));
+    assert_eq!(document.root().unwrap().tag_name, "html");
+
+    // Top-level nodes are all kept, whatever they are.
+    let document = HtmlParser::new("<p>one</p>text<div>two</div>").parse_full_document();
+    assert_eq!(document.doctype, None);
+    assert_eq!(document.quirks_mode(), QuirksMode::Quirks);
+    assert_eq!(document.children.len(), 3);
+    assert_eq!(document.root().unwrap().tag_name, "p");
+
+    let html = r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.01 Transitional//EN" "http://www.w3.org/TR/html4/loose.dtd"><html></html>"#;
+    let document = HtmlParser::new(html).parse_full_document();
+    assert_eq!(document.doctype.as_ref().unwrap().public_id.as_deref(), Some("-//W3C//DTD HTML 4.01 Transitional//EN"));
+    assert_eq!(document.quirks_mode(), QuirksMode::LimitedQuirks);
+    assert_eq!(HtmlParser::new(html).quirks_mode(), QuirksMode::LimitedQuirks);
+
+    // `parse_document` gives the root element of the same document.
+    let html = "<!-- x --><!DOCTYPE html><html lang=en><p>a</p></html>";
+    let root = HtmlParser::new(html).parse_full_document().root().cloned().map(Node::Element);
+    assert_eq!(HtmlParser::new(html).parse_document(), root);
+}
+
+#[test]
+fn test_html_fix_document_structure() {
+    use html_css_parser::html::HtmlParseOptions;
+
+    let options = HtmlParseOptions::default().fix_document_structure(true);
+    let parse = |html: &str| HtmlParser::with_options(html, options.clone()).parse_full_document();
+    let reparse = |html: &str| -> String { parse(html).children.iter().map(Node::to_html).collect() };
+
+    assert_eq!(reparse("<p>hello</p>"), "<html><head></head><body><p>hello</p></body></html>");
+    assert_eq!(reparse(""), "<html><head></head><body></body></html>");
+    // Metadata before any content goes in the head; after it, in the body.
+    assert_eq!(
+        reparse("<title>T</title><meta charset=utf-8><p>x</p><style>p {}</style>"),
+        r#"<html><head><title>T</title><meta charset="utf-8"></head><body><p>x</p><style>p {}</style></body></html>"#
+    );
+    // A body after an unclosed head, as the parser nests it, is moved out.
+    assert_eq!(
+        reparse(r#"<!DOCTYPE html><html lang="en"><head><title>T</title><body class="main"><p>a</body></html>"#),
+        r#"<html lang="en"><head><title>T</title></head><body class="main"><p>a</p></body></html>"#
+    );
+    // Text before the head starts the body, so the head's content lands
+    // there too.
+    assert_eq!(
+        reparse("hello<head><title>T</title></head><body><p>x</p></body>"),
+        "<html><head></head><body>hello<title>T</title><p>x</p></body></html>"
+    );
+    // Content after `</body>` or `</html>` goes at the end of the body, and
+    // comments around the root stay outside it.
+    assert_eq!(
+        reparse("<!-- a --><html><body><p>1</p></body></html><div>2</div><!-- b -->"),
+        "<!-- a --><html><head></head><body><p>1</p><div>2</div></body></html><!-- b -->"
+    );
+    // Repeated `html` and `body` tags add attributes they don't already have.
+    assert_eq!(
+        reparse(r#"<body id="a"><p>x</p><body id="b" class="c">"#),
+        r#"<html><head></head><body id="a" class="c"><p>x</p></body></html>"#
+    );
+
+    let document = parse("<!DOCTYPE html><link rel=stylesheet href=a.css>Text");
+    assert_eq!(document.doctype.as_ref().unwrap().name, "html");
+    assert_eq!(document.head().unwrap().children.len(), 1);
+    assert_eq!(document.body().unwrap().text_content(), "Text");
+
+    // Off by default.
+    let document = HtmlParser::new("<p>hello</p>").parse_full_document();
+    assert_eq!(document.body(), None);
+    assert_eq!(document.root().unwrap().tag_name, "p");
+}
+
+#[derive(Default)]
+struct CountingSink {
+    elements: usize,
+    texts: usize,
+    comments: usize,
+}
+
+impl TreeSink for CountingSink {
+    type Handle = ();
+    type Output = (usize, usize, usize);
+
+    fn create_element(&mut self, _name: &str) {
+        self.elements += 1;
+    }
+
+    fn create_text(&mut self, _text: &str) {
+        self.texts += 1;
+    }
+
+    fn create_comment(&mut self, _text: &str) {
+        self.comments += 1;
+    }
+
+    fn set_attributes(&mut self, _element: &mut (), _attributes: Vec<(String, AttrValue)>) {}
+
+    fn append_child(&mut self, _parent: Option<&mut ()>, _child: ()) {}
+
+    fn finish(self) -> (usize, usize, usize) {
+        (self.elements, self.texts, self.comments)
+    }
+}
+
+fn count_nodes(nodes: &[Node], counts: &mut (usize, usize, usize)) {
+    for node in nodes {
+        match node {
+            Node::Element(element) => {
+                counts.0 += 1;
+                count_nodes(&element.children, counts);
+            }
+            Node::Text(_) => counts.1 += 1,
+            Node::Comment(_) => counts.2 += 1,
+        }
+    }
+}
+
+#[test]
+fn test_html_parser_counting_sink_matches_tree() {
+    let html = r#"<!DOCTYPE html>
+        <html>
+        <head><title>Sink</title></head>
+        <body>
+            <!-- nav -->
+            <ul><li>One</li><li>Two <em>three</em></li></ul>
+            <img src="a.png"><p>Tail</p>
+        </body>
+        </html>"#;
+
+    let counts = HtmlParser::new(html).parse_with_sink(CountingSink::default());
+
+    let nodes = HtmlParser::new(html).parse();
+    let mut expected = (0, 0, 0);
+    count_nodes(&nodes, &mut expected);
+
+    assert_eq!(counts, expected);
+    assert_eq!(counts, (10, 5, 1));
+}
+
+#[test]
+fn test_html_parser_default_sink() {
+    let html = r#"<div class="a"><p>Hi</p><!-- c --></div>"#;
+
+    let via_sink = HtmlParser::new(html).parse_with_sink(NodeSink::new());
+    let via_parse = HtmlParser::new(html).parse();
+
+    assert_eq!(via_sink, via_parse);
+}
+
+#[test]
+fn test_html_parser_custom_elements() {
+    let html = "<my-card><p>x</p></my-card><font-face></font-face><div></div>";
+    let mut parser = HtmlParser::new(html);
+
+    let nodes = parser.parse();
+
+    assert_eq!(nodes.len(), 3);
+
+    match &nodes[0] {
+        Node::Element(card) => {
+            assert_eq!(card.tag_name, "my-card");
+            assert!(card.is_custom_element());
+            assert_eq!(card.children.len(), 1);
+
+            match &card.children[0] {
+                Node::Element(p) => {
+                    assert_eq!(p.tag_name, "p");
+                    assert_eq!(p.children, vec![Node::Text("x".to_string())]);
+                }
+                _ => panic!("Expected p element inside custom element"),
+            }
+        }
+        _ => panic!("Expected custom element"),
+    }
+
+    match (&nodes[1], &nodes[2]) {
+        (Node::Element(font_face), Node::Element(div)) => {
+            assert!(!font_face.is_custom_element());
+            assert!(!div.is_custom_element());
+        }
+        _ => panic!("Expected elements"),
+    }
+}
+
+#[test]
+fn test_html_parser_try_parse_errors() {
+    let mut parser = HtmlParser::new("<div>\n  <span>a</b>b</span></div>");
+    let error = parser.try_parse().unwrap_err();
+
+    assert_eq!(
+        error,
+        HtmlError::UnexpectedEndTag {
+            name: "b".to_string(),
+            span: Span::new(15, 19, 2, 10),
+        }
+    );
+    assert_eq!(error.to_string(), "unexpected end tag </b> at line 2, column 10");
+
+    let mut parser = HtmlParser::new("<!-- only a comment -->");
+    let error = parser.try_parse_document().unwrap_err();
+    assert_eq!(error, HtmlError::NoRootElement);
+    assert_eq!(error.to_string(), "document has no root element");
+
+    let boxed: Box<dyn std::error::Error> = Box::new(error);
+    assert_eq!(boxed.to_string(), "document has no root element");
+}
+
+fn start_tag_attributes(html: &str) -> Vec<(String, AttrValue)> {
+    match HtmlTokenizer::new(html).next() {
+        Some(HtmlToken::StartTag { attributes, .. }) => attributes,
+        other => panic!("Expected StartTag, got {:?}", other),
+    }
+}
+
+fn attr(name: &str, value: &str) -> (String, AttrValue) {
+    (name.to_string(), value.into())
+}
+
+fn flag(name: &str) -> (String, AttrValue) {
+    (name.to_string(), AttrValue::Bool)
+}
+
+#[test]
+fn test_html_tokenizer_trailing_valueless_attributes() {
+    assert_eq!(
+        start_tag_attributes(r#"<script src="x" crossorigin></script>"#),
+        vec![attr("src", "x"), flag("crossorigin")]
+    );
+    assert_eq!(
+        start_tag_attributes(r#"<link rel="preload" as="font" crossorigin>"#),
+        vec![attr("rel", "preload"), attr("as", "font"), flag("crossorigin")]
+    );
+    assert_eq!(
+        start_tag_attributes(r#"<link rel="preload" crossorigin="anonymous"/>"#),
+        vec![attr("rel", "preload"), attr("crossorigin", "anonymous")]
+    );
+    assert_eq!(
+        start_tag_attributes("<input value=a/ disabled>"),
+        vec![attr("value", "a/"), flag("disabled")]
+    );
+    assert_eq!(
+        start_tag_attributes("<input type=checkbox checked />"),
+        vec![attr("type", "checkbox"), flag("checked")]
+    );
+}
+
+#[test]
+fn test_html_tokenizer_integrity_attribute() {
+    let html = r#"<script src=/app.js integrity=sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC crossorigin></script>"#;
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new(html).collect();
+
+    assert_eq!(tokens.len(), 2);
+    match &tokens[0] {
+        HtmlToken::StartTag { name, attributes, self_closing } => {
+            assert_eq!(name, "script");
+            assert!(!self_closing);
+            assert_eq!(
+                attributes,
+                &vec![
+                    attr("src", "/app.js"),
+                    attr("integrity", "sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC"),
+                    flag("crossorigin"),
+                ]
+            );
+        }
+        _ => panic!("Expected StartTag"),
+    }
+
+    let mut parser = HtmlParser::new(r#"<head><link rel="stylesheet" href="a.css" crossorigin><meta charset=utf-8></head>"#);
+    let nodes = parser.parse();
+    match &nodes[0] {
+        Node::Element(head) => {
+            assert_eq!(head.children.len(), 2);
+            match &head.children[0] {
+                Node::Element(link) => assert_eq!(link.attribute_value("crossorigin"), Some(&AttrValue::Bool)),
+                _ => panic!("Expected link element"),
+            }
+        }
+        _ => panic!("Expected head element"),
+    }
+}
+
+#[test]
+fn test_extract_stylesheets_with_media() {
+    use html_css_parser::css::{parse_media_query_list, MediaEnv};
+    use html_css_parser::html::extract::{self, StylesheetSource};
+
+    let html = r#"<html><head>
+        <style>h1 { color: red; }</style>
+        <style media="print">h1 { color: black; }</style>
+        <link rel="stylesheet" href="narrow.css" media="(max-width: 600px)">
+        <link rel="icon" href="favicon.ico">
+    </head><body></body></html>"#;
+    let nodes = HtmlParser::new(html).parse();
+
+    let extracted = extract::stylesheets(&nodes);
+    assert_eq!(extracted.len(), 3);
+    assert_eq!(extracted[0].media, None);
+    assert_eq!(extracted[1].media, Some(parse_media_query_list("print")));
+    assert_eq!(extracted[1].source, StylesheetSource::Inline("h1 { color: black; }".to_string()));
+    assert_eq!(extracted[2].source, StylesheetSource::Link { href: "narrow.css".to_string() });
+    assert_eq!(extracted[2].media, Some(parse_media_query_list("(max-width: 600px)")));
+
+    // The print rules are in the combined model but only apply when printing.
+    let combined = extract::combined_stylesheet(&nodes);
+    assert_eq!(combined.rules.len(), 3);
+
+    let colors = |env: &MediaEnv| -> Vec<String> {
+        combined
+            .rules_for(env)
+            .iter()
+            .map(|rule| rule.declarations[0].value.clone())
+            .collect()
+    };
+    assert_eq!(colors(&MediaEnv::screen(1024.0, 768.0)), vec!["red"]);
+    assert_eq!(colors(&MediaEnv::print()), vec!["red", "black"]);
+}
+
+#[test]
+fn test_html_tokenizer_ambiguous_ampersand() {
+    let text = |input: &str| -> Vec<HtmlToken> { HtmlTokenizer::new(input).collect() };
+
+    assert_eq!(text("a & b"), vec![HtmlToken::Text("a & b".to_string())]);
+    assert_eq!(text("a&amp;b"), vec![HtmlToken::Text("a&b".to_string())]);
+    assert_eq!(text("a&ampx"), vec![HtmlToken::Text("a&ampx".to_string())]);
+    assert_eq!(text("Tom & Jerry; &lt;3"), vec![HtmlToken::Text("Tom & Jerry; <3".to_string())]);
+    assert_eq!(text("&&amp;&#65;&#x42;&"), vec![HtmlToken::Text("&&AB&".to_string())]);
+}
+
+#[test]
+fn test_element_replace_slot_comment() {
+    let mut nodes = HtmlParser::new("<div><p>before</p><!-- slot --><p>after</p></div>").parse();
+    let Node::Element(div) = &mut nodes[0] else {
+        panic!("expected an element");
+    };
+
+    let span = Node::Element(html_css_parser::Element {
+        tag_name: "span".to_string(),
+        attributes: html_css_parser::html::Attributes::new(),
+        children: vec![Node::Text("content".to_string())],
+        source_span: None,
+    });
+    let slot = Node::Comment(" slot ".to_string());
+
+    assert_eq!(div.replace_with(&slot, vec![span.clone()]), Some(slot.clone()));
+    assert_eq!(div.children.len(), 3);
+    assert_eq!(div.children[1], span);
+
+    // The placeholder is gone, so it can't be used as a reference any more.
+    assert!(!div.insert_before(&slot, vec![Node::Text("x".to_string())]));
+    assert_eq!(div.replace_with(&slot, Vec::new()), None);
+
+    assert!(div.insert_before(0, vec![Node::Text("a".to_string()), Node::Text("b".to_string())]));
+    assert_eq!(div.children.len(), 5);
+    assert_eq!(div.children[1], Node::Text("b".to_string()));
+    assert!(!div.insert_before(5, Vec::new()));
+
+    // Replacing with nothing removes the child.
+    assert_eq!(div.replace_with(0, Vec::new()), Some(Node::Text("a".to_string())));
+    assert_eq!(div.children.len(), 4);
+}
+
+#[test]
+fn test_element_children_mutation() {
+    let Node::Element(mut ul) = HtmlParser::new("<ul><li>b</li><!-- x --><li>d</li></ul>").parse().remove(0) else {
+        panic!("expected an element");
+    };
+    let li = |text: &str| HtmlParser::new(&format!("<li>{}</li>", text)).parse().remove(0);
+
+    ul.append_child(li("e"));
+    ul.insert_child(0, li("a"));
+    ul.insert_child(2, li("c"));
+    assert_eq!(ul.to_html(), "<ul><li>a</li><li>b</li><li>c</li><!-- x --><li>d</li><li>e</li></ul>");
+
+    assert_eq!(ul.remove_child(1), li("b"));
+    ul.retain_children(|node| !matches!(node, Node::Comment(_)));
+    assert_eq!(ul.to_html(), "<ul><li>a</li><li>c</li><li>d</li><li>e</li></ul>");
+    ul.retain_children(|_| false);
+    assert!(ul.children.is_empty());
+}
+
+#[test]
+fn test_element_attribute_mutation() {
+    let Node::Element(mut input) = HtmlParser::new(r#"<input ID="q" disabled data-track="1" value="x">"#).parse().remove(0) else {
+        panic!("expected an element");
+    };
+
+    // Setting replaces in place, matching names without regard to case and
+    // keeping the name as written.
+    input.set_attribute("id", "search");
+    input.set_attribute("name", "q");
+    input.set_attribute("hidden", AttrValue::Bool);
+    assert_eq!(input.to_html(), r#"<input ID="search" disabled data-track="1" value="x" name="q" hidden>"#);
+
+    assert_eq!(input.remove_attribute("DATA-TRACK"), Some("1".to_string()));
+    assert_eq!(input.remove_attribute("disabled"), Some(String::new()));
+    assert_eq!(input.remove_attribute("disabled"), None);
+    assert_eq!(input.to_html(), r#"<input ID="search" value="x" name="q" hidden>"#);
+}
+
+#[test]
+fn test_element_class_mutation() {
+    let Node::Element(mut div) = HtmlParser::new("<div class=\"  a b\ta  \"></div>").parse().remove(0) else {
+        panic!("expected an element");
+    };
+
+    // Changes rewrite the list with each class once.
+    div.add_class("b");
+    assert_eq!(div.attribute("class"), Some("a b"));
+    div.add_class(" c  a d ");
+    assert_eq!(div.attribute("class"), Some("a b c d"));
+
+    div.set_attribute("class", "a b a c");
+    div.remove_class("a");
+    assert_eq!(div.attribute("class"), Some("b c"));
+    div.remove_class("x c");
+    assert_eq!(div.attribute("class"), Some("b"));
+    div.remove_class("b");
+    assert_eq!(div.to_html(), r#"<div class=""></div>"#);
+
+    let Node::Element(mut span) = HtmlParser::new("<span></span>").parse().remove(0) else {
+        panic!("expected an element");
+    };
+    span.remove_class("a");
+    assert!(!span.has_attribute("class"));
+    span.add_class("a");
+    assert_eq!(span.to_html(), r#"<span class="a"></span>"#);
+}
+
+#[test]
+fn test_element_mutation_rewrites_document() {
+    let html = r#"<html><head><title>T</title></head><body><img src="a.png" data-track="x"><p class="intro"><img src="b.png"></p></body></html>"#;
+    let Node::Element(mut root) = HtmlParser::new(html).parse().remove(0) else {
+        panic!("expected an element");
+    };
+
+    root.for_each_descendant_mut(|node| {
+        let Node::Element(element) = node else {
+            return;
+        };
+        match element.tag_name.as_str() {
+            "head" => element.insert_child(0, HtmlParser::new(r#"<base href="https://example.com/">"#).parse().remove(0)),
+            "img" => {
+                element.set_attribute("loading", "lazy");
+                element.remove_attribute("data-track");
+            }
+            "p" => element.add_class("lead"),
+            _ => {}
+        }
+    });
+
+    let rewritten = root.to_html();
+    assert_eq!(
+        rewritten,
+        r#"<html><head><base href="https://example.com/"><title>T</title></head><body><img src="a.png" loading="lazy"><p class="intro lead"><img src="b.png" loading="lazy"></p></body></html>"#
+    );
+    assert_eq!(HtmlParser::new(&rewritten).parse(), vec![Node::Element(root)]);
+}
+
+#[test]
+fn test_html_parser_stats() {
+    use html_css_parser::html::HtmlStats;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static LAST_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    fn progress(bytes_done: usize, total: usize) {
+        assert!(bytes_done <= total);
+        assert!(bytes_done >= LAST_BYTES.swap(bytes_done, Ordering::SeqCst));
+        CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let html = "<!DOCTYPE html><div><p>Hi</p><!-- c --></div></b>";
+    let mut parser = HtmlParser::new(html).on_progress(2, progress);
+    let nodes = parser.parse();
+    assert_eq!(nodes.len(), 1);
+
+    // Doctype, <div>, <p>, text, </p>, comment, </div>, </b>.
+    assert_eq!(
+        parser.stats(),
+        HtmlStats {
+            tokens: 8,
+            nodes: 4,
+            bytes: html.len(),
+            max_depth: 2,
+            recoveries: 1,
+        }
+    );
+    // Every second token, plus completion.
+    assert_eq!(CALLS.load(Ordering::SeqCst), 5);
+    assert_eq!(LAST_BYTES.load(Ordering::SeqCst), html.len());
+}
+
+#[test]
+fn test_html_parser_mixed_case_document() {
+    let mut parser = HtmlParser::new("<HTML><HEAD></HEAD><BODY><P>x</P></BODY></HTML>");
+    let Some(Node::Element(html)) = parser.parse_document() else {
+        panic!("expected a document element");
+    };
+    assert_eq!(html.tag_name, "HTML");
+    assert_eq!(html.children.len(), 2);
+
+    let tags: Vec<&str> = html
+        .children
+        .iter()
+        .filter_map(|child| match child {
+            Node::Element(element) => Some(element.tag_name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(tags, vec!["HEAD", "BODY"]);
+
+    // End tags close their element whatever their case.
+    let mut parser = HtmlParser::new("<Html><Head><title>t</TITLE></head><Body><p>x</P><p>y</p></BODY></html>");
+    let Some(Node::Element(html)) = parser.parse_document() else {
+        panic!("expected a document element");
+    };
+    assert_eq!(html.children.len(), 2);
+    let Node::Element(body) = &html.children[1] else {
+        panic!("expected body");
+    };
+    assert!(body.tag_name.eq_ignore_ascii_case("body"));
+    assert_eq!(body.children.len(), 2);
+}
+
+#[test]
+fn test_html_parser_framework_attributes() {
+    use html_css_parser::html::HtmlParseOptions;
+
+    let template = r#"<div :class="{'active': isActive, 'text-danger': hasError}" v-if="count > 1" @click.prevent="toggle()">
+        <input [(ngModel)]="name" #nameInput *ngIf="items.length > 0" (blur)="save($event)">
+        <template #header><span v-for="item in items" :key="item.id">{{ item.name }}</span></template>
+    </div>"#;
+    let options = HtmlParseOptions::default().framework_attributes(true);
+    let nodes = HtmlParser::with_options(template, options.clone()).parse();
+
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(div.attributes.get(":class").map(AttrValue::as_str), Some("{'active': isActive, 'text-danger': hasError}"));
+    assert_eq!(div.attributes.get("v-if").map(AttrValue::as_str), Some("count > 1"));
+    assert_eq!(div.attributes.get("@click.prevent").map(AttrValue::as_str), Some("toggle()"));
+
+    let Node::Element(input) = &div.children[0] else {
+        panic!("expected an element");
+    };
+    let mut names: Vec<&str> = input.attributes.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, vec!["#nameInput", "(blur)", "*ngIf", "[(ngModel)]"]);
+    assert_eq!(input.attributes.get("*ngIf").map(AttrValue::as_str), Some("items.length > 0"));
+
+    // Every attribute survives serialization and reparsing.
+    let html: String = nodes.iter().map(Node::to_html).collect();
+    assert_eq!(HtmlParser::with_options(&html, options).parse(), nodes);
+
+    // Without the option, the framework characters aren't part of names.
+    let nodes = HtmlParser::new(r#"<button @click="go()">x</button>"#).parse();
+    let Node::Element(button) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(button.attributes.get("click").map(AttrValue::as_str), Some("go()"));
+}
+
+#[test]
+fn test_extract_only_tags_matches_full_parse() {
+    use html_css_parser::html::extract;
+    use html_css_parser::html::Element;
+
+    fn collect(nodes: &[Node], tags: &[&str], found: &mut Vec<Element>) {
+        for node in nodes {
+            if let Node::Element(element) = node {
+                if tags.iter().any(|tag| tag.eq_ignore_ascii_case(&element.tag_name)) {
+                    let mut copy = element.clone();
+                    copy.children = element.children.iter().filter(|child| matches!(child, Node::Text(_))).cloned().collect();
+                    found.push(copy);
+                }
+                collect(&element.children, tags, found);
+            }
+        }
+    }
+
+    let html = r#"<!DOCTYPE html>
+<html><head>
+  <meta charset="utf-8"><META name="viewport" content="width=device-width">
+  <script>document.write('<a href="/phantom">x</a>'); if (a < b) {}</script>
+  <style>a[href="<a href=x>"] { color: red; }</style>
+</head><body>
+  <!-- <a href="/commented">no</a> -->
+  <p title='<a href="/quoted">'>Intro <a href="/one" class=first>One <b>bold</b> tail</a></p>
+  <A HREF="/two">Two<img src="two.png" alt="">after</A>
+  <div><a href="/three">unclosed</div>
+  <a href="/four"><span>inner</span><a href="/nested">nested</a>outer</a>
+  <img src="/last.png"/>
+</body></html>"#;
+    let tags = ["a", "img", "meta"];
+
+    let mut expected = Vec::new();
+    collect(&HtmlParser::new(html).parse(), &tags, &mut expected);
+    let extracted = extract::only_tags(html, &tags);
+
+    assert_eq!(extracted, expected);
+    let hrefs: Vec<&str> = extracted
+        .iter()
+        .filter_map(|element| element.attributes.get("href").or(element.attributes.get("HREF")))
+        .map(AttrValue::as_str)
+        .collect();
+    assert_eq!(hrefs, ["/one", "/two", "/three", "/four", "/nested"]);
+    assert_eq!(extracted[2].children, [Node::Text("One".to_string()), Node::Text("tail".to_string())]);
+}
+
+#[test]
+fn test_collapse_whitespace() {
+    use html_css_parser::html::HtmlParseOptions;
+
+    let options = HtmlParseOptions::default().collapse_whitespace(true);
+
+    let nodes = HtmlParser::with_options("<p>a\n\n   b</p>", options.clone()).parse();
+    assert_eq!(nodes[0].text_content(), "a b");
+
+    let html = "<div>one\t two<pre>  keep\n   this  </pre><script>if (a)\n  b();</script></div>";
+    let nodes = HtmlParser::with_options(html, options).parse();
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(div.children[0], Node::Text("one two".to_string()));
+    assert_eq!(div.children[1].text_content(), "keep\n   this");
+    assert_eq!(div.children[2].text_content(), "if (a)\n  b();");
+
+    // Off by default.
+    let nodes = HtmlParser::new("<p>a\n\n   b</p>").parse();
+    assert_eq!(nodes[0].text_content(), "a\n\n   b");
+}
+
+#[test]
+fn test_parse_page() {
+    use html_css_parser::css::CssRule;
+    use html_css_parser::parse_page;
+
+    let html = r#"<!DOCTYPE html>
+<!-- generated -->
+<html><head>
+  <style>body { margin: 0; } .title { color: navy; }</style>
+  <style media="print">.title { color: black; }</style>
+</head><body>
+  <h1 class="title" style="font-size: 2em; color: red">Hello</h1>
+  <p style="">Empty styles add no rule.</p>
+</body></html>"#;
+    let (document, stylesheet) = parse_page(html);
+
+    let root = document.root().unwrap();
+    assert_eq!(root.tag_name, "html");
+    assert_eq!(document.children.len(), 2);
+
+    // Two rules, the print `@media` block and the inline style.
+    assert_eq!(stylesheet.rules.len(), 4);
+    assert_eq!(stylesheet.style_rules().count(), 3);
+    let Some(CssRule::Style(inline)) = stylesheet.rules.last() else {
+        panic!("expected a style rule");
+    };
+    assert_eq!(inline.selectors[0].to_string(), "h1[style=\"font-size: 2em; color: red\"]");
+    assert_eq!(inline.declarations.len(), 2);
+
+    let Node::Element(body) = &root.children[1] else {
+        panic!("expected an element");
+    };
+    let Node::Element(h1) = &body.children[0] else {
+        panic!("expected an element");
+    };
+    assert!(inline.selectors[0].matches(h1));
+}
+
+#[test]
+fn test_rendered_text() {
+    use html_css_parser::css::{ComputedStyles, CssParser, MediaEnv};
+    use html_css_parser::html::{rendered_text, Element};
+
+    fn element(tag_name: &str, attributes: &[(&str, &str)], children: Vec<Node>) -> Node {
+        Node::Element(Element {
+            tag_name: tag_name.to_string(),
+            attributes: attributes.iter().copied().collect(),
+            children,
+            source_span: None,
+        })
+    }
+    fn text(text: &str) -> Node {
+        Node::Text(text.to_string())
+    }
+
+    // Built by hand because the parser trims text nodes, which loses the
+    // whitespace between inline elements.
+    let page = element("body", &[], vec![
+        text("\n  "),
+        element("h1", &[], vec![text("  Release   notes\n  ")]),
+        element("p", &[], vec![
+            text("\n    The "),
+            element("b", &[], vec![text("fast")]),
+            text("\n    path is "),
+            element("a", &[("href", "#")], vec![element("em", &[], vec![text("now")])]),
+            text("\n    default.\n  "),
+        ]),
+        element("pre", &[], vec![text("fn main() {\n    run();\n}")]),
+        element("div", &[("class", "code")], vec![text("  keep   this  ")]),
+        element("script", &[], vec![text("var hidden = 1;")]),
+        element("p", &[], vec![text("line one"), element("br", &[], vec![]), text("  line two ")]),
+        element("table", &[], vec![element("tr", &[], vec![
+            element("td", &[], vec![text("a")]),
+            element("td", &[], vec![text("b")]),
+        ])]),
+        text("\n"),
+    ]);
+
+    assert_eq!(
+        rendered_text(&page, None),
+        "Release notes\nThe fast path is now default.\nfn main() {\n    run();\n}\nkeep this\nline one\nline two\na b"
+    );
+
+    // Computed styles make the `.code` div keep its whitespace and can hide
+    // elements.
+    let css = ".code { white-space: pre; } h1 { display: none; }";
+    let stylesheet = CssParser::new(css).parse_stylesheet();
+    let styles = ComputedStyles::compute(&page, &stylesheet, &MediaEnv::default());
+    assert_eq!(styles.get(&[4], "white-space"), Some("pre"));
+    assert_eq!(
+        rendered_text(&page, Some(&styles)),
+        "The fast path is now default.\nfn main() {\n    run();\n}\n  keep   this  \nline one\nline two\na b"
+    );
+
+    assert_eq!(rendered_text(&text("  a \n b  "), None), "a b");
+}
+
+#[test]
+fn test_html_token_ref_conversions() {
+    use html_css_parser::html::HtmlTokenRef;
+
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<!DOCTYPE html><a href=\"/\">Home</a>").collect();
+    let refs: Vec<HtmlTokenRef> = tokens.iter().map(HtmlTokenRef::from).collect();
+    assert!(matches!(refs[0], HtmlTokenRef::Doctype { name: "html", public_id: None, .. }));
+    assert!(matches!(refs[1], HtmlTokenRef::StartTag { name: "a", attributes, .. } if attributes[0].1 == "/"));
+    assert_eq!(refs[2], HtmlTokenRef::Text("Home"));
+
+    let owned: Vec<HtmlToken> = refs.into_iter().map(HtmlToken::from).collect();
+    assert_eq!(owned, tokens);
+}
+
+#[test]
+fn test_html_named_character_references() {
+    let text = |input: &str| -> Vec<HtmlToken> { HtmlTokenizer::new(input).collect() };
+
+    assert_eq!(
+        text("&amp; &lt; &gt; &quot; &apos; &nbsp; &copy;"),
+        vec![HtmlToken::Text("& < > \" ' \u{a0} ©".to_string())]
+    );
+    assert_eq!(text("&hellip;&mdash;&rarr;&NotEqualTilde;"), vec![HtmlToken::Text("…—→\u{2242}\u{338}".to_string())]);
+
+    // Legacy names decode without a semicolon unless a letter or digit follows.
+    assert_eq!(text("fish &amp chips &copy 2024"), vec![HtmlToken::Text("fish & chips © 2024".to_string())]);
+    assert_eq!(text("&copy2024 &hellip"), vec![HtmlToken::Text("&copy2024 &hellip".to_string())]);
+
+    // Unknown and case-mismatched names stay as they are.
+    assert_eq!(text("&notarealentity; &Amp;"), vec![HtmlToken::Text("&notarealentity; &Amp;".to_string())]);
+
+    let nodes = HtmlParser::new("<p>&copy; 2024</p>").parse();
+    let Node::Element(p) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(p.children, vec![Node::Text("© 2024".to_string())]);
+}
+
+#[test]
+fn test_html_attribute_character_references() {
+    let tokens: Vec<HtmlToken> =
+        HtmlTokenizer::new(r#"<a href="?a=1&amp;b=2" title="&lt;3 &amp more" data-q="?x=1&copy=2&lang=en">"#).collect();
+    let HtmlToken::StartTag { attributes, .. } = &tokens[0] else {
+        panic!("expected a start tag, got {:?}", tokens[0]);
+    };
+    assert_eq!(attributes[0], ("href".to_string(), "?a=1&b=2".into()));
+    assert_eq!(attributes[1], ("title".to_string(), "<3 & more".into()));
+    // A legacy name followed by `=` is part of a query string.
+    assert_eq!(attributes[2], ("data-q".to_string(), "?x=1&copy=2&lang=en".into()));
+}
+
+#[test]
+fn test_html_numeric_character_references() {
+    let text = |input: &str| -> Vec<HtmlToken> { HtmlTokenizer::new(input).collect() };
+
+    assert_eq!(text("It&#8217;s &#x2014; &#X1F600;"), vec![HtmlToken::Text("It’s — 😀".to_string())]);
+    assert_eq!(text("&#65&#x42 c"), vec![HtmlToken::Text("AB c".to_string())]);
+
+    // Code points that can't be in a document become U+FFFD, and C1 controls
+    // are read as Windows-1252.
+    assert_eq!(
+        text("&#0;&#xD800;&#x110000;&#99999999999999;&#150;"),
+        vec![HtmlToken::Text("\u{fffd}\u{fffd}\u{fffd}\u{fffd}–".to_string())]
+    );
+    assert_eq!(text("&#; &#x; &#xZ;"), vec![HtmlToken::Text("&#; &#x; &#xZ;".to_string())]);
+
+    let tokens = text(r#"<img alt="caf&#233;" title="&#x26;&#38;">"#);
+    assert!(matches!(&tokens[0], HtmlToken::StartTag { attributes, .. }
+        if attributes[0].1 == "café" && attributes[1].1 == "&&"));
+}
+
+#[test]
+fn test_html_character_reference_decoding_can_be_disabled() {
+    use html_css_parser::html::HtmlParseOptions;
+
+    let options = HtmlParseOptions::default().decode_character_references(false);
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::with_options(r#"<a href="?a=1&amp;b=2">&#8217; &copy;</a>"#, &options).collect();
+    assert!(matches!(&tokens[0], HtmlToken::StartTag { attributes, .. } if attributes[0].1 == "?a=1&amp;b=2"));
+    assert_eq!(tokens[1], HtmlToken::Text("&#8217; &copy;".to_string()));
+}
+
+#[test]
+fn test_html_script_raw_text() {
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<script>if (a < b && c > d) { x = '<p>'; }</script ><p>after</p>").collect();
+    assert_eq!(tokens[1], HtmlToken::Text("if (a < b && c > d) { x = '<p>'; }".to_string()));
+    assert_eq!(tokens[2], HtmlToken::EndTag { name: "script".to_string() });
+    assert!(matches!(&tokens[3], HtmlToken::StartTag { name, .. } if name == "p"));
+
+    // Entities aren't decoded and `</scripts>` doesn't close the element.
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<SCRIPT>a &amp;&& '</scripts>'</SCRIPT>").collect();
+    assert_eq!(tokens[1], HtmlToken::Text("a &amp;&& '</scripts>'".to_string()));
+    assert_eq!(tokens.len(), 3);
+
+    // An unclosed script runs to the end of the input.
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<script>let x = 1 < 2; <div>").collect();
+    assert_eq!(tokens, vec![
+        HtmlToken::StartTag { name: "script".to_string(), attributes: vec![], self_closing: false },
+        HtmlToken::Text("let x = 1 < 2; <div>".to_string()),
+    ]);
+
+    let nodes = HtmlParser::new("<body><script>if (a < b) { go(); }</script><p>after</p></body>").parse();
+    let Node::Element(body) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    let Node::Element(script) = &body.children[0] else {
+        panic!("expected a script element, got {:?}", body.children[0]);
+    };
+    assert_eq!(script.children, vec![Node::Text("if (a < b) { go(); }".to_string())]);
+    assert!(matches!(&body.children[1], Node::Element(p) if p.tag_name == "p"));
+    assert_eq!(body.children.len(), 2);
+}
+
+#[test]
+fn test_html_style_raw_text() {
+    let css = "div > p { color: red } a[href^=\"http\"]::after { content: '<ext>' } /* </p> */ q { quotes: \"<<\" '>>' }";
+    let html = format!("<style media=\"screen\">{css}</style><p>after</p>");
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new(&html).collect();
+    assert_eq!(tokens[1], HtmlToken::Text(css.to_string()));
+    assert_eq!(tokens[2], HtmlToken::EndTag { name: "style".to_string() });
+    assert_eq!(tokens.len(), 6);
+
+    let nodes = HtmlParser::new(&format!("<head>{html}</head>")).parse();
+    let Node::Element(head) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    let Node::Element(style) = &head.children[0] else {
+        panic!("expected a style element, got {:?}", head.children[0]);
+    };
+    assert_eq!(style.children, vec![Node::Text(css.to_string())]);
+
+    let rules = html_css_parser::CssParser::new(&style.text_content()).parse();
+    assert_eq!(rules.len(), 3);
+}
+
+#[test]
+fn test_html_trim_text_option() {
+    use html_css_parser::html::{rendered_text, HtmlParseOptions};
+
+    let lossless = || HtmlParseOptions::default().trim_text(false);
+    let html = "<p>Hello <b>world</b>! <i>a</i> <i>b</i>\n</p>";
+
+    // By default whitespace around tags is dropped.
+    let nodes = HtmlParser::new(html).parse();
+    let Node::Element(p) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(p.children.len(), 5);
+    assert_eq!(nodes[0].to_html(), "<p>Hello<b>world</b>!<i>a</i><i>b</i></p>");
+
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::with_options(" <i>a</i> <i>b</i>", &lossless()).collect();
+    assert_eq!(tokens[0], HtmlToken::Text(" ".to_string()));
+    assert_eq!(tokens[4], HtmlToken::Text(" ".to_string()));
+
+    let nodes = HtmlParser::with_options(html, lossless()).parse();
+    assert_eq!(nodes[0].to_html(), html);
+    assert_eq!(rendered_text(&nodes[0], None), "Hello world! a b");
+
+    let pre = "<pre>\n  fn main() {\n\t  run();\n  }\n\n</pre>";
+    let nodes = HtmlParser::with_options(&format!("<div> {pre} </div>"), lossless()).parse();
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(div.children.len(), 3);
+    assert_eq!(div.children[1].to_html(), pre);
+
+    // Collapsing still applies, but keeps a space where whitespace was.
+    let options = lossless().collapse_whitespace(true);
+    let nodes = HtmlParser::with_options("<p> a \n <b>b</b>  c</p>", options).parse();
+    assert_eq!(nodes[0].to_html(), "<p> a <b>b</b> c</p>");
+}
+
+#[test]
+fn test_html_spanned_tokens() {
+    let html = "<p>\r\n  héllo</p>\r\n<!-- ü -->\n<br>";
+    let tokens: Vec<(HtmlToken, Span)> = HtmlTokenizer::new(html).spanned().collect();
+    let spans: Vec<Span> = tokens.iter().map(|(_, span)| *span).collect();
+
+    assert_eq!(tokens[0].0, HtmlToken::StartTag { name: "p".to_string(), attributes: vec![], self_closing: false });
+    assert_eq!(spans[0], Span::new(0, 3, 1, 1));
+    // Leading whitespace isn't part of the text's span, and `é` is two bytes
+    // but one column.
+    assert_eq!(tokens[1].0, HtmlToken::Text("héllo".to_string()));
+    assert_eq!(spans[1], Span::new(7, 13, 2, 3));
+    assert_eq!(spans[2], Span::new(13, 17, 2, 8));
+    assert_eq!(&html[spans[3].start..spans[3].end], "<!-- ü -->");
+    assert_eq!((spans[3].line, spans[3].column), (3, 1));
+    assert_eq!(spans[4], Span::new(31, 35, 4, 1));
+}
+
+#[test]
+fn test_html_element_source_span() {
+    let html = "<div id=\"a\">\n  <p>one<br>\n  <span>two</span>\n</div>";
+    let nodes = HtmlParser::new(html).parse();
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(div.source_span, Some(Span::new(0, html.len(), 1, 1)));
+
+    // `<p>` is never closed, so it ends with its last child.
+    let Node::Element(p) = &div.children[0] else {
+        panic!("expected an element, got {:?}", div.children[0]);
+    };
+    let p_span = p.source_span.unwrap();
+    assert_eq!(&html[p_span.start..p_span.end], "<p>one<br>\n  <span>two</span>");
+    assert_eq!((p_span.line, p_span.column), (2, 3));
+
+    let Node::Element(br) = &p.children[1] else {
+        panic!("expected an element, got {:?}", p.children[1]);
+    };
+    assert_eq!(br.source_span, Some(Span::new(21, 25, 2, 9)));
+
+    // Spans don't affect equality.
+    let reparsed = HtmlParser::new("<div id=\"a\"><p>one<br><span>two</span></p></div>").parse();
+    assert_eq!(reparsed, nodes);
+}
+
+#[test]
+fn test_html_next_token_spanned() {
+    let html = "<ul>\n  <li>One</li>\n  <li class=\"x\">\n    Two\n  </li>\n</ul>";
+    let mut tokenizer = HtmlTokenizer::new(html);
+    let mut positions = Vec::new();
+    while let Some((token, span)) = tokenizer.next_token_spanned() {
+        positions.push((token, span.line, span.column));
+    }
+
+    let tag = |name: &str| HtmlToken::StartTag { name: name.to_string(), attributes: vec![], self_closing: false };
+    let end = |name: &str| HtmlToken::EndTag { name: name.to_string() };
+    assert_eq!(positions[0], (tag("ul"), 1, 1));
+    assert_eq!(positions[1], (tag("li"), 2, 3));
+    assert_eq!(positions[2], (HtmlToken::Text("One".to_string()), 2, 7));
+    assert_eq!(positions[3], (end("li"), 2, 10));
+    assert_eq!((positions[4].1, positions[4].2), (3, 3));
+    assert_eq!((positions[5].1, positions[5].2), (4, 5));
+    assert_eq!(positions[6], (end("li"), 5, 3));
+    assert_eq!(positions[7], (end("ul"), 6, 1));
+    assert_eq!(positions.len(), 8);
+
+    // Unspanned calls in between don't throw positions off.
+    let mut tokenizer = HtmlTokenizer::new(html);
+    tokenizer.next_token();
+    tokenizer.next_token();
+    let (_, span) = tokenizer.next_token_spanned().unwrap();
+    assert_eq!(&html[span.start..span.end], "One");
+    assert_eq!((span.line, span.column), (2, 7));
+}
+
+#[test]
+fn test_html_bogus_comments() {
+    let tokens = |input: &str| -> Vec<HtmlToken> { HtmlTokenizer::new(input).collect() };
+    let comment = |text: &str| HtmlToken::Comment(text.to_string());
+
+    assert_eq!(tokens("<!WEIRD thing><p>"), vec![
+        comment("WEIRD thing"),
+        HtmlToken::StartTag { name: "p".to_string(), attributes: vec![], self_closing: false },
+    ]);
+    assert_eq!(tokens("<!>"), vec![comment("")]);
+
+    // `</>` disappears; other end tags not starting with a letter are comments.
+    assert_eq!(tokens("a</>b"), vec![HtmlToken::Text("a".to_string()), HtmlToken::Text("b".to_string())]);
+    assert_eq!(tokens("</ 3>x"), vec![comment(" 3"), HtmlToken::Text("x".to_string())]);
+    assert_eq!(tokens("</#if>"), vec![comment("#if")]);
+    // `</` at the end of the input stays text.
+    assert_eq!(tokens("a </"), vec![HtmlToken::Text("a </".to_string())]);
+
+    let nodes = HtmlParser::new("<div>a</ 3>b</div>").parse();
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element, got {:?}", nodes[0]);
+    };
+    assert_eq!(div.children, vec![Node::Text("a".to_string()), Node::Comment(" 3".to_string()), Node::Text("b".to_string())]);
+}
+
+#[test]
+fn test_html_processing_instructions() {
+    let tokens = |input: &str| -> Vec<HtmlToken> { HtmlTokenizer::new(input).collect() };
+    let instruction = |text: &str| HtmlToken::ProcessingInstruction(text.to_string());
+
+    let html = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html><html><body><p>x</p></body></html>";
+    let found = tokens(html);
+    assert_eq!(found[0], instruction("xml version=\"1.0\" encoding=\"utf-8\""));
+    assert!(matches!(&found[1], HtmlToken::Doctype { name, .. } if name == "html"));
+
+    // An instruction ends at `?>`, not at a `>` inside it, or else at the end
+    // of the input.
+    assert_eq!(tokens("<?php if ($a->b > 1) echo '>'; ?>after"), vec![
+        instruction("php if ($a->b > 1) echo '>'; "),
+        HtmlToken::Text("after".to_string()),
+    ]);
+    assert_eq!(tokens("<??>"), vec![instruction("")]);
+    assert_eq!(tokens("a<?unclosed > b"), vec![HtmlToken::Text("a".to_string()), instruction("unclosed > b")]);
+    let spans: Vec<Span> = HtmlTokenizer::new("<?pi x?><b>").spanned().map(|(_, span)| span).collect();
+    assert_eq!((spans[0].start, spans[0].end, spans[1].start), (0, 8, 8));
+
+    // The tree leaves them out, and the rest of the document is unaffected.
+    let document = HtmlParser::new(html).parse_full_document();
+    assert_eq!(document.doctype.as_ref().unwrap().name, "html");
+    assert_eq!(document.children.iter().map(Node::to_html).collect::<String>(), "<html><body><p>x</p></body></html>");
+    let nodes = HtmlParser::new("<div>a<?php echo $b->c; ?>b</div>").parse();
+    assert_eq!(nodes.iter().map(Node::to_html).collect::<String>(), "<div>ab</div>");
+    let streamed: Vec<Node> = html_css_parser::html::StreamingHtmlParser::new("<div>a<?php echo $b->c; ?>b</div>").collect();
+    assert_eq!(streamed, nodes);
+
+    let links = html_css_parser::html::extract::only_tags("<?php echo '<a>'; ?><a>x</a>", &["a"]);
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].text_content(), "x");
+}
+
+#[test]
+fn test_html_comment_edge_cases() {
+    let comments = |input: &str| -> (Vec<HtmlToken>, Vec<TokenizeErrorKind>) {
+        let (tokens, errors) = HtmlTokenizer::tokenize_with_errors(input);
+        (tokens, errors.into_iter().map(|error| error.kind).collect())
+    };
+    let comment = |text: &str| HtmlToken::Comment(text.to_string());
+
+    // Only `-->` ends a comment; inner `--` is kept.
+    assert_eq!(comments("<!-- a -- b -->"), (vec![comment(" a -- b ")], vec![]));
+    assert_eq!(comments("<!--a--->"), (vec![comment("a-")], vec![]));
+    assert_eq!(comments("<!--a-- >b-->"), (vec![comment("a-- >b")], vec![]));
+    assert_eq!(comments("<!----->"), (vec![comment("-")], vec![]));
+
+    // `--!>` closes it too, as an error.
+    assert_eq!(
+        comments("<!-- a --!>x"),
+        (vec![comment(" a "), HtmlToken::Text("x".to_string())], vec![TokenizeErrorKind::IncorrectlyClosedComment])
+    );
+    assert_eq!(comments("<!--a--!-->"), (vec![comment("a--!")], vec![]));
+    assert_eq!(comments("<!--a--!"), (vec![comment("a")], vec![TokenizeErrorKind::EofInComment]));
+
+    // Abruptly closed comments are empty.
+    for input in ["<!-->", "<!--->"] {
+        assert_eq!(comments(input), (vec![comment("")], vec![TokenizeErrorKind::AbruptClosingOfEmptyComment]));
+    }
+
+    // Conditional comments, as in old email templates, are single comments
+    // whose markup isn't parsed.
+    let html = "<!--[if mso]><table><tr><td width=\"600\"><![endif]--><div>x</div><!--[if mso]></td></tr></table><![endif]-->";
+    let (tokens, errors) = comments(html);
+    assert!(errors.is_empty());
+    assert_eq!(tokens[0], comment("[if mso]><table><tr><td width=\"600\"><![endif]"));
+    assert_eq!(tokens.last(), Some(&comment("[if mso]></td></tr></table><![endif]")));
+    let nodes = HtmlParser::new(html).parse();
+    assert_eq!(nodes.len(), 3);
+    assert!(matches!(&nodes[1], Node::Element(div) if div.tag_name == "div"));
+
+    let html = "<!--[if IE]><p>IE only -- really</p><![endif]--><!--[if !IE]>--><p>Others</p><!--<![endif]-->";
+    let (tokens, errors) = comments(html);
+    assert!(errors.is_empty());
+    assert_eq!(tokens[0], comment("[if IE]><p>IE only -- really</p><![endif]"));
+    assert_eq!(tokens[1], comment("[if !IE]>"));
+    assert_eq!(tokens.last(), Some(&comment("<![endif]")));
+}
+
+#[test]
+fn test_html_tokenize_errors() {
+    let kinds = |input: &str| -> Vec<TokenizeErrorKind> {
+        HtmlTokenizer::tokenize_with_errors(input).1.into_iter().map(|error| error.kind).collect()
+    };
+
+    assert!(kinds("<!DOCTYPE html><p class=\"a\" id=b>Hi</p><!-- ok -->").is_empty());
+    assert_eq!(kinds("<?xml?>"), vec![TokenizeErrorKind::UnexpectedQuestionMarkInsteadOfTagName]);
+    assert_eq!(kinds("<!x>"), vec![TokenizeErrorKind::IncorrectlyOpenedComment]);
+    assert_eq!(kinds("a</>"), vec![TokenizeErrorKind::MissingEndTagName]);
+    // Dropped one after another without recursing.
+    let html = format!("{}<p>", "</>".repeat(1_000_000));
+    assert_eq!(HtmlTokenizer::new(&html).collect::<Vec<_>>().len(), 1);
+    assert_eq!(kinds(&html).len(), 1_000_000);
+    assert_eq!(kinds("</ 3>"), vec![TokenizeErrorKind::InvalidFirstCharacterOfTagName]);
+    assert_eq!(kinds("<!-- open"), vec![TokenizeErrorKind::EofInComment]);
+    assert_eq!(kinds("<!DOCTYPE html"), vec![TokenizeErrorKind::EofInDoctype]);
+    assert_eq!(kinds("<div class=\"a"), vec![TokenizeErrorKind::EofInTag]);
+    assert_eq!(kinds("<p></p"), vec![TokenizeErrorKind::EofInTag]);
+    assert_eq!(kinds("<p a=1 a=2>"), vec![TokenizeErrorKind::DuplicateAttribute]);
+    assert_eq!(kinds("<p a=>"), vec![TokenizeErrorKind::MissingAttributeValue]);
+    assert_eq!(kinds("<p a=b\"c>"), vec![TokenizeErrorKind::UnexpectedCharacterInUnquotedAttributeValue]);
+    assert_eq!(kinds("<p a=\"1\"b=\"2\">"), vec![TokenizeErrorKind::MissingWhitespaceBetweenAttributes]);
+    assert_eq!(kinds("<p \"a>"), vec![TokenizeErrorKind::UnexpectedCharacterInAttributeName]);
+    assert_eq!(kinds("<p></p class=x>"), vec![TokenizeErrorKind::EndTagWithAttributes]);
+
+    // Tokens don't change, and errors carry positions.
+    let html = "<p>\n  <img src=\"a\"alt=x src=b>\n<!-- end";
+    let (tokens, errors) = HtmlTokenizer::tokenize_with_errors(html);
+    assert_eq!(tokens, HtmlTokenizer::new(html).collect::<Vec<_>>());
+    let found: Vec<_> = errors.iter().map(|error| (error.kind.code(), error.span.line, error.span.column)).collect();
+    assert_eq!(found, vec![
+        ("missing-whitespace-between-attributes", 2, 15),
+        ("duplicate-attribute", 2, 21),
+        ("eof-in-comment", 3, 9),
+    ]);
+    assert_eq!(
+        errors[1].to_string(),
+        "attribute appears more than once; the first value is kept (duplicate-attribute) at line 2, column 21"
+    );
+
+    // The accessor reports errors as tokens are read.
+    let mut tokenizer = HtmlTokenizer::new("<p a=1 a=2><!x>");
+    tokenizer.next_token();
+    assert_eq!(tokenizer.errors().len(), 1);
+    tokenizer.next_token();
+    assert_eq!(tokenizer.errors()[1].kind, TokenizeErrorKind::IncorrectlyOpenedComment);
+}
+
+#[test]
+fn test_html_escapable_raw_text() {
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<script>if(a<b){}</script>").collect();
+    assert_eq!(tokens[1], HtmlToken::Text("if(a<b){}".to_string()));
+
+    // `textarea` and `title` hold text too, but decode character references.
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new("<textarea><b>1 &lt; 2</b></textarea><p>").collect();
+    assert_eq!(tokens[1], HtmlToken::Text("<b>1 < 2</b>".to_string()));
+    assert_eq!(tokens[2], HtmlToken::EndTag { name: "textarea".to_string() });
+    assert!(matches!(&tokens[3], HtmlToken::StartTag { name, .. } if name == "p"));
+
+    let nodes = HtmlParser::new("<head><TITLE>Tom &amp; <Jerry></Title><meta charset=utf-8></head>").parse();
+    let Node::Element(head) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    let Node::Element(title) = &head.children[0] else {
+        panic!("expected a title element, got {:?}", head.children[0]);
+    };
+    assert_eq!(title.children, vec![Node::Text("Tom & <Jerry>".to_string())]);
+    assert!(matches!(&head.children[1], Node::Element(meta) if meta.tag_name == "meta"));
+
+    let options = html_css_parser::html::HtmlParseOptions::default().decode_character_references(false);
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::with_options("<title>a &amp; b</title>", &options).collect();
+    assert_eq!(tokens[1], HtmlToken::Text("a &amp; b".to_string()));
+}
+
+#[test]
+fn test_html_optional_end_tags() {
+    let tag_names = |nodes: &[Node]| -> Vec<String> {
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Element(element) => Some(element.tag_name.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    let root = |html: &str| -> Element {
+        match HtmlParser::new(html).parse().into_iter().next() {
+            Some(Node::Element(element)) => element,
+            other => panic!("expected an element, got {:?}", other),
+        }
+    };
+
+    let ul = root("<ul><li>A<li>B</ul>");
+    assert_eq!(tag_names(&ul.children), vec!["li", "li"]);
+    assert_eq!(ul.children[0].text_content(), "A");
+    assert_eq!(ul.children[1].text_content(), "B");
+
+    let mut parser = HtmlParser::new("<body><p>one<p>two<div>three</div></body>");
+    let body = match parser.parse().remove(0) {
+        Node::Element(element) => element,
+        other => panic!("expected an element, got {:?}", other),
+    };
+    assert_eq!(tag_names(&body.children), vec!["p", "p", "div"]);
+    // Leaving out these end tags is valid, not a recovery.
+    assert_eq!(parser.stats().recoveries, 0);
+
+    let dl = root("<dl><dt>term<dd>one<dd>two<dt>next</dl>");
+    assert_eq!(tag_names(&dl.children), vec!["dt", "dd", "dd", "dt"]);
+
+    let select = root("<select><option>a<option>b<optgroup><option>c</select>");
+    assert_eq!(tag_names(&select.children), vec!["option", "option", "optgroup"]);
+
+    let table = root("<table><thead><tr><th>h<tbody><tr><td>1<td>2<tr><td>3</table>");
+    assert_eq!(tag_names(&table.children), vec!["thead", "tbody"]);
+    let Node::Element(tbody) = &table.children[1] else { unreachable!() };
+    assert_eq!(tag_names(&tbody.children), vec!["tr", "tr"]);
+    let Node::Element(row) = &tbody.children[0] else { unreachable!() };
+    assert_eq!(tag_names(&row.children), vec!["td", "td"]);
+
+    // Nested lists close only their own items.
+    let ul = root("<ul><li>A<ul><li>A1<li>A2</ul><li>B</ul>");
+    assert_eq!(tag_names(&ul.children), vec!["li", "li"]);
+    let Node::Element(first) = &ul.children[0] else { unreachable!() };
+    assert_eq!(tag_names(&first.children), vec!["ul"]);
+
+    // Inline elements don't close a paragraph.
+    let p = root("<p>a <b>b</b> <span>c</span>");
+    assert_eq!(tag_names(&p.children), vec!["b", "span"]);
+
+    let items = html_css_parser::html::extract::only_tags("<ul><li>A<li>B</ul>", &["li"]);
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1].text_content(), "B");
+}
+
+#[test]
+fn test_html_implied_end_tags_through_inline_elements() {
+    let reparse = |html: &str| -> String { HtmlParser::new(html).parse().iter().map(Node::to_html).collect() };
+
+    // A start tag ends the element it implies the end of even with inline
+    // elements still open inside it, which end too.
+    assert_eq!(reparse("<ul><li><span>One<li>Two</ul>"), "<ul><li><span>One</span></li><li>Two</li></ul>");
+    assert_eq!(reparse("<ul><li><div>One<li>Two</ul>"), "<ul><li><div>One</div></li><li>Two</li></ul>");
+    assert_eq!(reparse("<dl><dt><span>a<dd>b</dl>"), "<dl><dt><span>a</span></dt><dd>b</dd></dl>");
+    assert_eq!(reparse("<p>a<li>b"), "<p>a</p><li>b</li>");
+    assert_eq!(reparse("<p><span>one<h1>title</h1>"), "<p><span>one</span></p><h1>title</h1>");
+    assert_eq!(reparse("<table><tr><td><em>1<td>2</table>"), "<table><tr><td><em>1</em></td><td>2</td></tr></table>");
+    // Formatting elements are reopened in the content that follows, but not
+    // past a table cell.
+    assert_eq!(reparse("<p><b>bold<div>more</div>"), "<p><b>bold</b></p><div><b>more</b></div>");
+    assert_eq!(reparse("<ul><li><a href=\"#\">One<li>Two</ul>"), "<ul><li><a href=\"#\">One</a></li><li><a href=\"#\">Two</a></li></ul>");
+    assert_eq!(
+        reparse("<table><tr><td><b>1<tr><td>2</table>"),
+        "<table><tr><td><b>1</b></td></tr><tr><td>2</td></tr></table>"
+    );
+
+    // The search stops at elements a start tag can't close past.
+    assert_eq!(
+        reparse("<ul><li>a<ul><li>b<li>c</ul><li>d</ul>"),
+        "<ul><li>a<ul><li>b</li><li>c</li></ul></li><li>d</li></ul>"
+    );
+    assert_eq!(reparse("<ul><li><section>a<li>b</section></ul>"), "<ul><li><section>a<li>b</li></section></li></ul>");
+    assert_eq!(reparse("<p><button>a<div>b</div></button>"), "<p><button>a<div>b</div></button></p>");
+    assert_eq!(
+        reparse("<table><tr><td><table><tr><td>x</table>y</table>"),
+        "<table><tr><td><table><tr><td>x</td></tr></table>y</td></tr></table>"
+    );
+
+    let items = html_css_parser::html::extract::only_tags("<ul><li>A<p>a<li>B<ul><li>C</ul></ul>", &["li"]);
+    assert_eq!(items.iter().map(Element::text_content).collect::<Vec<_>>(), ["A", "B", "C"]);
+}
+
+#[test]
+fn test_html_boolean_attributes() {
+    assert_eq!(
+        start_tag_attributes(r#"<input disabled value="" checked=checked hidden= >"#),
+        vec![flag("disabled"), attr("value", ""), attr("checked", "checked"), attr("hidden", "")]
+    );
+
+    let nodes = HtmlParser::new(r#"<input DISABLED value="" name=q>"#).parse();
+    let Node::Element(input) = &nodes[0] else {
+        panic!("expected an element, got {:?}", nodes[0]);
+    };
+    assert!(input.has_attribute("disabled"));
+    assert!(!input.has_attribute("readonly"));
+    assert_eq!(input.attribute("disabled"), Some(""));
+    assert_eq!(input.attribute_value("disabled"), Some(&AttrValue::Bool));
+    assert_eq!(input.attribute_value("value"), Some(&AttrValue::Value(String::new())));
+    assert_eq!(input.attribute("name"), Some("q"));
+    assert_eq!(input.attribute("title"), None);
+
+    // Boolean attributes serialize without a value.
+    assert_eq!(nodes[0].to_html(), r#"<input DISABLED value="" name="q">"#);
+}
+
+#[test]
+fn test_html_boolean_attributes_are_kept_apart_from_empty_values() {
+    use html_css_parser::html::{minify, ChunkedHtmlTokenizer, MinifyOptions, PrettyOptions};
+
+    let html = r#"<input checked disabled="" value="x">"#;
+    let expected = vec![flag("checked"), attr("disabled", ""), attr("value", "x")];
+    assert_eq!(start_tag_attributes(html), expected);
+
+    let mut chunked = ChunkedHtmlTokenizer::new();
+    let mut tokens = chunked.feed(html);
+    tokens.extend(chunked.finish());
+    let Some(HtmlToken::StartTag { attributes, .. }) = tokens.first() else {
+        panic!("expected a start tag, got {:?}", tokens);
+    };
+    assert_eq!(attributes.iter().map(|(name, value)| (name.clone(), value.clone())).collect::<Vec<_>>(), expected);
+
+    let nodes = HtmlParser::new(html).parse();
+    let Node::Element(input) = &nodes[0] else {
+        panic!("expected an element, got {:?}", nodes[0]);
+    };
+    assert_ne!(input.attribute_value("checked"), input.attribute_value("disabled"));
+
+    // Each serializer writes the flag bare and the empty value quoted, so
+    // both survive a second parse.
+    assert_eq!(nodes[0].to_html(), html);
+    assert_eq!(nodes[0].to_html_pretty(&PrettyOptions::default()), html);
+    assert_eq!(minify(&nodes[0], &MinifyOptions::default()), r#"<input checked disabled="" value=x>"#);
+    for output in [nodes[0].to_html(), minify(&nodes[0], &MinifyOptions::default())] {
+        assert_eq!(HtmlParser::new(&output).parse(), nodes);
+    }
+}
+
+#[test]
+fn test_html_attributes_serialize_in_source_order() {
+    use html_css_parser::html::{minify, MinifyOptions, PrettyOptions};
+
+    let html = r#"<a href="x" class="y" id="z">link</a>"#;
+    let nodes = HtmlParser::new(html).parse();
+    let Node::Element(a) = &nodes[0] else {
+        panic!("expected an element, got {:?}", nodes[0]);
+    };
+    assert_eq!(a.attributes.keys().map(String::as_str).collect::<Vec<_>>(), ["href", "class", "id"]);
+    assert_eq!(a.get_attribute("class"), Some("y"));
+    assert_eq!(a.get_attribute("CLASS"), a.attribute("class"));
+    assert_eq!(a.get_attribute("title"), None);
+
+    assert_eq!(nodes[0].to_html(), html);
+    assert_eq!(nodes[0].to_html_pretty(&PrettyOptions::default()), html);
+    assert_eq!(minify(&nodes[0], &MinifyOptions::default()), "<a href=x class=y id=z>link</a>");
+
+    let reversed = r#"<a id="z" class="y" href="x">link</a>"#;
+    assert_eq!(HtmlParser::new(reversed).parse()[0].to_html(), reversed);
+}
+
+#[test]
+fn test_html_attribute_order_and_duplicates() {
+    use html_css_parser::html::{Attributes, HtmlParseOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let nodes = HtmlParser::new(r#"<a data-b="1" data-a="2" href="/x" data-b="3" HREF="/y">x</a>"#).parse();
+    let Node::Element(a) = &nodes[0] else {
+        panic!("expected an element, got {:?}", nodes[0]);
+    };
+    // Source order, and the first of each name wins.
+    let names: Vec<&str> = a.attributes.keys().map(String::as_str).collect();
+    assert_eq!(names, ["data-b", "data-a", "href"]);
+    assert_eq!(a.attribute("data-b"), Some("1"));
+    assert_eq!(a.attributes.get("href"), Some(&"/x".into()));
+    assert_eq!(nodes[0].to_html(), r#"<a data-b="1" data-a="2" href="/x">x</a>"#);
+
+    // Equality doesn't depend on order.
+    let reordered: Attributes = [("href", "/x"), ("data-a", "2"), ("data-b", "1")].into_iter().collect();
+    assert_eq!(a.attributes, reordered);
+
+    let mut attributes = reordered;
+    assert_eq!(attributes.insert("data-a", "9"), Some("2".into()));
+    attributes.insert("title", AttrValue::Bool);
+    assert_eq!(attributes.remove("href"), Some("/x".into()));
+    let entries: Vec<(String, AttrValue)> = attributes.into_iter().collect();
+    assert_eq!(entries, vec![attr("data-a", "9"), attr("data-b", "1"), flag("title")]);
+
+    static DUPLICATES: AtomicUsize = AtomicUsize::new(0);
+    fn on_duplicate(name: &str, span: Span) {
+        assert_eq!(name, "id");
+        assert_eq!((span.start, span.end), (10, 12));
+        DUPLICATES.fetch_add(1, Ordering::SeqCst);
+    }
+    let options = HtmlParseOptions::default().on_duplicate_attribute(on_duplicate);
+    let nodes = HtmlParser::with_options(r#"<p id=one id=two>"#, options).parse();
+    assert_eq!(DUPLICATES.load(Ordering::SeqCst), 1);
+    assert!(matches!(&nodes[0], Node::Element(p) if p.attribute("id") == Some("one")));
+}
+
+#[test]
+fn test_html_element_lookups() {
+    // The document from the CLI's `demo` command.
+    let html = r##"<!DOCTYPE html>
+<html>
+<head>
+    <title>Demo Page</title>
+</head>
+<body>
+    <div class="container">
+        <h1 id="title">Hello World</h1>
+        <p>This is a <strong>demo</strong> page.</p>
+    </div>
+</body>
+</html>"##;
+    let Some(Node::Element(document)) = HtmlParser::new(html).parse_document() else {
+        panic!("expected a document element");
+    };
+
+    let title = document.get_element_by_id("title").unwrap();
+    assert_eq!(title.tag_name, "h1");
+    assert_eq!(title.text_content(), "Hello World");
+    assert!(document.get_element_by_id("Title").is_none());
+    assert!(document.get_element_by_id("missing").is_none());
+
+    let tags = |elements: Vec<&Element>| -> Vec<String> { elements.iter().map(|e| e.tag_name.clone()).collect() };
+    assert_eq!(tags(document.get_elements_by_tag_name("P")), ["p"]);
+    assert_eq!(tags(document.get_elements_by_tag_name("html")), ["html"]);
+    assert_eq!(document.get_elements_by_tag_name("*").len(), 8);
+    assert_eq!(tags(document.get_elements_by_class_name("container")), ["div"]);
+    assert!(document.get_elements_by_class_name("").is_empty());
+
+    // Document order, and every listed class has to be present.
+    let nodes = HtmlParser::new(r#"<ul><li class="a b">1</li><li class="b">2<span class="b a">3</span></li></ul>"#).parse();
+    let Node::Element(list) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    let texts = |elements: Vec<&Element>| -> Vec<String> { elements.iter().map(|e| e.text_content()).collect() };
+    assert_eq!(texts(list.get_elements_by_class_name("b")), ["1", "23", "3"]);
+    assert_eq!(texts(list.get_elements_by_class_name(" b  a ")), ["1", "3"]);
+    assert_eq!(texts(list.get_elements_by_tag_name("LI")), ["1", "23"]);
+}
+
+#[test]
+fn test_html_streaming_parser_matches_eager_parser() {
+    use html_css_parser::html::{HtmlParseOptions, StreamingHtmlParser};
+
+    let mut html = String::from("<!DOCTYPE html>\n");
+    for i in 0..2000 {
+        html.push_str(&format!(
+            "<section id=s{i} class=\"item {}\"><h2>Item {i}</h2><!-- note {i} -->\n\
+             <ul><li>one<li>two &amp; three<li><a href=\"/{i}\">link</a></ul>\n\
+             <p>Text <b>bold</b> more<p>second</span><script>if (a < {i}) {{}}</script></section>\n",
+            if i % 2 == 0 { "even" } else { "odd" }
+        ));
+        if i % 100 == 0 {
+            html.push_str("</div> stray text <br>\n");
+        }
+    }
+
+    let eager = HtmlParser::new(&html).parse();
+    let mut streaming = StreamingHtmlParser::new(&html);
+    let mut count = 0;
+    for (streamed, expected) in (&mut streaming).zip(&eager) {
+        assert_eq!(&streamed, expected);
+        count += 1;
+    }
+    assert_eq!(count, eager.len());
+    assert!(streaming.next().is_none());
+
+    let mut parser = HtmlParser::new(&html);
+    parser.parse();
+    assert_eq!(streaming.stats(), parser.stats());
+
+    // Spans and options carry over.
+    let options = || HtmlParseOptions::default().trim_text(false).collapse_whitespace(true);
+    let input = "<p>a  <i>b</i>\n</p> <div>x</div>";
+    let streamed: Vec<Node> = StreamingHtmlParser::with_options(input, options()).collect();
+    assert_eq!(streamed, HtmlParser::with_options(input, options()).parse());
+    let Node::Element(div) = &streamed[2] else {
+        panic!("expected an element, got {:?}", streamed[2]);
+    };
+    assert_eq!(div.source_span.map(|span| &input[span.start..span.end]), Some("<div>x</div>"));
+}
+
+#[test]
+fn test_html_tokenizer_truncated_input() {
+    let comment = |text: &str| HtmlToken::Comment(text.to_string());
+    let tokenize = |input: &str| HtmlTokenizer::tokenize_with_errors(input);
+
+    let (tokens, errors) = tokenize("<div class=\"x");
+    assert!(matches!(&tokens[..], [HtmlToken::StartTag { name, attributes, .. }]
+        if name == "div" && attributes[0].1 == "x"));
+    assert_eq!(errors[0].kind, TokenizeErrorKind::EofInTag);
+    assert_eq!(errors[0].span.start, 13);
+
+    assert_eq!(tokenize("<!-- a --").0, vec![comment(" a ")]);
+    assert_eq!(tokenize("<!-- a -").0, vec![comment(" a ")]);
+    assert_eq!(tokenize("<!-- a").1[0].kind, TokenizeErrorKind::EofInComment);
+    assert_eq!(tokenize("<!--").0, vec![comment("")]);
+
+    // `<!-->` and `<!--->` close at once.
+    let (tokens, errors) = tokenize("<!-->a<!--->b");
+    assert_eq!(tokens, vec![comment(""), HtmlToken::Text("a".to_string()), comment(""), HtmlToken::Text("b".to_string())]);
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|error| error.kind == TokenizeErrorKind::AbruptClosingOfEmptyComment));
+    let elements = html_css_parser::html::extract::only_tags("<!--><p>x</p>", &["p"]);
+    assert_eq!(elements.len(), 1);
+
+    // Every prefix of a document tokenizes without panicking, and anything
+    // left open at the end is reported there.
+    let html = "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"x\">\n\
+        <html lang=en><head><title>T &amp; é</title><style>p > a { color: red }</style></head>\n\
+        <body><!-- note --><p class='a b' data-x=1 hidden>Hi<br/>there</p><?pi x?>\n\
+        <script>if (a < b) {}</script><textarea>1 &lt; 2</textarea></ p></body></html>";
+    let open_at_end = [
+        TokenizeErrorKind::EofInTag,
+        TokenizeErrorKind::EofInComment,
+        TokenizeErrorKind::EofInDoctype,
+    ];
+    for end in (0..=html.len()).filter(|&end| html.is_char_boundary(end)) {
+        let input = &html[..end];
+        let (tokens, errors) = tokenize(input);
+        assert_eq!(tokens, HtmlTokenizer::new(input).collect::<Vec<_>>(), "{input:?}");
+        for error in &errors {
+            assert!(error.span.start <= error.span.end && error.span.end <= input.len(), "{input:?}: {error:?}");
+            if open_at_end.contains(&error.kind) {
+                assert_eq!(error.span.start, input.len(), "{input:?}: {error:?}");
+            }
+        }
+        let spans: Vec<Span> = HtmlTokenizer::new(input).spanned().map(|(_, span)| span).collect();
+        assert!(spans.windows(2).all(|pair| pair[0].end <= pair[1].start), "{input:?}");
+        assert!(spans.last().is_none_or(|span| span.end <= input.len()), "{input:?}");
+        HtmlParser::new(input).parse();
+    }
+
+    // Cut inside a tag, the partial tag is still a token.
+    let (tokens, errors) = tokenize(&html[..html.find("hidden").unwrap() + 3]);
+    assert!(matches!(tokens.last(), Some(HtmlToken::StartTag { name, .. }) if name == "p"));
+    assert_eq!(errors.last().map(|error| error.kind), Some(TokenizeErrorKind::EofInTag));
+}
+
+#[test]
+fn test_html_parse_with_diagnostics() {
+    let html = "<div><span>text</div></p><ul><li>one<li>two</ul><p>end";
+    let (nodes, errors) = HtmlParser::new(html).parse_with_diagnostics();
+    assert_eq!(nodes, HtmlParser::new(html).parse());
+
+    let unclosed: Vec<&str> = errors
+        .iter()
+        .filter_map(|error| match error {
+            HtmlError::UnclosedElement { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(unclosed, ["span"]);
+    let stray: Vec<(&str, usize)> = errors
+        .iter()
+        .filter_map(|error| match error {
+            HtmlError::UnexpectedEndTag { name, span } => Some((name.as_str(), span.column)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stray, [("p", 22)]);
+    assert!(errors.windows(2).all(|pair| pair[0].span().unwrap().start <= pair[1].span().unwrap().start));
+
+    // Tokenizer errors are included.
+    let (_, errors) = HtmlParser::new("<p a=1 a=2>x</p><div>").parse_with_diagnostics();
+    assert!(matches!(&errors[0], HtmlError::Tokenize(error) if error.kind == TokenizeErrorKind::DuplicateAttribute));
+    assert!(matches!(&errors[1], HtmlError::UnclosedElement { name, .. } if name == "div"));
+    assert_eq!(errors.len(), 2);
+
+    let (_, errors) = HtmlParser::new("<ul><li>a</li></ul>").parse_with_diagnostics();
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_html_mismatched_and_unclosed_tags() {
+    let reparse = |html: &str| -> String { HtmlParser::new(html).parse().iter().map(Node::to_html).collect() };
+
+    // An end tag closes everything up to the element it names.
+    assert_eq!(reparse("<div><p>unclosed</div>after"), "<div><p>unclosed</p></div>after");
+    // One naming no open element is dropped.
+    assert_eq!(reparse("<div><span>a</p>b</span></div>"), "<div><span>ab</span></div>");
+    // Unclosed elements end with the input.
+    assert_eq!(reparse("<div><section><p>end"), "<div><section><p>end</p></section></div>");
+
+    // Misnested formatting: `</b>` closes the `<i>` inside it, and the
+    // later `</i>` then matches nothing.
+    assert_eq!(reparse("<b><i>text</b></i>"), "<b><i>text</i></b>");
+    // Content after the `</b>` is still italic: the `<i>` is reopened.
+    assert_eq!(reparse("<b><i>x</b>y</i>z"), "<b><i>x</i></b><i>y</i>z");
+    assert_eq!(
+        reparse(r#"<p><b class="k"><i>one</p><p>two</i> three</b></p>"#),
+        r#"<p><b class="k"><i>one</i></b></p><p><b class="k"><i>two</i>three</b></p>"#
+    );
+    // A link isn't reopened around another link.
+    assert_eq!(reparse(r#"<div><a href="1">x</div><a href="2">y</a>"#), r#"<div><a href="1">x</a></div><a href="2">y</a>"#);
+
+    let (_, errors) = HtmlParser::new("<b><i>text</b></i>").parse_with_diagnostics();
+    assert!(matches!(&errors[..], [
+        HtmlError::UnclosedElement { name: unclosed, .. },
+        HtmlError::UnexpectedEndTag { name: stray, .. },
+    ] if unclosed == "i" && stray == "i"));
+
+    // The streaming parser recovers the same way.
+    let html = "<b><i>x</b>y</i><div><span>a</p>b</div>";
+    assert_eq!(html_css_parser::html::StreamingHtmlParser::new(html).collect::<Vec<_>>(), HtmlParser::new(html).parse());
+}
+
+#[test]
+fn test_html_end_tag_recovery() {
+    let reparse = |html: &str| -> String { HtmlParser::new(html).parse().iter().map(Node::to_html).collect() };
+
+    // Trees browsers build for the same input.
+    assert_eq!(reparse("<div><span>a</b>b</div><p>c</p>"), "<div><span>ab</span></div><p>c</p>");
+    assert_eq!(reparse("<b><i>x</b>y</i>"), "<b><i>x</i></b><i>y</i>");
+    assert_eq!(reparse("<div><p>a</div>b"), "<div><p>a</p></div>b");
+    assert_eq!(reparse("<ul><li>one<li>two</ul><p>after"), "<ul><li>one</li><li>two</li></ul><p>after</p>");
+    // Browsers turn a stray `</p>` into an empty paragraph; it's dropped
+    // like any other end tag that matches nothing.
+    assert_eq!(reparse("</p>a<p>b</p>"), "a<p>b</p>");
+    assert_eq!(reparse("a</div></span>b"), "ab");
+
+    // However tags are misnested, every piece of text is kept, in order.
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let pieces = ["<b>", "</b>", "<i>", "</i>", "<div>", "</div>", "<p>", "</p>", "<span>", "</span>", "<li>", "</ul>", "<a>", "</a>"];
+    for _ in 0..500 {
+        let mut html = String::new();
+        let mut text = String::new();
+        for _ in 0..next() % 24 {
+            if next() % 3 == 0 {
+                let letter = char::from(b'a' + (next() % 26) as u8);
+                html.push(letter);
+                text.push(letter);
+            } else {
+                html.push_str(pieces[(next() % pieces.len() as u64) as usize]);
+            }
+        }
+        let nodes = HtmlParser::new(&html).parse();
+        assert_eq!(nodes.iter().map(Node::text_content).collect::<String>(), text, "{html}");
+        let streamed: Vec<Node> = html_css_parser::html::StreamingHtmlParser::new(&html).collect();
+        assert_eq!(streamed, nodes, "{html}");
+    }
+}
+
+#[test]
+fn test_html_tokenizer_checkpoint() {
+    let html = "<p title=\"café\">naïve ☃</p><!-- template:x --><script>a <b></script><b x=1 x=2>é";
+    let expected: Vec<(HtmlToken, Span)> = HtmlTokenizer::new(html).spanned().collect();
+
+    // Restoring to any point between tokens reads the same tokens and spans
+    // again.
+    for split in 0..=expected.len() {
+        let mut tokenizer = HtmlTokenizer::new(html);
+        for _ in 0..split {
+            tokenizer.next_token_spanned();
+        }
+        let checkpoint = tokenizer.checkpoint();
+        let errors = tokenizer.errors().len();
+        let position = tokenizer.byte_position();
+
+        let rest: Vec<(HtmlToken, Span)> = std::iter::from_fn(|| tokenizer.next_token_spanned()).collect();
+        assert_eq!(rest, expected[split..]);
+        // Past the end, and back.
+        assert_eq!(tokenizer.next_token(), None);
+        assert_eq!(tokenizer.peek_token(), None);
+        tokenizer.restore(checkpoint.clone());
+        assert_eq!(tokenizer.byte_position(), position);
+        assert_eq!(tokenizer.errors().len(), errors);
+
+        assert_eq!(tokenizer.peek_token(), expected.get(split).map(|(token, _)| token.clone()));
+        assert_eq!(tokenizer.peek_token(), expected.get(split).map(|(token, _)| token.clone()));
+        let rest: Vec<(HtmlToken, Span)> = std::iter::from_fn(|| tokenizer.next_token_spanned()).collect();
+        assert_eq!(rest, expected[split..]);
+    }
+
+    // Errors found after a checkpoint are found again, not twice.
+    let mut tokenizer = HtmlTokenizer::new(html);
+    let checkpoint = tokenizer.checkpoint();
+    tokenizer.by_ref().for_each(drop);
+    let errors = tokenizer.errors().to_vec();
+    assert_eq!(errors.iter().map(|error| error.kind).collect::<Vec<_>>(), [TokenizeErrorKind::DuplicateAttribute]);
+    tokenizer.restore(checkpoint);
+    assert!(tokenizer.errors().is_empty());
+    tokenizer.by_ref().for_each(drop);
+    assert_eq!(tokenizer.errors(), errors);
+
+    // Looking ahead for a closing marker, then going back.
+    let mut tokenizer = HtmlTokenizer::new("<!-- template:x --><p>body</p><!-- /template:x -->tail");
+    assert_eq!(tokenizer.next_token(), Some(HtmlToken::Comment(" template:x ".to_string())));
+    let start = tokenizer.checkpoint();
+    let closed = std::iter::from_fn(|| tokenizer.next_token()).any(|token| token == HtmlToken::Comment(" /template:x ".to_string()));
+    assert!(closed);
+    tokenizer.restore(start);
+    assert!(matches!(tokenizer.next_token(), Some(HtmlToken::StartTag { name, .. }) if name == "p"));
+}
+
+#[test]
+fn test_html_tokenizer_peek_then_next() {
+    let html = "<ul><li>one<li><a href=x>two</a></ul><!-- end --><script>a<b</script>";
+    let expected: Vec<HtmlToken> = HtmlTokenizer::new(html).collect();
+
+    // Peeking before every token gives the token `next` returns, and leaves
+    // the position where the last token ended.
+    let mut tokenizer = HtmlTokenizer::new(html);
+    let mut tokens = Vec::new();
+    loop {
+        let position = tokenizer.byte_position();
+        let peeked = tokenizer.peek_token();
+        assert_eq!(tokenizer.byte_position(), position);
+        let next = tokenizer.next();
+        assert_eq!(peeked, next);
+        match next {
+            Some(token) => tokens.push(token),
+            None => break,
+        }
+        assert!(tokenizer.byte_position() > position);
+    }
+    assert_eq!(tokens, expected);
+    assert_eq!(tokenizer.byte_position(), html.len());
+
+    // A parser built on lookahead: collect each `<li>`'s text, which ends at
+    // the next `<li>` or `</ul>` without consuming it.
+    let mut tokenizer = HtmlTokenizer::new(html);
+    let mut items = Vec::new();
+    while let Some(token) = tokenizer.next() {
+        if !matches!(&token, HtmlToken::StartTag { name, .. } if name == "li") {
+            continue;
+        }
+        let mut item = String::new();
+        while let Some(next) = tokenizer.peek_token() {
+            match next {
+                HtmlToken::StartTag { name, .. } | HtmlToken::EndTag { name, .. } if name == "li" || name == "ul" => break,
+                HtmlToken::Text(text) => item.push_str(&text),
+                _ => {}
+            }
+            tokenizer.next();
+        }
+        items.push(item);
+    }
+    assert_eq!(items, ["one", "two"]);
+}
+
+#[test]
+fn test_html_chunked_tokenizer() {
+    use html_css_parser::html::ChunkedHtmlTokenizer;
+    use std::io::Read;
+
+    // Reads one byte at a time, splitting multi-byte characters.
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    let documents = [
+        concat!(
+            "<!DOCTYPE html><html lang=\"en\"><head><title>Tom &amp; Jerry — ☃</title>",
+            "<script>if (a < b) { s = '</scriptx>'; }</script><style>p > a { x: 1 }</style></head>\n",
+            "<body><!-- a -- b --><p class=\"naïve\" data-x='1' disabled>Text &copy; 2024 &#x1F600;</p>",
+            "<textarea>a &lt; b</textarea><img src=x.png/><br/>trailing text",
+        ),
+        "<p>cut off <a href=\"unterminated",
+        "<div>text<!-- unclosed comment",
+        "<script>never closed",
+        "plain text only, no markup",
+        "",
+    ];
+
+    for html in documents {
+        let expected: Vec<HtmlToken> = HtmlTokenizer::new(html).collect();
+
+        let mut chunked = ChunkedHtmlTokenizer::new();
+        let mut tokens = Vec::new();
+        let mut buffer = [0; 4];
+        for ch in html.chars() {
+            tokens.extend(chunked.feed(ch.encode_utf8(&mut buffer)));
+            assert!(chunked.buffered() < 200, "{html:?}");
+        }
+        tokens.extend(chunked.finish());
+        assert_eq!(tokens, expected, "{html:?}");
+
+        let tokens: Vec<HtmlToken> = ChunkedHtmlTokenizer::from_reader(ByteReader(html.as_bytes()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, expected, "{html:?}");
+        let tokens: Vec<HtmlToken> = ChunkedHtmlTokenizer::from_reader(html.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, expected, "{html:?}");
+    }
+
+    // Tokens come out as soon as enough input follows them.
+    let mut chunked = ChunkedHtmlTokenizer::new();
+    assert!(chunked.feed("<p>a").is_empty());
+    let tokens = chunked.feed("</p><div>more text to come");
+    assert_eq!(tokens[0], HtmlToken::StartTag { name: "p".to_string(), attributes: vec![], self_closing: false });
+
+    // Bad UTF-8 is an error.
+    let mut tokens = ChunkedHtmlTokenizer::from_reader(&b"<p>\xff</p>"[..]);
+    assert_eq!(tokens.next().unwrap().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    assert!(tokens.next().is_none());
+    let mut tokens = ChunkedHtmlTokenizer::from_reader(&b"<p>\xe2\x98"[..]);
+    assert_eq!(tokens.next().unwrap().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_html_chunked_tokenizer_long_tokens() {
+    use html_css_parser::html::{ChunkedHtmlTokenizer, HtmlParseOptions};
+
+    // Multi-megabyte single tokens, each split over many reads. Tokenizing
+    // the unfinished token again on every read would take minutes.
+    let long = "A".repeat(4 << 20);
+    let documents = [
+        format!("<p><img src=\"data:image/png;base64,{long}\" alt=x></p>"),
+        format!("<script>{}</script><p>after</p>", "if (a < b && c > d) x();\n".repeat(160_000)),
+        format!("<!-- {} --><p>after</p>", "<b>a</b> ".repeat(400_000)),
+        format!("<p>{long}</p>"),
+        format!("<title>{long}</TITLE><p>after</p>"),
+    ];
+
+    for html in &documents {
+        let expected: Vec<HtmlToken> = HtmlTokenizer::new(html).collect();
+        let tokens: Vec<HtmlToken> = ChunkedHtmlTokenizer::from_reader(html.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert!(tokens == expected, "{:?}", &html[..20]);
+
+        let mut chunked = ChunkedHtmlTokenizer::new();
+        let mut tokens = Vec::new();
+        for chunk in html.as_bytes().chunks(1000) {
+            tokens.extend(chunked.feed(std::str::from_utf8(chunk).unwrap()));
+        }
+        tokens.extend(chunked.finish());
+        assert!(tokens == expected, "{:?}", &html[..20]);
+    }
+
+    // Options apply to readers too.
+    let html = "<p> a <b> b </b></p>";
+    let options = HtmlParseOptions::default().trim_text(false);
+    let expected: Vec<HtmlToken> = HtmlTokenizer::with_options(html, &options).collect();
+    let tokens: Vec<HtmlToken> = ChunkedHtmlTokenizer::from_reader_with_options(html.as_bytes(), options)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(tokens, expected);
+    assert!(tokens.contains(&HtmlToken::Text(" a ".to_string())));
+}
+
+#[test]
+fn test_html_null_and_control_characters() {
+    let html = "<p\0x a\0b=\"c\0d\" e=f\0>t\0\u{1}<!--\0--><script>\0</script><textarea>\0</textarea>";
+    let (tokens, errors) = HtmlTokenizer::tokenize_with_errors(html);
+    assert_eq!(tokens, vec![
+        HtmlToken::StartTag {
+            name: "p\u{FFFD}x".to_string(),
+            attributes: vec![attr("a\u{FFFD}b", "c\u{FFFD}d"), attr("e", "f\u{FFFD}")],
+            self_closing: false,
+        },
+        HtmlToken::Text("t\u{FFFD}\u{1}".to_string()),
+        HtmlToken::Comment("\u{FFFD}".to_string()),
+        HtmlToken::StartTag { name: "script".to_string(), attributes: vec![], self_closing: false },
+        HtmlToken::Text("\u{FFFD}".to_string()),
+        HtmlToken::EndTag { name: "script".to_string() },
+        HtmlToken::StartTag { name: "textarea".to_string(), attributes: vec![], self_closing: false },
+        HtmlToken::Text("\u{FFFD}".to_string()),
+        HtmlToken::EndTag { name: "textarea".to_string() },
+    ]);
+    // The input is ASCII on one line, so columns follow byte offsets.
+    let found: Vec<(TokenizeErrorKind, usize)> = errors.iter().map(|error| (error.kind, error.span.column)).collect();
+    let expected: Vec<(TokenizeErrorKind, usize)> = html
+        .char_indices()
+        .filter_map(|(index, c)| match c {
+            '\0' => Some((TokenizeErrorKind::UnexpectedNullCharacter, index + 1)),
+            '\u{1}' => Some((TokenizeErrorKind::ControlCharacterInInputStream, index + 1)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(found, expected);
+    assert!(errors.windows(2).all(|pair| pair[0].span.start < pair[1].span.start));
+    // Decoded references can't produce NUL either.
+    assert_eq!(HtmlTokenizer::new("&#0;").collect::<Vec<_>>(), vec![HtmlToken::Text("\u{FFFD}".to_string())]);
+
+    // Arbitrary bytes, read as lossy UTF-8, always tokenize to an end and
+    // never give a token containing NUL.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let alphabet = b"<>/!-?=\"' \0\x01\x7fabcdpscript&#;x\xc2\x9f\xff";
+    for _ in 0..500 {
+        let length = (next() % 64) as usize;
+        let bytes: Vec<u8> = (0..length).map(|_| alphabet[(next() % alphabet.len() as u64) as usize]).collect();
+        let input = String::from_utf8_lossy(&bytes);
+        let tokens: Vec<HtmlToken> = HtmlTokenizer::new(&input).take(input.len() + 1).collect();
+        assert!(tokens.len() <= input.len(), "{input:?}");
+        assert!(!format!("{tokens:?}").contains("\\0"), "{input:?}: {tokens:?}");
+        HtmlParser::new(&input).parse();
+    }
+}
+
+#[test]
+fn test_html_descendants() {
+    let html = r##"<!DOCTYPE html>
+<html>
+<head>
+    <title>Demo Page</title>
+</head>
+<body>
+    <div class="container">
+        <h1 id="title">Hello World</h1>
+        <p>This is a <strong>demo</strong> page.</p>
+    </div>
+</body>
+</html>"##;
+    let Some(Node::Element(root)) = HtmlParser::new(html).parse_document() else {
+        panic!("expected an element");
+    };
+
+    let tags: Vec<&str> = root.descendants().map(|element| element.tag_name.as_str()).collect();
+    assert_eq!(tags, ["head", "title", "body", "div", "h1", "p", "strong"]);
+    assert_eq!(root.descendants().count(), root.get_elements_by_tag_name("*").len() - 1);
+
+    let nodes: Vec<String> = root
+        .descendant_nodes()
+        .map(|node| match node {
+            Node::Element(element) => element.tag_name.clone(),
+            Node::Text(text) => format!("{:?}", text),
+            Node::Comment(comment) => format!("<!--{}-->", comment),
+        })
+        .collect();
+    assert_eq!(
+        nodes,
+        ["head", "title", "\"Demo Page\"", "body", "div", "h1", "\"Hello World\"", "p", "\"This is a\"", "strong", "\"demo\"", "\"page.\""]
+    );
+
+    let leaf = Element { tag_name: "br".to_string(), attributes: Default::default(), children: Vec::new(), source_span: None };
+    assert_eq!(leaf.descendant_nodes().count(), 0);
+}
+
+#[test]
+fn test_html_text_content() {
+    use html_css_parser::html::HtmlParseOptions;
+
+    let parse = |html: &str| {
+        let options = HtmlParseOptions::default().trim_text(false);
+        match HtmlParser::with_options(html, options).parse().into_iter().next() {
+            Some(Node::Element(element)) => element,
+            other => panic!("expected an element, got {:?}", other),
+        }
+    };
+
+    assert_eq!(parse("<p>This is a <strong>demo</strong> page.</p>").text_content(), "This is a demo page.");
+    assert_eq!(parse("<div>a<!-- skipped --><ul><li>b<em>c<b>d</b></em></li></ul>e</div>").text_content(), "abcde");
+    assert_eq!(parse("<div><img src=x><span></span><!-- c --></div>").text_content(), "");
+
+    let depth = 200;
+    let html = format!("{}x{}", "<span>".repeat(depth), "</span>".repeat(depth));
+    assert_eq!(parse(&html).text_content(), "x");
+
+    // Trimmed text nodes are joined as they are.
+    let nodes = HtmlParser::new("<p>This is a <strong>demo</strong> page.</p>").parse();
+    assert_eq!(nodes[0].text_content(), "This is ademopage.");
+}
+
+#[test]
+fn test_html_traversal_iterators() {
+    let html = r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Test Page</title>
+            <meta charset="utf-8">
+        </head>
+        <body>
+            <div class="container">
+                <h1 id="title">Hello World</h1>
+                <p>This is a test paragraph with <strong>bold</strong> text.</p>
+                <ul>
+                    <li>Item 1</li>
+                    <li>Item 2</li>
+                    <li>Item 3</li>
+                </ul>
+            </div>
+        </body>
+        </html>
+    "#;
+    let document = HtmlParser::new(html).parse_document().unwrap();
+    let Node::Element(root) = &document else {
+        panic!("expected an element");
+    };
+
+    assert_eq!(document.descendants().count(), 20);
+    assert!(document.descendants().eq(root.descendant_nodes()));
+    let tags: Vec<&str> = root.descendants().map(|element| element.tag_name.as_str()).collect();
+    assert_eq!(tags, ["head", "title", "meta", "body", "div", "h1", "p", "strong", "ul", "li", "li", "li"]);
+    let texts: Vec<&str> = document
+        .descendants()
+        .filter_map(|node| match node {
+            Node::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(texts, ["Test Page", "Hello World", "This is a test paragraph with", "bold", "text.", "Item 1", "Item 2", "Item 3"]);
+    assert_eq!(Node::Text("x".to_string()).descendants().count(), 0);
+
+    let children: Vec<&str> = root.child_elements().map(|element| element.tag_name.as_str()).collect();
+    assert_eq!(children, ["head", "body"]);
+    let p = root.descendants().find(|element| element.tag_name == "p").unwrap();
+    assert_eq!(p.children.len(), 3);
+    assert_eq!(p.child_elements().count(), 1);
+
+    // Deep nesting needs no recursion.
+    let mut deep = Element { tag_name: "b".to_string(), attributes: Default::default(), children: Vec::new(), source_span: None };
+    for _ in 0..5000 {
+        let child = std::mem::take(&mut deep.children);
+        deep.children = vec![Node::Element(Element { tag_name: "i".to_string(), attributes: Default::default(), children: child, source_span: None })];
+    }
+    assert_eq!(deep.descendants().count(), 5000);
+    let mut visited = 0;
+    deep.for_each_descendant_mut(|_| visited += 1);
+    assert_eq!(visited, 5000);
+}
+
+#[test]
+fn test_html_for_each_descendant_mut() {
+    let Node::Element(mut div) = HtmlParser::new("<div><p>a <!-- x --><b>b</b></p><ul><li>c</li></ul></div>").parse().remove(0) else {
+        panic!("expected an element");
+    };
+
+    let mut order = Vec::new();
+    div.for_each_descendant_mut(|node| {
+        match node {
+            Node::Element(element) => {
+                order.push(element.tag_name.clone());
+                // Children added here are visited as well.
+                if element.tag_name == "li" {
+                    element.children.push(Node::Element(Element {
+                        tag_name: "span".to_string(),
+                        attributes: Default::default(),
+                        children: vec![Node::Text("new".to_string())],
+                        source_span: None,
+                    }));
+                }
+            }
+            Node::Text(text) => *text = text.to_uppercase(),
+            Node::Comment(_) => order.push("comment".to_string()),
+        }
+    });
+    assert_eq!(order, ["p", "comment", "b", "ul", "li", "span"]);
+    assert_eq!(div.to_html(), "<div><p>A<!-- x --><b>B</b></p><ul><li>C<span>NEW</span></li></ul></div>");
+}
+
+#[test]
+fn test_html_traversal_large_document() {
+    fn count(nodes: &[Node]) -> usize {
+        nodes
+            .iter()
+            .map(|node| match node {
+                Node::Element(element) => 1 + count(&element.children),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    let mut html = String::from("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Large</title></head><body>");
+    for i in 0..2000 {
+        html.push_str(&format!(
+            r#"<article class="post" id="post-{i}">
+  <h2><a href="/posts/{i}">Post {i}</a></h2>
+  <p>Some <em>emphasized</em> text with a <a href="/tags/{i}" rel="tag">tag link</a> and more words.</p>
+  <img src="/images/{i}.png" alt="Image {i}">
+  <script>track('<a href="/phantom/{i}">', {i});</script>
+  <ul><li>One</li><li>Two</li><li>Three</li></ul>
+</article>
+"#
+        ));
+    }
+    html.push_str("</body></html>");
+
+    let document = HtmlParser::new(&html).parse_document().unwrap();
+    assert_eq!(document.descendants().count(), count(std::slice::from_ref(&document)) - 1);
+    let Node::Element(root) = &document else {
+        panic!("expected an element");
+    };
+    assert_eq!(root.descendants().filter(|element| element.tag_name == "a").count(), 4000);
+    let ids: Vec<&str> = root.descendants().filter_map(|element| element.attribute("id")).take(3).collect();
+    assert_eq!(ids, ["post-0", "post-1", "post-2"]);
+}
+
+// The depth of the deepest element, found without recursing.
+fn nesting_depth(nodes: &[Node]) -> usize {
+    let mut deepest = 0;
+    let mut stack: Vec<(&Node, usize)> = nodes.iter().map(|node| (node, 1)).collect();
+    while let Some((node, depth)) = stack.pop() {
+        if let Node::Element(element) = node {
+            deepest = deepest.max(depth);
+            stack.extend(element.children.iter().map(|child| (child, depth + 1)));
+        }
+    }
+    deepest
+}
+
+#[test]
+fn test_html_max_depth_flattens_deeper_elements() {
+    use html_css_parser::html::{HtmlParseOptions, StreamingHtmlParser};
+
+    let html = "<div><div><div><span>x<i>y</i>z</span>w</div>v</div></div><p>after</p>";
+    let options = HtmlParseOptions::default().max_depth(3);
+    let expected = "<div><div><div></div><span></span>x<i></i>yzwv</div></div><p>after</p>";
+
+    let (nodes, errors) = HtmlParser::with_options(html, options.clone()).parse_with_diagnostics();
+    assert_eq!(nodes.iter().map(Node::to_html).collect::<String>(), expected);
+    assert_eq!(nesting_depth(&nodes), 3);
+    assert!(matches!(errors.as_slice(), [HtmlError::NestingTooDeep { name, span }] if name == "div" && span.start == 10));
+
+    let streamed: Vec<Node> = StreamingHtmlParser::with_options(html, options.clone()).collect();
+    assert_eq!(streamed, nodes);
+
+    // Flattened elements still close implicitly and end at their own end
+    // tags, while an ancestor's end tag closes them all.
+    let html = "<ul><li><ul><li>a<li>b<p>c</ul>d</li></ul>e";
+    let nodes = HtmlParser::with_options(html, HtmlParseOptions::default().max_depth(3)).parse();
+    assert_eq!(nodes.iter().map(Node::to_html).collect::<String>(), "<ul><li><ul></ul><li></li>a<li></li>b<p></p>cd</li></ul>e");
+
+    // Within the limit nothing changes.
+    let html = "<div><p>a<b>b</b></p></div>";
+    assert_eq!(
+        HtmlParser::with_options(html, HtmlParseOptions::default().max_depth(4)).parse(),
+        HtmlParser::new(html).parse()
+    );
+}
+
+#[test]
+fn test_html_adversarial_nesting_does_not_overflow() {
+    // A million unclosed elements in 5MB of input.
+    let html = "<div>".repeat(1_000_000);
+    let mut parser = HtmlParser::new(&html);
+    let (nodes, errors) = parser.parse_with_diagnostics();
+    assert_eq!(nesting_depth(&nodes), 512);
+    assert_eq!(parser.stats().max_depth, 512);
+    assert_eq!(errors.iter().filter(|error| matches!(error, HtmlError::NestingTooDeep { .. })).count(), 1);
+    let Node::Element(root) = &nodes[0] else {
+        panic!("expected an element");
+    };
+    assert_eq!(root.descendants().count(), 999_999);
+    assert_eq!(nodes[0].to_html().len(), html.len() + "</div>".len() * 1_000_000);
+    drop(nodes);
+
+    // Closed, with content after it.
+    let html = format!("{}x{}<p>after</p>", "<span>".repeat(200_000), "</span>".repeat(200_000));
+    let nodes = HtmlParser::new(&html).parse();
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nesting_depth(&nodes), 512);
+    assert_eq!(nodes[1].to_html(), "<p>after</p>");
+    assert_eq!(nodes[0].text_content(), "x");
+}
+
+#[test]
+fn test_html_serialize_deep_tree() {
+    // Built by hand, past any parsing limit. Neither serializing nor
+    // dropping recurses.
+    let depth = 100_000;
+    let mut element = Element::new("b").text("x");
+    for _ in 1..depth {
+        element = Element::new("b").child(element);
+    }
+    let html = element.to_html();
+    assert_eq!(html, format!("{}x{}", "<b>".repeat(depth), "</b>".repeat(depth)));
+    drop(element);
+
+    // Nor do nodes holding such a tree.
+    let mut nodes = vec![Node::Text("x".to_string())];
+    for _ in 0..depth {
+        let mut element = Element::new("i");
+        element.children = nodes;
+        nodes = vec![Node::from(element), Node::Comment("z".to_string())];
+    }
+    drop(nodes);
+}
+
+#[test]
+fn test_html_duplicate_attributes_first_wins_everywhere() {
+    use html_css_parser::html::{extract, ChunkedHtmlTokenizer, StreamingHtmlParser};
+
+    let html = r#"<div id="a" ID="b" class=x id=c>t</div>"#;
+    let first_wins = |element: &Element| {
+        assert_eq!(element.attribute("id"), Some("a"));
+        assert_eq!(element.attributes.len(), 2);
+    };
+
+    let (nodes, errors) = HtmlParser::new(html).parse_with_diagnostics();
+    let Node::Element(div) = &nodes[0] else {
+        panic!("expected an element, got {:?}", nodes[0]);
+    };
+    first_wins(div);
+    assert_eq!(nodes[0].to_html(), r#"<div id="a" class="x">t</div>"#);
+
+    // Each dropped attribute is reported where its name appears.
+    let duplicates: Vec<(usize, usize)> = errors
+        .iter()
+        .filter_map(|error| match error {
+            HtmlError::Tokenize(error) if error.kind == TokenizeErrorKind::DuplicateAttribute => Some((error.span.start, error.span.end)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(duplicates, [(12, 14), (27, 29)]);
+    assert!(HtmlParser::new(r#"<div id="a" class="b">"#).parse_with_diagnostics().1.iter().all(|error| !matches!(
+        error,
+        HtmlError::Tokenize(error) if error.kind == TokenizeErrorKind::DuplicateAttribute
+    )));
+
+    let streamed: Vec<Node> = StreamingHtmlParser::new(html).collect();
+    assert_eq!(streamed, nodes);
+
+    for split in 1..html.len() {
+        let mut chunked = ChunkedHtmlTokenizer::new();
+        let mut tokens = chunked.feed(&html[..split]);
+        tokens.extend(chunked.feed(&html[split..]));
+        tokens.extend(chunked.finish());
+        let Some(HtmlToken::StartTag { attributes, .. }) = tokens.first() else {
+            panic!("expected a start tag, got {:?}", tokens);
+        };
+        assert_eq!(*attributes, vec![attr("id", "a"), attr("class", "x")], "split at {}", split);
+    }
+
+    let divs = extract::only_tags(html, &["div"]);
+    first_wins(&divs[0]);
+}
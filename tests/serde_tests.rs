@@ -0,0 +1,78 @@
+#![cfg(feature = "serde")]
+
+use html_css_parser::html::{HtmlParseOptions, HtmlParser};
+use html_css_parser::{CssParser, CssToken, CssTokenizer, Element, HtmlToken, HtmlTokenizer, Node, Rule};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+// The page `parse_html` benchmarks.
+fn bench_document() -> String {
+    let source = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/html_benches.rs")).unwrap();
+    let start = source.find("fn parse_html").unwrap();
+    let rest = &source[start..];
+    let open = rest.find("r#\"").unwrap() + 3;
+    let close = rest[open..].find("\"#").unwrap();
+    rest[open..open + close].to_string()
+}
+
+#[test]
+fn test_serde_node_shape() {
+    let nodes = HtmlParser::new(r#"<p class="x" hidden>Hi<!-- note --><br></p>"#).parse();
+    assert_eq!(
+        serde_json::to_value(&nodes).unwrap(),
+        json!([{
+            "type": "element",
+            "tag": "p",
+            "attributes": {"class": "x", "hidden": true},
+            "children": [
+                {"type": "text", "text": "Hi"},
+                {"type": "comment", "text": " note "},
+                {"type": "element", "tag": "br", "attributes": {}, "children": []},
+            ],
+        }])
+    );
+
+    // Attributes keep their order, and missing fields default to empty.
+    let json = r#"{"type":"element","tag":"a","attributes":{"z":"1","a":"2","z":"3"}}"#;
+    let element: Element = serde_json::from_str(json).unwrap();
+    assert_eq!(element.to_html(), r#"<a z="1" a="2"></a>"#);
+
+    assert!(serde_json::from_str::<Element>(r#"{"type":"text","text":"x"}"#).is_err());
+    assert!(serde_json::from_str::<Node>(r#"{"type":"element","tag":"p","attributes":{"hidden":false}}"#).is_err());
+    assert!(serde_json::from_str::<Node>(r#"{"type":"doctype"}"#).is_err());
+}
+
+#[test]
+fn test_serde_json_roundtrip_bench_document() {
+    let html = bench_document();
+    assert!(html.contains("Test Page"));
+
+    for options in [HtmlParseOptions::default(), HtmlParseOptions::default().trim_text(false)] {
+        let document = HtmlParser::with_options(&html, options).parse();
+        let json = serde_json::to_string(&document).unwrap();
+        let back: Vec<Node> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, document);
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+    }
+
+    let tokens: Vec<HtmlToken> = HtmlTokenizer::new(&html).collect();
+    let json = serde_json::to_string(&tokens).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<HtmlToken>>(&json).unwrap(), tokens);
+}
+
+#[test]
+fn test_serde_json_roundtrip_css() {
+    let css = "ul > li.item:nth-child(2n+1), a[href^=\"http\" i] ~ p::before { margin: calc(1px + 2em) !important; color: #fff }";
+
+    let rules = CssParser::new(css).parse();
+    let json = serde_json::to_string(&rules).unwrap();
+    let back: Vec<Rule> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, rules);
+    assert_eq!(back[0].declarations[0].span, rules[0].declarations[0].span);
+    assert_eq!(back[0].span, rules[0].span);
+
+    let tokens: Vec<CssToken> = CssTokenizer::new(css).collect();
+    let json = serde_json::to_string(&tokens).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<CssToken>>(&json).unwrap(), tokens);
+}